@@ -0,0 +1,54 @@
+#import "shaders/sdf_common.wgsl"::{SdfEntity, BRICK_MAP_RESOLUTION, MAX_BRICK_ENTITIES, brick_index, entity_half_extents}
+
+// Grid of the build (must match BrickMapParams in brick_map.rs)
+struct BrickMapParams {
+    grid_min: vec3<f32>,
+    entity_count: u32,
+    grid_max: vec3<f32>,
+    _padding: u32,
+}
+
+@group(0) @binding(0) var<uniform> params: BrickMapParams;
+@group(0) @binding(1) var<storage, read> build_entities: array<SdfEntity>;
+// Cleared before the build; may end up above MAX_BRICK_ENTITIES
+@group(0) @binding(2) var<storage, read_write> build_brick_counts: array<atomic<u32>>;
+@group(0) @binding(3) var<storage, read_write> build_brick_entities: array<u32>;
+
+// One invocation per entity, adding it to every brick its bounds overlap.
+// Must match BUILD_WORKGROUP_SIZE in brick_map.rs
+@compute @workgroup_size(64, 1, 1)
+fn build(@builtin(global_invocation_id) global_id: vec3<u32>) {
+    let index = global_id.x;
+    if (index >= params.entity_count) {
+        return;
+    }
+
+    let entity = build_entities[index];
+    let center = entity.position_scale.xyz;
+    let half_size = entity_half_extents(entity);
+
+    let cells = f32(BRICK_MAP_RESOLUTION);
+    let brick_size = (params.grid_max - params.grid_min) / cells;
+    let first = vec3<u32>(clamp(
+        floor((center - half_size - params.grid_min) / brick_size),
+        vec3<f32>(0.0),
+        vec3<f32>(cells - 1.0),
+    ));
+    let last = vec3<u32>(clamp(
+        floor((center + half_size - params.grid_min) / brick_size),
+        vec3<f32>(0.0),
+        vec3<f32>(cells - 1.0),
+    ));
+
+    for (var z = first.z; z <= last.z; z++) {
+        for (var y = first.y; y <= last.y; y++) {
+            for (var x = first.x; x <= last.x; x++) {
+                let brick = brick_index(vec3<u32>(x, y, z));
+                let slot = atomicAdd(&build_brick_counts[brick], 1u);
+                if (slot < MAX_BRICK_ENTITIES) {
+                    build_brick_entities[brick * MAX_BRICK_ENTITIES + slot] = index;
+                }
+            }
+        }
+    }
+}
@@ -1,5 +1,5 @@
 #import bevy_core_pipeline::fullscreen_vertex_shader::FullscreenVertexOutput
-#import "shaders/sdf_common.wgsl"::{PostProcessSettings, SceneSdfResult, RaymarchConfig, evaluate_scene_sdf, get_camera_position, get_ray_direction, get_inverse_view_projection, get_coarse_max_steps, get_coarse_distance_multiplier, raymarch}
+#import "shaders/sdf_common.wgsl"::{PostProcessSettings, SceneSdfResult, RaymarchConfig, evaluate_scene_sdf, get_ray_origin, get_ray_direction, get_inverse_view_projection, get_coarse_max_steps, get_coarse_distance_multiplier, raymarch}
 
 @group(0) @binding(0) var screen_texture: texture_2d<f32>;
 @group(0) @binding(1) var texture_sampler: sampler;
@@ -22,8 +22,8 @@ fn fragment(in: FullscreenVertexOutput) -> @location(0) vec4<f32> {
     let uv = in.uv;
     let config = coarse_raymarch_config();
 
-    // Ray origin (actual camera position)
-    let ray_origin = get_camera_position();
+    // Ray origin (camera position, or the near plane for orthographic views)
+    let ray_origin = get_ray_origin(uv, get_inverse_view_projection());
 
     // Perform coarse raymarching
     let result = raymarch(uv, ray_origin, config);
@@ -3,7 +3,7 @@
 // BIND GROUP STRUCTURE:
 // This module defines bind group 1 for SDF scene data that can be shared across shaders:
 // - Group 1, Binding 0: PostProcessSettings uniform (camera matrices, entity count, etc.)
-// - Group 1, Binding 1: Entity transforms storage buffer (array of vec4: x, y, z, scale)
+// - Group 1, Binding 1: Entity storage buffer (array of SdfEntity: position/scale + operation)
 //
 // Shaders that import this module should:
 // 1. Use their own bind group 0 for shader-specific resources
@@ -39,8 +39,76 @@ struct SDFRenderSettings {
     coarse_resolution_factor: f32,
     coarse_distance_multiplier: f32,
     coarse_max_steps: u32,
+    ground_height: f32,
+    ground_enabled: u32,
+    clip_from_world: mat4x4<f32>,
+    light_count: u32,
+    shadow_softness: f32,
+    ao_intensity: f32,
+    ao_radius: f32,
+    environment_intensity: f32,
+    viewport_size: vec2<f32>,
+    previous_clip_from_world: mat4x4<f32>,
+    temporal_blend: f32,
+    temporal_jitter: vec2<f32>,
+    debug_view: u32,
+    clip_plane: vec4<f32>,
+    clip_enabled: u32,
+    clip_cap_color: vec4<f32>,
+    background_zenith: vec4<f32>,
+    background_horizon: vec4<f32>,
+    grid_color: vec4<f32>,
+    grid_spacing: f32,
+    grid_enabled: u32,
+    fog_color: vec4<f32>,
+    fog_density: f32,
+    fog_start: f32,
+    viewport_offset: vec2<f32>,
+    isolate_enabled: u32,
+    isolate_opacity: f32,
+    volume_min: vec3<f32>,
+    volume_enabled: u32,
+    volume_max: vec3<f32>,
+    volume_dirty_min: vec3<f32>,
+    volume_dirty_max: vec3<f32>,
+    acceleration_structure: u32,
+    brick_grid_min: vec3<f32>,
+    brick_grid_max: vec3<f32>,
 }
 
+// Per-entity data (must match GpuSdfEntity on the Rust side)
+struct SdfEntity {
+    position_scale: vec4<f32>,
+    // xyz: ellipsoid radii or capsule half segment, w: blend radius (the blend
+    // factor times the scale on the Rust side)
+    shape: vec4<f32>,
+    // x: noise amplitude, y: noise frequency, z: shell thickness, w: unused
+    modifiers: vec4<f32>,
+    operation: u32,
+    primitive: u32,
+    // ENTITY_FLAG_* bits
+    flags: u32,
+    _padding: u32,
+}
+
+// Set on instances of selected entities (must match the Rust side)
+const ENTITY_FLAG_SELECTED: u32 = 1u;
+
+// Shape of an entity (must match SdfPrimitive on the Rust side)
+const PRIMITIVE_SPHERE: u32 = 0u;
+const PRIMITIVE_ELLIPSOID: u32 = 1u;
+const PRIMITIVE_CAPSULE: u32 = 2u;
+
+// How an entity is combined with the scene (must match SdfOperation on the Rust side)
+const OPERATION_UNION: u32 = 0u;
+const OPERATION_SUBTRACT: u32 = 1u;
+
+// Blend radius used when carving subtractive entities out of the scene
+const SUBTRACT_SMOOTHING: f32 = 0.05;
+
+// Blend radius between the ground plane and the entities resting on it
+const GROUND_SMOOTHING: f32 = 0.1;
+
 struct BVHNode {
     min: vec4<f32>,
     max: vec4<f32>,
@@ -54,9 +122,36 @@ struct BVHNode {
 // This allows the common functions to access scene data directly
 // without needing pointer parameters, and keeps indexing consistent across shaders
 @group(1) @binding(0) var<uniform> sdf_settings: SDFRenderSettings;
-@group(1) @binding(1) var<storage, read> entities: array<vec4<f32>>;
+@group(1) @binding(1) var<storage, read> entities: array<SdfEntity>;
 @group(1) @binding(2) var<storage, read> bvh_nodes: array<BVHNode>;
 
+// Distance to the entities baked by sdf_volume_cache.rs, in the red channel
+@group(1) @binding(5) var volume_texture: texture_3d<f32>;
+@group(1) @binding(6) var volume_sampler: sampler;
+
+// Brick map built by brick_map.rs: how many entities overlap each brick of a
+// uniform grid over the scene, and up to MAX_BRICK_ENTITIES of their indices
+@group(1) @binding(7) var<storage, read> brick_counts: array<u32>;
+@group(1) @binding(8) var<storage, read> brick_entities: array<u32>;
+
+#ifdef SDF_VIEW_CULLING
+// Indices of the entities within the view's frustum, built by view_culling.rs
+struct ViewEntities {
+    count: u32,
+    indices: array<u32>,
+}
+
+@group(1) @binding(9) var<storage, read> view_entities: ViewEntities;
+#endif
+
+// Must match AccelerationStructure::as_gpu on the Rust side
+const ACCELERATION_BVH: u32 = 0u;
+const ACCELERATION_BRICK_MAP: u32 = 1u;
+
+// Must match brick_map.rs
+const BRICK_MAP_RESOLUTION: u32 = 32u;
+const MAX_BRICK_ENTITIES: u32 = 32u;
+
 
 
 // Initialize a scene SDF result with default values
@@ -83,6 +178,93 @@ fn sphere_sdf(point: vec3<f32>, center: vec3<f32>, radius: f32) -> f32 {
     return length(point - center) - radius;
 }
 
+// Approximate SDF for an axis-aligned ellipsoid
+fn ellipsoid_sdf(point: vec3<f32>, center: vec3<f32>, radii: vec3<f32>) -> f32 {
+    let q = point - center;
+    let k0 = length(q / radii);
+    let k1 = length(q / (radii * radii));
+    return k0 * (k0 - 1.0) / max(k1, 0.000001);
+}
+
+// SDF for a capsule around the segment a-b
+fn capsule_sdf(point: vec3<f32>, a: vec3<f32>, b: vec3<f32>, radius: f32) -> f32 {
+    let pa = point - a;
+    let ba = b - a;
+    let h = clamp(dot(pa, ba) / max(dot(ba, ba), 0.000001), 0.0, 1.0);
+    return length(pa - ba * h) - radius;
+}
+
+// Hash used by the value noise (must match sdf_cpu.rs)
+fn hash3(p: vec3<f32>) -> f32 {
+    return fract(sin(dot(p, vec3<f32>(127.1, 311.7, 74.7))) * 43758.5453);
+}
+
+// Trilinearly interpolated value noise in [-1, 1]
+fn value_noise(p: vec3<f32>) -> f32 {
+    let i = floor(p);
+    let f = fract(p);
+    let u = f * f * (3.0 - 2.0 * f);
+
+    let n000 = hash3(i + vec3<f32>(0.0, 0.0, 0.0));
+    let n100 = hash3(i + vec3<f32>(1.0, 0.0, 0.0));
+    let n010 = hash3(i + vec3<f32>(0.0, 1.0, 0.0));
+    let n110 = hash3(i + vec3<f32>(1.0, 1.0, 0.0));
+    let n001 = hash3(i + vec3<f32>(0.0, 0.0, 1.0));
+    let n101 = hash3(i + vec3<f32>(1.0, 0.0, 1.0));
+    let n011 = hash3(i + vec3<f32>(0.0, 1.0, 1.0));
+    let n111 = hash3(i + vec3<f32>(1.0, 1.0, 1.0));
+
+    let nx00 = mix(n000, n100, u.x);
+    let nx10 = mix(n010, n110, u.x);
+    let nx01 = mix(n001, n101, u.x);
+    let nx11 = mix(n011, n111, u.x);
+    let nxy0 = mix(nx00, nx10, u.y);
+    let nxy1 = mix(nx01, nx11, u.y);
+    return mix(nxy0, nxy1, u.z) * 2.0 - 1.0;
+}
+
+// Half size of the box around an entity, including the blend radius and
+// anything modifiers add (must match the Bounded impl of GpuSdfEntity)
+fn entity_half_extents(entity: SdfEntity) -> vec3<f32> {
+    let scale = entity.position_scale.w;
+    var half_extents = vec3<f32>(scale);
+    if (entity.primitive == PRIMITIVE_ELLIPSOID) {
+        half_extents = entity.shape.xyz;
+    } else if (entity.primitive == PRIMITIVE_CAPSULE) {
+        half_extents = abs(entity.shape.xyz) + vec3<f32>(scale);
+    }
+    let padding = max(0.5, entity.shape.w);
+    return half_extents + vec3<f32>(padding + abs(entity.modifiers.x) + entity.modifiers.z);
+}
+
+// Distance to a single entity's primitive
+fn entity_sdf(point: vec3<f32>, entity: SdfEntity) -> f32 {
+    let center = entity.position_scale.xyz;
+    var distance: f32;
+    if (entity.primitive == PRIMITIVE_ELLIPSOID) {
+        distance = ellipsoid_sdf(point, center, entity.shape.xyz);
+    } else if (entity.primitive == PRIMITIVE_CAPSULE) {
+        let half_segment = entity.shape.xyz;
+        distance = capsule_sdf(point, center - half_segment, center + half_segment, entity.position_scale.w);
+    } else {
+        distance = sphere_sdf(point, center, entity.position_scale.w);
+    }
+
+    // Noise displacement, sampled relative to the entity so it moves with it
+    let amplitude = entity.modifiers.x;
+    if (amplitude != 0.0) {
+        distance += amplitude * value_noise((point - center) * entity.modifiers.y);
+    }
+
+    // Onion: keep only a shell of the given thickness around the surface
+    let shell_thickness = entity.modifiers.z;
+    if (shell_thickness > 0.0) {
+        distance = abs(distance) - shell_thickness;
+    }
+
+    return distance;
+}
+
 // Smooth minimum operation for blending SDFs
 fn smooth_min(a: f32, b: f32, k: f32) -> f32 {
     let h = clamp(0.5 + 0.5 * (b - a) / k, 0.0, 1.0);
@@ -102,6 +284,133 @@ fn quadratic_smin( a: f32, b: f32, k: f32 ) -> f32
     return min(a,b) - h*h*k4*(1.0/4.0);
 }
 
+fn quadratic_smax(a: f32, b: f32, k: f32) -> f32 {
+    return -quadratic_smin(-a, -b, k);
+}
+
+// Blend the infinite ground plane into the scene, it is not part of the BVH
+fn combine_ground_plane(result: SceneSdfResult, point: vec3<f32>, processed_any: bool) -> SceneSdfResult {
+    if (sdf_settings.ground_enabled == 0u) {
+        return result;
+    }
+
+    var combined = result;
+    let ground_distance = point.y - sdf_settings.ground_height;
+    if (processed_any) {
+        combined.distance = quadratic_smin(result.distance, ground_distance, GROUND_SMOOTHING);
+    } else {
+        combined.distance = ground_distance;
+    }
+    return combined;
+}
+
+// Carve the accumulated subtractive volume out of the scene distance
+fn apply_carve(result: SceneSdfResult, carve_distance: f32) -> SceneSdfResult {
+    var carved = result;
+    carved.distance = quadratic_smax(result.distance, -carve_distance, SUBTRACT_SMOOTHING);
+    return carved;
+}
+
+// Signed distance to the clip plane, positive on the side its normal points to
+fn clip_plane_distance(point: vec3<f32>) -> f32 {
+    return dot(point, sdf_settings.clip_plane.xyz) - sdf_settings.clip_plane.w;
+}
+
+// Cut away everything on the positive side of the clip plane
+fn apply_clip_plane(result: SceneSdfResult, point: vec3<f32>) -> SceneSdfResult {
+    if (sdf_settings.clip_enabled == 0u) {
+        return result;
+    }
+
+    var clipped = result;
+    clipped.distance = max(result.distance, clip_plane_distance(point));
+    return clipped;
+}
+
+// Color for surface points on the cut face, alpha 0 if the point isn't on it
+// or capping is off
+fn clip_cap_color(point: vec3<f32>, normal: vec3<f32>) -> vec4<f32> {
+    if (sdf_settings.clip_enabled == 0u) {
+        return vec4<f32>(0.0);
+    }
+
+    let on_plane = abs(clip_plane_distance(point)) < 0.02;
+    let facing_plane = dot(normal, sdf_settings.clip_plane.xyz) > 0.99;
+    return select(vec4<f32>(0.0), sdf_settings.clip_cap_color, on_plane && facing_plane);
+}
+
+// Gradient behind the scene, from the horizon color to the zenith color and
+// mirrored below the horizon
+fn background_color(ray_dir: vec3<f32>) -> vec3<f32> {
+    let elevation = sqrt(abs(ray_dir.y));
+    return mix(sdf_settings.background_horizon.rgb, sdf_settings.background_zenith.rgb, elevation);
+}
+
+// Minimum width of the grid lines, they widen with distance to stay a pixel wide
+const GRID_LINE_WIDTH: f32 = 0.01;
+// Distance, in grid cells, over which the grid fades out
+const GRID_FADE_CELLS: f32 = 50.0;
+
+// Opacity of the reference grid where the ray crosses the ground plane height,
+// 0 if it crosses behind the surface at `surface_distance` or not at all
+fn grid_opacity(ray_origin: vec3<f32>, ray_dir: vec3<f32>, surface_distance: f32) -> f32 {
+    if (sdf_settings.grid_enabled == 0u || abs(ray_dir.y) < 1e-4) {
+        return 0.0;
+    }
+
+    // A little past the surface still counts, that's the ground plane itself
+    let t = (sdf_settings.ground_height - ray_origin.y) / ray_dir.y;
+    if (t < 0.0 || t > surface_distance + 0.01) {
+        return 0.0;
+    }
+
+    // Distance to the nearest line along either axis
+    let spacing = sdf_settings.grid_spacing;
+    let point = (ray_origin + ray_dir * t).xz;
+    let offset = abs(fract(point / spacing + 0.5) - 0.5) * spacing;
+    let line_distance = min(offset.x, offset.y);
+
+    let width = max(GRID_LINE_WIDTH, t / sdf_settings.viewport_size.y);
+    let line = 1.0 - smoothstep(0.0, width, line_distance);
+    let fade = 1.0 - clamp(t / (spacing * GRID_FADE_CELLS), 0.0, 1.0);
+    return line * fade * sdf_settings.grid_color.a;
+}
+
+fn get_grid_color() -> vec3<f32> {
+    return sdf_settings.grid_color.rgb;
+}
+
+// Distance over which selected entities blend into the ghosted rest of the scene
+const ISOLATE_BLEND_DISTANCE: f32 = 0.05;
+
+// How much a surface point belongs to the selected entities in the isolate
+// view, 1 on them (and always while isolating is off) and 0 on everything else
+fn isolation_focus(point: vec3<f32>) -> f32 {
+    if (sdf_settings.isolate_enabled == 0u) {
+        return 1.0;
+    }
+
+    var nearest = 999999.0;
+    for (var i = 0u; i < sdf_settings.entity_count; i++) {
+        let entity = entities[i];
+        if ((entity.flags & ENTITY_FLAG_SELECTED) != 0u && entity.operation == OPERATION_UNION) {
+            nearest = min(nearest, entity_sdf(point, entity));
+        }
+    }
+    return 1.0 - smoothstep(0.0, ISOLATE_BLEND_DISTANCE, nearest);
+}
+
+fn get_isolate_opacity() -> f32 {
+    return sdf_settings.isolate_opacity;
+}
+
+// Blends in the fog for something `distance` away from the camera
+fn apply_fog(color: vec3<f32>, distance: f32) -> vec3<f32> {
+    let fogged_distance = max(distance - sdf_settings.fog_start, 0.0);
+    let transmittance = exp(-sdf_settings.fog_density * fogged_distance);
+    return mix(sdf_settings.fog_color.rgb, color, transmittance);
+}
+
 
 // Calculate surface normal using finite differences
 fn calculate_normal(point: vec3<f32>) -> vec3<f32> {
@@ -131,6 +440,18 @@ fn calculate_normal_bvh(point: vec3<f32>, candidates: ptr<function, array<u32, 3
     return normalize(normal);
 }
 
+// Start of the ray through a uv: the camera for perspective projections, the
+// near plane for orthographic ones, whose rays are parallel
+fn get_ray_origin(uv: vec2<f32>, inverse_view_projection: mat4x4<f32>) -> vec3<f32> {
+    if (sdf_settings.projection_matrix[3][3] != 1.0) {
+        return sdf_settings.camera_position;
+    }
+
+    let ndc = vec2<f32>(uv.x * 2.0 - 1.0, (1.0 - uv.y) * 2.0 - 1.0);
+    let world_near = inverse_view_projection * vec4<f32>(ndc.x, ndc.y, -1.0, 1.0);
+    return world_near.xyz / world_near.w;
+}
+
 // Get ray direction from UV using precomputed inverse view-projection matrix
 fn get_ray_direction(uv: vec2<f32>, inverse_view_projection: mat4x4<f32>) -> vec3<f32> {
     // Convert UV to NDC (Normalized Device Coordinates)
@@ -167,6 +488,107 @@ fn get_inverse_view_projection() -> mat4x4<f32> {
     return sdf_settings.inverse_view_projection;
 }
 
+// Size in texels of the coarse pass texture (matches manage_coarse_pass_texture)
+fn get_coarse_size() -> vec2<u32> {
+    let size = sdf_settings.viewport_size * sdf_settings.coarse_resolution_factor;
+    return max(vec2<u32>(size), vec2<u32>(1u));
+}
+
+fn get_environment_intensity() -> f32 {
+    return sdf_settings.environment_intensity;
+}
+
+fn get_light_count() -> u32 {
+    return sdf_settings.light_count;
+}
+
+// Steps of the march towards the light for soft shadows
+const SHADOW_MAX_STEPS: i32 = 32;
+
+// Soft shadow factor in [0, 1] for a surface point: marches towards a light
+// and darkens by how closely the ray passes other geometry along the way
+fn soft_shadow(
+    surface_position: vec3<f32>,
+    normal: vec3<f32>,
+    light_dir: vec3<f32>,
+    max_distance: f32,
+) -> f32 {
+    let softness = sdf_settings.shadow_softness;
+    if (softness <= 0.0) {
+        return 1.0;
+    }
+
+    // Start slightly off the surface so the march doesn't hit its own origin
+    let origin = surface_position + normal * 0.02;
+    let dir = light_dir;
+    var candidates = traverse_for_entities(origin, dir);
+
+    var shadow = 1.0;
+    var t = 0.02;
+    for (var step = 0; step < SHADOW_MAX_STEPS && t < max_distance; step++) {
+        let h = evaluate_scene_sdf_with_bvh(origin + dir * t, &candidates, step).distance;
+        if (h < 0.001) {
+            return 0.0;
+        }
+        shadow = min(shadow, h / (softness * t));
+        t += clamp(h, 0.01, 0.5);
+    }
+    return clamp(shadow, 0.0, 1.0);
+}
+
+// Samples along the normal for ambient occlusion
+const AO_SAMPLES: i32 = 5;
+
+// Ambient occlusion factor in [0, 1] for a surface point: compares how far
+// points along the normal are from the surface with how far they would be
+// without any nearby geometry, weighting closer samples more
+fn ambient_occlusion(surface_position: vec3<f32>, normal: vec3<f32>) -> f32 {
+    let intensity = sdf_settings.ao_intensity;
+    if (intensity <= 0.0) {
+        return 1.0;
+    }
+
+    let radius = sdf_settings.ao_radius;
+    var candidates = traverse_for_entities(surface_position, normal);
+    var occlusion = 0.0;
+    var weight = 1.0;
+    for (var i = 1; i <= AO_SAMPLES; i++) {
+        let h = radius * f32(i) / f32(AO_SAMPLES);
+        let d = evaluate_scene_sdf_with_bvh(surface_position + normal * h, &candidates, i).distance;
+        occlusion += (h - d) * weight;
+        weight *= 0.5;
+    }
+    return clamp(1.0 - intensity * occlusion / radius, 0.0, 1.0);
+}
+
+// Depth buffer value of a world position (reverse-z, 0 is infinitely far)
+fn get_depth(world_position: vec3<f32>) -> f32 {
+    let clip = sdf_settings.clip_from_world * vec4<f32>(world_position, 1.0);
+    return clamp(clip.z / clip.w, 0.0, 1.0);
+}
+
+// Screen uv (xy) and depth (z) of a world position in the previous frame
+fn get_previous_screen_position(world_position: vec3<f32>) -> vec3<f32> {
+    let clip = sdf_settings.previous_clip_from_world * vec4<f32>(world_position, 1.0);
+    let ndc = clip.xyz / clip.w;
+    return vec3<f32>(ndc.x * 0.5 + 0.5, 0.5 - ndc.y * 0.5, ndc.z);
+}
+
+// Turns a uv within the camera's viewport into a uv of a texture covering the
+// whole render target
+fn viewport_to_target_uv(uv: vec2<f32>, target_size: vec2<f32>) -> vec2<f32> {
+    return (sdf_settings.viewport_offset + uv * sdf_settings.viewport_size) / target_size;
+}
+
+fn get_temporal_blend() -> f32 {
+    return sdf_settings.temporal_blend;
+}
+
+// This frame's sub-pixel ray offset in uv units
+fn get_temporal_jitter() -> vec2<f32> {
+    return sdf_settings.temporal_jitter / sdf_settings.viewport_size;
+}
+
 // Get coarse pass settings
 fn get_coarse_max_steps() -> u32 {
     return sdf_settings.coarse_max_steps;
@@ -228,25 +650,148 @@ fn bvh_traverse_for_entities(ray_origin: vec3<f32>, ray_dir: vec3<f32>) -> array
     return candidate_entities;
 }
 
-// Combine a sphere into the existing scene result with smooth blending
-fn combine_sphere_into_scene_result(
+fn brick_index(cell: vec3<u32>) -> u32 {
+    return (cell.z * BRICK_MAP_RESOLUTION + cell.y) * BRICK_MAP_RESOLUTION + cell.x;
+}
+
+// Entities listed in the bricks a ray passes through, front to back, found by
+// stepping from brick to brick along the ray (Amanatides & Woo)
+fn brick_map_traverse_for_entities(ray_origin: vec3<f32>, ray_dir: vec3<f32>) -> array<u32, 32> {
+    var candidate_entities: array<u32, 32>;
+    for (var i = 0u; i < 32u; i++) {
+        candidate_entities[i] = 0xFFFFFFFFu;
+    }
+    var candidate_count = 0u;
+
+    let grid_min = sdf_settings.brick_grid_min;
+    let grid_max = sdf_settings.brick_grid_max;
+    let cells = f32(BRICK_MAP_RESOLUTION);
+    let brick_size = (grid_max - grid_min) / cells;
+
+    // Where the ray enters and leaves the grid
+    let inv_dir = 1.0 / ray_dir;
+    let t0 = (grid_min - ray_origin) * inv_dir;
+    let t1 = (grid_max - ray_origin) * inv_dir;
+    let t_near = min(t0, t1);
+    let t_far = max(t0, t1);
+    let t_enter = max(max(max(t_near.x, t_near.y), t_near.z), 0.0);
+    let t_exit = min(min(t_far.x, t_far.y), t_far.z);
+    if (t_enter > t_exit) {
+        return candidate_entities;
+    }
+
+    let entry = ray_origin + ray_dir * t_enter;
+    var cell = vec3<i32>(clamp(
+        floor((entry - grid_min) / brick_size),
+        vec3<f32>(0.0),
+        vec3<f32>(cells - 1.0),
+    ));
+    let cell_step = vec3<i32>(sign(ray_dir));
+    // Ray distance to the next brick boundary on each axis, and between boundaries
+    let moving = ray_dir != vec3<f32>(0.0);
+    let far_side = select(vec3<f32>(0.0), vec3<f32>(1.0), ray_dir > vec3<f32>(0.0));
+    let boundary = grid_min + (vec3<f32>(cell) + far_side) * brick_size;
+    var t_max = select(vec3<f32>(1e30), (boundary - ray_origin) * inv_dir, moving);
+    let t_delta = select(vec3<f32>(1e30), abs(brick_size * inv_dir), moving);
+
+    let resolution = i32(BRICK_MAP_RESOLUTION);
+    for (var i = 0u; i < 3u * BRICK_MAP_RESOLUTION && candidate_count < 32u; i++) {
+        if (any(cell < vec3<i32>(0)) || any(cell >= vec3<i32>(resolution))) {
+            break;
+        }
+
+        let brick = brick_index(vec3<u32>(cell));
+        let count = min(brick_counts[brick], MAX_BRICK_ENTITIES);
+        for (var j = 0u; j < count && candidate_count < 32u; j++) {
+            let entity_index = brick_entities[brick * MAX_BRICK_ENTITIES + j];
+            // Entities spanning several bricks are listed in each of them
+            var seen = false;
+            for (var k = 0u; k < candidate_count; k++) {
+                if (candidate_entities[k] == entity_index) {
+                    seen = true;
+                    break;
+                }
+            }
+            if (!seen) {
+                candidate_entities[candidate_count] = entity_index;
+                candidate_count += 1u;
+            }
+        }
+
+        // Into the neighbouring brick whose boundary the ray reaches first
+        if (t_max.x < t_max.y && t_max.x < t_max.z) {
+            cell.x += cell_step.x;
+            t_max.x += t_delta.x;
+        } else if (t_max.y < t_max.z) {
+            cell.y += cell_step.y;
+            t_max.y += t_delta.y;
+        } else {
+            cell.z += cell_step.z;
+            t_max.z += t_delta.z;
+        }
+    }
+
+    return candidate_entities;
+}
+
+// Entities a ray may hit, from whichever acceleration structure is selected
+fn traverse_for_entities(ray_origin: vec3<f32>, ray_dir: vec3<f32>) -> array<u32, 32> {
+    if (sdf_settings.acceleration_structure == ACCELERATION_BRICK_MAP) {
+        return brick_map_traverse_for_entities(ray_origin, ray_dir);
+    }
+    return bvh_traverse_for_entities(ray_origin, ray_dir);
+}
+
+// Number of nodes bvh_traverse_for_entities visits for a ray, for the debug view
+fn count_bvh_node_visits(ray_origin: vec3<f32>, ray_dir: vec3<f32>) -> u32 {
+    var visits = 0u;
+    var candidate_count = 0u;
+    var index = 0u;
+    let max_length = sdf_settings.num_bvh_nodes;
+
+    while (index < max_length && candidate_count < 32u) {
+        let node = bvh_nodes[index];
+        visits += 1u;
+
+        if (node.shape_index < 0xFFFFFFFFu) {
+            candidate_count += 1u;
+            index = node.exit_index;
+        } else if (ray_aabb_intersect(ray_origin, ray_dir, node.min.xyz, node.max.xyz)) {
+            index = node.entry_index;
+        } else {
+            index = node.exit_index;
+        }
+    }
+
+    return visits;
+}
+
+fn get_num_bvh_nodes() -> u32 {
+    return sdf_settings.num_bvh_nodes;
+}
+
+fn get_debug_view() -> u32 {
+    return sdf_settings.debug_view;
+}
+
+// Combine an entity into the existing scene result with smooth blending
+fn combine_entity_into_scene_result(
     current_result: SceneSdfResult,
     point: vec3<f32>,
-    sphere_center: vec3<f32>,
-    sphere_radius: f32,
+    entity: SdfEntity,
     smoothing_factor: f32,
     is_first: bool
 ) -> SceneSdfResult {
-    let sphere_distance = sphere_sdf(point, sphere_center, sphere_radius);
+    let entity_distance = entity_sdf(point, entity);
 
     var result = current_result;
 
     if (is_first) {
-        // First sphere - just use its values
-        result.distance = sphere_distance;
+        // First entity - just use its values
+        result.distance = entity_distance;
     } else {
         // Combine with existing result using smooth minimum
-        result.distance = quadratic_smin(current_result.distance, sphere_distance, smoothing_factor);
+        result.distance = quadratic_smin(current_result.distance, entity_distance, smoothing_factor);
     }
 
     return result;
@@ -255,9 +800,9 @@ fn combine_sphere_into_scene_result(
 // Evaluate SDF at a specific point using BVH acceleration
 fn evaluate_scene_sdf_with_bvh(point: vec3<f32>, candidates: ptr<function, array<u32, 32>>, steps: i32) -> SceneSdfResult {
     var result = init_scene_sdf_result(point, steps);
-    let smoothing_factor = 0.5; // Adjust for more/less blending
 
     var processed_any = false;
+    var carve_distance = 999999.0;
     for (var i = 0u; i < 32u; i++) {
         let entity_index = (*candidates)[i];
         // Check if we have a valid entity index
@@ -267,50 +812,145 @@ fn evaluate_scene_sdf_with_bvh(point: vec3<f32>, candidates: ptr<function, array
 
         let entity = entities[entity_index];
 
-        let sphere_center = entity.xyz;
-        let sphere_radius = entity.w;
+        // Subtractive entities are accumulated separately and carved out at the end
+        if (entity.operation == OPERATION_SUBTRACT) {
+            carve_distance = min(carve_distance, entity_sdf(point, entity));
+            continue;
+        }
 
-        result = combine_sphere_into_scene_result(
+        result = combine_entity_into_scene_result(
             result,
             point,
-            sphere_center,
-            sphere_radius,
-            smoothing_factor * sphere_radius,
+            entity,
+            entity.shape.w,
             !processed_any
         );
 
         processed_any = true;
     }
-    return result;
+    result = combine_ground_plane(result, point, processed_any);
+    return apply_clip_plane(apply_carve(result, carve_distance), point);
+}
+
+// Distance to all entities, blended like evaluate_scene_sdf_with_bvh but without
+// the ground and clip plane, as baked into the volume cache
+fn evaluate_entities_sdf(point: vec3<f32>, entity_count: u32) -> f32 {
+    var distance = 999999.0;
+    var carve_distance = 999999.0;
+    var processed_any = false;
+    for (var i = 0u; i < entity_count; i++) {
+        let entity = entities[i];
+        let entity_distance = entity_sdf(point, entity);
+        if (entity.operation == OPERATION_SUBTRACT) {
+            carve_distance = min(carve_distance, entity_distance);
+            continue;
+        }
+
+        if (processed_any) {
+            distance = quadratic_smin(distance, entity_distance, entity.shape.w);
+        } else {
+            distance = entity_distance;
+        }
+        processed_any = true;
+    }
+    return quadratic_smax(distance, -carve_distance, SUBTRACT_SMOOTHING);
+}
+
+// Distance from a point to a box, zero inside it
+fn box_distance(point: vec3<f32>, box_min: vec3<f32>, box_max: vec3<f32>) -> f32 {
+    return length(max(max(box_min - point, point - box_max), vec3<f32>(0.0)));
+}
+
+// Steps shorter than this many voxel diagonals are marched analytically, the
+// interpolated distance is too coarse that close to a surface
+const VOLUME_MIN_STEP_VOXELS: f32 = 2.0;
+
+// A step along any ray from the point that can't pass through the scene, taken
+// from the baked volume, or zero where the scene must be evaluated instead
+fn volume_cache_step(point: vec3<f32>) -> f32 {
+    if (sdf_settings.volume_enabled == 0u) {
+        return 0.0;
+    }
+
+    let volume_min = sdf_settings.volume_min;
+    let volume_max = sdf_settings.volume_max;
+    let resolution = vec3<f32>(textureDimensions(volume_texture));
+    let voxel_diagonal = length((volume_max - volume_min) / resolution);
+
+    // Outside the volume the baked entities are at least as far as the volume
+    var step = box_distance(point, volume_min, volume_max);
+    if (step <= 0.0) {
+        let uvw = (point - volume_min) / (volume_max - volume_min);
+        let baked = textureSampleLevel(volume_texture, volume_sampler, uvw, 0.0).r;
+        // Interpolating between voxels can overestimate by up to a voxel diagonal
+        step = baked - voxel_diagonal;
+    }
+
+    // Entities edited since the bake aren't in the volume
+    let dirty_distance = box_distance(
+        point,
+        sdf_settings.volume_dirty_min,
+        sdf_settings.volume_dirty_max,
+    );
+    step = min(step, dirty_distance);
+    if (sdf_settings.ground_enabled != 0u) {
+        // Blending with the ground pulls the surface in by up to a quarter of the blend radius
+        step = min(step, point.y - sdf_settings.ground_height - GROUND_SMOOTHING * 0.25);
+    }
+
+    return select(0.0, step, step >= voxel_diagonal * VOLUME_MIN_STEP_VOXELS);
 }
 
 // Evaluate SDF at a specific point using the scene data from the dedicated bind group
 fn evaluate_scene_sdf(point: vec3<f32>, steps: i32) -> SceneSdfResult {
     var result = init_scene_sdf_result(point, steps);
-    let smoothing_factor = 0.1; // Adjust for more/less blending
 
+    var processed_any = false;
+    var carve_distance = 999999.0;
+#ifdef SDF_VIEW_CULLING
+    for (var i = 0u; i < view_entities.count; i++) {
+        let entity = entities[view_entities.indices[i]];
+#else
     for (var i = 0u; i < sdf_settings.entity_count; i++) {
         let entity = entities[i];
+#endif
 
-        // Extract sphere properties using common utilities
-        let sphere_center = entity.xyz;
-        let sphere_radius = entity.w;
+        // Subtractive entities are accumulated separately and carved out at the end
+        if (entity.operation == OPERATION_SUBTRACT) {
+            carve_distance = min(carve_distance, entity_sdf(point, entity));
+            continue;
+        }
 
         // Use reusable combination function from common module
-        result = combine_sphere_into_scene_result(
+        result = combine_entity_into_scene_result(
             result,
             point,
-            sphere_center,
-            sphere_radius,
-            smoothing_factor,
-            i == 0u
+            entity,
+            entity.shape.w,
+            !processed_any
         );
+
+        processed_any = true;
     }
 
-    return result;
+    result = combine_ground_plane(result, point, processed_any);
+    return apply_clip_plane(apply_carve(result, carve_distance), point);
 }
 
 fn raymarch(uv: vec2<f32>, ray_origin: vec3<f32>, config: RaymarchConfig) -> SceneSdfResult {
+    var result = raymarch_surface(uv, ray_origin, config);
+
+    // If we're close enough to a surface, we've hit something
+    if (result.distance < config.surface_threshold) {
+        // Calculate normal at the surface point
+        result.normal = calculate_normal(result.position);
+    }
+    return result;
+}
+
+// Like raymarch, but leaves the normal zero, which saves the six scene
+// evaluations of its finite differences
+fn raymarch_surface(uv: vec2<f32>, ray_origin: vec3<f32>, config: RaymarchConfig) -> SceneSdfResult {
     // Ray direction using actual camera matrices
     let ray_dir = get_ray_direction(uv, sdf_settings.inverse_view_projection);
 
@@ -323,10 +963,7 @@ fn raymarch(uv: vec2<f32>, ray_origin: vec3<f32>, config: RaymarchConfig) -> Sce
 
         // If we're close enough to a surface, we've hit something
         if (sdf_result.distance < config.surface_threshold) {
-            // Calculate normal at the surface point
-            var result = sdf_result;
-            result.normal = calculate_normal(ray_pos);
-            return result;
+            return sdf_result;
         }
 
         // If we've traveled too far, we haven't hit anything
@@ -386,9 +1023,22 @@ fn raymarch_from_position_bvh(start_pos: vec3<f32>, ray_dir: vec3<f32>, config:
 
     // Use BVH to get candidate entities
     // let candidates = bvh_traverse_regarded();
-    var candidates = bvh_traverse_for_entities(start_pos, ray_dir);
+    var candidates = traverse_for_entities(start_pos, ray_dir);
+    var steps = config.max_steps;
     // Raymarching loop starting from given position with BVH acceleration
     for (var step = 0; step < config.max_steps; step++) {
+        // Far from any surface the baked volume gives a step without evaluating the entities
+        let cached_step = volume_cache_step(ray_pos);
+        if (cached_step > 0.0) {
+            if (total_distance > config.max_distance) {
+                steps = step;
+                break;
+            }
+            ray_pos += ray_dir * cached_step;
+            total_distance += cached_step;
+            continue;
+        }
+
         // let sdf_result = evaluate_scene_sdf(ray_pos, step);
         let sdf_result = evaluate_scene_sdf_with_bvh(ray_pos, &candidates, step);
 
@@ -402,6 +1052,7 @@ fn raymarch_from_position_bvh(start_pos: vec3<f32>, ray_dir: vec3<f32>, config:
 
         // If we've traveled too far, we haven't hit anything
         if (total_distance > config.max_distance) {
+            steps = step;
             break;
         }
 
@@ -414,5 +1065,6 @@ fn raymarch_from_position_bvh(start_pos: vec3<f32>, ray_dir: vec3<f32>, config:
     result.distance = config.max_distance;
     result.position = ray_pos;
     result.normal = vec3<f32>(0.0, 0.0, 0.0);
+    result.steps = steps;
     return result;
 }
@@ -1,14 +1,47 @@
-#import "shaders/sdf_common.wgsl"::{SceneSdfResult, raymarch, get_camera_position, default_raymarch_config}
+#import "shaders/sdf_common.wgsl"::{SceneSdfResult, evaluate_scene_sdf, raymarch_surface, raymarch_from_position, calculate_normal, get_ray_origin, get_inverse_view_projection, default_raymarch_config}
+
+// Must match GpuQueryPoint: viewport uvs in a.xy for ray marches, origin in
+// a.xyz and direction and max distance in b for raycasts, world positions in
+// a.xyz for projections
+struct QueryPoint {
+    a: vec4<f32>,
+    b: vec4<f32>,
+}
 
 // Input buffer for query points
-@group(0) @binding(0) var<storage, read> query_points: array<vec2<f32>>;
+@group(0) @binding(0) var<storage, read> query_points: array<QueryPoint>;
 
-struct OnlyDistance {
+// Normal first so the struct packs into 32 bytes, matching SdfResult
+struct QueryResult {
+    normal: vec3<f32>,
     distance: f32,
+    position: vec3<f32>,
+    _padding: f32,
 }
 
 // Output buffer for SDF results
-@group(0) @binding(1) var<storage, read_write> sdf_results: array<OnlyDistance>;
+@group(0) @binding(1) var<storage, read_write> sdf_results: array<QueryResult>;
+
+// Must match SdfComputeParams. The points of ray marches that asked for the
+// gradient come first, so only the first gradient_count points pay for it.
+// Raycasts follow up to raycast_end, then projections up to project_end, and
+// the points past it are only sampled
+struct ComputeParams {
+    points_count: u32,
+    gradient_count: u32,
+    raymarch_count: u32,
+    raycast_end: u32,
+    project_end: u32,
+    _padding0: u32,
+    _padding1: u32,
+    _padding2: u32,
+}
+
+@group(0) @binding(2) var<uniform> params: ComputeParams;
+
+// Steps along the gradient are exact for true distance fields, a few more
+// converge where blending bends the field
+const PROJECT_ITERATIONS: i32 = 4;
 
 // Note: SDF scene data (settings and transforms) are now in group 1 via sdf_common.wgsl
 
@@ -17,23 +50,90 @@ fn main(@builtin(global_invocation_id) global_id: vec3<u32>) {
     let index = global_id.x;
 
     // Check bounds
-    if (index >= arrayLength(&query_points)) {
+    if (index >= params.points_count) {
+        return;
+    }
+
+    if (index >= params.project_end) {
+        let point = query_points[index].a.xyz;
+        var sample: QueryResult;
+        sample.distance = evaluate_scene_sdf(point, 0).distance;
+        sample.position = point;
+        sample.normal = vec3<f32>(0.0);
+        sdf_results[index] = sample;
+        return;
+    }
+    if (index >= params.raycast_end) {
+        sdf_results[index] = project_to_surface(query_points[index].a.xyz);
+        return;
+    }
+    if (index >= params.raymarch_count) {
+        sdf_results[index] = raycast(query_points[index]);
         return;
     }
 
     // Get the query point
-    let point = query_points[index];
+    let point = query_points[index].a.xy;
 
     let config = default_raymarch_config();
 
-    // Ray origin (actual camera position)
-    let ray_origin = get_camera_position();
+    // Ray origin (camera position, or the near plane for orthographic views)
+    let ray_origin = get_ray_origin(point, get_inverse_view_projection());
 
-    let raymarch_result = raymarch(point, ray_origin, config);
+    let raymarch_result = raymarch_surface(point, ray_origin, config);
 
-    var result: OnlyDistance;
+    var result: QueryResult;
     result.distance = length(raymarch_result.position - ray_origin);
+    result.position = raymarch_result.position;
+    // Zero when the ray missed or the gradient wasn't requested
+    result.normal = vec3<f32>(0.0);
+    let hit = raymarch_result.distance < config.surface_threshold;
+    if (hit && index < params.gradient_count) {
+        result.normal = calculate_normal(raymarch_result.position);
+    }
 
     // Store result
     sdf_results[index] = result;
 }
+
+// Marches a world space ray, with the normal of the hit
+fn raycast(ray: QueryPoint) -> QueryResult {
+    var config = default_raymarch_config();
+    config.max_distance = ray.b.w;
+
+    let origin = ray.a.xyz;
+    let raymarch_result = raymarch_from_position(origin, normalize(ray.b.xyz), config);
+
+    var result: QueryResult;
+    result.position = raymarch_result.position;
+    // Zero when the ray missed
+    result.normal = raymarch_result.normal;
+    result.distance = config.max_distance;
+    if (raymarch_result.distance < config.surface_threshold) {
+        result.distance = length(raymarch_result.position - origin);
+    }
+    return result;
+}
+
+// Closest surface point: point - distance * gradient, repeated until the
+// distance left is below the surface threshold
+fn project_to_surface(point: vec3<f32>) -> QueryResult {
+    let config = default_raymarch_config();
+
+    var result: QueryResult;
+    result.distance = evaluate_scene_sdf(point, 0).distance;
+
+    var position = point;
+    var distance = result.distance;
+    for (var i = 0; i < PROJECT_ITERATIONS; i++) {
+        if (abs(distance) < config.surface_threshold) {
+            break;
+        }
+        position -= distance * calculate_normal(position);
+        distance = evaluate_scene_sdf(position, 0).distance;
+    }
+
+    result.position = position;
+    result.normal = calculate_normal(position);
+    return result;
+}
@@ -1,5 +1,5 @@
 #import bevy_core_pipeline::fullscreen_vertex_shader::FullscreenVertexOutput
-#import "shaders/sdf_common.wgsl"::{PostProcessSettings, SceneSdfResult, RaymarchConfig, default_raymarch_config, raymarch, get_camera_position, get_ray_direction, get_inverse_view_projection, raymarch_from_position, raymarch_from_position_bvh}
+#import "shaders/sdf_common.wgsl"::{PostProcessSettings, SceneSdfResult, RaymarchConfig, default_raymarch_config, raymarch, get_camera_position, get_ray_origin, get_ray_direction, get_inverse_view_projection, get_depth, clip_cap_color, get_debug_view, get_num_bvh_nodes, count_bvh_node_visits, get_previous_screen_position, viewport_to_target_uv, get_temporal_blend, get_temporal_jitter, get_coarse_size, background_color, grid_opacity, get_grid_color, apply_fog, isolation_focus, get_isolate_opacity, get_environment_intensity, get_light_count, soft_shadow, ambient_occlusion, raymarch_from_position, raymarch_from_position_bvh}
 
 @group(0) @binding(0) var screen_texture: texture_2d<f32>;
 @group(0) @binding(1) var texture_sampler: sampler;
@@ -10,40 +10,285 @@
 @group(0) @binding(4) var coarse_pass_texture: texture_2d<f32>;
 @group(0) @binding(5) var coarse_pass_sampler: sampler;
 
+@group(0) @binding(6) var environment_texture: texture_2d<f32>;
+@group(0) @binding(7) var environment_sampler: sampler;
+
+@group(0) @binding(8) var history_color: texture_2d<f32>;
+@group(0) @binding(9) var history_depth: texture_2d<f32>;
+
+// Lights of the scene (must match GpuSdfLight on the Rust side)
+struct SdfLight {
+    // xyz: position of a point light or direction towards a directional light,
+    // w: light kind
+    position: vec4<f32>,
+    // rgb: color times intensity, w: range of a point light
+    color: vec4<f32>,
+}
+
+@group(1) @binding(3) var<storage, read> lights: array<SdfLight>;
+
+// Fine march iterations of a sample of the pixels, read back to tune the coarse
+// pass (must match MarchStats in coarse_tuning.rs)
+struct MarchStats {
+    fine_steps: atomic<u32>,
+    pixels: atomic<u32>,
+}
+
+// Only one pixel per block of this size is counted, a counter per pixel would
+// serialize the pass on the atomics
+const MARCH_STATS_STRIDE: u32 = 8u;
+
+@group(1) @binding(4) var<storage, read_write> march_stats: MarchStats;
+
+const LIGHT_POINT: f32 = 0.0;
+const LIGHT_DIRECTIONAL: f32 = 1.0;
+
+// How far shadows of directional lights are marched
+const DIRECTIONAL_SHADOW_DISTANCE: f32 = 100.0;
+
+const PI: f32 = 3.14159265359;
+
+// Direct light arriving at a surface point from all scene lights
+fn direct_lighting(position: vec3<f32>, normal: vec3<f32>) -> vec3<f32> {
+    var total = vec3<f32>(0.0);
+    for (var i = 0u; i < get_light_count(); i++) {
+        let light = lights[i];
+        var light_dir = normalize(light.position.xyz);
+        var max_distance = DIRECTIONAL_SHADOW_DISTANCE;
+        var attenuation = 1.0;
+        if (light.position.w == LIGHT_POINT) {
+            let to_light = light.position.xyz - position;
+            max_distance = length(to_light);
+            light_dir = to_light / max_distance;
+            // Inverse square falloff, faded out towards the light's range
+            let range_fade = saturate(1.0 - pow(max_distance / light.color.w, 4.0));
+            attenuation = range_fade * range_fade / max(max_distance * max_distance, 0.0001);
+        }
+
+        let ndotl = dot(normal, light_dir);
+        if (ndotl <= 0.0 || attenuation <= 0.0) {
+            continue;
+        }
+        let shadow = soft_shadow(position, normal, light_dir, max_distance);
+        total += light.color.rgb * ndotl * attenuation * shadow;
+    }
+    return total;
+}
+
+// Radiance of the equirectangular environment map in a direction
+fn sample_environment(direction: vec3<f32>) -> vec3<f32> {
+    let u = atan2(direction.z, direction.x) / (2.0 * PI) + 0.5;
+    let v = acos(clamp(direction.y, -1.0, 1.0)) / PI;
+    // Explicit level, since this is only reached for pixels that hit the surface
+    return textureSampleLevel(environment_texture, environment_sampler, vec2<f32>(u, v), 0.0).rgb;
+}
+
+// Must match SdfDebugView::as_gpu
+const DEBUG_VIEW_SHADED: u32 = 0u;
+const DEBUG_VIEW_NORMALS: u32 = 1u;
+const DEBUG_VIEW_STEPS: u32 = 2u;
+const DEBUG_VIEW_DEPTH: u32 = 3u;
+const DEBUG_VIEW_BVH_VISITS: u32 = 4u;
+
+// Blue (0) over green to red (1)
+fn heatmap(t: f32) -> vec3<f32> {
+    let x = saturate(t);
+    let low = mix(vec3<f32>(0.0, 0.0, 1.0), vec3<f32>(0.0, 1.0, 0.0), saturate(x * 2.0));
+    return mix(low, vec3<f32>(1.0, 0.0, 0.0), saturate(x * 2.0 - 1.0));
+}
+
+// Diagnostic output for the non-shaded debug views
+fn debug_color(
+    debug_view: u32,
+    result: SceneSdfResult,
+    ray_origin: vec3<f32>,
+    ray_dir: vec3<f32>,
+    config: RaymarchConfig,
+) -> vec3<f32> {
+    let hit = result.distance < config.max_distance;
+    switch debug_view {
+        case DEBUG_VIEW_NORMALS: {
+            return select(vec3<f32>(0.0), result.normal * 0.5 + 0.5, hit);
+        }
+        case DEBUG_VIEW_STEPS: {
+            return heatmap(f32(result.steps) / f32(config.max_steps));
+        }
+        case DEBUG_VIEW_DEPTH: {
+            let distance = length(result.position - get_camera_position());
+            return select(vec3<f32>(0.0), vec3<f32>(1.0 - distance / config.max_distance), hit);
+        }
+        case DEBUG_VIEW_BVH_VISITS: {
+            let visits = count_bvh_node_visits(ray_origin, ray_dir);
+            return heatmap(f32(visits) / f32(max(get_num_bvh_nodes(), 1u)));
+        }
+        default: {
+            return vec3<f32>(0.0);
+        }
+    }
+}
+
+// Sky gradient and ground grid seen where a ray misses the scene
+fn backdrop(ray_origin: vec3<f32>, ray_dir: vec3<f32>, max_distance: f32) -> vec3<f32> {
+    let grid = grid_opacity(ray_origin, ray_dir, max_distance);
+    let color = mix(background_color(ray_dir), get_grid_color(), grid);
+    // As fogged as a surface at the far end of the march, so the two meet without an edge
+    return apply_fog(color, max_distance);
+}
+
+// Fraction of the march distance after which surfaces fade into the backdrop
+const FAR_FADE_START: f32 = 0.8;
+
+// Relative depth difference up to which history shows the same surface
+const HISTORY_DEPTH_TOLERANCE: f32 = 0.01;
+
+// Blends this frame's color with last frame's at the same surface point, if
+// that point was visible then
+fn accumulate(color: vec3<f32>, world_position: vec3<f32>) -> vec3<f32> {
+    let previous = get_previous_screen_position(world_position);
+    if (any(previous.xy < vec2<f32>(0.0)) || any(previous.xy > vec2<f32>(1.0))) {
+        return color;
+    }
+
+    // The history covers the whole render target, not just this camera's viewport
+    let size = textureDimensions(history_depth);
+    let history_uv = viewport_to_target_uv(previous.xy, vec2<f32>(size));
+    let texel = min(vec2<u32>(history_uv * vec2<f32>(size)), size - 1u);
+    let depth = textureLoad(history_depth, texel, 0).r;
+    if (abs(depth - previous.z) > previous.z * HISTORY_DEPTH_TOLERANCE) {
+        return color;
+    }
+
+    let history = textureSampleLevel(history_color, texture_sampler, history_uv, 0.0).rgb;
+    return mix(color, history, get_temporal_blend());
+}
+
+struct FragmentOutput {
+    @location(0) color: vec4<f32>,
+    // Depth of the marched surface, so later passes can depth test against it
+#ifdef SCALED_OUTPUT
+    // Below full resolution it goes to a target of its own, which the upscale
+    // pass writes into the view depth
+    @location(1) depth: f32,
+#else
+    @builtin(frag_depth) depth: f32,
+#endif
+#ifdef TEMPORAL_ACCUMULATION
+    // Read back as history next frame
+    @location(1) history_color: vec4<f32>,
+    @location(2) history_depth: f32,
+#endif
+}
+
+fn fragment_output(color: vec4<f32>, depth: f32) -> FragmentOutput {
+    var out: FragmentOutput;
+    out.color = color;
+    out.depth = depth;
+#ifdef TEMPORAL_ACCUMULATION
+    out.history_color = color;
+    out.history_depth = depth;
+#endif
+    return out;
+}
 
 @fragment
-fn fragment(in: FullscreenVertexOutput) -> @location(0) vec4<f32> {
+fn fragment(in: FullscreenVertexOutput) -> FragmentOutput {
     // Setup ray for raymarching using actual camera parameters
     let uv = in.uv;
 
-    // Sample coarse pass result
-    let coarse_distance = textureSample(coarse_pass_texture, coarse_pass_sampler, uv).r;
+    // Load the coarse pass texel covering this pixel
+    let coarse_size = get_coarse_size();
+    let coarse_texel = min(vec2<u32>(uv * vec2<f32>(coarse_size)), coarse_size - 1u);
+    let coarse_distance = textureLoad(coarse_pass_texture, coarse_texel, 0).r;
 
     let config = default_raymarch_config();
 
+#ifdef TEMPORAL_ACCUMULATION
+    // A different sub-pixel offset each frame, so accumulation anti-aliases edges
+    let ray_uv = uv + get_temporal_jitter();
+#else
+    let ray_uv = uv;
+#endif
+    // Ray origin (camera position, or the near plane for orthographic views)
+    let ray_origin = get_ray_origin(ray_uv, get_inverse_view_projection());
+    let ray_dir = get_ray_direction(ray_uv, get_inverse_view_projection());
+
     // Early termination: if coarse pass found nothing, return immediately
     if (coarse_distance >= config.max_distance) {
-        return vec4<f32>(1.0, 0.0, 0.0, 1.0);
+        let color = backdrop(ray_origin, ray_dir, config.max_distance);
+        return fragment_output(vec4<f32>(color, 1.0), 0.0);
     }
 
-    // Ray origin (actual camera position)
-    let ray_origin = get_camera_position();
-    let ray_dir = get_ray_direction(uv, get_inverse_view_projection());
-
     // Start raymarching from coarse distance
     let start_pos = ray_origin + ray_dir * (coarse_distance);
 
     // Perform fine raymarching starting from the coarse position with BVH acceleration
     let result = raymarch_from_position_bvh(start_pos, ray_dir, config);
 
+    let pixel = vec2<u32>(in.position.xy);
+    if (all(pixel % MARCH_STATS_STRIDE == vec2<u32>(0u))) {
+        atomicAdd(&march_stats.fine_steps, u32(max(result.steps, 0)));
+        atomicAdd(&march_stats.pixels, 1u);
+    }
+
+    let debug_view = get_debug_view();
+    if (debug_view != DEBUG_VIEW_SHADED) {
+        let color = debug_color(debug_view, result, start_pos, ray_dir, config);
+        let depth = select(0.0, get_depth(result.position), result.distance < config.max_distance);
+        return fragment_output(vec4<f32>(color, 1.0), depth);
+    }
+
     if (result.distance < config.max_distance) {
-        // Simple lighting calculation using surface normal from raymarch result
+        // The cut face of the clip plane is drawn flat, so it reads as a section
+        let cap = clip_cap_color(result.position, result.normal);
+        if (cap.a > 0.0) {
+            return fragment_output(vec4<f32>(cap.rgb, 1.0), get_depth(result.position));
+        }
+
+        // Diffuse lighting from the scene lights, shadowed by the rest of the scene
         let normal = result.normal;
-        let light_dir = normalize(vec3<f32>(1.0, 1.0, 1.0));
-        let diffuse = max(dot(normal, light_dir), 0.1);
+        let occlusion = ambient_occlusion(result.position, normal);
+        let diffuse = max(direct_lighting(result.position, normal), vec3<f32>(0.1)) * occlusion;
+        var color = diffuse;
+
+        // Ambient light from the environment around the normal, plus a
+        // Fresnel-weighted reflection of it
+        let environment_intensity = get_environment_intensity();
+        if (environment_intensity > 0.0) {
+            let ambient = sample_environment(normal) * occlusion;
+            let reflected = sample_environment(reflect(ray_dir, normal));
+            let fresnel = 0.04 + 0.96 * pow(1.0 - max(dot(normal, -ray_dir), 0.0), 5.0);
+            color = color * (0.5 + 0.5 * ambient * environment_intensity)
+                + reflected * fresnel * occlusion * environment_intensity;
+        }
+
+        // In the isolate view everything but the selection is a faint gray ghost
+        let focus = isolation_focus(result.position);
+        if (focus < 1.0) {
+            let gray = vec3<f32>(dot(color, vec3<f32>(0.2126, 0.7152, 0.0722)));
+            let ghost = mix(backdrop(ray_origin, ray_dir, config.max_distance), gray, get_isolate_opacity());
+            color = mix(ghost, color, focus);
+        }
+
+        // Grid lines on the ground plane, or in front of what lies below it
+        let surface_distance = length(result.position - ray_origin);
+        color = mix(color, get_grid_color(), grid_opacity(ray_origin, ray_dir, surface_distance));
+
+        // Fog, and a fade into the backdrop before the march gives up
+        color = apply_fog(color, surface_distance);
+        let far_fade = smoothstep(
+            config.max_distance * FAR_FADE_START,
+            config.max_distance,
+            surface_distance,
+        );
+        color = mix(color, backdrop(ray_origin, ray_dir, config.max_distance), far_fade);
+
+#ifdef TEMPORAL_ACCUMULATION
+        color = accumulate(color, result.position);
+#endif
 
-        return vec4<f32>(diffuse, diffuse, diffuse, 1.0);
+        return fragment_output(vec4<f32>(color, 1.0), get_depth(result.position));
     }
 
-    return vec4<f32>(0.0, 0.0, 0.0, 1.0);
+    let color = backdrop(ray_origin, ray_dir, config.max_distance);
+    return fragment_output(vec4<f32>(color, 1.0), 0.0);
 }
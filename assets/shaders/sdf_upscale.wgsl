@@ -0,0 +1,22 @@
+#import bevy_core_pipeline::fullscreen_vertex_shader::FullscreenVertexOutput
+
+@group(0) @binding(0) var scaled_color: texture_2d<f32>;
+@group(0) @binding(1) var scaled_sampler: sampler;
+@group(0) @binding(2) var scaled_depth: texture_2d<f32>;
+
+struct FragmentOutput {
+    @location(0) color: vec4<f32>,
+    @builtin(frag_depth) depth: f32,
+}
+
+@fragment
+fn fragment(in: FullscreenVertexOutput) -> FragmentOutput {
+    let color = textureSample(scaled_color, scaled_sampler, in.uv);
+
+    // Depth can't be blended across silhouettes, so take the nearest texel
+    let size = textureDimensions(scaled_depth);
+    let texel = min(vec2<u32>(in.uv * vec2<f32>(size)), size - 1u);
+    let depth = textureLoad(scaled_depth, texel, 0).r;
+
+    return FragmentOutput(color, depth);
+}
@@ -0,0 +1,30 @@
+#import "shaders/sdf_common.wgsl"::evaluate_entities_sdf
+
+// Where the bake of this frame goes (must match VolumeBakeParams in sdf_volume_cache.rs)
+struct VolumeBakeParams {
+    volume_min: vec3<f32>,
+    entity_count: u32,
+    volume_max: vec3<f32>,
+    first_slice: u32,
+}
+
+@group(0) @binding(0) var volume: texture_storage_3d<rgba16float, write>;
+@group(0) @binding(1) var<uniform> params: VolumeBakeParams;
+
+// Entities are read from group 1 by sdf_common.wgsl
+
+// Must match BAKE_WORKGROUP_SIZE in sdf_volume_cache.rs
+@compute @workgroup_size(4, 4, 4)
+fn bake(@builtin(global_invocation_id) global_id: vec3<u32>) {
+    let size = textureDimensions(volume);
+    let voxel = vec3<u32>(global_id.xy, global_id.z + params.first_slice);
+    if (any(voxel >= size)) {
+        return;
+    }
+
+    // Distances are taken at voxel centers, where the SDF pass samples them
+    let uvw = (vec3<f32>(voxel) + 0.5) / vec3<f32>(size);
+    let point = mix(params.volume_min, params.volume_max, uvw);
+    let distance = evaluate_entities_sdf(point, params.entity_count);
+    textureStore(volume, voxel, vec4<f32>(distance, 0.0, 0.0, 1.0));
+}
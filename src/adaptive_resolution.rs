@@ -0,0 +1,295 @@
+//! Dynamic resolution for the SDF pass
+//!
+//! When enabled, the resolution the SDF is marched at follows the GPU time of
+//! the SDF pass (or the frame time where timestamp queries aren't available).
+//! Below full resolution the pass renders color and depth into smaller
+//! intermediate targets, which an upscale pass then stretches over the view
+//! target and depth buffer.
+
+use bevy::{
+    core_pipeline::{
+        core_3d::CORE_3D_DEPTH_FORMAT, fullscreen_vertex_shader::fullscreen_shader_vertex_state,
+    },
+    diagnostic::{DiagnosticsStore, FrameTimeDiagnosticsPlugin},
+    prelude::*,
+    render::{
+        extract_resource::{ExtractResource, ExtractResourcePlugin},
+        render_resource::{
+            binding_types::{sampler, texture_2d},
+            *,
+        },
+        renderer::RenderDevice,
+        Render, RenderApp, RenderSet,
+    },
+};
+
+use crate::sdf_render::{
+    SDFRenderSettings, SdfPassConfig, SdfRenderCamera, SDF_RENDER_PASS_GPU_TIME,
+};
+
+const UPSCALE_SHADER_ASSET_PATH: &str = "shaders/sdf_upscale.wgsl";
+
+// Format of the intermediate depth target (the marched depth, not a depth buffer)
+pub const SCALED_DEPTH_FORMAT: TextureFormat = TextureFormat::R32Float;
+
+// Per-frame scale changes; shrinking reacts faster than growing back
+const SCALE_DOWN_STEP: f32 = 0.05;
+const SCALE_UP_STEP: f32 = 0.01;
+
+pub struct AdaptiveResolutionPlugin;
+
+// Settings and current state of the dynamic resolution
+#[derive(Resource, Clone)]
+pub struct SdfAdaptiveResolution {
+    pub enabled: bool,
+    // Milliseconds the SDF pass may take before the resolution drops
+    pub target_frame_time: f32,
+    // Lowest fraction of the viewport resolution the pass may render at
+    pub min_scale: f32,
+    // Fraction of the viewport resolution the pass currently renders at
+    pub scale: f32,
+}
+
+impl Default for SdfAdaptiveResolution {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            target_frame_time: 16.0,
+            min_scale: 0.5,
+            scale: 1.0,
+        }
+    }
+}
+
+impl ExtractResource for SdfAdaptiveResolution {
+    type Source = SdfAdaptiveResolution;
+
+    fn extract_resource(source: &Self::Source) -> Self {
+        source.clone()
+    }
+}
+
+// Intermediate targets the SDF pass renders into below full resolution
+#[derive(Resource)]
+pub struct ScaledSdfTargets {
+    pub color_texture: Texture,
+    pub color_view: TextureView,
+    pub depth_texture: Texture,
+    pub depth_view: TextureView,
+    pub size: Extent3d,
+}
+
+// Stretches the scaled targets over the view target and depth buffer
+#[derive(Resource)]
+pub struct SdfUpscalePipeline {
+    pub layout: BindGroupLayout,
+    pub sampler: Sampler,
+    pub pipeline_id: CachedRenderPipelineId,
+}
+
+impl Plugin for AdaptiveResolutionPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SdfAdaptiveResolution>()
+            .add_plugins(ExtractResourcePlugin::<SdfAdaptiveResolution>::default())
+            .add_systems(Update, adjust_resolution_scale);
+
+        let Some(render_app) = app.get_sub_app_mut(RenderApp) else {
+            return;
+        };
+
+        render_app.add_systems(
+            Render,
+            manage_scaled_targets.in_set(RenderSet::PrepareResources),
+        );
+    }
+
+    fn finish(&self, app: &mut App) {
+        let Some(render_app) = app.get_sub_app_mut(RenderApp) else {
+            return;
+        };
+
+        render_app.init_resource::<SdfUpscalePipeline>();
+    }
+}
+
+fn adjust_resolution_scale(
+    diagnostics: Res<DiagnosticsStore>,
+    mut adaptive: ResMut<SdfAdaptiveResolution>,
+) {
+    if !adaptive.enabled {
+        if adaptive.scale != 1.0 {
+            adaptive.scale = 1.0;
+        }
+        return;
+    }
+
+    let Some(frame_time) = sdf_pass_time(&diagnostics) else {
+        return;
+    };
+
+    // Leave some slack around the target so the scale doesn't oscillate
+    let target = adaptive.target_frame_time as f64;
+    let scale = if frame_time > target * 1.1 {
+        adaptive.scale - SCALE_DOWN_STEP
+    } else if frame_time < target * 0.8 {
+        adaptive.scale + SCALE_UP_STEP
+    } else {
+        return;
+    };
+
+    let scale = scale.clamp(adaptive.min_scale, 1.0);
+    if scale != adaptive.scale {
+        adaptive.scale = scale;
+    }
+}
+
+// Smoothed milliseconds spent on the SDF pass. Prefers its GPU time, the frame
+// time is only a rough stand-in where timestamp queries aren't supported
+pub fn sdf_pass_time(diagnostics: &DiagnosticsStore) -> Option<f64> {
+    diagnostics
+        .get(&SDF_RENDER_PASS_GPU_TIME)
+        .and_then(|diagnostic| diagnostic.smoothed())
+        .or_else(|| {
+            diagnostics
+                .get(&FrameTimeDiagnosticsPlugin::FRAME_TIME)
+                .and_then(|diagnostic| diagnostic.smoothed())
+        })
+}
+
+fn create_target(
+    render_device: &RenderDevice,
+    label: &str,
+    size: Extent3d,
+    format: TextureFormat,
+) -> Texture {
+    render_device.create_texture(&TextureDescriptor {
+        label: Some(label),
+        size,
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: TextureDimension::D2,
+        format,
+        usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING,
+        view_formats: &[],
+    })
+}
+
+fn manage_scaled_targets(
+    mut commands: Commands,
+    render_device: Res<RenderDevice>,
+    config: Res<SdfPassConfig>,
+    adaptive: Option<Res<SdfAdaptiveResolution>>,
+    targets: Option<Res<ScaledSdfTargets>>,
+    camera_query: Query<&SDFRenderSettings, With<SdfRenderCamera>>,
+) {
+    let scale = adaptive.map_or(1.0, |adaptive| adaptive.scale);
+    let Ok(settings) = camera_query.single() else {
+        return;
+    };
+
+    // At full resolution the SDF pass renders straight into the view target
+    if scale >= 1.0 {
+        if targets.is_some() {
+            commands.remove_resource::<ScaledSdfTargets>();
+        }
+        return;
+    }
+
+    let scaled_size = (settings.viewport_size * scale).as_uvec2().max(UVec2::ONE);
+    let desired_size = Extent3d {
+        width: scaled_size.x,
+        height: scaled_size.y,
+        depth_or_array_layers: 1,
+    };
+
+    if targets.is_some_and(|targets| targets.size == desired_size) {
+        return;
+    }
+
+    let color_texture = create_target(
+        &render_device,
+        "sdf_scaled_color_texture",
+        desired_size,
+        config.color_format(),
+    );
+    let depth_texture = create_target(
+        &render_device,
+        "sdf_scaled_depth_texture",
+        desired_size,
+        SCALED_DEPTH_FORMAT,
+    );
+
+    commands.insert_resource(ScaledSdfTargets {
+        color_view: color_texture.create_view(&TextureViewDescriptor::default()),
+        depth_view: depth_texture.create_view(&TextureViewDescriptor::default()),
+        color_texture,
+        depth_texture,
+        size: desired_size,
+    });
+}
+
+impl FromWorld for SdfUpscalePipeline {
+    fn from_world(world: &mut World) -> Self {
+        let color_format = world.resource::<SdfPassConfig>().color_format();
+        let render_device = world.resource::<RenderDevice>();
+
+        let layout = render_device.create_bind_group_layout(
+            "sdf_upscale_bind_group_layout",
+            &BindGroupLayoutEntries::sequential(
+                ShaderStages::FRAGMENT,
+                (
+                    // The scaled color target
+                    texture_2d(TextureSampleType::Float { filterable: true }),
+                    sampler(SamplerBindingType::Filtering),
+                    // The scaled depth target, loaded without filtering
+                    texture_2d(TextureSampleType::Float { filterable: false }),
+                ),
+            ),
+        );
+
+        let sampler = render_device.create_sampler(&SamplerDescriptor {
+            mag_filter: FilterMode::Linear,
+            min_filter: FilterMode::Linear,
+            ..default()
+        });
+
+        let shader = world.load_asset(UPSCALE_SHADER_ASSET_PATH);
+
+        let pipeline_id =
+            world
+                .resource_mut::<PipelineCache>()
+                .queue_render_pipeline(RenderPipelineDescriptor {
+                    label: Some("sdf_upscale_pipeline".into()),
+                    layout: vec![layout.clone()],
+                    vertex: fullscreen_shader_vertex_state(),
+                    fragment: Some(FragmentState {
+                        shader,
+                        shader_defs: vec![],
+                        entry_point: "fragment".into(),
+                        targets: vec![Some(ColorTargetState {
+                            format: color_format,
+                            blend: None,
+                            write_mask: ColorWrites::ALL,
+                        })],
+                    }),
+                    primitive: PrimitiveState::default(),
+                    // Overwrites the view depth like the full resolution SDF pass does
+                    depth_stencil: Some(DepthStencilState {
+                        format: CORE_3D_DEPTH_FORMAT,
+                        depth_write_enabled: true,
+                        depth_compare: CompareFunction::Always,
+                        stencil: StencilState::default(),
+                        bias: DepthBiasState::default(),
+                    }),
+                    multisample: MultisampleState::default(),
+                    push_constant_ranges: vec![],
+                    zero_initialize_workgroup_memory: false,
+                });
+
+        Self {
+            layout,
+            sampler,
+            pipeline_id,
+        }
+    }
+}
@@ -0,0 +1,94 @@
+use bevy::prelude::*;
+
+use crate::command_bridge::{align_selected, distribute_selected};
+use crate::mode::{AppMode, AppModeState};
+use crate::translation::DragData;
+
+// Keyboard shortcuts for aligning and distributing the selection
+pub struct AlignPlugin;
+
+impl Plugin for AlignPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, handle_align_shortcuts);
+    }
+}
+
+// Which edge of the selection the entities are lined up with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AlignMode {
+    Min,
+    Center,
+    Max,
+}
+
+impl AlignMode {
+    pub fn parse(mode: &str) -> Option<Self> {
+        match mode {
+            "Min" => Some(AlignMode::Min),
+            "Center" => Some(AlignMode::Center),
+            "Max" => Some(AlignMode::Max),
+            _ => None,
+        }
+    }
+
+    // Coordinate every entity is moved to, given their coordinates along the axis
+    pub fn target(self, values: &[f32]) -> f32 {
+        let min = values.iter().copied().fold(f32::INFINITY, f32::min);
+        let max = values.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+        match self {
+            AlignMode::Min => min,
+            AlignMode::Center => (min + max) * 0.5,
+            AlignMode::Max => max,
+        }
+    }
+}
+
+// Evenly spaced coordinates between the outermost entities, in the same order
+// as `values`; the outermost ones stay in place
+pub fn distribute_targets(values: &[f32]) -> Vec<f32> {
+    if values.len() < 3 {
+        return values.to_vec();
+    }
+    let mut order: Vec<usize> = (0..values.len()).collect();
+    order.sort_by(|a, b| values[*a].total_cmp(&values[*b]));
+
+    let first = values[order[0]];
+    let spacing = (values[order[order.len() - 1]] - first) / (values.len() - 1) as f32;
+
+    let mut targets = vec![0.; values.len()];
+    for (rank, index) in order.into_iter().enumerate() {
+        targets[index] = first + spacing * rank as f32;
+    }
+    targets
+}
+
+// Alt+X/Y/Z aligns the selection's centers on that axis,
+// Alt+Shift+X/Y/Z distributes the selection along it
+fn handle_align_shortcuts(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    app_mode: Res<AppModeState>,
+    drag_data: Res<DragData>,
+) {
+    if !app_mode.is_mode(AppMode::Translate) || !matches!(*drag_data, DragData::Idle) {
+        return;
+    }
+    if !keyboard_input.any_pressed([KeyCode::AltLeft, KeyCode::AltRight]) {
+        return;
+    }
+    let shift = keyboard_input.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+
+    for (key, axis) in [
+        (KeyCode::KeyX, "X"),
+        (KeyCode::KeyY, "Y"),
+        (KeyCode::KeyZ, "Z"),
+    ] {
+        if !keyboard_input.just_pressed(key) {
+            continue;
+        }
+        if shift {
+            distribute_selected(axis);
+        } else {
+            align_selected(axis, "Center");
+        }
+    }
+}
@@ -0,0 +1,423 @@
+//! Brick map acceleration structure
+//!
+//! An alternative to the BVH for gathering the entities a ray may hit: a
+//! uniform grid of bricks over the scene, each listing the entities that
+//! overlap it. A compute pass rebuilds the lists from the entity buffer
+//! whenever the entities change, which stays cheap for dense scenes where
+//! rebuilding the BVH is not, and the SDF pass steps through the bricks along
+//! each ray front to back. Which structure is used is picked with the
+//! `AccelerationStructure` resource; F9 benchmarks both on the current scene.
+
+use bevy::{
+    core_pipeline::core_3d::graph::Core3d,
+    diagnostic::DiagnosticsStore,
+    prelude::*,
+    render::{
+        extract_resource::{ExtractResource, ExtractResourcePlugin},
+        render_graph::{self, RenderGraphApp, RenderLabel},
+        render_resource::{binding_types::*, *},
+        renderer::{RenderContext, RenderDevice},
+        Render, RenderApp, RenderSet,
+    },
+};
+use bytemuck::{Pod, Zeroable};
+
+use crate::adaptive_resolution::sdf_pass_time;
+use crate::sdf_render::{
+    EntityBuffer, EntityData, SDFCoarsePrepassLabel, SDFRenderSettings, SdfRenderCamera,
+};
+
+const SHADER_ASSET_PATH: &str = "shaders/brick_map_build.wgsl";
+
+// Bricks along each axis of the grid (must match sdf_common.wgsl)
+pub const BRICK_MAP_RESOLUTION: u32 = 32;
+// Entities listed per brick, the rest are dropped (must match sdf_common.wgsl)
+pub const MAX_BRICK_ENTITIES: u32 = 32;
+
+// Entities the build pass handles per invocation group (must match brick_map_build.wgsl)
+const BUILD_WORKGROUP_SIZE: u32 = 64;
+
+// Frames a benchmark waits after switching structures, and then measures
+const BENCHMARK_WARMUP_FRAMES: u32 = 30;
+const BENCHMARK_SAMPLE_FRAMES: u32 = 120;
+
+pub struct BrickMapPlugin;
+
+// How the SDF pass finds the entities along a ray
+#[derive(Resource, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum AccelerationStructure {
+    #[default]
+    Bvh,
+    BrickMap,
+}
+
+impl AccelerationStructure {
+    const ALL: [AccelerationStructure; 2] =
+        [AccelerationStructure::Bvh, AccelerationStructure::BrickMap];
+
+    // Must match the ACCELERATION_* constants in sdf_common.wgsl
+    pub fn as_gpu(&self) -> u32 {
+        match self {
+            AccelerationStructure::Bvh => 0,
+            AccelerationStructure::BrickMap => 1,
+        }
+    }
+}
+
+#[derive(Event)]
+pub struct StartAccelerationBenchmark;
+
+// Times the SDF pass with each acceleration structure in turn and logs the results
+#[derive(Resource, Default)]
+pub struct AccelerationBenchmark {
+    active: Option<BenchmarkRun>,
+}
+
+struct BenchmarkRun {
+    // Structure to switch back to afterwards
+    restore: AccelerationStructure,
+    structure: usize,
+    frame: u32,
+    samples: Vec<f64>,
+    results: Vec<(AccelerationStructure, f64)>,
+}
+
+// Grid of the brick map as last built, extracted for the build pass
+#[derive(Resource, Clone, Default)]
+struct BrickMapGrid {
+    min: Vec3,
+    max: Vec3,
+    entity_count: u32,
+    // Bumped whenever the bricks need building again
+    generation: u32,
+}
+
+impl ExtractResource for BrickMapGrid {
+    type Source = BrickMapGrid;
+
+    fn extract_resource(source: &Self::Source) -> Self {
+        source.clone()
+    }
+}
+
+impl Plugin for BrickMapPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<AccelerationStructure>()
+            .init_resource::<AccelerationBenchmark>()
+            .init_resource::<BrickMapGrid>()
+            .add_event::<StartAccelerationBenchmark>()
+            .add_plugins(ExtractResourcePlugin::<BrickMapGrid>::default())
+            .add_systems(Update, run_acceleration_benchmark)
+            // After the entity data of the frame has been collected
+            .add_systems(
+                PostUpdate,
+                (update_brick_map_grid, update_acceleration_in_settings).chain(),
+            );
+
+        let Some(render_app) = app.get_sub_app_mut(RenderApp) else {
+            return;
+        };
+
+        render_app
+            .add_systems(
+                Render,
+                prepare_brick_map_build.in_set(RenderSet::PrepareBindGroups),
+            )
+            .add_render_graph_node::<BrickMapBuildNode>(Core3d, BrickMapBuildLabel)
+            .add_render_graph_edge(Core3d, BrickMapBuildLabel, SDFCoarsePrepassLabel);
+    }
+
+    fn finish(&self, app: &mut App) {
+        let Some(render_app) = app.get_sub_app_mut(RenderApp) else {
+            return;
+        };
+
+        render_app
+            .init_resource::<BrickMapBuffers>()
+            .init_resource::<BrickMapBuildPipeline>();
+    }
+}
+
+// Fits the grid around the entities whenever they change, while the brick map is in use
+fn update_brick_map_grid(
+    structure: Res<AccelerationStructure>,
+    entity_data: Option<Res<EntityData>>,
+    mut grid: ResMut<BrickMapGrid>,
+) {
+    let Some(entity_data) = entity_data else {
+        return;
+    };
+    if *structure != AccelerationStructure::BrickMap
+        || !(entity_data.is_changed() || structure.is_changed())
+    {
+        return;
+    }
+
+    if let Some((min, max)) = entity_data.bounds() {
+        grid.min = min;
+        grid.max = max;
+    }
+    grid.entity_count = entity_data.entities().len() as u32;
+    grid.generation = grid.generation.wrapping_add(1);
+}
+
+fn update_acceleration_in_settings(
+    structure: Res<AccelerationStructure>,
+    grid: Res<BrickMapGrid>,
+    mut camera_query: Query<&mut SDFRenderSettings, With<SdfRenderCamera>>,
+) {
+    for mut settings in camera_query.iter_mut() {
+        settings.acceleration_structure = structure.as_gpu();
+        settings.brick_grid_min = grid.min;
+        settings.brick_grid_max = grid.max;
+    }
+}
+
+fn run_acceleration_benchmark(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut start_events: EventReader<StartAccelerationBenchmark>,
+    diagnostics: Res<DiagnosticsStore>,
+    entity_data: Option<Res<EntityData>>,
+    mut benchmark: ResMut<AccelerationBenchmark>,
+    mut structure: ResMut<AccelerationStructure>,
+) {
+    let start = start_events.read().count() > 0 || keyboard_input.just_pressed(KeyCode::F9);
+    if start && benchmark.active.is_none() {
+        info!("Benchmarking acceleration structures");
+        benchmark.active = Some(BenchmarkRun {
+            restore: *structure,
+            structure: 0,
+            frame: 0,
+            samples: Vec::new(),
+            results: Vec::new(),
+        });
+    }
+    let Some(run) = &mut benchmark.active else {
+        return;
+    };
+
+    let current = AccelerationStructure::ALL[run.structure];
+    if *structure != current {
+        *structure = current;
+    }
+    run.frame += 1;
+    if run.frame <= BENCHMARK_WARMUP_FRAMES {
+        return;
+    }
+    if let Some(time) = sdf_pass_time(&diagnostics) {
+        run.samples.push(time);
+    }
+    if run.frame < BENCHMARK_WARMUP_FRAMES + BENCHMARK_SAMPLE_FRAMES {
+        return;
+    }
+
+    let average = run.samples.iter().sum::<f64>() / run.samples.len().max(1) as f64;
+    run.results.push((current, average));
+    run.samples.clear();
+    run.frame = 0;
+    run.structure += 1;
+    if run.structure < AccelerationStructure::ALL.len() {
+        return;
+    }
+
+    let entity_count = entity_data.map_or(0, |data| data.entities().len());
+    for (structure, average) in &run.results {
+        info!(
+            "{:?}: {:.2} ms in the SDF pass with {} entities",
+            structure, average, entity_count
+        );
+    }
+    *structure = run.restore;
+    benchmark.active = None;
+}
+
+// Entity lists of the bricks in the render world
+#[derive(Resource)]
+pub struct BrickMapBuffers {
+    // Entities overlapping each brick, including those that didn't fit its list
+    pub counts: Buffer,
+    // MAX_BRICK_ENTITIES entity indices per brick
+    pub entities: Buffer,
+    built_generation: u32,
+}
+
+impl FromWorld for BrickMapBuffers {
+    fn from_world(world: &mut World) -> Self {
+        let render_device = world.resource::<RenderDevice>();
+        let bricks = (BRICK_MAP_RESOLUTION * BRICK_MAP_RESOLUTION * BRICK_MAP_RESOLUTION) as u64;
+
+        let counts = render_device.create_buffer(&BufferDescriptor {
+            label: Some("brick_map_counts_buffer"),
+            size: bricks * 4,
+            usage: BufferUsages::STORAGE | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let entities = render_device.create_buffer(&BufferDescriptor {
+            label: Some("brick_map_entities_buffer"),
+            size: bricks * MAX_BRICK_ENTITIES as u64 * 4,
+            usage: BufferUsages::STORAGE,
+            mapped_at_creation: false,
+        });
+
+        Self {
+            counts,
+            entities,
+            built_generation: 0,
+        }
+    }
+}
+
+// Grid of the build (must match BrickMapParams in brick_map_build.wgsl)
+#[repr(C)]
+#[derive(Clone, Copy, Pod, Zeroable)]
+struct BrickMapParams {
+    grid_min: Vec3,
+    entity_count: u32,
+    grid_max: Vec3,
+    __padding: u32,
+}
+
+#[derive(Resource)]
+struct BrickMapBuildBindGroup {
+    bind_group: BindGroup,
+    entity_count: u32,
+}
+
+// Sets up a build for a new generation of the grid once the pipeline is ready
+fn prepare_brick_map_build(
+    mut commands: Commands,
+    grid: Option<Res<BrickMapGrid>>,
+    mut buffers: ResMut<BrickMapBuffers>,
+    pipeline: Res<BrickMapBuildPipeline>,
+    pipeline_cache: Res<PipelineCache>,
+    entity_buffer: Res<EntityBuffer>,
+    render_device: Res<RenderDevice>,
+) {
+    commands.remove_resource::<BrickMapBuildBindGroup>();
+    let Some(grid) = grid.filter(|grid| grid.generation != buffers.built_generation) else {
+        return;
+    };
+    if pipeline_cache.get_compute_pipeline(pipeline.pipeline).is_none() {
+        return;
+    }
+    let Some(entity_buffer) = &entity_buffer.buffer else {
+        return;
+    };
+
+    let params = BrickMapParams {
+        grid_min: grid.min,
+        entity_count: grid.entity_count,
+        grid_max: grid.max,
+        __padding: 0,
+    };
+    let params_buffer = render_device.create_buffer_with_data(&BufferInitDescriptor {
+        label: Some("brick_map_params"),
+        contents: bytemuck::bytes_of(&params),
+        usage: BufferUsages::UNIFORM,
+    });
+
+    let bind_group = render_device.create_bind_group(
+        Some("brick_map_build_bind_group"),
+        &pipeline.layout,
+        &BindGroupEntries::sequential((
+            params_buffer.as_entire_binding(),
+            entity_buffer.as_entire_binding(),
+            buffers.counts.as_entire_binding(),
+            buffers.entities.as_entire_binding(),
+        )),
+    );
+
+    buffers.built_generation = grid.generation;
+    commands.insert_resource(BrickMapBuildBindGroup {
+        bind_group,
+        entity_count: grid.entity_count,
+    });
+}
+
+#[derive(Resource)]
+struct BrickMapBuildPipeline {
+    layout: BindGroupLayout,
+    pipeline: CachedComputePipelineId,
+}
+
+impl FromWorld for BrickMapBuildPipeline {
+    fn from_world(world: &mut World) -> Self {
+        let render_device = world.resource::<RenderDevice>();
+
+        let layout = render_device.create_bind_group_layout(
+            Some("brick_map_build_layout"),
+            &BindGroupLayoutEntries::sequential(
+                ShaderStages::COMPUTE,
+                (
+                    uniform_buffer_sized(false, None),
+                    // Entities
+                    storage_buffer_read_only_sized(false, None),
+                    // Brick counts
+                    storage_buffer_sized(false, None),
+                    // Brick entity lists
+                    storage_buffer_sized(false, None),
+                ),
+            ),
+        );
+
+        let shader = world.load_asset(SHADER_ASSET_PATH);
+        let pipeline_cache = world.resource::<PipelineCache>();
+        let pipeline = pipeline_cache.queue_compute_pipeline(ComputePipelineDescriptor {
+            label: Some("brick_map_build_pipeline".into()),
+            layout: vec![layout.clone()],
+            push_constant_ranges: Vec::new(),
+            shader,
+            shader_defs: Vec::new(),
+            entry_point: "build".into(),
+            zero_initialize_workgroup_memory: false,
+        });
+
+        Self { layout, pipeline }
+    }
+}
+
+#[derive(Debug, Hash, PartialEq, Eq, Clone, RenderLabel)]
+struct BrickMapBuildLabel;
+
+// Rebuilds the brick lists ahead of the SDF passes. The graph runs it for every
+// view, which builds the same lists again but is otherwise harmless
+#[derive(Default)]
+struct BrickMapBuildNode;
+
+impl render_graph::Node for BrickMapBuildNode {
+    fn run(
+        &self,
+        _graph: &mut render_graph::RenderGraphContext,
+        render_context: &mut RenderContext,
+        world: &World,
+    ) -> Result<(), render_graph::NodeRunError> {
+        let Some(build) = world.get_resource::<BrickMapBuildBindGroup>() else {
+            return Ok(());
+        };
+        let pipeline = world.resource::<BrickMapBuildPipeline>();
+        let Some(compute_pipeline) = world
+            .resource::<PipelineCache>()
+            .get_compute_pipeline(pipeline.pipeline)
+        else {
+            return Ok(());
+        };
+
+        // The entities add themselves to the bricks they overlap, starting from empty lists
+        let buffers = world.resource::<BrickMapBuffers>();
+        render_context
+            .command_encoder()
+            .clear_buffer(&buffers.counts, 0, None);
+
+        let mut pass = render_context
+            .command_encoder()
+            .begin_compute_pass(&ComputePassDescriptor {
+                label: Some("brick_map_build_pass"),
+                ..default()
+            });
+        pass.set_pipeline(compute_pipeline);
+        pass.set_bind_group(0, &build.bind_group, &[]);
+        pass.dispatch_workgroups(build.entity_count.div_ceil(BUILD_WORKGROUP_SIZE), 1, 1);
+
+        Ok(())
+    }
+}
@@ -1,90 +1,1257 @@
+use bevy::input::touch::{ForceTouch, TouchInput, TouchPhase};
 use bevy::prelude::*;
-use bevy::tasks::Task;
+use bevy::tasks::{block_on, futures_lite::future, Task};
 use bevy::window::PrimaryWindow;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
 
-use crate::command_bridge::spawn_sphere_at_pos;
+use crate::command_bridge::{
+    begin_edit_group, end_edit_group, erase_at_pos, paint_at_pos, spawn_capsule_between,
+    spawn_sphere_with_operation,
+};
+use crate::edit_history::{Edit, EditHistory};
 use crate::mode::{AppMode, AppModeState};
 use crate::overlay::OverlayCamera;
-use crate::sdf_compute::{evaluate_sdf_async, SdfEvaluationSender};
+use crate::pipeline_warmup::PipelineWarmupState;
+use crate::sdf_compute::{
+    evaluate_sdf_async, evaluate_sdf_with_gradient_async, SdfEvaluationSender, SdfResult,
+};
+use crate::sdf_cpu::entity_distance;
+use crate::sdf_render::{SDFRenderEntity, SdfOperation, SdfSceneQuery};
+use crate::settings::KeyBindings;
+use crate::symmetry::Symmetry;
 
 pub struct BrushModePlugin;
 
-#[derive(Resource)]
-pub struct BrushTask {
-    pub task: Option<Task<()>>,
+// Radius around the brush hit point in which the eraser removes entities
+const ERASE_RADIUS: f32 = 0.2;
+
+// Radius of the capsule emitted by a capsule stroke
+const CAPSULE_RADIUS: f32 = 0.1;
+
+// Entities whose surface is within this distance of the grabbed point follow it
+const GRAB_RADIUS: f32 = 0.5;
+
+// Radius of the paint brush
+const PAINT_RADIUS: f32 = 0.3;
+
+// Radius of the spheres placed by the sculpt tool
+const SCULPT_RADIUS: f32 = 0.1;
+
+// How far additive spheres are pushed out along the surface normal, relative
+// to their radius; below 1 they stay partly embedded and grow the surface
+const SCULPT_SURFACE_OFFSET: f32 = 0.5;
+
+// Distance between dabs along a stroke, relative to the brush radius
+const STROKE_SPACING: f32 = 0.5;
+
+// Length of each capsule in a tube stroke, relative to its radius
+const TUBE_SEGMENT_LENGTH: f32 = 2.0;
+
+// How far from an entity's own surface the eyedropper's hit may be
+const EYEDROPPER_TOLERANCE: f32 = 0.1;
+
+// Size of the markers drawn at the control points of a curve
+const CURVE_POINT_RADIUS: f32 = 0.03;
+
+// Straight pieces each curve segment is flattened into before stamping
+const CURVE_SAMPLES_PER_SEGMENT: usize = 16;
+
+// What a brush stroke does to the scene
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, serde::Serialize, serde::Deserialize)]
+pub enum BrushTool {
+    // Add spheres (or carve them away while holding the carve key)
+    #[default]
+    Sculpt,
+    // Despawn existing entities under the brush
+    Erase,
+    // Emit a single capsule from drag start to drag end
+    Capsule,
+    // Pull the entities near the surface under the cursor along with it
+    Grab,
+    // Lay a tube of capsules connecting successive points of the stroke
+    Tube,
+    // Blend the color of the entities under the brush towards the brush color
+    Paint,
+    // Click control points on the surface, then Enter stamps spheres along a
+    // smooth curve through them
+    Curve,
+}
+
+impl BrushTool {
+    // Tools whose size the eyedropper sets
+    fn places_primitives(self) -> bool {
+        matches!(self, BrushTool::Sculpt | BrushTool::Capsule | BrushTool::Tube)
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct BrushToolState {
+    pub tool: BrushTool,
+    // Color the paint tool blends towards
+    pub color: Color,
+}
+
+// Maps pen pressure in [0, 1] to a brush size factor:
+// min_scale + (max_scale - min_scale) * pressure^exponent
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct PressureCurve {
+    pub min_scale: f32,
+    pub max_scale: f32,
+    pub exponent: f32,
 }
 
-impl Default for BrushTask {
+impl Default for PressureCurve {
     fn default() -> Self {
-        Self { task: None }
+        Self {
+            min_scale: 0.25,
+            max_scale: 1.5,
+            exponent: 1.0,
+        }
+    }
+}
+
+impl PressureCurve {
+    pub fn scale(&self, pressure: f32) -> f32 {
+        let pressure = pressure.clamp(0., 1.).powf(self.exponent);
+        self.min_scale + (self.max_scale - self.min_scale) * pressure
+    }
+}
+
+// Random variation of the spheres a stroke places, so organic surfaces don't
+// look perfectly uniform. Zero position and size jitter disables it.
+#[derive(Debug, Clone, Copy, PartialEq, Default, serde::Serialize, serde::Deserialize)]
+pub struct BrushJitter {
+    // Largest offset of a sphere from the stroke along each axis
+    pub position: f32,
+    // Largest change of a sphere's radius, relative to the brush radius
+    pub size_variance: f32,
+    // Every stroke repeats the same pattern when set, otherwise each stroke
+    // draws a fresh seed
+    pub seed: Option<u64>,
+}
+
+impl BrushJitter {
+    fn stroke_rng(&self) -> StdRng {
+        match self.seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_os_rng(),
+        }
+    }
+
+    fn apply(&self, rng: &mut StdRng, position: Vec3, radius: f32) -> (Vec3, f32) {
+        let offset = Vec3::new(
+            rng.random_range(-1.0..=1.0),
+            rng.random_range(-1.0..=1.0),
+            rng.random_range(-1.0..=1.0),
+        ) * self.position;
+        let variance = self.size_variance.clamp(0., 1.);
+        let scale = 1. + rng.random_range(-1.0..=1.0) * variance;
+        (position + offset, radius * scale)
+    }
+}
+
+// Spheres stamped by the curve brush; their radius goes linearly from
+// start_radius to end_radius along the curve
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct CurveProfile {
+    // Distance between spheres, relative to their radius
+    pub spacing: f32,
+    pub start_radius: f32,
+    pub end_radius: f32,
+}
+
+impl Default for CurveProfile {
+    fn default() -> Self {
+        Self {
+            spacing: STROKE_SPACING,
+            start_radius: SCULPT_RADIUS,
+            end_radius: SCULPT_RADIUS,
+        }
+    }
+}
+
+impl CurveProfile {
+    fn radius_at(&self, t: f32) -> f32 {
+        self.start_radius + (self.end_radius - self.start_radius) * t.clamp(0., 1.)
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct BrushSettings {
+    pub pressure_curve: PressureCurve,
+    pub jitter: BrushJitter,
+    pub curve: CurveProfile,
+    // Radius picked with the eyedropper, replacing the default radius of the
+    // tools that place primitives
+    pub radius: Option<f32>,
+    // Whether strokes add or carve; holding the carve key does the opposite
+    pub operation: SdfOperation,
+    // Pressure of the pen touching the screen, None for mice and devices
+    // that don't report it
+    pub pressure: Option<f32>,
+}
+
+impl BrushSettings {
+    // Factor the brush radius and stroke spacing are scaled by
+    pub fn size_scale(&self) -> f32 {
+        self.pressure.map_or(1., |pressure| self.pressure_curve.scale(pressure))
     }
+
+    // Radius of the brush for a tool, before pressure is applied
+    fn base_radius(&self, tool: BrushTool) -> f32 {
+        match self.radius {
+            Some(radius) if tool.places_primitives() => radius,
+            _ => tool_radius(tool),
+        }
+    }
+}
+
+// Edit a stroke makes once it is committed
+#[derive(Debug, Clone, Copy)]
+enum PendingDab {
+    Sphere {
+        position: Vec3,
+        radius: f32,
+    },
+    Capsule {
+        start: Vec3,
+        end: Vec3,
+        radius: f32,
+    },
+    Erase {
+        position: Vec3,
+        radius: f32,
+    },
+    Paint {
+        position: Vec3,
+        radius: f32,
+        color: Color,
+    },
+}
+
+// Sculpt, erase, paint and tube strokes: cursor samples are batched into one
+// SDF evaluation per round trip and the brush is applied at even arc-length
+// intervals along the resulting path. Geometry is only previewed while the
+// mouse is held and spawned when the stroke ends.
+#[derive(Resource, Default)]
+pub struct BrushStroke {
+    // Cursor samples not sent to the GPU yet, with their rays
+    pending: Vec<(Vec2, Ray3d)>,
+    // Brush positions for the samples in flight
+    task: Option<Task<Vec<Vec3>>>,
+    // End of the path so far and the distance along it since the last dab
+    last_point: Option<Vec3>,
+    travelled: f32,
+    // Where the previous tube segment ended
+    last_dab: Option<Vec3>,
+    // Edits waiting for the stroke to be committed
+    dabs: Vec<(PendingDab, SdfOperation)>,
+    // The button was released; commit once the last samples are back
+    released: bool,
+    // Esc was pressed or the press was an eyedropper click; ignore the rest
+    // of the drag
+    cancelled: bool,
+    // Source of the jitter of this stroke's spheres
+    rng: Option<StdRng>,
+}
+
+impl BrushStroke {
+    // Extend the path to `point`, returning where dabs land along the new segment
+    fn advance(&mut self, point: Vec3, spacing: f32) -> Vec<Vec3> {
+        let Some(mut start) = self.last_point else {
+            // The first sample of a stroke is always applied
+            self.last_point = Some(point);
+            self.travelled = 0.;
+            return vec![point];
+        };
+
+        let mut dabs = Vec::new();
+        let mut length = start.distance(point);
+        while self.travelled + length >= spacing {
+            let dab = start.lerp(point, (spacing - self.travelled) / length);
+            dabs.push(dab);
+            start = dab;
+            length = start.distance(point);
+            self.travelled = 0.;
+        }
+        self.travelled += length;
+        self.last_point = Some(point);
+        dabs
+    }
+
+    // Apply the previewed edits, spawning geometry in all symmetric positions,
+    // undone as a single step
+    fn commit(&mut self, symmetry: &Symmetry) {
+        if self.dabs.is_empty() {
+            return;
+        }
+        begin_edit_group();
+        for (dab, operation) in self.dabs.drain(..) {
+            match dab {
+                PendingDab::Sphere { position, radius } => {
+                    spawn_symmetric_sphere(symmetry, position, radius, operation)
+                }
+                PendingDab::Capsule { start, end, radius } => {
+                    spawn_symmetric_capsule(symmetry, start, end, radius, operation)
+                }
+                PendingDab::Erase { position, radius } => erase_at_pos(position, radius),
+                PendingDab::Paint {
+                    position,
+                    radius,
+                    color,
+                } => paint_at_pos(position, radius, color),
+            }
+        }
+        end_edit_group();
+    }
+}
+
+// Drag state of an in-progress capsule stroke
+#[derive(Resource, Default)]
+pub struct CapsuleStroke {
+    // Pending SDF evaluation for the point under the cursor when the drag started
+    start_task: Option<Task<Vec3>>,
+    start: Option<Vec3>,
+    operation: SdfOperation,
+}
+
+// Drag state of an in-progress grab stroke
+#[derive(Resource, Default)]
+pub struct GrabStroke {
+    // Pending SDF evaluation for the point under the cursor when the drag started
+    start_task: Option<Task<Option<Vec3>>>,
+    // Grabbed surface point
+    anchor: Option<Vec3>,
+    // Entities pulled along, with their transform at the start and falloff weight
+    grabbed: Vec<(Entity, Transform, f32)>,
+}
+
+// Control points of the curve being placed
+#[derive(Resource, Default)]
+pub struct CurveStroke {
+    // Pending SDF evaluation for the clicked point, None when it missed
+    task: Option<Task<Option<Vec3>>>,
+    points: Vec<Vec3>,
+    // Whether the curve adds or carves, decided by the first control point
+    operation: SdfOperation,
+}
+
+// Alt+click samples the entity under the cursor
+#[derive(Resource, Default)]
+pub struct Eyedropper {
+    // Pending SDF evaluation for the clicked point, None when it missed
+    task: Option<Task<Option<Vec3>>>,
+}
+
+// Ghost of what a click would place, kept in sync with the cursor
+#[derive(Resource, Default)]
+pub struct BrushPreview {
+    // Pending SDF evaluation for the point under the cursor
+    task: Option<Task<Option<SdfResult>>>,
+    // Surface under the cursor from the last finished evaluation
+    result: Option<SdfResult>,
 }
 
 impl Plugin for BrushModePlugin {
     fn build(&self, app: &mut App) {
-        app.init_resource::<BrushTask>()
-            .add_systems(Update, handle_click_brush);
+        app.init_resource::<BrushStroke>()
+            .init_resource::<BrushToolState>()
+            .init_resource::<BrushSettings>()
+            .init_resource::<CapsuleStroke>()
+            .init_resource::<GrabStroke>()
+            .init_resource::<CurveStroke>()
+            .init_resource::<Symmetry>()
+            .init_resource::<Eyedropper>()
+            .init_resource::<BrushPreview>()
+            .add_systems(
+                Update,
+                (
+                    track_pen_pressure,
+                    handle_click_brush,
+                    handle_capsule_stroke,
+                    handle_grab_stroke,
+                    handle_curve_brush,
+                    handle_eyedropper,
+                    update_brush_preview,
+                ),
+            );
     }
 }
 
-// System to handle mode changes for brush mode
+// Default radius of the brush for a tool
+fn tool_radius(tool: BrushTool) -> f32 {
+    match tool {
+        BrushTool::Sculpt | BrushTool::Curve => SCULPT_RADIUS,
+        BrushTool::Erase => ERASE_RADIUS,
+        BrushTool::Capsule | BrushTool::Tube => CAPSULE_RADIUS,
+        BrushTool::Grab => GRAB_RADIUS,
+        BrushTool::Paint => PAINT_RADIUS,
+    }
+}
+
+// Pens report their pressure through touch events
+fn track_pen_pressure(
+    mut touch_events: EventReader<TouchInput>,
+    mut settings: ResMut<BrushSettings>,
+) {
+    for event in touch_events.read() {
+        settings.pressure = match (event.phase, event.force) {
+            (TouchPhase::Ended | TouchPhase::Canceled, _) | (_, None) => None,
+            (_, Some(ForceTouch::Normalized(force))) => Some(force as f32),
+            // Devices that report no maximum are treated like those without pressure
+            (
+                _,
+                Some(ForceTouch::Calibrated {
+                    force,
+                    max_possible_force,
+                    ..
+                }),
+            ) => (max_possible_force > 0.).then(|| (force / max_possible_force) as f32),
+        };
+    }
+}
+
+fn spawn_symmetric_sphere(
+    symmetry: &Symmetry,
+    position: Vec3,
+    radius: f32,
+    operation: SdfOperation,
+) {
+    for image in symmetry.images(position) {
+        spawn_sphere_with_operation(image, radius, operation);
+    }
+}
+
+fn spawn_symmetric_capsule(
+    symmetry: &Symmetry,
+    start: Vec3,
+    end: Vec3,
+    radius: f32,
+    operation: SdfOperation,
+) {
+    for (start, end) in symmetry.images(start).into_iter().zip(symmetry.images(end)) {
+        spawn_capsule_between(start, end, radius, operation);
+    }
+}
+
+// Holding the carve key (left Ctrl by default) flips between adding and carving.
+// Not Cmd, which pans the camera
+fn brush_operation(
+    keyboard_input: &ButtonInput<KeyCode>,
+    keys: &KeyBindings,
+    settings: &BrushSettings,
+) -> SdfOperation {
+    let invert = keyboard_input.pressed(keys.carve);
+    match (settings.operation, invert) {
+        (SdfOperation::Union, false) | (SdfOperation::Subtract, true) => SdfOperation::Union,
+        _ => SdfOperation::Subtract,
+    }
+}
+
+// Alt+click picks brush settings instead of starting a stroke
+fn eyedropper_held(keyboard_input: &ButtonInput<KeyCode>) -> bool {
+    keyboard_input.any_pressed([KeyCode::AltLeft, KeyCode::AltRight])
+}
+
+// Additive shapes are embedded in the surface, subtractive ones are centered on it
+fn sculpt_offset(operation: SdfOperation, radius: f32) -> f32 {
+    match operation {
+        SdfOperation::Union => radius * SCULPT_SURFACE_OFFSET,
+        SdfOperation::Subtract => 0.,
+    }
+}
+
+// Where the brush lands for a ray-march result: pushed out along the surface
+// normal, or back along the ray when it missed and there is no normal
+fn brush_position(ray: Ray3d, result: &SdfResult, offset: f32) -> Vec3 {
+    let hit = ray.get_point(result.distance);
+    if result.normal == Vec3::ZERO {
+        hit - *ray.direction * offset
+    } else {
+        hit + result.normal * offset
+    }
+}
+
+fn operation_color(operation: SdfOperation) -> Color {
+    match operation {
+        SdfOperation::Union => Color::srgba(1., 1., 1., 0.6),
+        SdfOperation::Subtract => Color::srgba(0.9, 0.2, 0.2, 0.6),
+    }
+}
+
+// System to apply the brush along the cursor path while the mouse is held.
+// Every cursor movement adds a sample; whenever the previous batch came back
+// from the GPU, all samples gathered since are evaluated in one call.
+// Every edit is drawn as a translucent preview until release, and Esc throws
+// the stroke away.
 fn handle_click_brush(
     mode_state: Res<AppModeState>,
     window: Single<&Window, With<PrimaryWindow>>,
     buttons: Res<ButtonInput<MouseButton>>,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    keys: Res<KeyBindings>,
+    sdf_sender: Res<SdfEvaluationSender>,
+    camera_query: Query<(&Camera, &GlobalTransform, &OverlayCamera)>,
+    mut stroke: ResMut<BrushStroke>,
+    warmup: Res<PipelineWarmupState>,
+    tool_state: Res<BrushToolState>,
+    settings: Res<BrushSettings>,
+    symmetry: Res<Symmetry>,
+    mut cursor_moved: EventReader<CursorMoved>,
+    mut gizmos: Gizmos,
+) {
+    // Every position the cursor passed through this frame, so fast strokes
+    // don't depend on the frame rate for their density
+    let mut cursor_positions: Vec<Vec2> = cursor_moved.read().map(|e| e.position).collect();
+
+    if !mode_state.is_mode(AppMode::Brush)
+        || matches!(
+            tool_state.tool,
+            BrushTool::Capsule | BrushTool::Grab | BrushTool::Curve
+        )
+    {
+        *stroke = BrushStroke::default();
+        return;
+    }
+
+    // The compute pipeline isn't ready to answer SDF queries yet
+    if !warmup.is_ready() {
+        return;
+    }
+
+    let tool = tool_state.tool;
+    let operation = brush_operation(&keyboard_input, &keys, &settings);
+    let radius = settings.base_radius(tool) * settings.size_scale();
+    let spacing = match tool {
+        BrushTool::Tube => radius * TUBE_SEGMENT_LENGTH,
+        _ => radius * STROKE_SPACING,
+    };
+
+    if let Some(task) = &mut stroke.task {
+        if let Some(points) = block_on(future::poll_once(task)) {
+            stroke.task = None;
+            for point in points {
+                for dab in stroke.advance(point, spacing) {
+                    match tool {
+                        BrushTool::Erase => {
+                            let erase = PendingDab::Erase {
+                                position: dab,
+                                radius,
+                            };
+                            stroke.dabs.push((erase, operation));
+                        }
+                        BrushTool::Paint => {
+                            let paint = PendingDab::Paint {
+                                position: dab,
+                                radius,
+                                color: tool_state.color,
+                            };
+                            stroke.dabs.push((paint, operation));
+                        }
+                        BrushTool::Tube => {
+                            if let Some(start) = stroke.last_dab {
+                                let capsule = PendingDab::Capsule {
+                                    start,
+                                    end: dab,
+                                    radius,
+                                };
+                                stroke.dabs.push((capsule, operation));
+                            }
+                            stroke.last_dab = Some(dab);
+                        }
+                        _ => {
+                            let rng = stroke
+                                .rng
+                                .get_or_insert_with(|| settings.jitter.stroke_rng());
+                            let (position, radius) = settings.jitter.apply(rng, dab, radius);
+                            let sphere = PendingDab::Sphere { position, radius };
+                            stroke.dabs.push((sphere, operation));
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    if buttons.just_pressed(MouseButton::Left) {
+        // A stroke still waiting on the GPU is committed as it stands
+        stroke.commit(&symmetry);
+        *stroke = BrushStroke::default();
+    }
+
+    let eyedropper_click =
+        buttons.just_pressed(MouseButton::Left) && eyedropper_held(&keyboard_input);
+    let escaped =
+        keyboard_input.just_pressed(KeyCode::Escape) && buttons.pressed(MouseButton::Left);
+    if eyedropper_click || escaped {
+        *stroke = BrushStroke {
+            cancelled: true,
+            ..default()
+        };
+    }
+
+    if buttons.just_released(MouseButton::Left) {
+        stroke.released = true;
+    }
+
+    for (dab, operation) in &stroke.dabs {
+        let color = operation_color(*operation);
+        match *dab {
+            PendingDab::Sphere { position, radius } => {
+                for image in symmetry.images(position) {
+                    gizmos.sphere(Isometry3d::from_translation(image), radius, color);
+                }
+            }
+            PendingDab::Capsule { start, end, radius } => {
+                for (start, end) in symmetry.images(start).into_iter().zip(symmetry.images(end)) {
+                    gizmos.line(start, end, color);
+                    gizmos.sphere(Isometry3d::from_translation(start), radius, color);
+                    gizmos.sphere(Isometry3d::from_translation(end), radius, color);
+                }
+            }
+            PendingDab::Erase { position, radius } => {
+                let color = operation_color(SdfOperation::Subtract);
+                gizmos.sphere(Isometry3d::from_translation(position), radius, color);
+            }
+            PendingDab::Paint {
+                position,
+                radius,
+                color,
+            } => {
+                let color = color.with_alpha(0.6);
+                gizmos.sphere(Isometry3d::from_translation(position), radius, color);
+            }
+        }
+    }
+
+    if stroke.released && stroke.task.is_none() && stroke.pending.is_empty() {
+        stroke.commit(&symmetry);
+        *stroke = BrushStroke::default();
+        return;
+    }
+
+    if buttons.pressed(MouseButton::Left) && !stroke.cancelled {
+        // A cursor at rest still needs a sample when the stroke starts
+        if cursor_positions.is_empty() && buttons.just_pressed(MouseButton::Left) {
+            cursor_positions.extend(window.cursor_position());
+        }
+        let Ok((camera, camera_transform, _)) = camera_query.single() else {
+            return;
+        };
+        for viewport_position in cursor_positions {
+            let Ok(ray) = camera.viewport_to_world(camera_transform, viewport_position) else {
+                continue;
+            };
+            let uv = Vec2 {
+                x: viewport_position.x / window.resolution.width(),
+                y: viewport_position.y / window.resolution.height(),
+            };
+            stroke.pending.push((uv, ray));
+        }
+    }
+
+    if stroke.task.is_some() || stroke.pending.is_empty() {
+        return;
+    }
+
+    let samples = std::mem::take(&mut stroke.pending);
+    let offset = match tool {
+        BrushTool::Erase | BrushTool::Paint => 0.,
+        _ => sculpt_offset(operation, radius),
+    };
+
+    // Clone the sender to move into the async task
+    let sender_clone = sdf_sender.clone();
+    stroke.task = Some(bevy::tasks::AsyncComputeTaskPool::get().spawn(async move {
+        let points = samples.iter().map(|(uv, _)| *uv).collect();
+        let Ok(results) = evaluate_sdf_with_gradient_async(points, &sender_clone).await else {
+            return Vec::new();
+        };
+        samples
+            .iter()
+            .zip(results)
+            .map(|((_, ray), result)| brush_position(*ray, &result, offset))
+            .collect()
+    }));
+}
+
+// System to drag out a single capsule per stroke: the start is placed on the
+// surface under the cursor, the end follows the cursor on the camera-facing
+// plane through the start, and the capsule is committed on release.
+fn handle_capsule_stroke(
+    mode_state: Res<AppModeState>,
+    tool_state: Res<BrushToolState>,
+    window: Single<&Window, With<PrimaryWindow>>,
+    buttons: Res<ButtonInput<MouseButton>>,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    keys: Res<KeyBindings>,
     sdf_sender: Res<SdfEvaluationSender>,
     camera_query: Query<(&Camera, &GlobalTransform, &OverlayCamera)>,
-    mut brush_task: ResMut<BrushTask>,
+    warmup: Res<PipelineWarmupState>,
+    settings: Res<BrushSettings>,
+    symmetry: Res<Symmetry>,
+    mut stroke: ResMut<CapsuleStroke>,
+    mut gizmos: Gizmos,
 ) {
-    if !mode_state.is_mode(AppMode::Brush) {
+    if !mode_state.is_mode(AppMode::Brush)
+        || tool_state.tool != BrushTool::Capsule
+        || !warmup.is_ready()
+    {
+        *stroke = CapsuleStroke::default();
         return;
     }
 
-    if let Some(task) = &brush_task.task {
-        if !task.is_finished() {
+    let Ok((camera, camera_transform, _)) = camera_query.single() else {
+        return;
+    };
+    let cursor_ray = window
+        .cursor_position()
+        .and_then(|position| camera.viewport_to_world(camera_transform, position).ok());
+
+    // Start a stroke by evaluating the surface under the cursor
+    if buttons.just_pressed(MouseButton::Left) && !eyedropper_held(&keyboard_input) {
+        let (Some(ray), Some(viewport_position)) = (cursor_ray, window.cursor_position()) else {
             return;
+        };
+        let uv = Vec2 {
+            x: viewport_position.x / window.resolution.width(),
+            y: viewport_position.y / window.resolution.height(),
+        };
+        let sender_clone = sdf_sender.clone();
+        stroke.operation = brush_operation(&keyboard_input, &keys, &settings);
+        stroke.start = None;
+        stroke.start_task = Some(bevy::tasks::AsyncComputeTaskPool::get().spawn(async move {
+            let distance = evaluate_sdf_async(vec![uv], &sender_clone)
+                .await
+                .ok()
+                .and_then(|results| results.first().map(|r| r.distance))
+                .unwrap_or(0.);
+            ray.get_point(distance)
+        }));
+    }
+
+    if let Some(task) = &mut stroke.start_task {
+        if let Some(start) = block_on(future::poll_once(task)) {
+            stroke.start = Some(start);
+            stroke.start_task = None;
+        }
+    }
+
+    let Some(start) = stroke.start else {
+        // Released before the start point came back from the GPU
+        if buttons.just_released(MouseButton::Left) {
+            *stroke = CapsuleStroke::default();
+        }
+        return;
+    };
+
+    // Project the cursor onto the camera-facing plane through the stroke start
+    let end = cursor_ray.and_then(|ray| {
+        ray.intersect_plane(start, InfinitePlane3d::new(camera_transform.forward()))
+            .map(|t| ray.get_point(t))
+    });
+
+    let radius = settings.base_radius(BrushTool::Capsule);
+    if let Some(end) = end {
+        let color = operation_color(stroke.operation);
+        gizmos.line(start, end, color);
+        gizmos.sphere(Isometry3d::from_translation(start), radius, color);
+        gizmos.sphere(Isometry3d::from_translation(end), radius, color);
+    }
+
+    if buttons.just_released(MouseButton::Left) {
+        if let Some(end) = end {
+            begin_edit_group();
+            spawn_symmetric_capsule(&symmetry, start, end, radius, stroke.operation);
+            end_edit_group();
+        }
+        *stroke = CapsuleStroke::default();
+    }
+}
+
+// Weight of the grab for an entity whose surface is `distance` from the
+// grabbed point: 1 at the surface, easing out to 0 at GRAB_RADIUS
+fn grab_falloff(distance: f32) -> f32 {
+    let t = (distance.max(0.) / GRAB_RADIUS).min(1.);
+    (1. - t * t).powi(2)
+}
+
+// System to drag the entities near the surface under the cursor. The grabbed
+// point follows the cursor on the camera-facing plane through it, and every
+// entity within GRAB_RADIUS moves by the same offset scaled by its falloff.
+fn handle_grab_stroke(
+    mode_state: Res<AppModeState>,
+    tool_state: Res<BrushToolState>,
+    window: Single<&Window, With<PrimaryWindow>>,
+    buttons: Res<ButtonInput<MouseButton>>,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    sdf_sender: Res<SdfEvaluationSender>,
+    camera_query: Query<(&Camera, &GlobalTransform, &OverlayCamera)>,
+    warmup: Res<PipelineWarmupState>,
+    mut stroke: ResMut<GrabStroke>,
+    mut entities: Query<(Entity, &SDFRenderEntity, &mut Transform)>,
+    mut history: ResMut<EditHistory>,
+) {
+    if !mode_state.is_mode(AppMode::Brush)
+        || tool_state.tool != BrushTool::Grab
+        || !warmup.is_ready()
+    {
+        *stroke = GrabStroke::default();
+        return;
+    }
+
+    let Ok((camera, camera_transform, _)) = camera_query.single() else {
+        return;
+    };
+    let cursor_ray = window
+        .cursor_position()
+        .and_then(|position| camera.viewport_to_world(camera_transform, position).ok());
+
+    // Find the grabbed point on the surface under the cursor
+    if buttons.just_pressed(MouseButton::Left) && !eyedropper_held(&keyboard_input) {
+        let (Some(ray), Some(viewport_position)) = (cursor_ray, window.cursor_position()) else {
+            return;
+        };
+        let uv = Vec2 {
+            x: viewport_position.x / window.resolution.width(),
+            y: viewport_position.y / window.resolution.height(),
+        };
+        let sender_clone = sdf_sender.clone();
+        *stroke = GrabStroke::default();
+        stroke.start_task = Some(bevy::tasks::AsyncComputeTaskPool::get().spawn(async move {
+            evaluate_sdf_with_gradient_async(vec![uv], &sender_clone)
+                .await
+                .ok()
+                .and_then(|results| results.first().copied())
+                // Misses have no normal
+                .filter(|result| result.normal != Vec3::ZERO)
+                .map(|result| ray.get_point(result.distance))
+        }));
+    }
+
+    if let Some(task) = &mut stroke.start_task {
+        if let Some(anchor) = block_on(future::poll_once(task)) {
+            stroke.start_task = None;
+            stroke.anchor = anchor;
+            if let Some(anchor) = anchor {
+                stroke.grabbed = entities
+                    .iter()
+                    .map(|(entity, sdf_entity, transform)| {
+                        (entity, *transform, grab_falloff(entity_distance(sdf_entity, anchor)))
+                    })
+                    .filter(|(_, _, weight)| *weight > 0.)
+                    .collect();
+            }
+        }
+    }
+
+    let Some(anchor) = stroke.anchor else {
+        // Released before the grabbed point came back from the GPU
+        if buttons.just_released(MouseButton::Left) {
+            *stroke = GrabStroke::default();
+        }
+        return;
+    };
+
+    let target = cursor_ray.and_then(|ray| {
+        ray.intersect_plane(anchor, InfinitePlane3d::new(camera_transform.forward()))
+            .map(|t| ray.get_point(t))
+    });
+    if let Some(target) = target {
+        let offset = target - anchor;
+        for (entity, start, weight) in &stroke.grabbed {
+            if let Ok((_, _, mut transform)) = entities.get_mut(*entity) {
+                transform.translation = start.translation + offset * *weight;
+            }
+        }
+    }
+
+    if buttons.just_released(MouseButton::Left) {
+        let edits = stroke
+            .grabbed
+            .iter()
+            .filter_map(|(entity, before, _)| {
+                let (_, _, after) = entities.get(*entity).ok()?;
+                (*after != *before).then(|| Edit::Transform {
+                    entity: *entity,
+                    before: *before,
+                    after: *after,
+                })
+            })
+            .collect();
+        history.record_all(edits);
+        *stroke = GrabStroke::default();
+    }
+}
+
+// Point on a uniform Catmull-Rom segment between p1 and p2, t in [0, 1]
+fn catmull_rom(p0: Vec3, p1: Vec3, p2: Vec3, p3: Vec3, t: f32) -> Vec3 {
+    let t2 = t * t;
+    let t3 = t2 * t;
+    0.5 * (2. * p1
+        + (p2 - p0) * t
+        + (2. * p0 - 5. * p1 + 4. * p2 - p3) * t2
+        + (3. * p1 - p0 - 3. * p2 + p3) * t3)
+}
+
+// Polyline approximating the curve through all control points. The end
+// points are repeated so the curve starts and ends on them.
+fn curve_polyline(points: &[Vec3]) -> Vec<Vec3> {
+    let (Some(first), Some(last)) = (points.first(), points.last()) else {
+        return Vec::new();
+    };
+    if points.len() == 1 {
+        return vec![*first];
+    }
+
+    let padded: Vec<Vec3> = std::iter::once(*first)
+        .chain(points.iter().copied())
+        .chain(std::iter::once(*last))
+        .collect();
+    let mut polyline = vec![*first];
+    for window in padded.windows(4) {
+        for i in 1..=CURVE_SAMPLES_PER_SEGMENT {
+            let t = i as f32 / CURVE_SAMPLES_PER_SEGMENT as f32;
+            polyline.push(catmull_rom(window[0], window[1], window[2], window[3], t));
         }
     }
+    polyline
+}
+
+// Centers and radii of the spheres stamped along a polyline, spaced by the
+// profile relative to the radius at each point
+fn curve_stamps(polyline: &[Vec3], profile: &CurveProfile) -> Vec<(Vec3, f32)> {
+    let Some(first) = polyline.first() else {
+        return Vec::new();
+    };
+    let total: f32 = polyline.windows(2).map(|w| w[0].distance(w[1])).sum();
+    let radius_at = |travelled: f32| {
+        profile.radius_at(if total > 0. { travelled / total } else { 0. })
+    };
 
-    if buttons.pressed(MouseButton::Left) {
-        info!("drag paint");
+    let mut stamps = vec![(*first, radius_at(0.))];
+    let mut travelled = 0.;
+    let mut next = radius_at(0.) * profile.spacing;
+    for segment in polyline.windows(2) {
+        let length = segment[0].distance(segment[1]);
+        while length > 0. && travelled + length >= next {
+            let position = segment[0].lerp(segment[1], (next - travelled) / length);
+            let radius = radius_at(next);
+            stamps.push((position, radius));
+            next += radius * profile.spacing;
+        }
+        travelled += length;
+    }
+    stamps
+}
+
+// System to build a curve out of clicked control points. Each click adds the
+// surface point under the cursor, Backspace removes the last one, Esc drops
+// the curve and Enter stamps spheres along it as a single undo step.
+fn handle_curve_brush(
+    mode_state: Res<AppModeState>,
+    tool_state: Res<BrushToolState>,
+    settings: Res<BrushSettings>,
+    window: Single<&Window, With<PrimaryWindow>>,
+    buttons: Res<ButtonInput<MouseButton>>,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    keys: Res<KeyBindings>,
+    sdf_sender: Res<SdfEvaluationSender>,
+    camera_query: Query<(&Camera, &GlobalTransform, &OverlayCamera)>,
+    warmup: Res<PipelineWarmupState>,
+    symmetry: Res<Symmetry>,
+    mut stroke: ResMut<CurveStroke>,
+    mut gizmos: Gizmos,
+) {
+    if !mode_state.is_mode(AppMode::Brush)
+        || tool_state.tool != BrushTool::Curve
+        || !warmup.is_ready()
+    {
+        *stroke = CurveStroke::default();
+        return;
+    }
+
+    if buttons.just_pressed(MouseButton::Left)
+        && stroke.task.is_none()
+        && !eyedropper_held(&keyboard_input)
+    {
         let Some(viewport_position) = window.cursor_position() else {
             return;
         };
         let Ok((camera, camera_transform, _)) = camera_query.single() else {
             return;
         };
+        let Ok(ray) = camera.viewport_to_world(camera_transform, viewport_position) else {
+            return;
+        };
+        let uv = Vec2 {
+            x: viewport_position.x / window.resolution.width(),
+            y: viewport_position.y / window.resolution.height(),
+        };
+        if stroke.points.is_empty() {
+            stroke.operation = brush_operation(&keyboard_input, &keys, &settings);
+        }
+        let sender_clone = sdf_sender.clone();
+        stroke.task = Some(bevy::tasks::AsyncComputeTaskPool::get().spawn(async move {
+            evaluate_sdf_with_gradient_async(vec![uv], &sender_clone)
+                .await
+                .ok()
+                .and_then(|results| results.first().copied())
+                // Misses have no normal
+                .filter(|result| result.normal != Vec3::ZERO)
+                .map(|result| ray.get_point(result.distance))
+        }));
+    }
+
+    if let Some(task) = &mut stroke.task {
+        if let Some(point) = block_on(future::poll_once(task)) {
+            stroke.task = None;
+            stroke.points.extend(point);
+        }
+    }
+
+    if keyboard_input.just_pressed(KeyCode::Escape) {
+        *stroke = CurveStroke::default();
+        return;
+    }
+    if keyboard_input.just_pressed(KeyCode::Backspace) {
+        stroke.points.pop();
+    }
+
+    let polyline = curve_polyline(&stroke.points);
+    let stamps = curve_stamps(&polyline, &settings.curve);
+
+    if keyboard_input.just_pressed(KeyCode::Enter) {
+        if !stamps.is_empty() {
+            begin_edit_group();
+            for (position, radius) in stamps {
+                spawn_symmetric_sphere(&symmetry, position, radius, stroke.operation);
+            }
+            end_edit_group();
+        }
+        *stroke = CurveStroke::default();
+        return;
+    }
+
+    let color = operation_color(stroke.operation);
+    for point in &stroke.points {
+        gizmos.sphere(Isometry3d::from_translation(*point), CURVE_POINT_RADIUS, color);
+    }
+    gizmos.linestrip(polyline, color);
+    for (position, radius) in stamps {
+        gizmos.sphere(Isometry3d::from_translation(position), radius, color);
+    }
+}
 
+// System to copy the radius, color and operation of the entity under an
+// Alt+click into the brush. The clicked surface point is found on the GPU and
+// the entity owning it through the BVH.
+fn handle_eyedropper(
+    mode_state: Res<AppModeState>,
+    window: Single<&Window, With<PrimaryWindow>>,
+    buttons: Res<ButtonInput<MouseButton>>,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    sdf_sender: Res<SdfEvaluationSender>,
+    camera_query: Query<(&Camera, &GlobalTransform, &OverlayCamera)>,
+    warmup: Res<PipelineWarmupState>,
+    scene_query: SdfSceneQuery,
+    sdf_entities: Query<(&SDFRenderEntity, Option<&MeshMaterial3d<StandardMaterial>>)>,
+    materials: Res<Assets<StandardMaterial>>,
+    mut eyedropper: ResMut<Eyedropper>,
+    mut tool_state: ResMut<BrushToolState>,
+    mut settings: ResMut<BrushSettings>,
+) {
+    if !mode_state.is_mode(AppMode::Brush) || !warmup.is_ready() {
+        *eyedropper = Eyedropper::default();
+        return;
+    }
+
+    if buttons.just_pressed(MouseButton::Left)
+        && eyedropper_held(&keyboard_input)
+        && eyedropper.task.is_none()
+    {
+        let Some(viewport_position) = window.cursor_position() else {
+            return;
+        };
+        let Ok((camera, camera_transform, _)) = camera_query.single() else {
+            return;
+        };
         let Ok(ray) = camera.viewport_to_world(camera_transform, viewport_position) else {
             return;
         };
+        let uv = Vec2 {
+            x: viewport_position.x / window.resolution.width(),
+            y: viewport_position.y / window.resolution.height(),
+        };
+        let sender_clone = sdf_sender.clone();
+        eyedropper.task = Some(bevy::tasks::AsyncComputeTaskPool::get().spawn(async move {
+            evaluate_sdf_with_gradient_async(vec![uv], &sender_clone)
+                .await
+                .ok()
+                .and_then(|results| results.first().copied())
+                // Misses have no normal
+                .filter(|result| result.normal != Vec3::ZERO)
+                .map(|result| ray.get_point(result.distance))
+        }));
+    }
 
-        let width = window.resolution.width();
-        let height = window.resolution.height();
+    let Some(task) = &mut eyedropper.task else {
+        return;
+    };
+    let Some(hit) = block_on(future::poll_once(task)) else {
+        return;
+    };
+    eyedropper.task = None;
+    let Some(hit) = hit else {
+        return;
+    };
 
-        let mut gpu_points: Vec<Vec2> = Vec::new();
-        gpu_points.push(Vec2 {
-            x: viewport_position.x / width,
-            y: viewport_position.y / height,
+    let picked = scene_query
+        .entities_overlapping_sphere(hit, EYEDROPPER_TOLERANCE)
+        .into_iter()
+        .filter_map(|entity| sdf_entities.get(entity).ok())
+        .min_by(|(a, _), (b, _)| {
+            entity_distance(a, hit).abs().total_cmp(&entity_distance(b, hit).abs())
         });
+    let Some((sdf_entity, material)) = picked else {
+        return;
+    };
+
+    settings.radius = Some(sdf_entity.scale);
+    settings.operation = sdf_entity.operation;
+    if let Some(material) = material.and_then(|m| materials.get(&m.0)) {
+        tool_state.color = material.base_color;
+    }
+}
+
+// System to draw a translucent ghost of the primitive a click would place.
+// The surface under the cursor is re-evaluated on the GPU whenever the
+// previous evaluation has finished, so the ghost trails the cursor by at most
+// one round trip.
+fn update_brush_preview(
+    mode_state: Res<AppModeState>,
+    tool_state: Res<BrushToolState>,
+    settings: Res<BrushSettings>,
+    window: Single<&Window, With<PrimaryWindow>>,
+    buttons: Res<ButtonInput<MouseButton>>,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    keys: Res<KeyBindings>,
+    sdf_sender: Res<SdfEvaluationSender>,
+    camera_query: Query<(&Camera, &GlobalTransform, &OverlayCamera)>,
+    warmup: Res<PipelineWarmupState>,
+    stroke: Res<CapsuleStroke>,
+    mut preview: ResMut<BrushPreview>,
+    mut gizmos: Gizmos,
+) {
+    if !mode_state.is_mode(AppMode::Brush) || !warmup.is_ready() {
+        *preview = BrushPreview::default();
+        return;
+    }
+
+    if let Some(task) = &mut preview.task {
+        if let Some(result) = block_on(future::poll_once(task)) {
+            preview.result = result;
+            preview.task = None;
+        }
+    }
+
+    let Some(viewport_position) = window.cursor_position() else {
+        preview.result = None;
+        return;
+    };
+    let Ok((camera, camera_transform, _)) = camera_query.single() else {
+        return;
+    };
+    let Ok(ray) = camera.viewport_to_world(camera_transform, viewport_position) else {
+        return;
+    };
 
-        // Clone the sender to move into the async task
+    if preview.task.is_none() {
+        let uv = Vec2 {
+            x: viewport_position.x / window.resolution.width(),
+            y: viewport_position.y / window.resolution.height(),
+        };
         let sender_clone = sdf_sender.clone();
+        preview.task = Some(bevy::tasks::AsyncComputeTaskPool::get().spawn(async move {
+            evaluate_sdf_with_gradient_async(vec![uv], &sender_clone)
+                .await
+                .ok()
+                .and_then(|results| results.first().copied())
+        }));
+    }
 
-        // Spawn the future and handle results when ready
-        // Spawn the future and store the task
-        let task = bevy::tasks::AsyncComputeTaskPool::get().spawn(async move {
-            let Ok(results) = evaluate_sdf_async(gpu_points, &sender_clone).await else {
-                return;
-            };
-            for (_, result) in results.iter().enumerate() {
-                let new_sphere_radius = 0.1;
-                let pos = ray.get_point(result.distance - new_sphere_radius);
+    let Some(result) = preview.result else {
+        return;
+    };
+    let distance = result.distance;
 
-                spawn_sphere_at_pos(pos, new_sphere_radius);
-            }
-        });
+    if eyedropper_held(&keyboard_input) {
+        gizmos.sphere(
+            Isometry3d::from_translation(ray.get_point(distance)),
+            EYEDROPPER_TOLERANCE,
+            Color::srgba(0.9, 0.9, 0.2, 0.6),
+        );
+        return;
+    }
 
-        brush_task.task = Some(task);
+    let operation = brush_operation(&keyboard_input, &keys, &settings);
+    let radius = settings.base_radius(tool_state.tool) * settings.size_scale();
+    match tool_state.tool {
+        BrushTool::Sculpt => {
+            let pos = brush_position(ray, &result, sculpt_offset(operation, radius));
+            gizmos.sphere(
+                Isometry3d::from_translation(pos),
+                radius,
+                operation_color(operation),
+            );
+        }
+        BrushTool::Tube => {
+            let pos = brush_position(ray, &result, sculpt_offset(operation, radius));
+            gizmos.sphere(
+                Isometry3d::from_translation(pos),
+                radius,
+                operation_color(operation),
+            );
+        }
+        BrushTool::Erase => {
+            gizmos.sphere(
+                Isometry3d::from_translation(ray.get_point(distance)),
+                radius,
+                Color::srgba(0.9, 0.6, 0.2, 0.6),
+            );
+        }
+        BrushTool::Paint => {
+            gizmos.sphere(
+                Isometry3d::from_translation(ray.get_point(distance)),
+                radius,
+                tool_state.color.with_alpha(0.6),
+            );
+        }
+        BrushTool::Curve => {
+            gizmos.sphere(
+                Isometry3d::from_translation(ray.get_point(distance)),
+                settings.curve.start_radius,
+                operation_color(operation),
+            );
+        }
+        BrushTool::Grab => {
+            gizmos.sphere(
+                Isometry3d::from_translation(ray.get_point(distance)),
+                GRAB_RADIUS,
+                Color::srgba(0.2, 0.6, 0.9, 0.6),
+            );
+        }
+        BrushTool::Capsule => {
+            // The stroke draws its own preview once a drag is underway
+            if stroke.start.is_some() || buttons.pressed(MouseButton::Left) {
+                return;
+            }
+            gizmos.sphere(
+                Isometry3d::from_translation(ray.get_point(distance)),
+                settings.base_radius(BrushTool::Capsule),
+                operation_color(operation),
+            );
+        }
     }
 }
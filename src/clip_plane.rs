@@ -0,0 +1,203 @@
+//! Cut-away view of the SDF
+//!
+//! A clip plane slices the scene so its inside can be inspected. The plane's
+//! position comes from a handle in the overlay that can be dragged along the
+//! plane normal; the shaders cut away everything on the side the normal points
+//! to and can fill the cut face with a flat cap color.
+
+use bevy::{prelude::*, render::view::RenderLayers};
+use bevy_panorbit_camera::PanOrbitCamera;
+
+use crate::overlay::{OverlayCamera, OVERLAY_LAYER};
+use crate::sdf_render::{SDFRenderSettings, SdfRenderCamera};
+use crate::translation::ray_axis_offset;
+
+pub struct ClipPlanePlugin;
+
+// Side length of the square drawn to show the plane
+const CLIP_PLANE_GIZMO_SIZE: f32 = 4.0;
+const CLIP_PLANE_COLOR: Color = Color::srgb(0.9, 0.6, 0.2);
+
+#[derive(Resource, Clone)]
+pub struct SdfClipPlane {
+    pub enabled: bool,
+    // Unit normal, the scene is cut away on the side it points to
+    pub normal: Vec3,
+    // Fill the cut face with `cap_color` instead of shading it
+    pub cap: bool,
+    pub cap_color: Color,
+}
+
+impl Default for SdfClipPlane {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            normal: Vec3::X,
+            cap: true,
+            cap_color: Color::srgb(0.8, 0.25, 0.25),
+        }
+    }
+}
+
+// Draggable handle at the plane's origin
+#[derive(Component, Default)]
+struct ClipPlaneHandle {
+    // Hit position and plane origin when the current drag started
+    drag_start: Option<(Vec3, Vec3)>,
+}
+
+impl Plugin for ClipPlanePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SdfClipPlane>().add_systems(
+            Update,
+            (
+                sync_clip_plane_handle,
+                update_clip_plane_in_settings,
+                draw_clip_plane,
+            )
+                .chain(),
+        );
+    }
+}
+
+fn sync_clip_plane_handle(
+    clip_plane: Res<SdfClipPlane>,
+    mut handles: Query<(Entity, &mut Transform), With<ClipPlaneHandle>>,
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    if !clip_plane.is_changed() {
+        return;
+    }
+
+    let rotation = Quat::from_rotation_arc(Vec3::Y, clip_plane.normal);
+    match (clip_plane.enabled, handles.single_mut()) {
+        (true, Ok((_, mut transform))) => transform.rotation = rotation,
+        (true, Err(_)) => {
+            commands
+                .spawn((
+                    Transform::from_rotation(rotation),
+                    Mesh3d(meshes.add(Sphere {
+                        radius: 0.12,
+                        ..default()
+                    })),
+                    MeshMaterial3d(materials.add(StandardMaterial {
+                        base_color: CLIP_PLANE_COLOR,
+                        ..default()
+                    })),
+                    RenderLayers::layer(OVERLAY_LAYER),
+                    ClipPlaneHandle::default(),
+                ))
+                .observe(on_drag_start_clip_handle)
+                .observe(on_drag_clip_handle)
+                .observe(on_drag_end_clip_handle);
+        }
+        (false, Ok((entity, _))) => commands.entity(entity).despawn(),
+        (false, Err(_)) => {}
+    }
+}
+
+fn update_clip_plane_in_settings(
+    clip_plane: Res<SdfClipPlane>,
+    handles: Query<&Transform, With<ClipPlaneHandle>>,
+    mut camera_query: Query<&mut SDFRenderSettings, With<SdfRenderCamera>>,
+) {
+    // The handle is spawned a frame after enabling, until then nothing is cut
+    let origin = handles.single().ok().map(|transform| transform.translation);
+    let normal = clip_plane.normal;
+
+    for mut settings in camera_query.iter_mut() {
+        settings.clip_enabled = (clip_plane.enabled && origin.is_some()) as u32;
+        settings.clip_plane = normal.extend(normal.dot(origin.unwrap_or_default()));
+        settings.clip_cap_color = if clip_plane.cap {
+            clip_plane.cap_color.to_linear().to_vec3().extend(1.0)
+        } else {
+            Vec4::ZERO
+        };
+    }
+}
+
+fn draw_clip_plane(
+    clip_plane: Res<SdfClipPlane>,
+    handles: Query<&Transform, With<ClipPlaneHandle>>,
+    mut gizmos: Gizmos,
+) {
+    let Ok(transform) = handles.single() else {
+        return;
+    };
+
+    // Rects lie in the XY plane of their isometry, so Z is turned onto the normal
+    let rotation = Quat::from_rotation_arc(Vec3::Z, clip_plane.normal);
+    gizmos.rect(
+        Isometry3d::new(transform.translation, rotation),
+        Vec2::splat(CLIP_PLANE_GIZMO_SIZE),
+        CLIP_PLANE_COLOR,
+    );
+    gizmos.arrow(
+        transform.translation,
+        transform.translation + clip_plane.normal * 0.5,
+        CLIP_PLANE_COLOR,
+    );
+}
+
+fn on_drag_start_clip_handle(
+    trigger: Trigger<Pointer<DragStart>>,
+    mut handles: Query<(&mut ClipPlaneHandle, &Transform)>,
+    mut pan_orbit_query: Query<&mut PanOrbitCamera>,
+) {
+    let Some(hit_position) = trigger.event().hit.position else {
+        return;
+    };
+    let Ok((mut handle, transform)) = handles.get_mut(trigger.target()) else {
+        return;
+    };
+
+    handle.drag_start = Some((hit_position, transform.translation));
+
+    if let Ok(mut pan_orbit) = pan_orbit_query.single_mut() {
+        pan_orbit.enabled = false;
+    };
+}
+
+// Moves the plane along its normal to the point of the normal axis closest to the cursor
+fn on_drag_clip_handle(
+    trigger: Trigger<Pointer<Drag>>,
+    clip_plane: Res<SdfClipPlane>,
+    mut handles: Query<(&ClipPlaneHandle, &mut Transform)>,
+    cameras: Query<(&Camera, &GlobalTransform), With<OverlayCamera>>,
+) {
+    let Ok((handle, mut transform)) = handles.get_mut(trigger.target()) else {
+        return;
+    };
+    let Some((hit_start, origin_start)) = handle.drag_start else {
+        return;
+    };
+    let Ok((camera, camera_transform)) = cameras.single() else {
+        return;
+    };
+    let Ok(ray) = camera
+        .viewport_to_world(camera_transform, trigger.event().pointer_location.position)
+    else {
+        return;
+    };
+
+    let Some(offset) = ray_axis_offset(ray, hit_start, clip_plane.normal) else {
+        return;
+    };
+    transform.translation = origin_start + clip_plane.normal * offset;
+}
+
+fn on_drag_end_clip_handle(
+    trigger: Trigger<Pointer<DragEnd>>,
+    mut handles: Query<&mut ClipPlaneHandle>,
+    mut pan_orbit_query: Query<&mut PanOrbitCamera>,
+) {
+    if let Ok(mut handle) = handles.get_mut(trigger.target()) {
+        handle.drag_start = None;
+    }
+
+    if let Ok(mut pan_orbit) = pan_orbit_query.single_mut() {
+        pan_orbit.enabled = true;
+    };
+}
@@ -0,0 +1,188 @@
+//! Runtime tuning of the coarse prepass
+//!
+//! The SDF pass counts its fine march iterations for a sample of the pixels
+//! into a small storage buffer that is read back every frame. While the SDF
+//! pass runs over its target time, the coarse prepass resolution and step count
+//! shift towards whichever side does too much of the work: many fine steps per
+//! pixel mean the coarse distances stop too far from the surface, few mean the
+//! coarse pass itself is the expensive part.
+
+use bevy::{
+    diagnostic::DiagnosticsStore,
+    prelude::*,
+    render::{
+        extract_resource::{ExtractResource, ExtractResourcePlugin},
+        gpu_readback::{Readback, ReadbackComplete},
+        render_asset::RenderAssets,
+        render_resource::{BufferUsages, ShaderType},
+        renderer::RenderQueue,
+        storage::{GpuShaderStorageBuffer, ShaderStorageBuffer},
+        Render, RenderApp, RenderSet,
+    },
+};
+
+use crate::adaptive_resolution::sdf_pass_time;
+use crate::sdf_render::{SDFRenderSettings, SdfRenderCamera};
+
+// Bounds of the tuned coarse parameters
+pub const MIN_RESOLUTION_FACTOR: f32 = 1.0 / 32.0;
+pub const MAX_RESOLUTION_FACTOR: f32 = 1.0 / 4.0;
+const MIN_MAX_STEPS: u32 = 8;
+const MAX_MAX_STEPS: u32 = 64;
+const MAX_STEPS_STEP: u32 = 4;
+
+// Average fine iterations per pixel above which the coarse pass should do more,
+// and below which it should do less
+const FINE_STEPS_HIGH: f32 = 16.0;
+const FINE_STEPS_LOW: f32 = 4.0;
+
+// Frames between adjustments, so the timings can settle on the new parameters
+const ADJUST_INTERVAL: u32 = 30;
+
+pub struct CoarseTuningPlugin;
+
+#[derive(Resource, Clone)]
+pub struct SdfCoarseTuning {
+    pub enabled: bool,
+    // Milliseconds the SDF pass may take before the coarse pass is retuned
+    pub target_frame_time: f32,
+    pub resolution_factor: f32,
+    pub max_steps: u32,
+    // Fine march iterations per sampled pixel in the last read back frame
+    pub average_fine_steps: f32,
+    frames_since_adjustment: u32,
+}
+
+impl Default for SdfCoarseTuning {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            target_frame_time: 16.0,
+            resolution_factor: 0.0625,
+            max_steps: 24,
+            average_fine_steps: 0.0,
+            frames_since_adjustment: 0,
+        }
+    }
+}
+
+// Counters the SDF pass adds to (must match MarchStats in sdf_render.wgsl)
+#[derive(ShaderType, Clone, Copy, Default)]
+struct MarchStats {
+    fine_steps: u32,
+    pixels: u32,
+}
+
+// Storage buffer holding the MarchStats of the current frame
+#[derive(Resource, Clone)]
+pub struct SdfMarchStatsBuffer(pub Handle<ShaderStorageBuffer>);
+
+impl ExtractResource for SdfMarchStatsBuffer {
+    type Source = SdfMarchStatsBuffer;
+
+    fn extract_resource(source: &Self::Source) -> Self {
+        source.clone()
+    }
+}
+
+impl Plugin for CoarseTuningPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SdfCoarseTuning>()
+            .add_plugins(ExtractResourcePlugin::<SdfMarchStatsBuffer>::default())
+            .add_systems(Startup, setup_march_stats)
+            .add_systems(
+                Update,
+                (tune_coarse_prepass, update_coarse_in_settings).chain(),
+            );
+
+        let Some(render_app) = app.get_sub_app_mut(RenderApp) else {
+            return;
+        };
+
+        render_app.add_systems(
+            Render,
+            reset_march_stats.in_set(RenderSet::PrepareResources),
+        );
+    }
+}
+
+fn setup_march_stats(mut commands: Commands, mut buffers: ResMut<Assets<ShaderStorageBuffer>>) {
+    let mut buffer = ShaderStorageBuffer::from(MarchStats::default());
+    // Reset by the render world every frame and copied out for the readback
+    buffer.buffer_description.usage |= BufferUsages::COPY_SRC | BufferUsages::COPY_DST;
+    let handle = buffers.add(buffer);
+
+    commands.insert_resource(SdfMarchStatsBuffer(handle.clone()));
+    commands.spawn(Readback::buffer(handle)).observe(
+        |trigger: Trigger<ReadbackComplete>, mut tuning: ResMut<SdfCoarseTuning>| {
+            let stats: MarchStats = trigger.event().to_shader_type();
+            if stats.pixels > 0 {
+                tuning.average_fine_steps = stats.fine_steps as f32 / stats.pixels as f32;
+            }
+        },
+    );
+}
+
+fn tune_coarse_prepass(diagnostics: Res<DiagnosticsStore>, mut tuning: ResMut<SdfCoarseTuning>) {
+    if !tuning.enabled {
+        return;
+    }
+
+    tuning.frames_since_adjustment += 1;
+    if tuning.frames_since_adjustment < ADJUST_INTERVAL {
+        return;
+    }
+
+    let Some(frame_time) = sdf_pass_time(&diagnostics) else {
+        return;
+    };
+    if frame_time <= tuning.target_frame_time as f64 {
+        return;
+    }
+
+    // Step counts are tuned first since they're the finer-grained knob
+    let average_fine_steps = tuning.average_fine_steps;
+    if average_fine_steps > FINE_STEPS_HIGH {
+        if tuning.max_steps < MAX_MAX_STEPS {
+            tuning.max_steps = (tuning.max_steps + MAX_STEPS_STEP).min(MAX_MAX_STEPS);
+        } else if tuning.resolution_factor < MAX_RESOLUTION_FACTOR {
+            tuning.resolution_factor = (tuning.resolution_factor * 2.0).min(MAX_RESOLUTION_FACTOR);
+        }
+    } else if average_fine_steps < FINE_STEPS_LOW {
+        if tuning.max_steps > MIN_MAX_STEPS {
+            tuning.max_steps = tuning.max_steps.saturating_sub(MAX_STEPS_STEP).max(MIN_MAX_STEPS);
+        } else if tuning.resolution_factor > MIN_RESOLUTION_FACTOR {
+            tuning.resolution_factor = (tuning.resolution_factor * 0.5).max(MIN_RESOLUTION_FACTOR);
+        }
+    } else {
+        return;
+    }
+
+    tuning.frames_since_adjustment = 0;
+    info!(
+        "Coarse prepass retuned: {} steps at {} resolution ({:.1} fine steps per pixel)",
+        tuning.max_steps, tuning.resolution_factor, average_fine_steps
+    );
+}
+
+fn update_coarse_in_settings(
+    tuning: Res<SdfCoarseTuning>,
+    mut camera_query: Query<&mut SDFRenderSettings, With<SdfRenderCamera>>,
+) {
+    for mut settings in camera_query.iter_mut() {
+        settings.coarse_resolution_factor = tuning.resolution_factor;
+        settings.coarse_max_steps = tuning.max_steps;
+    }
+}
+
+// Zeroes the counters before the SDF pass adds this frame's iterations
+fn reset_march_stats(
+    stats_buffer: Option<Res<SdfMarchStatsBuffer>>,
+    buffers: Res<RenderAssets<GpuShaderStorageBuffer>>,
+    render_queue: Res<RenderQueue>,
+) {
+    let Some(buffer) = stats_buffer.and_then(|stats_buffer| buffers.get(&stats_buffer.0)) else {
+        return;
+    };
+    render_queue.write_buffer(&buffer.buffer, 0, bytemuck::bytes_of(&[0u32; 2]));
+}
@@ -1,13 +1,62 @@
-use bevy::prelude::*;
+use bevy::{
+    prelude::*,
+    render::view::screenshot::{save_to_disk, Screenshot},
+};
 use crossbeam_queue::SegQueue;
+use rand::Rng;
 
+use std::collections::HashMap;
 use std::sync::LazyLock;
 use wasm_bindgen::{prelude::wasm_bindgen, JsValue};
 
+use crate::adaptive_resolution::SdfAdaptiveResolution;
+use crate::align::{distribute_targets, AlignMode};
+use crate::clip_plane::SdfClipPlane;
+use crate::brick_map::{AccelerationStructure, StartAccelerationBenchmark};
+use crate::coarse_tuning::SdfCoarseTuning;
+use crate::sdf_volume_cache::SdfVolumeCache;
+use crate::view_culling::SdfViewCulling;
+use crate::volume_estimate::{
+    EstimateVolume, VolumeEstimated, DEFAULT_VOLUME_SAMPLES, MAX_VOLUME_SAMPLES,
+};
+use crate::brush_mode::{
+    BrushJitter, BrushSettings, BrushTool, BrushToolState, CurveProfile, PressureCurve,
+};
+use crate::edit_history::{Edit, EditHistory, EntitySnapshot, HistoryAction, RenderParts};
+use crate::entity_info::{EntityDetailsQuery, EntitySummary, SdfEntityInfo};
+use crate::export::{build_scene_gltf, build_sphere_colliders, colliders_to_json};
+use crate::journal::{ExportJournal, JournalExported, ReplayJournal, StartJournal, StopJournal};
+use crate::localization::Localization;
+use crate::meshing::{ExportMesh, MeshExported, MeshFormat, DEFAULT_MESH_RESOLUTION};
 use crate::mode::{AppMode, AppModeState};
-use crate::sdf_render::{SDFRenderEnabled, SDFRenderEntity};
-use crate::selection::handle_selection;
-use crate::translation::Translatable;
+use crate::sdf_cpu::entity_distance;
+use crate::pivot::{bounds_center, pivot_offset_at};
+use crate::preview_mesh::SdfPreviewMesh;
+use crate::progress::AppEvent;
+use crate::project_file::{
+    ListRecentProjects, OpenProject, ProjectSaved, RecentProjectsListed, SaveProject,
+};
+use crate::scene_io::{ImportScene, LoadScene, SaveScene, SceneSaved};
+use crate::scene_snapshots::{
+    CreateSnapshot, DeleteSnapshot, ListSnapshots, RestoreSnapshot, SnapshotsListed,
+};
+use crate::sdf_render::{
+    NoiseDisplacement, Repetition, SDFRenderEnabled, SDFRenderEntity, SdfAmbientOcclusion,
+    SdfBlend, SdfEnvironment, SdfFog, SdfGroundPlane, SdfIsolateMode, SdfOperation, SdfPrimitive,
+    SdfSceneQuery, SdfShadows, ViewportAppearance, MAX_REPETITIONS,
+};
+use crate::selection::{
+    click_select, deselect, DeleteEntities, DeleteTarget, EntitiesDeleted,
+    EntityDeselectedEvent, EntitySelectedEvent, SelectionState,
+};
+use crate::symmetry::Symmetry;
+use crate::temporal_accumulation::SdfTemporalAccumulation;
+use crate::translation::GizmoOcclusion;
+use crate::turntable_capture::StartTurntableCapture;
+use crate::view_presets::CameraRequest;
+
+// How far a single paint dab blends towards the brush color at its center
+const PAINT_STRENGTH: f32 = 0.3;
 
 #[derive(Resource)]
 pub struct EntityIndexCounter {
@@ -25,7 +74,26 @@ pub struct CommandBridgePlugin;
 impl Plugin for CommandBridgePlugin {
     fn build(&self, app: &mut App) {
         app.init_resource::<EntityIndexCounter>()
-            .add_systems(Update, (process_app_commands, monitor_mode_changes));
+            .add_event::<SceneQuery>()
+            .add_observer(dispatch_entity_selected)
+            .add_observer(dispatch_entity_deselected)
+            .add_systems(
+                Update,
+                (
+                    process_app_commands,
+                    monitor_mode_changes,
+                    dispatch_volume_estimates,
+                    deliver_mesh_exports,
+                    deliver_saved_scenes,
+                    deliver_saved_projects,
+                    dispatch_recent_projects,
+                    dispatch_snapshots,
+                    deliver_journal_exports,
+                    dispatch_deleted_entities,
+                    answer_scene_queries,
+                    dispatch_progress,
+                ),
+            );
     }
 }
 
@@ -35,11 +103,26 @@ extern "C" {
     fn dispatch_bevy_event_js(event_name: &str, detail: JsValue);
 }
 
+// Read-only questions about the scene, answered by answer_scene_queries
+#[derive(Event)]
+pub enum SceneQuery {
+    Summary,
+    Entity(usize),
+}
+
 pub enum AppCommand {
-    SpawnSphereCommand {
+    SpawnPrimitiveCommand {
         position: Vec3,
         scale: f32,
         color: Color,
+        operation: SdfOperation,
+        primitive: SdfPrimitive,
+    },
+    SpawnBlobCommand {
+        position: Vec3,
+        count: u32,
+        radius: f32,
+        jitter: f32,
     },
     SetModeCommand {
         mode: String,
@@ -47,6 +130,229 @@ pub enum AppCommand {
     SetPostProcessEnabledCommand {
         enabled: bool,
     },
+    ExportCollidersCommand,
+    ExportSceneGltfCommand,
+    ExportMeshCommand {
+        format: String,
+        resolution: u32,
+    },
+    SetGroundPlaneCommand {
+        enabled: bool,
+        height: f32,
+    },
+    SetShadowsCommand {
+        enabled: bool,
+        softness: f32,
+    },
+    SetBackgroundCommand {
+        zenith: Color,
+        horizon: Color,
+    },
+    SetGroundGridCommand {
+        enabled: bool,
+        spacing: f32,
+        color: Color,
+    },
+    SetAmbientOcclusionCommand {
+        enabled: bool,
+        intensity: f32,
+        radius: f32,
+    },
+    SetEnvironmentCommand {
+        path: Option<String>,
+        intensity: f32,
+    },
+    SetIsolateModeCommand {
+        enabled: bool,
+        opacity: f32,
+    },
+    SetFogCommand {
+        enabled: bool,
+        color: Color,
+        density: f32,
+        start: f32,
+    },
+    SetGizmoOcclusionCommand {
+        enabled: bool,
+    },
+    SetAdaptiveResolutionCommand {
+        enabled: bool,
+        target_frame_time: f32,
+        min_scale: f32,
+    },
+    SetCoarseTuningCommand {
+        enabled: bool,
+        target_frame_time: f32,
+    },
+    SetVolumeCacheCommand {
+        enabled: bool,
+        resolution: u32,
+    },
+    SetAccelerationStructureCommand {
+        structure: String,
+    },
+    BenchmarkAccelerationStructuresCommand,
+    EstimateVolumeCommand {
+        samples: u32,
+    },
+    SetViewCullingCommand {
+        enabled: bool,
+        margin: f32,
+    },
+    SetPreviewMeshCommand {
+        enabled: bool,
+        cell_size: f32,
+    },
+    SaveSceneCommand {
+        path: String,
+    },
+    LoadSceneCommand {
+        contents: String,
+    },
+    ImportSceneCommand {
+        contents: String,
+        translation: Vec3,
+        scale: f32,
+    },
+    SaveProjectCommand {
+        path: String,
+        author: Option<String>,
+    },
+    OpenProjectCommand {
+        path: String,
+        bytes: Vec<u8>,
+    },
+    ListRecentProjectsCommand,
+    CreateSnapshotCommand {
+        name: String,
+    },
+    RestoreSnapshotCommand {
+        name: String,
+    },
+    DeleteSnapshotCommand {
+        name: String,
+    },
+    ListSnapshotsCommand,
+    StartJournalCommand {
+        path: Option<String>,
+    },
+    StopJournalCommand,
+    ExportJournalCommand,
+    ReplayJournalCommand {
+        contents: String,
+        speed: Option<f32>,
+    },
+    SetTemporalAccumulationCommand {
+        enabled: bool,
+        blend: f32,
+    },
+    SetClipPlaneCommand {
+        enabled: bool,
+        normal: Vec3,
+    },
+    SetClipCapCommand {
+        enabled: bool,
+        color: Color,
+    },
+    StartTurntableCaptureCommand {
+        frames: u32,
+    },
+    CaptureScreenshotCommand {
+        path: String,
+    },
+    SetLanguageCommand {
+        code: String,
+    },
+    SetSelectedDisplacementCommand {
+        amplitude: f32,
+        frequency: f32,
+    },
+    SetSelectedShellCommand {
+        thickness: f32,
+    },
+    SetSelectedRepetitionCommand {
+        count: u32,
+        axis: String,
+        spacing: f32,
+    },
+    TranslateSelectedCommand {
+        axis: String,
+        offset: f32,
+    },
+    AlignSelectedCommand {
+        axis: String,
+        mode: String,
+    },
+    DistributeSelectedCommand {
+        axis: String,
+    },
+    SetSelectedPivotCommand {
+        position: Vec3,
+    },
+    SetEntityTransformCommand {
+        id: usize,
+        transform: Transform,
+    },
+    CameraCommand {
+        request: CameraRequest,
+    },
+    CenterSelectedPivotCommand,
+    EraseAtCommand {
+        position: Vec3,
+        radius: f32,
+    },
+    PaintAtCommand {
+        position: Vec3,
+        radius: f32,
+        color: Color,
+    },
+    SetBrushColorCommand {
+        color: Color,
+    },
+    SetPressureCurveCommand {
+        curve: PressureCurve,
+    },
+    SetBrushJitterCommand {
+        jitter: BrushJitter,
+    },
+    SetCurveProfileCommand {
+        profile: CurveProfile,
+    },
+    SetBrushSymmetryCommand {
+        axis: String,
+        fold: u32,
+        mirror_axis: Option<String>,
+    },
+    SetBrushToolCommand {
+        tool: String,
+    },
+    RenameEntityCommand {
+        id: usize,
+        name: String,
+    },
+    TagEntityCommand {
+        id: usize,
+        tag: String,
+    },
+    FindEntitiesByTagCommand {
+        tag: String,
+    },
+    DeleteEntityCommand {
+        id: usize,
+    },
+    DeleteSelectedCommand,
+    ClearSceneCommand,
+    GetSceneSummaryCommand,
+    GetEntityCommand {
+        id: usize,
+    },
+    SelectEntityCommand {
+        id: usize,
+    },
+    UndoCommand,
+    RedoCommand,
+    BeginEditGroupCommand,
+    EndEditGroupCommand,
 }
 
 // Global thread-safe queue for JS commands
@@ -57,40 +363,161 @@ pub fn process_app_commands(
     mut commands: Commands,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<StandardMaterial>>,
-    _camera: Query<(&Camera, &GlobalTransform)>,
     mut mode_state: ResMut<AppModeState>,
     mut post_process_enabled: ResMut<SDFRenderEnabled>,
     mut entity_index_counter: ResMut<EntityIndexCounter>,
+    mut sdf_entities: Query<(Entity, &mut SDFRenderEntity)>,
+    mut entity_infos: Query<&mut SdfEntityInfo>,
+    mut transforms: Query<&mut Transform>,
+    (render_parts, children): (Query<RenderParts>, Query<&Children>),
+    mut history: ResMut<EditHistory>,
+    mut selection_state: ResMut<SelectionState>,
+    (
+        mut ground_plane,
+        mut shadows,
+        mut ambient_occlusion,
+        mut environment,
+        mut gizmo_occlusion,
+        mut adaptive_resolution,
+        mut temporal_accumulation,
+        mut clip_plane,
+        mut viewport_appearance,
+        mut fog,
+        mut isolate_mode,
+        mut coarse_tuning,
+        mut volume_cache,
+        mut acceleration_structure,
+        mut view_culling,
+        blend,
+    ): (
+        ResMut<SdfGroundPlane>,
+        ResMut<SdfShadows>,
+        ResMut<SdfAmbientOcclusion>,
+        ResMut<SdfEnvironment>,
+        ResMut<GizmoOcclusion>,
+        ResMut<SdfAdaptiveResolution>,
+        ResMut<SdfTemporalAccumulation>,
+        ResMut<SdfClipPlane>,
+        ResMut<ViewportAppearance>,
+        ResMut<SdfFog>,
+        ResMut<SdfIsolateMode>,
+        ResMut<SdfCoarseTuning>,
+        ResMut<SdfVolumeCache>,
+        ResMut<AccelerationStructure>,
+        ResMut<SdfViewCulling>,
+        Res<SdfBlend>,
+    ),
+    (mut localization, asset_server, mut preview_mesh): (
+        ResMut<Localization>,
+        Res<AssetServer>,
+        ResMut<SdfPreviewMesh>,
+    ),
+    scene_query: SdfSceneQuery,
+    (mut brush_tool, mut brush_settings, mut symmetry): (
+        ResMut<BrushToolState>,
+        ResMut<BrushSettings>,
+        ResMut<Symmetry>,
+    ),
 ) {
     while let Some(cmd) = APP_COMMAND_QUEUE.pop() {
         match cmd {
-            AppCommand::SpawnSphereCommand {
+            AppCommand::SpawnPrimitiveCommand {
                 position,
                 color,
                 scale,
+                operation,
+                primitive,
             } => {
                 let index = entity_index_counter.counter;
                 entity_index_counter.counter += 1;
-                commands
-                    .spawn((
-                        Translatable,
-                        SDFRenderEntity {
+                let mesh = primitive.proxy_mesh(scale);
+                let mut snapshot = EntitySnapshot {
+                    entity: Entity::PLACEHOLDER,
+                    sdf: Some(SDFRenderEntity {
+                        node_index: index,
+                        position,
+                        scale,
+                        operation,
+                        primitive,
+                        displacement: NoiseDisplacement::default(),
+                        shell_thickness: 0.,
+                        repetition: Repetition::default(),
+                    }),
+                    info: Some(SdfEntityInfo::numbered(primitive, index)),
+                    transform: Transform::from_translation(position),
+                    mesh: Some(meshes.add(mesh)),
+                    material: Some(materials.add(StandardMaterial {
+                        base_color: color,
+                        ..default()
+                    })),
+                    parent: None,
+                    pivot: None,
+                };
+                snapshot.entity = snapshot.spawn(&mut commands);
+                history.record(Edit::Spawn(snapshot));
+            }
+            AppCommand::SpawnBlobCommand {
+                position,
+                count,
+                radius,
+                jitter,
+            } => {
+                let mut rng = rand::rng();
+                let first_index = entity_index_counter.counter;
+                // The group is what gets selected and dragged; the spheres follow
+                // it through their GlobalTransform
+                let mut group_snapshot = EntitySnapshot {
+                    entity: Entity::PLACEHOLDER,
+                    sdf: None,
+                    info: Some(SdfEntityInfo {
+                        name: format!("Blob {}", first_index + 1),
+                        tags: vec!["blob".to_string()],
+                    }),
+                    transform: Transform::from_translation(position),
+                    mesh: None,
+                    material: None,
+                    parent: None,
+                    pivot: None,
+                };
+                let group = group_snapshot.spawn(&mut commands);
+                group_snapshot.entity = group;
+                let mut edits = vec![Edit::Spawn(group_snapshot)];
+                let mesh = meshes.add(Mesh::from(Sphere { radius }));
+                let material = materials.add(StandardMaterial {
+                    base_color: Color::Srgba(Srgba::WHITE),
+                    ..default()
+                });
+                for _ in 0..count.clamp(1, MAX_BLOB_SPHERES) {
+                    let index = entity_index_counter.counter;
+                    entity_index_counter.counter += 1;
+                    let offset = Vec3::new(
+                        rng.random_range(-1.0..1.0),
+                        rng.random_range(-1.0..1.0),
+                        rng.random_range(-1.0..1.0),
+                    ) * jitter;
+                    let mut snapshot = EntitySnapshot {
+                        entity: Entity::PLACEHOLDER,
+                        sdf: Some(SDFRenderEntity {
                             node_index: index,
-                            position,
-                            scale,
-                        },
-                        Transform::from_translation(position),
-                        Mesh3d(meshes.add(Sphere {
-                            radius: scale,
-                            ..default()
-                        })),
-                        MeshMaterial3d(materials.add(StandardMaterial {
-                            base_color: color,
-                            ..default()
-                        })),
-                        GlobalTransform::default(),
-                    ))
-                    .observe(handle_selection);
+                            position: position + offset,
+                            scale: radius,
+                            operation: SdfOperation::Union,
+                            primitive: SdfPrimitive::Sphere,
+                            displacement: NoiseDisplacement::default(),
+                            shell_thickness: 0.,
+                            repetition: Repetition::default(),
+                        }),
+                        info: Some(SdfEntityInfo::numbered(SdfPrimitive::Sphere, index)),
+                        transform: Transform::from_translation(offset),
+                        mesh: Some(mesh.clone()),
+                        material: Some(material.clone()),
+                        parent: Some(group),
+                        pivot: None,
+                    };
+                    snapshot.entity = snapshot.spawn(&mut commands);
+                    edits.push(Edit::Spawn(snapshot));
+                }
+                history.record_all(edits);
             }
             AppCommand::SetModeCommand { mode } => {
                 match mode.as_str() {
@@ -105,24 +532,722 @@ pub fn process_app_commands(
             AppCommand::SetPostProcessEnabledCommand { enabled } => {
                 post_process_enabled.enabled = enabled;
             }
+            AppCommand::ExportCollidersCommand => {
+                let colliders = build_sphere_colliders(sdf_entities.iter().map(|(_, e)| e));
+                info!("Exporting {} collider spheres", colliders.spheres.len());
+                deliver_export(
+                    "collidersExported",
+                    "colliders.json",
+                    &colliders_to_json(&colliders),
+                );
+            }
+            AppCommand::ExportMeshCommand { format, resolution } => {
+                let format = match format.as_str() {
+                    "obj" => MeshFormat::Obj,
+                    "stl" => MeshFormat::Stl,
+                    "gltf" => MeshFormat::Gltf,
+                    _ => {
+                        warn!("Unknown mesh format requested: {}", format);
+                        continue;
+                    }
+                };
+                commands.send_event(ExportMesh { format, resolution });
+            }
+            AppCommand::SetGroundPlaneCommand { enabled, height } => {
+                ground_plane.enabled = enabled;
+                ground_plane.height = height;
+            }
+            AppCommand::SetShadowsCommand { enabled, softness } => {
+                shadows.enabled = enabled;
+                shadows.softness = softness;
+            }
+            AppCommand::SetBackgroundCommand { zenith, horizon } => {
+                viewport_appearance.zenith_color = zenith;
+                viewport_appearance.horizon_color = horizon;
+            }
+            AppCommand::SetGroundGridCommand {
+                enabled,
+                spacing,
+                color,
+            } => {
+                viewport_appearance.grid_enabled = enabled;
+                viewport_appearance.grid_spacing = spacing;
+                viewport_appearance.grid_color = color;
+            }
+            AppCommand::SetAmbientOcclusionCommand {
+                enabled,
+                intensity,
+                radius,
+            } => {
+                ambient_occlusion.enabled = enabled;
+                ambient_occlusion.intensity = intensity;
+                ambient_occlusion.radius = radius;
+            }
+            AppCommand::SetIsolateModeCommand { enabled, opacity } => {
+                isolate_mode.enabled = enabled;
+                isolate_mode.opacity = opacity;
+            }
+            AppCommand::SetFogCommand {
+                enabled,
+                color,
+                density,
+                start,
+            } => {
+                fog.enabled = enabled;
+                fog.color = color;
+                fog.density = density;
+                fog.start = start;
+            }
+            AppCommand::SetEnvironmentCommand { path, intensity } => {
+                environment.image = path.map(|path| asset_server.load(path));
+                environment.intensity = intensity;
+            }
+            AppCommand::SetGizmoOcclusionCommand { enabled } => {
+                gizmo_occlusion.enabled = enabled;
+            }
+            AppCommand::SetAdaptiveResolutionCommand {
+                enabled,
+                target_frame_time,
+                min_scale,
+            } => {
+                adaptive_resolution.enabled = enabled;
+                adaptive_resolution.target_frame_time = target_frame_time;
+                adaptive_resolution.min_scale = min_scale;
+            }
+            AppCommand::SetCoarseTuningCommand {
+                enabled,
+                target_frame_time,
+            } => {
+                coarse_tuning.enabled = enabled;
+                coarse_tuning.target_frame_time = target_frame_time;
+            }
+            AppCommand::SetVolumeCacheCommand {
+                enabled,
+                resolution,
+            } => {
+                volume_cache.enabled = enabled;
+                volume_cache.resolution = resolution;
+            }
+            AppCommand::SetAccelerationStructureCommand { structure } => {
+                match structure.as_str() {
+                    "bvh" => *acceleration_structure = AccelerationStructure::Bvh,
+                    "brick_map" => *acceleration_structure = AccelerationStructure::BrickMap,
+                    _ => {
+                        warn!("Unknown acceleration structure requested: {}", structure);
+                    }
+                }
+            }
+            AppCommand::BenchmarkAccelerationStructuresCommand => {
+                commands.send_event(StartAccelerationBenchmark);
+            }
+            AppCommand::EstimateVolumeCommand { samples } => {
+                commands.send_event(EstimateVolume { samples });
+            }
+            AppCommand::SetViewCullingCommand { enabled, margin } => {
+                view_culling.enabled = enabled;
+                view_culling.margin = margin;
+            }
+            AppCommand::SetPreviewMeshCommand { enabled, cell_size } => {
+                preview_mesh.enabled = enabled;
+                preview_mesh.cell_size = cell_size;
+            }
+            AppCommand::SaveSceneCommand { path } => {
+                commands.send_event(SaveScene { path });
+            }
+            AppCommand::LoadSceneCommand { contents } => {
+                commands.send_event(LoadScene { contents });
+            }
+            AppCommand::ImportSceneCommand {
+                contents,
+                translation,
+                scale,
+            } => {
+                commands.send_event(ImportScene {
+                    contents,
+                    translation,
+                    scale,
+                });
+            }
+            AppCommand::SaveProjectCommand { path, author } => {
+                commands.send_event(SaveProject { path, author });
+            }
+            AppCommand::OpenProjectCommand { path, bytes } => {
+                commands.send_event(OpenProject { path, bytes });
+            }
+            AppCommand::ListRecentProjectsCommand => {
+                commands.send_event(ListRecentProjects);
+            }
+            AppCommand::CreateSnapshotCommand { name } => {
+                commands.send_event(CreateSnapshot { name });
+            }
+            AppCommand::RestoreSnapshotCommand { name } => {
+                commands.send_event(RestoreSnapshot { name });
+            }
+            AppCommand::DeleteSnapshotCommand { name } => {
+                commands.send_event(DeleteSnapshot { name });
+            }
+            AppCommand::ListSnapshotsCommand => {
+                commands.send_event(ListSnapshots);
+            }
+            AppCommand::StartJournalCommand { path } => {
+                commands.send_event(StartJournal { path });
+            }
+            AppCommand::StopJournalCommand => {
+                commands.send_event(StopJournal);
+            }
+            AppCommand::ExportJournalCommand => {
+                commands.send_event(ExportJournal);
+            }
+            AppCommand::ReplayJournalCommand { contents, speed } => {
+                commands.send_event(ReplayJournal { contents, speed });
+            }
+            AppCommand::SetTemporalAccumulationCommand { enabled, blend } => {
+                temporal_accumulation.enabled = enabled;
+                temporal_accumulation.blend = blend;
+            }
+            AppCommand::SetClipPlaneCommand { enabled, normal } => {
+                clip_plane.enabled = enabled;
+                clip_plane.normal = normal;
+            }
+            AppCommand::SetClipCapCommand { enabled, color } => {
+                clip_plane.cap = enabled;
+                clip_plane.cap_color = color;
+            }
+            AppCommand::StartTurntableCaptureCommand { frames } => {
+                commands.send_event(StartTurntableCapture { frames });
+            }
+            AppCommand::CaptureScreenshotCommand { path } => {
+                // Read back once the frame, SDF pass and post processing included, is done
+                commands
+                    .spawn(Screenshot::primary_window())
+                    .observe(save_to_disk(path));
+            }
+            AppCommand::SetLanguageCommand { code } => {
+                localization.set_language(&code);
+                info!("Language changed to: {:?}", localization.language);
+            }
+            AppCommand::SetSelectedDisplacementCommand {
+                amplitude,
+                frequency,
+            } => {
+                if selection_state.is_empty() {
+                    warn!("No entity selected to displace");
+                    continue;
+                }
+                let mut edits = Vec::new();
+                for selected in selection_state.iter() {
+                    let Ok((_, mut sdf_entity)) = sdf_entities.get_mut(selected) else {
+                        continue;
+                    };
+                    let before = sdf_entity.clone();
+                    sdf_entity.displacement = NoiseDisplacement {
+                        amplitude,
+                        frequency,
+                    };
+                    edits.push(Edit::Sdf {
+                        entity: selected,
+                        before,
+                        after: sdf_entity.clone(),
+                    });
+                }
+                history.record_all(edits);
+            }
+            AppCommand::SetSelectedShellCommand { thickness } => {
+                if selection_state.is_empty() {
+                    warn!("No entity selected to hollow out");
+                    continue;
+                }
+                let mut edits = Vec::new();
+                for selected in selection_state.iter() {
+                    let Ok((_, mut sdf_entity)) = sdf_entities.get_mut(selected) else {
+                        continue;
+                    };
+                    let before = sdf_entity.clone();
+                    sdf_entity.shell_thickness = thickness.max(0.);
+                    edits.push(Edit::Sdf {
+                        entity: selected,
+                        before,
+                        after: sdf_entity.clone(),
+                    });
+                }
+                history.record_all(edits);
+            }
+            AppCommand::SetSelectedRepetitionCommand {
+                count,
+                axis,
+                spacing,
+            } => {
+                let direction = match axis.as_str() {
+                    "X" => Vec3::X,
+                    "Y" => Vec3::Y,
+                    "Z" => Vec3::Z,
+                    _ => {
+                        warn!("Unknown repetition axis requested: {}", axis);
+                        continue;
+                    }
+                };
+                if selection_state.is_empty() {
+                    warn!("No entity selected to repeat");
+                    continue;
+                }
+                let mut edits = Vec::new();
+                for selected in selection_state.iter() {
+                    let Ok((_, mut sdf_entity)) = sdf_entities.get_mut(selected) else {
+                        continue;
+                    };
+                    let before = sdf_entity.clone();
+                    sdf_entity.repetition = Repetition {
+                        count: count.clamp(1, MAX_REPETITIONS),
+                        spacing: direction * spacing,
+                    };
+                    edits.push(Edit::Sdf {
+                        entity: selected,
+                        before,
+                        after: sdf_entity.clone(),
+                    });
+                }
+                history.record_all(edits);
+            }
+            AppCommand::TranslateSelectedCommand { axis, offset } => {
+                let direction = match axis.as_str() {
+                    "X" => Vec3::X,
+                    "Y" => Vec3::Y,
+                    "Z" => Vec3::Z,
+                    _ => {
+                        warn!("Unknown translation axis requested: {}", axis);
+                        continue;
+                    }
+                };
+                if selection_state.is_empty() {
+                    warn!("No entity selected to translate");
+                    continue;
+                }
+                let mut edits = Vec::new();
+                for selected in selection_state.iter() {
+                    let Ok(mut transform) = transforms.get_mut(selected) else {
+                        continue;
+                    };
+                    let before = *transform;
+                    transform.translation += direction * offset;
+                    edits.push(Edit::Transform {
+                        entity: selected,
+                        before,
+                        after: *transform,
+                    });
+                }
+                history.record_all(edits);
+            }
+            AppCommand::AlignSelectedCommand { axis, mode } => {
+                let direction = match axis.as_str() {
+                    "X" => Vec3::X,
+                    "Y" => Vec3::Y,
+                    "Z" => Vec3::Z,
+                    _ => {
+                        warn!("Unknown alignment axis requested: {}", axis);
+                        continue;
+                    }
+                };
+                let Some(align_mode) = AlignMode::parse(&mode) else {
+                    warn!("Unknown alignment mode requested: {}", mode);
+                    continue;
+                };
+                let selected = selected_coordinates(&selection_state, &transforms, direction);
+                if selected.len() < 2 {
+                    warn!("Select at least two entities to align");
+                    continue;
+                }
+                let values: Vec<f32> = selected.iter().map(|(_, value)| *value).collect();
+                let target = align_mode.target(&values);
+                let targets: Vec<(Entity, f32)> =
+                    selected.iter().map(|(entity, _)| (*entity, target)).collect();
+                history.record_all(move_along_axis(&mut transforms, direction, &targets));
+            }
+            AppCommand::DistributeSelectedCommand { axis } => {
+                let direction = match axis.as_str() {
+                    "X" => Vec3::X,
+                    "Y" => Vec3::Y,
+                    "Z" => Vec3::Z,
+                    _ => {
+                        warn!("Unknown distribution axis requested: {}", axis);
+                        continue;
+                    }
+                };
+                let selected = selected_coordinates(&selection_state, &transforms, direction);
+                if selected.len() < 3 {
+                    warn!("Select at least three entities to distribute");
+                    continue;
+                }
+                let values: Vec<f32> = selected.iter().map(|(_, value)| *value).collect();
+                let targets: Vec<(Entity, f32)> = selected
+                    .iter()
+                    .map(|(entity, _)| *entity)
+                    .zip(distribute_targets(&values))
+                    .collect();
+                history.record_all(move_along_axis(&mut transforms, direction, &targets));
+            }
+            AppCommand::CameraCommand { request } => {
+                commands.send_event(request);
+            }
+            AppCommand::SetEntityTransformCommand { id, transform } => {
+                let Some(entity) = find_entity_by_id(&sdf_entities, id) else {
+                    warn!("No entity with id {} to transform", id);
+                    continue;
+                };
+                let Ok(mut entity_transform) = transforms.get_mut(entity) else {
+                    continue;
+                };
+                let before = *entity_transform;
+                *entity_transform = transform;
+                let mut edits = vec![Edit::Transform {
+                    entity,
+                    before,
+                    after: transform,
+                }];
+
+                // The SDF is resized along the entity's own axes like a gizmo
+                // scale drag would, then turned with it; its position follows
+                // the GlobalTransform
+                let factor = transform.scale / before.scale.max(Vec3::splat(f32::EPSILON));
+                if factor != Vec3::ONE || transform.rotation != before.rotation {
+                    if let Ok((_, mut sdf_entity)) = sdf_entities.get_mut(entity) {
+                        let unturned = sdf_entity.primitive.rotated(before.rotation.inverse());
+                        let (scale, primitive) = unturned.scaled(sdf_entity.scale, factor);
+                        let before = sdf_entity.clone();
+                        sdf_entity.scale = scale;
+                        sdf_entity.primitive = primitive.rotated(transform.rotation);
+                        edits.push(Edit::Sdf {
+                            entity,
+                            before,
+                            after: sdf_entity.clone(),
+                        });
+                    }
+                }
+                history.record_all(edits);
+            }
+            AppCommand::SetSelectedPivotCommand { position } => {
+                if selection_state.is_empty() {
+                    warn!("No entity selected to set the pivot of");
+                    continue;
+                }
+                for selected in selection_state.iter() {
+                    if let Ok(transform) = transforms.get(selected) {
+                        commands
+                            .entity(selected)
+                            .insert(pivot_offset_at(transform, position));
+                    }
+                }
+            }
+            AppCommand::CenterSelectedPivotCommand => {
+                if selection_state.is_empty() {
+                    warn!("No entity selected to center the pivot of");
+                    continue;
+                }
+                for selected in selection_state.iter() {
+                    let Ok(transform) = transforms.get(selected) else {
+                        continue;
+                    };
+                    // Groups are bounded by their children
+                    let center = bounds_center(
+                        std::iter::once(selected)
+                            .chain(children.iter_descendants(selected))
+                            .filter_map(|entity| sdf_entities.get(entity).ok().map(|(_, e)| e)),
+                    );
+                    if let Some(center) = center {
+                        commands
+                            .entity(selected)
+                            .insert(pivot_offset_at(transform, center));
+                    }
+                }
+            }
+            AppCommand::EraseAtCommand { position, radius } => {
+                let mut edits = Vec::new();
+                for entity in scene_query.entities_overlapping_sphere(position, radius) {
+                    edits.push(Edit::Delete(EntitySnapshot::capture(
+                        entity,
+                        transforms.get(entity).copied().unwrap_or_default(),
+                        sdf_entities.get(entity).ok().map(|(_, e)| e),
+                        entity_infos.get(entity).ok(),
+                        render_parts.get(entity).unwrap_or_default(),
+                    )));
+                    if selection_state.is_selected(entity) {
+                        deselect(&mut commands, &mut selection_state, entity);
+                    }
+                    commands.entity(entity).despawn();
+                }
+                history.record_all(edits);
+            }
+            AppCommand::PaintAtCommand {
+                position,
+                radius,
+                color,
+            } => {
+                // The SDF pass has no per-entity colors, so painting tints the
+                // material entities were spawned with. Entities can share one,
+                // like the spheres of a blob, so painted ones get their own first
+                let mut users = HashMap::<AssetId<StandardMaterial>, usize>::new();
+                for (_, _, material, _) in render_parts.iter() {
+                    if let Some(material) = material {
+                        *users.entry(material.id()).or_default() += 1;
+                    }
+                }
+                let mut edits = Vec::new();
+                for entity in scene_query.entities_overlapping_sphere(position, radius) {
+                    let Ok((_, sdf_entity)) = sdf_entities.get(entity) else {
+                        continue;
+                    };
+                    let Some(mut handle) = render_parts
+                        .get(entity)
+                        .ok()
+                        .and_then(|(_, _, material, _)| material.map(|m| m.0.clone()))
+                    else {
+                        continue;
+                    };
+                    let shared = users.get_mut(&handle.id()).filter(|count| **count > 1);
+                    if let Some(count) = shared {
+                        let Some(own) = materials.get(&handle).cloned() else {
+                            continue;
+                        };
+                        *count -= 1;
+                        handle = materials.add(own);
+                        commands.entity(entity).insert(MeshMaterial3d(handle.clone()));
+                    }
+                    let Some(material) = materials.get_mut(&handle) else {
+                        continue;
+                    };
+                    // Full strength at the brush center, fading out towards its edge
+                    let t = (entity_distance(sdf_entity, position).max(0.) / radius).min(1.);
+                    let before = material.base_color;
+                    material.base_color = before.mix(&color, PAINT_STRENGTH * (1. - t * t));
+                    edits.push(Edit::Color {
+                        entity,
+                        before,
+                        after: material.base_color,
+                    });
+                }
+                history.record_all(edits);
+            }
+            AppCommand::SetBrushColorCommand { color } => {
+                brush_tool.color = color;
+            }
+            AppCommand::SetPressureCurveCommand { curve } => {
+                brush_settings.pressure_curve = curve;
+            }
+            AppCommand::SetBrushJitterCommand { jitter } => {
+                brush_settings.jitter = jitter;
+            }
+            AppCommand::SetCurveProfileCommand { profile } => {
+                brush_settings.curve = profile;
+            }
+            AppCommand::SetBrushSymmetryCommand {
+                axis,
+                fold,
+                mirror_axis,
+            } => {
+                let parse_axis = |axis: &str| match axis {
+                    "X" => Some(Vec3::X),
+                    "Y" => Some(Vec3::Y),
+                    "Z" => Some(Vec3::Z),
+                    _ => None,
+                };
+                let Some(direction) = parse_axis(&axis) else {
+                    warn!("Unknown symmetry axis requested: {}", axis);
+                    continue;
+                };
+                let mirror_normal = match mirror_axis {
+                    Some(mirror_axis) => match parse_axis(&mirror_axis) {
+                        Some(normal) => Some(normal),
+                        None => {
+                            warn!("Unknown mirror axis requested: {}", mirror_axis);
+                            continue;
+                        }
+                    },
+                    None => None,
+                };
+                symmetry.axis = direction;
+                symmetry.fold = fold.max(1);
+                symmetry.mirror_normal = mirror_normal;
+            }
+            AppCommand::SetBrushToolCommand { tool } => {
+                match tool.as_str() {
+                    "Sculpt" => brush_tool.tool = BrushTool::Sculpt,
+                    "Erase" => brush_tool.tool = BrushTool::Erase,
+                    "Capsule" => brush_tool.tool = BrushTool::Capsule,
+                    "Grab" => brush_tool.tool = BrushTool::Grab,
+                    "Tube" => brush_tool.tool = BrushTool::Tube,
+                    "Paint" => brush_tool.tool = BrushTool::Paint,
+                    "Curve" => brush_tool.tool = BrushTool::Curve,
+                    _ => {
+                        warn!("Unknown brush tool requested: {}", tool);
+                    }
+                }
+                info!("Brush tool changed to: {:?}", brush_tool.tool);
+            }
+            AppCommand::RenameEntityCommand { id, name } => {
+                let Some(entity) = find_entity_by_id(&sdf_entities, id) else {
+                    warn!("No entity with id {} to rename", id);
+                    continue;
+                };
+                if let Ok(mut info) = entity_infos.get_mut(entity) {
+                    let before = info.clone();
+                    info.name = name;
+                    history.record(Edit::Info {
+                        entity,
+                        before,
+                        after: info.clone(),
+                    });
+                }
+            }
+            AppCommand::TagEntityCommand { id, tag } => {
+                let Some(entity) = find_entity_by_id(&sdf_entities, id) else {
+                    warn!("No entity with id {} to tag", id);
+                    continue;
+                };
+                if let Ok(mut info) = entity_infos.get_mut(entity) {
+                    let before = info.clone();
+                    info.add_tag(&tag);
+                    history.record(Edit::Info {
+                        entity,
+                        before,
+                        after: info.clone(),
+                    });
+                }
+            }
+            AppCommand::FindEntitiesByTagCommand { tag } => {
+                let mut found: Vec<EntitySummary> = sdf_entities
+                    .iter()
+                    .filter_map(|(entity, sdf_entity)| {
+                        let info = entity_infos.get(entity).ok()?;
+                        info.has_tag(&tag).then(|| EntitySummary {
+                            id: sdf_entity.node_index,
+                            name: info.name.clone(),
+                            tags: info.tags.clone(),
+                        })
+                    })
+                    .collect();
+                found.sort_by_key(|summary| summary.id);
+                dispatch_json_event("entitiesFound", &found);
+            }
+            AppCommand::DeleteEntityCommand { id } => {
+                commands.send_event(DeleteEntities(DeleteTarget::Id(id)));
+            }
+            AppCommand::DeleteSelectedCommand => {
+                commands.send_event(DeleteEntities(DeleteTarget::Selected));
+            }
+            AppCommand::ClearSceneCommand => {
+                commands.send_event(DeleteEntities(DeleteTarget::All));
+            }
+            AppCommand::GetSceneSummaryCommand => {
+                commands.send_event(SceneQuery::Summary);
+            }
+            AppCommand::GetEntityCommand { id } => {
+                commands.send_event(SceneQuery::Entity(id));
+            }
+            AppCommand::SelectEntityCommand { id } => {
+                if !mode_state.is_selection_enabled() {
+                    warn!("Selection is disabled in the current mode");
+                    continue;
+                }
+                let Some(entity) = find_entity_by_id(&sdf_entities, id) else {
+                    warn!("No entity with id {} to select", id);
+                    continue;
+                };
+                click_select(&mut commands, &mut selection_state, entity, false);
+            }
+            AppCommand::UndoCommand => history.request(HistoryAction::Undo),
+            AppCommand::RedoCommand => history.request(HistoryAction::Redo),
+            AppCommand::BeginEditGroupCommand => history.begin_group(),
+            AppCommand::EndEditGroupCommand => history.end_group(),
+            AppCommand::ExportSceneGltfCommand => {
+                let gltf = build_scene_gltf(
+                    sdf_entities
+                        .iter()
+                        .map(|(entity, e)| (e, entity_infos.get(entity).ok())),
+                    blend.factor,
+                );
+                match serde_json::to_string_pretty(&gltf) {
+                    Ok(contents) => deliver_export("sceneGltfExported", "scene.gltf", &contents),
+                    Err(err) => warn!("Failed to serialize glTF scene: {}", err),
+                }
+            }
         }
     }
 }
 
 #[wasm_bindgen]
 pub fn spawn_sphere_at_origin() {
-    APP_COMMAND_QUEUE.push(AppCommand::SpawnSphereCommand {
+    APP_COMMAND_QUEUE.push(AppCommand::SpawnPrimitiveCommand {
         position: Vec3::new(0., 0., 0.),
         color: Color::Srgba(Srgba::WHITE),
         scale: 1.,
+        operation: SdfOperation::Union,
+        primitive: SdfPrimitive::Sphere,
+    });
+}
+
+// Most spheres a single blob spawns; each one is its own entity and GPU SDF, so
+// larger counts are clamped
+pub const MAX_BLOB_SPHERES: u32 = 256;
+
+// Spawn a cluster of overlapping spheres, grouped under one draggable entity.
+// `count` is clamped to MAX_BLOB_SPHERES
+#[wasm_bindgen]
+pub fn spawn_blob(x: f32, y: f32, z: f32, count: u32, radius: f32, jitter: f32) {
+    APP_COMMAND_QUEUE.push(AppCommand::SpawnBlobCommand {
+        position: Vec3::new(x, y, z),
+        count,
+        radius,
+        jitter,
     });
 }
 
 pub fn spawn_sphere_at_pos(pos: Vec3, scale: f32) {
-    APP_COMMAND_QUEUE.push(AppCommand::SpawnSphereCommand {
+    spawn_sphere_with_operation(pos, scale, SdfOperation::Union);
+}
+
+pub fn spawn_sphere_with_operation(pos: Vec3, scale: f32, operation: SdfOperation) {
+    APP_COMMAND_QUEUE.push(AppCommand::SpawnPrimitiveCommand {
         position: pos,
         color: Color::Srgba(Srgba::WHITE),
         scale,
+        operation,
+        primitive: SdfPrimitive::Sphere,
+    });
+}
+
+pub fn spawn_ellipsoid_at_pos(pos: Vec3, radii: Vec3) {
+    APP_COMMAND_QUEUE.push(AppCommand::SpawnPrimitiveCommand {
+        position: pos,
+        color: Color::Srgba(Srgba::WHITE),
+        scale: radii.max_element(),
+        operation: SdfOperation::Union,
+        primitive: SdfPrimitive::Ellipsoid { radii },
+    });
+}
+
+pub fn erase_at_pos(pos: Vec3, radius: f32) {
+    APP_COMMAND_QUEUE.push(AppCommand::EraseAtCommand {
+        position: pos,
+        radius,
+    });
+}
+
+pub fn paint_at_pos(pos: Vec3, radius: f32, color: Color) {
+    APP_COMMAND_QUEUE.push(AppCommand::PaintAtCommand {
+        position: pos,
+        radius,
+        color,
+    });
+}
+
+pub fn spawn_capsule_between(start: Vec3, end: Vec3, radius: f32, operation: SdfOperation) {
+    APP_COMMAND_QUEUE.push(AppCommand::SpawnPrimitiveCommand {
+        position: (start + end) * 0.5,
+        color: Color::Srgba(Srgba::WHITE),
+        scale: radius,
+        operation,
+        primitive: SdfPrimitive::Capsule {
+            half_segment: (end - start) * 0.5,
+        },
     });
 }
 
@@ -136,16 +1261,860 @@ pub fn monitor_mode_changes(mode_state: Res<AppModeState>) {
         };
         dispatch_bevy_event_js("modeChanged", JsValue::from_str(mode_name));
     }
-}
 
-#[wasm_bindgen]
-pub fn set_mode(mode: &str) {
-    APP_COMMAND_QUEUE.push(AppCommand::SetModeCommand {
-        mode: mode.to_string(),
-    });
+    #[cfg(all(feature = "remote", not(target_arch = "wasm32")))]
+    if mode_state.is_changed() {
+        let mode_name = match mode_state.current_mode {
+            AppMode::Translate => "Translate",
+            AppMode::Brush => "Brush",
+        };
+        crate::remote::broadcast("modeChanged", mode_name.into());
+    }
+}
+
+// Selection changes made in the viewport or through the bridge, so the host UI
+// can follow them. Groups have no id and aren't reported.
+fn dispatch_entity_selected(trigger: Trigger<EntitySelectedEvent>, query: EntityDetailsQuery) {
+    if let Some(details) = query.details(trigger.target()) {
+        dispatch_json_event("entitySelected", &details);
+    }
+}
+
+fn dispatch_entity_deselected(
+    trigger: Trigger<EntityDeselectedEvent>,
+    query: EntityDetailsQuery,
+) {
+    if let Some(details) = query.details(trigger.target()) {
+        dispatch_json_event("entityDeselected", &details);
+    }
+}
+
+pub fn dispatch_progress(mut app_events: EventReader<AppEvent>) {
+    for event in app_events.read() {
+        match event {
+            AppEvent::Progress(update) => dispatch_json_event("exportProgress", update),
+            AppEvent::Complete(complete) => dispatch_json_event("exportComplete", complete),
+        }
+    }
+}
+
+pub fn dispatch_volume_estimates(mut estimated_events: EventReader<VolumeEstimated>) {
+    for VolumeEstimated(estimate) in estimated_events.read() {
+        dispatch_json_event("volumeEstimated", estimate);
+    }
+}
+
+pub fn deliver_mesh_exports(mut exported_events: EventReader<MeshExported>) {
+    for exported in exported_events.read() {
+        let format = exported.format;
+        deliver_export(format.event_name(), format.file_name(), &exported.contents);
+    }
+}
+
+pub fn deliver_saved_scenes(mut saved_events: EventReader<SceneSaved>) {
+    for saved in saved_events.read() {
+        deliver_export("sceneSaved", &saved.path, &saved.contents);
+    }
+}
+
+pub fn deliver_saved_projects(mut saved_events: EventReader<ProjectSaved>) {
+    for saved in saved_events.read() {
+        deliver_binary_export("projectSaved", &saved.path, &saved.bytes);
+    }
+}
+
+pub fn dispatch_recent_projects(mut listed_events: EventReader<RecentProjectsListed>) {
+    for RecentProjectsListed(projects) in listed_events.read() {
+        dispatch_json_event("recentProjects", projects);
+    }
+}
+
+pub fn dispatch_snapshots(mut listed_events: EventReader<SnapshotsListed>) {
+    for SnapshotsListed(snapshots) in listed_events.read() {
+        dispatch_json_event("snapshotsListed", snapshots);
+    }
+}
+
+pub fn dispatch_deleted_entities(mut deleted_events: EventReader<EntitiesDeleted>) {
+    for EntitiesDeleted(ids) in deleted_events.read() {
+        dispatch_json_event("entitiesDeleted", ids);
+    }
+}
+
+pub fn answer_scene_queries(
+    mut query_events: EventReader<SceneQuery>,
+    details: EntityDetailsQuery,
+) {
+    for query in query_events.read() {
+        match query {
+            SceneQuery::Summary => dispatch_json_event("sceneSummary", &details.summary()),
+            // null when there is no entity with the id
+            SceneQuery::Entity(id) => dispatch_json_event("entityDetails", &details.entity(*id)),
+        }
+    }
+}
+
+pub fn deliver_journal_exports(mut exported_events: EventReader<JournalExported>) {
+    for exported in exported_events.read() {
+        deliver_export("journalExported", "journal.ron", &exported.contents);
+    }
+}
+
+#[wasm_bindgen]
+pub fn set_mode(mode: &str) {
+    APP_COMMAND_QUEUE.push(AppCommand::SetModeCommand {
+        mode: mode.to_string(),
+    });
+}
+
+#[wasm_bindgen]
+pub fn set_brush_tool(tool: &str) {
+    APP_COMMAND_QUEUE.push(AppCommand::SetBrushToolCommand {
+        tool: tool.to_string(),
+    });
+}
+
+// Color the paint brush blends towards, components from 0 to 1
+#[wasm_bindgen]
+pub fn set_brush_color(r: f32, g: f32, b: f32) {
+    APP_COMMAND_QUEUE.push(AppCommand::SetBrushColorCommand {
+        color: Color::srgb(r, g, b),
+    });
+}
+
+// Brush size factor at no and full pen pressure, and the exponent shaping
+// the curve between them
+#[wasm_bindgen]
+pub fn set_pressure_curve(min_scale: f32, max_scale: f32, exponent: f32) {
+    APP_COMMAND_QUEUE.push(AppCommand::SetPressureCurveCommand {
+        curve: PressureCurve {
+            min_scale,
+            max_scale,
+            exponent: exponent.max(0.01),
+        },
+    });
+}
+
+#[wasm_bindgen]
+pub fn set_brush_jitter(position: f32, size_variance: f32, seed: Option<u32>) {
+    APP_COMMAND_QUEUE.push(AppCommand::SetBrushJitterCommand {
+        jitter: BrushJitter {
+            position: position.max(0.),
+            size_variance: size_variance.clamp(0., 1.),
+            seed: seed.map(u64::from),
+        },
+    });
+}
+
+// Repeat brush placements `fold` times around the world axis through the
+// origin, and mirrored across the plane normal to `mirror_axis` if given
+#[wasm_bindgen]
+pub fn set_brush_symmetry(axis: &str, fold: u32, mirror_axis: Option<String>) {
+    APP_COMMAND_QUEUE.push(AppCommand::SetBrushSymmetryCommand {
+        axis: axis.to_string(),
+        fold,
+        mirror_axis,
+    });
+}
+
+#[wasm_bindgen]
+pub fn set_curve_profile(spacing: f32, start_radius: f32, end_radius: f32) {
+    APP_COMMAND_QUEUE.push(AppCommand::SetCurveProfileCommand {
+        profile: CurveProfile {
+            spacing: spacing.max(0.1),
+            start_radius: start_radius.max(0.01),
+            end_radius: end_radius.max(0.01),
+        },
+    });
+}
+
+#[wasm_bindgen]
+pub fn rename_entity(id: usize, name: &str) {
+    APP_COMMAND_QUEUE.push(AppCommand::RenameEntityCommand {
+        id,
+        name: name.to_string(),
+    });
+}
+
+#[wasm_bindgen]
+pub fn tag_entity(id: usize, tag: &str) {
+    APP_COMMAND_QUEUE.push(AppCommand::TagEntityCommand {
+        id,
+        tag: tag.to_string(),
+    });
+}
+
+#[wasm_bindgen]
+pub fn find_entities_by_tag(tag: &str) {
+    APP_COMMAND_QUEUE.push(AppCommand::FindEntitiesByTagCommand {
+        tag: tag.to_string(),
+    });
+}
+
+#[wasm_bindgen]
+pub fn delete_entity(id: usize) {
+    APP_COMMAND_QUEUE.push(AppCommand::DeleteEntityCommand { id });
+}
+
+#[wasm_bindgen]
+pub fn delete_selected() {
+    APP_COMMAND_QUEUE.push(AppCommand::DeleteSelectedCommand);
+}
+
+// Deletes every entity as one undoable step
+#[wasm_bindgen]
+pub fn clear_scene() {
+    APP_COMMAND_QUEUE.push(AppCommand::ClearSceneCommand);
+}
+
+// Answered through the sceneSummary event, for outliners
+#[wasm_bindgen]
+pub fn get_scene_summary() {
+    APP_COMMAND_QUEUE.push(AppCommand::GetSceneSummaryCommand);
+}
+
+// Answered through the entityDetails event
+#[wasm_bindgen]
+pub fn get_entity(id: usize) {
+    APP_COMMAND_QUEUE.push(AppCommand::GetEntityCommand { id });
+}
+
+// Replaces the selection, like clicking the entity in the viewport
+#[wasm_bindgen]
+pub fn select_entity(id: usize) {
+    APP_COMMAND_QUEUE.push(AppCommand::SelectEntityCommand { id });
 }
 
 #[wasm_bindgen]
 pub fn set_post_process_enabled(enabled: bool) {
     APP_COMMAND_QUEUE.push(AppCommand::SetPostProcessEnabledCommand { enabled });
 }
+
+#[wasm_bindgen]
+pub fn set_language(code: &str) {
+    APP_COMMAND_QUEUE.push(AppCommand::SetLanguageCommand {
+        code: code.to_string(),
+    });
+}
+
+#[wasm_bindgen]
+pub fn set_ground_plane(enabled: bool, height: f32) {
+    APP_COMMAND_QUEUE.push(AppCommand::SetGroundPlaneCommand { enabled, height });
+}
+
+// Color components are sRGB in 0-1
+#[wasm_bindgen]
+pub fn set_background(
+    zenith_r: f32,
+    zenith_g: f32,
+    zenith_b: f32,
+    horizon_r: f32,
+    horizon_g: f32,
+    horizon_b: f32,
+) {
+    APP_COMMAND_QUEUE.push(AppCommand::SetBackgroundCommand {
+        zenith: Color::srgb(zenith_r, zenith_g, zenith_b),
+        horizon: Color::srgb(horizon_r, horizon_g, horizon_b),
+    });
+}
+
+#[wasm_bindgen]
+pub fn set_ground_grid(enabled: bool, spacing: f32, r: f32, g: f32, b: f32, opacity: f32) {
+    APP_COMMAND_QUEUE.push(AppCommand::SetGroundGridCommand {
+        enabled,
+        spacing: spacing.max(0.001),
+        color: Color::srgba(r, g, b, opacity.clamp(0., 1.)),
+    });
+}
+
+#[wasm_bindgen]
+pub fn set_shadows(enabled: bool, softness: f32) {
+    APP_COMMAND_QUEUE.push(AppCommand::SetShadowsCommand {
+        enabled,
+        softness: softness.max(0.001),
+    });
+}
+
+#[wasm_bindgen]
+pub fn set_ambient_occlusion(enabled: bool, intensity: f32, radius: f32) {
+    APP_COMMAND_QUEUE.push(AppCommand::SetAmbientOcclusionCommand {
+        enabled,
+        intensity: intensity.max(0.),
+        radius: radius.max(0.001),
+    });
+}
+
+// Ghosts every entity but the selection, `opacity` is how much of them still shows
+#[wasm_bindgen]
+pub fn set_isolate_mode(enabled: bool, opacity: f32) {
+    APP_COMMAND_QUEUE.push(AppCommand::SetIsolateModeCommand {
+        enabled,
+        opacity: opacity.clamp(0., 1.),
+    });
+}
+
+#[wasm_bindgen]
+pub fn set_fog(enabled: bool, r: f32, g: f32, b: f32, density: f32, start: f32) {
+    APP_COMMAND_QUEUE.push(AppCommand::SetFogCommand {
+        enabled,
+        color: Color::srgb(r, g, b),
+        density: density.max(0.),
+        start: start.max(0.),
+    });
+}
+
+// Lets the SDF pass drop resolution to keep its GPU time near the target
+#[wasm_bindgen]
+pub fn set_adaptive_resolution(enabled: bool, target_frame_time_ms: f32, min_scale: f32) {
+    APP_COMMAND_QUEUE.push(AppCommand::SetAdaptiveResolutionCommand {
+        enabled,
+        target_frame_time: target_frame_time_ms.max(1.),
+        min_scale: min_scale.clamp(0.1, 1.),
+    });
+}
+
+// Retunes the coarse prepass from the march statistics while the SDF pass is over budget
+#[wasm_bindgen]
+pub fn set_coarse_tuning(enabled: bool, target_frame_time_ms: f32) {
+    APP_COMMAND_QUEUE.push(AppCommand::SetCoarseTuningCommand {
+        enabled,
+        target_frame_time: target_frame_time_ms.max(1.),
+    });
+}
+
+// Bakes static scenes into a distance volume the SDF pass can skip empty space with
+#[wasm_bindgen]
+pub fn set_volume_cache(enabled: bool, resolution: u32) {
+    APP_COMMAND_QUEUE.push(AppCommand::SetVolumeCacheCommand {
+        enabled,
+        resolution: resolution.clamp(16, 256),
+    });
+}
+
+// "bvh" or "brick_map"
+#[wasm_bindgen]
+pub fn set_acceleration_structure(structure: &str) {
+    APP_COMMAND_QUEUE.push(AppCommand::SetAccelerationStructureCommand {
+        structure: structure.to_string(),
+    });
+}
+
+// Times the SDF pass with each acceleration structure and logs the results
+#[wasm_bindgen]
+pub fn benchmark_acceleration_structures() {
+    APP_COMMAND_QUEUE.push(AppCommand::BenchmarkAccelerationStructuresCommand);
+}
+
+// Estimates the enclosed volume and centroid; the result arrives as a volumeEstimated event.
+// `samples` is clamped to MAX_VOLUME_SAMPLES
+#[wasm_bindgen]
+pub fn estimate_volume(samples: Option<u32>) {
+    APP_COMMAND_QUEUE.push(AppCommand::EstimateVolumeCommand {
+        samples: samples
+            .unwrap_or(DEFAULT_VOLUME_SAMPLES)
+            .clamp(1, MAX_VOLUME_SAMPLES),
+    });
+}
+
+// Skips entities outside the camera's frustum, grown by `margin`, in the coarse prepass
+#[wasm_bindgen]
+pub fn set_view_culling(enabled: bool, margin: f32) {
+    APP_COMMAND_QUEUE.push(AppCommand::SetViewCullingCommand {
+        enabled,
+        margin: margin.max(0.),
+    });
+}
+
+// Shows a background-updated mesh with `cell_size` cells instead of the ray-marched SDF
+#[wasm_bindgen]
+pub fn set_preview_mesh(enabled: bool, cell_size: f32) {
+    APP_COMMAND_QUEUE.push(AppCommand::SetPreviewMeshCommand {
+        enabled,
+        cell_size: cell_size.max(0.01),
+    });
+}
+
+// Serializes the scene to RON when `path` ends in .ron, JSON otherwise. Natively the
+// document is written to `path`, on the web it arrives with the sceneSaved event.
+#[wasm_bindgen]
+pub fn save_scene(path: &str) {
+    APP_COMMAND_QUEUE.push(AppCommand::SaveSceneCommand {
+        path: path.to_string(),
+    });
+}
+
+// Replaces the scene with the document at `path`, for native builds
+#[wasm_bindgen]
+pub fn load_scene(path: &str) {
+    match std::fs::read_to_string(path) {
+        Ok(contents) => APP_COMMAND_QUEUE.push(AppCommand::LoadSceneCommand { contents }),
+        Err(err) => warn!("Failed to read {}: {}", path, err),
+    }
+}
+
+// Replaces the scene with a RON or JSON document, as saved by save_scene
+#[wasm_bindgen]
+pub fn load_scene_from_string(contents: &str) {
+    APP_COMMAND_QUEUE.push(AppCommand::LoadSceneCommand {
+        contents: contents.to_string(),
+    });
+}
+
+// Merges the document at `path` into the scene at (x, y, z), scaled by `scale`, for
+// native builds
+#[wasm_bindgen]
+pub fn import_scene(path: &str, x: f32, y: f32, z: f32, scale: Option<f32>) {
+    match std::fs::read_to_string(path) {
+        Ok(contents) => import_scene_from_string(&contents, x, y, z, scale),
+        Err(err) => warn!("Failed to read {}: {}", path, err),
+    }
+}
+
+// Merges a saved scene into the current one at (x, y, z), scaled by `scale`
+#[wasm_bindgen]
+pub fn import_scene_from_string(contents: &str, x: f32, y: f32, z: f32, scale: Option<f32>) {
+    APP_COMMAND_QUEUE.push(AppCommand::ImportSceneCommand {
+        contents: contents.to_string(),
+        translation: Vec3::new(x, y, z),
+        scale: scale.unwrap_or(1.0),
+    });
+}
+
+// Saves the scene, a thumbnail of the viewport and metadata as a zip archive. Natively
+// the archive is written to `path`, on the web it arrives base64 encoded with the
+// projectSaved event.
+#[wasm_bindgen]
+pub fn save_project(path: &str, author: Option<String>) {
+    APP_COMMAND_QUEUE.push(AppCommand::SaveProjectCommand {
+        path: path.to_string(),
+        author,
+    });
+}
+
+// Replaces the scene with the project at `path`, for native builds
+#[wasm_bindgen]
+pub fn open_project(path: &str) {
+    match std::fs::read(path) {
+        Ok(bytes) => open_project_from_bytes(path, &bytes),
+        Err(err) => warn!("Failed to read {}: {}", path, err),
+    }
+}
+
+// Replaces the scene with a project archive; `path` names it in the recent files list
+#[wasm_bindgen]
+pub fn open_project_from_bytes(path: &str, bytes: &[u8]) {
+    APP_COMMAND_QUEUE.push(AppCommand::OpenProjectCommand {
+        path: path.to_string(),
+        bytes: bytes.to_vec(),
+    });
+}
+
+// Sends the recently saved or opened projects through the recentProjects event
+#[wasm_bindgen]
+pub fn list_recent_projects() {
+    APP_COMMAND_QUEUE.push(AppCommand::ListRecentProjectsCommand);
+}
+
+// Keeps the current entities under `name`, replacing an earlier snapshot of that name
+#[wasm_bindgen]
+pub fn create_snapshot(name: &str) {
+    APP_COMMAND_QUEUE.push(AppCommand::CreateSnapshotCommand {
+        name: name.to_string(),
+    });
+}
+
+// Swaps the current entities for those of the snapshot, as one undo step
+#[wasm_bindgen]
+pub fn restore_snapshot(name: &str) {
+    APP_COMMAND_QUEUE.push(AppCommand::RestoreSnapshotCommand {
+        name: name.to_string(),
+    });
+}
+
+#[wasm_bindgen]
+pub fn delete_snapshot(name: &str) {
+    APP_COMMAND_QUEUE.push(AppCommand::DeleteSnapshotCommand {
+        name: name.to_string(),
+    });
+}
+
+// Sends the snapshots, oldest first, through the snapshotsListed event
+#[wasm_bindgen]
+pub fn list_snapshots() {
+    APP_COMMAND_QUEUE.push(AppCommand::ListSnapshotsCommand);
+}
+
+// Starts journaling every edit from the current scene on. Native builds keep the
+// journal written to `path`, for crash recovery.
+#[wasm_bindgen]
+pub fn start_journal(path: Option<String>) {
+    APP_COMMAND_QUEUE.push(AppCommand::StartJournalCommand { path });
+}
+
+#[wasm_bindgen]
+pub fn stop_journal() {
+    APP_COMMAND_QUEUE.push(AppCommand::StopJournalCommand);
+}
+
+// Sends the journal recorded so far through the journalExported event
+#[wasm_bindgen]
+pub fn export_journal() {
+    APP_COMMAND_QUEUE.push(AppCommand::ExportJournalCommand);
+}
+
+// Rebuilds a journaled session; `speed` scales the recorded timing, None replays as
+// fast as possible
+#[wasm_bindgen]
+pub fn replay_journal(contents: &str, speed: Option<f32>) {
+    APP_COMMAND_QUEUE.push(AppCommand::ReplayJournalCommand {
+        contents: contents.to_string(),
+        speed,
+    });
+}
+
+// Replays the journal at `path`, for native builds
+#[wasm_bindgen]
+pub fn replay_journal_file(path: &str, speed: Option<f32>) {
+    match std::fs::read_to_string(path) {
+        Ok(contents) => replay_journal(&contents, speed),
+        Err(err) => warn!("Failed to read {}: {}", path, err),
+    }
+}
+
+// Blends each frame with the reprojected previous one, `blend` is the history weight
+#[wasm_bindgen]
+pub fn set_temporal_accumulation(enabled: bool, blend: f32) {
+    APP_COMMAND_QUEUE.push(AppCommand::SetTemporalAccumulationCommand {
+        enabled,
+        blend: blend.clamp(0., 0.98),
+    });
+}
+
+// Cuts away the scene on the side the normal points to; the plane starts at the
+// origin and is moved by dragging its handle
+#[wasm_bindgen]
+pub fn set_clip_plane(enabled: bool, normal_x: f32, normal_y: f32, normal_z: f32) {
+    APP_COMMAND_QUEUE.push(AppCommand::SetClipPlaneCommand {
+        enabled,
+        normal: Vec3::new(normal_x, normal_y, normal_z)
+            .try_normalize()
+            .unwrap_or(Vec3::X),
+    });
+}
+
+#[wasm_bindgen]
+pub fn set_clip_cap(enabled: bool, r: f32, g: f32, b: f32) {
+    APP_COMMAND_QUEUE.push(AppCommand::SetClipCapCommand {
+        enabled,
+        color: Color::srgb(r, g, b),
+    });
+}
+
+#[wasm_bindgen]
+pub fn start_turntable_capture(frames: u32) {
+    APP_COMMAND_QUEUE.push(AppCommand::StartTurntableCaptureCommand { frames });
+}
+
+// The image format follows the extension of `path`; in the browser the file is downloaded
+#[wasm_bindgen]
+pub fn capture_screenshot(path: String) {
+    APP_COMMAND_QUEUE.push(AppCommand::CaptureScreenshotCommand { path });
+}
+
+// Path of an equirectangular HDR image under assets/, or None to remove it
+#[wasm_bindgen]
+pub fn set_environment_map(path: Option<String>, intensity: f32) {
+    APP_COMMAND_QUEUE.push(AppCommand::SetEnvironmentCommand {
+        path,
+        intensity: intensity.max(0.),
+    });
+}
+
+#[wasm_bindgen]
+pub fn set_gizmo_occlusion(enabled: bool) {
+    APP_COMMAND_QUEUE.push(AppCommand::SetGizmoOcclusionCommand { enabled });
+}
+
+#[wasm_bindgen]
+pub fn set_selected_displacement(amplitude: f32, frequency: f32) {
+    APP_COMMAND_QUEUE.push(AppCommand::SetSelectedDisplacementCommand {
+        amplitude,
+        frequency,
+    });
+}
+
+#[wasm_bindgen]
+pub fn set_selected_shell(thickness: f32) {
+    APP_COMMAND_QUEUE.push(AppCommand::SetSelectedShellCommand { thickness });
+}
+
+// `count` is clamped to MAX_REPETITIONS
+#[wasm_bindgen]
+pub fn set_selected_repetition(count: u32, axis: &str, spacing: f32) {
+    APP_COMMAND_QUEUE.push(AppCommand::SetSelectedRepetitionCommand {
+        count: count.clamp(1, MAX_REPETITIONS),
+        axis: axis.to_string(),
+        spacing,
+    });
+}
+
+#[wasm_bindgen]
+pub fn translate_selected(axis: &str, offset: f32) {
+    APP_COMMAND_QUEUE.push(AppCommand::TranslateSelectedCommand {
+        axis: axis.to_string(),
+        offset,
+    });
+}
+
+// Lines the selected entities up on their min, center or max along an axis
+#[wasm_bindgen]
+pub fn align_selected(axis: &str, mode: &str) {
+    APP_COMMAND_QUEUE.push(AppCommand::AlignSelectedCommand {
+        axis: axis.to_string(),
+        mode: mode.to_string(),
+    });
+}
+
+// Spaces the selected entities evenly between the outermost ones along an axis
+#[wasm_bindgen]
+pub fn distribute_selected(axis: &str) {
+    APP_COMMAND_QUEUE.push(AppCommand::DistributeSelectedCommand {
+        axis: axis.to_string(),
+    });
+}
+
+// Moves the pivot of the selected entities to a world position
+#[wasm_bindgen]
+pub fn set_selected_pivot(x: f32, y: f32, z: f32) {
+    APP_COMMAND_QUEUE.push(AppCommand::SetSelectedPivotCommand {
+        position: Vec3::new(x, y, z),
+    });
+}
+
+// Orbits the camera around `target`, viewing it from `position`; both are
+// [x, y, z] arrays
+#[wasm_bindgen]
+pub fn set_camera_pose(position: &[f32], target: &[f32]) {
+    let (Ok(position), Ok(target)) = (
+        <[f32; 3]>::try_from(position),
+        <[f32; 3]>::try_from(target),
+    ) else {
+        warn!("Camera poses take three components per vector");
+        return;
+    };
+    APP_COMMAND_QUEUE.push(AppCommand::CameraCommand {
+        request: CameraRequest::Pose {
+            position: Vec3::from_array(position),
+            target: Vec3::from_array(target),
+        },
+    });
+}
+
+// Zooms to the selection, or to the whole scene when nothing is selected
+#[wasm_bindgen]
+pub fn frame_selected() {
+    APP_COMMAND_QUEUE.push(AppCommand::CameraCommand {
+        request: CameraRequest::FrameSelected,
+    });
+}
+
+// "ortho" or "persp"
+#[wasm_bindgen]
+pub fn set_projection(projection: &str) {
+    let orthographic = match projection {
+        "ortho" | "orthographic" => true,
+        "persp" | "perspective" => false,
+        _ => {
+            warn!("Unknown projection requested: {}", projection);
+            return;
+        }
+    };
+    APP_COMMAND_QUEUE.push(AppCommand::CameraCommand {
+        request: CameraRequest::Projection { orthographic },
+    });
+}
+
+// Sets an entity's transform as one undo step. Each argument is an [x, y, z]
+// array: the position relative to the entity's group, XYZ Euler angles in
+// degrees, and the scale relative to the size the entity was spawned with.
+// Ellipsoids turned by other than quarter turns keep their axis aligned extents.
+#[wasm_bindgen]
+pub fn set_entity_transform(id: usize, position: &[f32], rotation: &[f32], scale: &[f32]) {
+    let (Ok(position), Ok(rotation), Ok(scale)) = (
+        <[f32; 3]>::try_from(position),
+        <[f32; 3]>::try_from(rotation),
+        <[f32; 3]>::try_from(scale),
+    ) else {
+        warn!("Entity transforms take three components per vector");
+        return;
+    };
+    let [x, y, z] = rotation.map(f32::to_radians);
+    APP_COMMAND_QUEUE.push(AppCommand::SetEntityTransformCommand {
+        id,
+        transform: Transform {
+            translation: Vec3::from_array(position),
+            rotation: Quat::from_euler(EulerRot::XYZ, x, y, z),
+            // Zero would collapse the entity beyond recovery by scaling
+            scale: Vec3::from_array(scale).max(Vec3::splat(0.01)),
+        },
+    });
+}
+
+// Moves the pivot of the selected entities to the center of their bounds
+#[wasm_bindgen]
+pub fn center_selected_pivot() {
+    APP_COMMAND_QUEUE.push(AppCommand::CenterSelectedPivotCommand);
+}
+
+#[wasm_bindgen]
+pub fn undo() {
+    APP_COMMAND_QUEUE.push(AppCommand::UndoCommand);
+}
+
+#[wasm_bindgen]
+pub fn redo() {
+    APP_COMMAND_QUEUE.push(AppCommand::RedoCommand);
+}
+
+// Edits between these calls are undone as a single step, e.g. a brush stroke
+pub fn begin_edit_group() {
+    APP_COMMAND_QUEUE.push(AppCommand::BeginEditGroupCommand);
+}
+
+pub fn end_edit_group() {
+    APP_COMMAND_QUEUE.push(AppCommand::EndEditGroupCommand);
+}
+
+#[wasm_bindgen]
+pub fn export_colliders() {
+    APP_COMMAND_QUEUE.push(AppCommand::ExportCollidersCommand);
+}
+
+#[wasm_bindgen]
+pub fn export_scene_gltf() {
+    APP_COMMAND_QUEUE.push(AppCommand::ExportSceneGltfCommand);
+}
+
+// Meshes the SDF surface with `resolution` cells along the longest side of the scene
+#[wasm_bindgen]
+pub fn export_mesh(format: &str, resolution: Option<u32>) {
+    APP_COMMAND_QUEUE.push(AppCommand::ExportMeshCommand {
+        format: format.to_string(),
+        resolution: resolution.unwrap_or(DEFAULT_MESH_RESOLUTION),
+    });
+}
+
+// Entities are identified by their node index on the JavaScript side
+fn find_entity_by_id(
+    sdf_entities: &Query<(Entity, &mut SDFRenderEntity)>,
+    id: usize,
+) -> Option<Entity> {
+    sdf_entities
+        .iter()
+        .find(|(_, sdf_entity)| sdf_entity.node_index == id)
+        .map(|(entity, _)| entity)
+}
+
+// Coordinates of the selected entities along an axis, in selection order
+fn selected_coordinates(
+    selection_state: &SelectionState,
+    transforms: &Query<&mut Transform>,
+    direction: Vec3,
+) -> Vec<(Entity, f32)> {
+    selection_state
+        .iter()
+        .filter_map(|entity| {
+            let transform = transforms.get(entity).ok()?;
+            Some((entity, transform.translation.dot(direction)))
+        })
+        .collect()
+}
+
+// Move entities along an axis to the given coordinates. The SDF positions
+// follow through their GlobalTransform.
+fn move_along_axis(
+    transforms: &mut Query<&mut Transform>,
+    direction: Vec3,
+    targets: &[(Entity, f32)],
+) -> Vec<Edit> {
+    let mut edits = Vec::new();
+    for (entity, target) in targets {
+        let Ok(mut transform) = transforms.get_mut(*entity) else {
+            continue;
+        };
+        let before = *transform;
+        transform.translation += direction * (target - before.translation.dot(direction));
+        if *transform != before {
+            edits.push(Edit::Transform {
+                entity: *entity,
+                before,
+                after: *transform,
+            });
+        }
+    }
+    edits
+}
+
+// Send a serializable payload to JavaScript as a JSON string
+fn dispatch_json_event<T: serde::Serialize>(event_name: &str, payload: &T) {
+    let json = match serde_json::to_string(payload) {
+        Ok(json) => json,
+        Err(err) => {
+            warn!("Failed to serialize {} payload: {}", event_name, err);
+            return;
+        }
+    };
+
+    #[cfg(target_arch = "wasm32")]
+    dispatch_bevy_event_js(event_name, JsValue::from_str(&json));
+
+    #[cfg(not(target_arch = "wasm32"))]
+    info!("{}: {}", event_name, json);
+
+    #[cfg(all(feature = "remote", not(target_arch = "wasm32")))]
+    if let Ok(detail) = serde_json::from_str(&json) {
+        crate::remote::broadcast(event_name, detail);
+    }
+}
+
+// Hand exported data to JavaScript on the web, or write it to disk on native builds
+// Binary files reach JavaScript base64 encoded
+fn deliver_binary_export(event_name: &str, file_name: &str, bytes: &[u8]) {
+    #[cfg(target_arch = "wasm32")]
+    {
+        let _ = file_name;
+        dispatch_bevy_event_js(event_name, JsValue::from_str(&crate::meshing::base64(bytes)));
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        let _ = event_name;
+        match std::fs::write(file_name, bytes) {
+            Ok(_) => info!("Wrote {}", file_name),
+            Err(err) => warn!("Failed to write {}: {}", file_name, err),
+        }
+
+        #[cfg(feature = "remote")]
+        crate::remote::broadcast(event_name, crate::meshing::base64(bytes).into());
+    }
+}
+
+fn deliver_export(event_name: &str, file_name: &str, contents: &str) {
+    #[cfg(target_arch = "wasm32")]
+    {
+        let _ = file_name;
+        dispatch_bevy_event_js(event_name, JsValue::from_str(contents));
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        let _ = event_name;
+        match std::fs::write(file_name, contents) {
+            Ok(_) => info!("Wrote {}", file_name),
+            Err(err) => warn!("Failed to write {}: {}", file_name, err),
+        }
+
+        #[cfg(feature = "remote")]
+        crate::remote::broadcast(event_name, contents.into());
+    }
+}
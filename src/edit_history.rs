@@ -0,0 +1,398 @@
+//! Undo/redo history for edits to the scene
+//!
+//! Every mutation of an SDF entity is recorded as an `Edit` holding enough
+//! state to apply it in both directions. Edits recorded together (a brush
+//! stroke, a drag of several entities) form one step on the undo stack.
+//! Despawned entities are kept as snapshots; when one is respawned it gets a
+//! new `Entity`, so every edit referring to the old one is remapped.
+
+use bevy::prelude::*;
+
+use crate::entity_info::SdfEntityInfo;
+use crate::pivot::PivotOffset;
+use crate::sdf_render::SDFRenderEntity;
+use crate::selection::{deselect, handle_selection, SelectionState};
+use crate::settings::KeyBindings;
+use crate::translation::{DragData, Translatable};
+
+// Oldest steps are dropped beyond this
+const MAX_HISTORY: usize = 200;
+
+pub struct EditHistoryPlugin;
+
+impl Plugin for EditHistoryPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<EditHistory>()
+            .add_systems(Update, (handle_history_shortcuts, apply_history_requests).chain());
+    }
+}
+
+// Hierarchy, rendering and pivot components captured alongside the SDF state
+pub type RenderParts<'a> = (
+    Option<&'a ChildOf>,
+    Option<&'a Mesh3d>,
+    Option<&'a MeshMaterial3d<StandardMaterial>>,
+    Option<&'a PivotOffset>,
+);
+
+// Everything needed to respawn an entity after it was despawned
+#[derive(Clone, Debug)]
+pub struct EntitySnapshot {
+    pub entity: Entity,
+    // None for groups like blob clusters, which only hold children
+    pub sdf: Option<SDFRenderEntity>,
+    pub info: Option<SdfEntityInfo>,
+    pub transform: Transform,
+    pub mesh: Option<Handle<Mesh>>,
+    pub material: Option<Handle<StandardMaterial>>,
+    // Children follow their parent and are not selectable on their own
+    pub parent: Option<Entity>,
+    pub pivot: Option<PivotOffset>,
+}
+
+impl EntitySnapshot {
+    pub fn capture(
+        entity: Entity,
+        transform: Transform,
+        sdf: Option<&SDFRenderEntity>,
+        info: Option<&SdfEntityInfo>,
+        (parent, mesh, material, pivot): RenderParts,
+    ) -> Self {
+        Self {
+            entity,
+            sdf: sdf.cloned(),
+            info: info.cloned(),
+            transform,
+            mesh: mesh.map(|m| m.0.clone()),
+            material: material.map(|m| m.0.clone()),
+            parent: parent.map(|p| p.parent()),
+            pivot: pivot.copied(),
+        }
+    }
+
+    pub fn spawn(&self, commands: &mut Commands) -> Entity {
+        let mut entity_commands = commands.spawn((self.transform, Visibility::default()));
+        if let Some(sdf) = &self.sdf {
+            entity_commands.insert(sdf.clone());
+        }
+        if let Some(info) = &self.info {
+            entity_commands.insert(info.clone());
+        }
+        if let Some(mesh) = &self.mesh {
+            entity_commands.insert(Mesh3d(mesh.clone()));
+        }
+        if let Some(material) = &self.material {
+            entity_commands.insert(MeshMaterial3d(material.clone()));
+        }
+        if let Some(pivot) = self.pivot {
+            entity_commands.insert(pivot);
+        }
+        match self.parent {
+            Some(parent) => {
+                entity_commands.insert(ChildOf(parent));
+            }
+            None => {
+                entity_commands.insert(Translatable).observe(handle_selection);
+            }
+        }
+        entity_commands.id()
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum Edit {
+    Spawn(EntitySnapshot),
+    Delete(EntitySnapshot),
+    Transform {
+        entity: Entity,
+        before: Transform,
+        after: Transform,
+    },
+    // Property edits of the SDF itself; the position follows the transform
+    Sdf {
+        entity: Entity,
+        before: SDFRenderEntity,
+        after: SDFRenderEntity,
+    },
+    Info {
+        entity: Entity,
+        before: SdfEntityInfo,
+        after: SdfEntityInfo,
+    },
+    // Base color of the entity's material
+    Color {
+        entity: Entity,
+        before: Color,
+        after: Color,
+    },
+}
+
+impl Edit {
+    fn remap(&mut self, old: Entity, new: Entity) {
+        let replace = |entity: &mut Entity| {
+            if *entity == old {
+                *entity = new;
+            }
+        };
+        match self {
+            Edit::Spawn(snapshot) | Edit::Delete(snapshot) => {
+                replace(&mut snapshot.entity);
+                if let Some(parent) = &mut snapshot.parent {
+                    replace(parent);
+                }
+            }
+            Edit::Transform { entity, .. }
+            | Edit::Sdf { entity, .. }
+            | Edit::Info { entity, .. }
+            | Edit::Color { entity, .. } => replace(entity),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HistoryAction {
+    Undo,
+    Redo,
+}
+
+// What happened to the history, for observers like the journal
+#[derive(Clone, Debug)]
+pub enum HistoryChange {
+    Recorded(Vec<Edit>),
+    Applied(HistoryAction),
+    // An undo or redo request that found nothing to apply or came mid-drag
+    Skipped(HistoryAction),
+    // An undo or redo respawned a despawned entity
+    Remapped { old: Entity, new: Entity },
+    Cleared,
+}
+
+#[derive(Resource, Default)]
+pub struct EditHistory {
+    undo_stack: Vec<Vec<Edit>>,
+    redo_stack: Vec<Vec<Edit>>,
+    // Edits collected into a single step until the group is closed
+    open_group: Option<Vec<Edit>>,
+    pending: Vec<HistoryAction>,
+    // Only collected once someone asked for them with track_changes
+    changes: Option<Vec<HistoryChange>>,
+}
+
+impl EditHistory {
+    pub fn record(&mut self, edit: Edit) {
+        self.record_all(vec![edit]);
+    }
+
+    // Record several edits as one undo step
+    pub fn record_all(&mut self, edits: Vec<Edit>) {
+        if let Some(group) = &mut self.open_group {
+            group.extend(edits);
+            return;
+        }
+        self.push_step(edits);
+    }
+
+    pub fn begin_group(&mut self) {
+        if self.open_group.is_none() {
+            self.open_group = Some(Vec::new());
+        }
+    }
+
+    pub fn end_group(&mut self) {
+        if let Some(group) = self.open_group.take() {
+            self.push_step(group);
+        }
+    }
+
+    // Forgets every step, e.g. once the scene was replaced by a loaded one
+    pub fn clear(&mut self) {
+        self.undo_stack.clear();
+        self.redo_stack.clear();
+        self.open_group = None;
+        self.pending.clear();
+        self.push_change(HistoryChange::Cleared);
+    }
+
+    pub fn track_changes(&mut self) {
+        self.changes.get_or_insert_with(Vec::new);
+    }
+
+    // Changes since the last call, in order
+    pub fn take_changes(&mut self) -> Vec<HistoryChange> {
+        self.changes.as_mut().map(std::mem::take).unwrap_or_default()
+    }
+
+    fn push_change(&mut self, change: HistoryChange) {
+        if let Some(changes) = &mut self.changes {
+            changes.push(change);
+        }
+    }
+
+    pub fn request(&mut self, action: HistoryAction) {
+        self.pending.push(action);
+    }
+
+    fn push_step(&mut self, edits: Vec<Edit>) {
+        if edits.is_empty() {
+            return;
+        }
+        self.push_change(HistoryChange::Recorded(edits.clone()));
+        self.undo_stack.push(edits);
+        if self.undo_stack.len() > MAX_HISTORY {
+            self.undo_stack.remove(0);
+        }
+        self.redo_stack.clear();
+    }
+
+    fn remap(&mut self, old: Entity, new: Entity) {
+        for edit in self
+            .undo_stack
+            .iter_mut()
+            .chain(self.redo_stack.iter_mut())
+            .flatten()
+        {
+            edit.remap(old, new);
+        }
+        self.push_change(HistoryChange::Remapped { old, new });
+    }
+}
+
+// Ctrl+Z undoes, Ctrl+Shift+Z redoes (Z is rebindable)
+fn handle_history_shortcuts(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    keys: Res<KeyBindings>,
+    mut history: ResMut<EditHistory>,
+) {
+    let ctrl = keyboard_input.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]);
+    if !ctrl || !keyboard_input.just_pressed(keys.undo) {
+        return;
+    }
+    if keyboard_input.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]) {
+        history.request(HistoryAction::Redo);
+    } else {
+        history.request(HistoryAction::Undo);
+    }
+}
+
+fn apply_history_requests(
+    mut commands: Commands,
+    mut history: ResMut<EditHistory>,
+    drag_data: Res<DragData>,
+    mut selection_state: ResMut<SelectionState>,
+    mut transforms: Query<&mut Transform>,
+    mut sdf_entities: Query<&mut SDFRenderEntity>,
+    mut entity_infos: Query<&mut SdfEntityInfo>,
+    entity_materials: Query<&MeshMaterial3d<StandardMaterial>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    if history.pending.is_empty() {
+        return;
+    }
+
+    // Finish the current drag before walking the history
+    if !matches!(*drag_data, DragData::Idle) {
+        for action in std::mem::take(&mut history.pending) {
+            history.push_change(HistoryChange::Skipped(action));
+        }
+        return;
+    }
+
+    // Undoing in the middle of a brush stroke closes the stroke first
+    history.end_group();
+
+    for action in std::mem::take(&mut history.pending) {
+        let popped = match action {
+            HistoryAction::Undo => history.undo_stack.pop(),
+            HistoryAction::Redo => history.redo_stack.pop(),
+        };
+        let Some(mut step) = popped else {
+            history.push_change(HistoryChange::Skipped(action));
+            continue;
+        };
+
+        // Undo applies the step's edits in reverse order
+        let order: Vec<usize> = match action {
+            HistoryAction::Undo => (0..step.len()).rev().collect(),
+            HistoryAction::Redo => (0..step.len()).collect(),
+        };
+        for i in order {
+            let undo = action == HistoryAction::Undo;
+            let respawn = match &step[i] {
+                Edit::Spawn(snapshot) if !undo => Some(snapshot.clone()),
+                Edit::Delete(snapshot) if undo => Some(snapshot.clone()),
+                Edit::Spawn(snapshot) | Edit::Delete(snapshot) => {
+                    if selection_state.is_selected(snapshot.entity) {
+                        deselect(&mut commands, &mut selection_state, snapshot.entity);
+                    }
+                    if let Ok(mut entity_commands) = commands.get_entity(snapshot.entity) {
+                        entity_commands.despawn();
+                    }
+                    None
+                }
+                Edit::Transform {
+                    entity,
+                    before,
+                    after,
+                } => {
+                    if let Ok(mut transform) = transforms.get_mut(*entity) {
+                        *transform = if undo { *before } else { *after };
+                    }
+                    None
+                }
+                Edit::Sdf {
+                    entity,
+                    before,
+                    after,
+                } => {
+                    if let Ok(mut sdf_entity) = sdf_entities.get_mut(*entity) {
+                        let value = if undo { before } else { after };
+                        *sdf_entity = SDFRenderEntity {
+                            node_index: sdf_entity.node_index,
+                            position: sdf_entity.position,
+                            ..value.clone()
+                        };
+                    }
+                    None
+                }
+                Edit::Info {
+                    entity,
+                    before,
+                    after,
+                } => {
+                    if let Ok(mut info) = entity_infos.get_mut(*entity) {
+                        *info = if undo { before.clone() } else { after.clone() };
+                    }
+                    None
+                }
+                Edit::Color {
+                    entity,
+                    before,
+                    after,
+                } => {
+                    let material = entity_materials
+                        .get(*entity)
+                        .ok()
+                        .and_then(|handle| materials.get_mut(&handle.0));
+                    if let Some(material) = material {
+                        material.base_color = if undo { *before } else { *after };
+                    }
+                    None
+                }
+            };
+
+            if let Some(snapshot) = respawn {
+                let new = snapshot.spawn(&mut commands);
+                for edit in step.iter_mut() {
+                    edit.remap(snapshot.entity, new);
+                }
+                history.remap(snapshot.entity, new);
+            }
+        }
+
+        match action {
+            HistoryAction::Undo => history.redo_stack.push(step),
+            HistoryAction::Redo => history.undo_stack.push(step),
+        }
+        history.push_change(HistoryChange::Applied(action));
+    }
+}
@@ -0,0 +1,176 @@
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use serde::Serialize;
+
+use crate::scene_io::SceneShape;
+use crate::sdf_render::{SDFRenderEntity, SdfPrimitive};
+use crate::selection::SelectionState;
+
+// Component holding user-facing metadata for an SDF entity
+#[derive(Component, Clone, Debug, Default)]
+pub struct SdfEntityInfo {
+    pub name: String,
+    pub tags: Vec<String>,
+}
+
+impl SdfEntityInfo {
+    // Auto-numbered name based on the primitive and the entity index
+    pub fn numbered(primitive: SdfPrimitive, index: usize) -> Self {
+        let kind = match primitive {
+            SdfPrimitive::Sphere => "Sphere",
+            SdfPrimitive::Ellipsoid { .. } => "Ellipsoid",
+            SdfPrimitive::Capsule { .. } => "Capsule",
+        };
+        Self {
+            name: format!("{} {}", kind, index + 1),
+            tags: Vec::new(),
+        }
+    }
+
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.iter().any(|t| t == tag)
+    }
+
+    pub fn add_tag(&mut self, tag: &str) {
+        if !self.has_tag(tag) {
+            self.tags.push(tag.to_string());
+        }
+    }
+}
+
+// Summary of an entity as sent to JavaScript; `id` is the entity's node index
+#[derive(Serialize, Clone, Debug)]
+pub struct EntitySummary {
+    pub id: usize,
+    pub name: String,
+    pub tags: Vec<String>,
+}
+
+// Everything an outliner shows about an SDF entity
+#[derive(Serialize, Debug)]
+pub struct EntityDetails {
+    pub id: usize,
+    pub name: String,
+    pub tags: Vec<String>,
+    // World space, like the SDF pass sees it
+    pub position: [f32; 3],
+    pub shape: SceneShape,
+    // sRGB with alpha, None when the entity has no material
+    pub color: Option<[f32; 4]>,
+    pub selected: bool,
+    // Name of the group the entity is in
+    pub group: Option<String>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct GroupDetails {
+    pub name: String,
+    pub tags: Vec<String>,
+    pub selected: bool,
+    // Ids of the entities in the group
+    pub children: Vec<usize>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct SceneSummary {
+    // Ordered by id
+    pub entities: Vec<EntityDetails>,
+    pub groups: Vec<GroupDetails>,
+    // Ids of the selected entities, in selection order
+    pub selected: Vec<usize>,
+}
+
+#[derive(SystemParam)]
+pub struct EntityDetailsQuery<'w, 's> {
+    entities: Query<
+        'w,
+        's,
+        (
+            Entity,
+            &'static SDFRenderEntity,
+            Option<&'static SdfEntityInfo>,
+            Option<&'static ChildOf>,
+            Option<&'static MeshMaterial3d<StandardMaterial>>,
+        ),
+    >,
+    groups: Query<
+        'w,
+        's,
+        (Entity, &'static SdfEntityInfo, Option<&'static Children>),
+        Without<SDFRenderEntity>,
+    >,
+    materials: Res<'w, Assets<StandardMaterial>>,
+    selection: Res<'w, SelectionState>,
+}
+
+impl EntityDetailsQuery<'_, '_> {
+    pub fn entity(&self, id: usize) -> Option<EntityDetails> {
+        self.entities
+            .iter()
+            .find(|(_, sdf, ..)| sdf.node_index == id)
+            .and_then(|(entity, ..)| self.details(entity))
+    }
+
+    pub fn summary(&self) -> SceneSummary {
+        let mut entities: Vec<EntityDetails> = self
+            .entities
+            .iter()
+            .filter_map(|(entity, ..)| self.details(entity))
+            .collect();
+        entities.sort_by_key(|details| details.id);
+
+        let mut groups: Vec<(Entity, GroupDetails)> = self
+            .groups
+            .iter()
+            .map(|(entity, info, children)| {
+                let children = children
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|child| self.entities.get(*child).ok())
+                    .map(|(_, sdf, ..)| sdf.node_index)
+                    .collect();
+                let details = GroupDetails {
+                    name: info.name.clone(),
+                    tags: info.tags.clone(),
+                    selected: self.selection.is_selected(entity),
+                    children,
+                };
+                (entity, details)
+            })
+            .collect();
+        groups.sort_by_key(|(entity, _)| *entity);
+
+        SceneSummary {
+            entities,
+            groups: groups.into_iter().map(|(_, details)| details).collect(),
+            selected: self
+                .selection
+                .iter()
+                .filter_map(|entity| self.entities.get(entity).ok())
+                .map(|(_, sdf, ..)| sdf.node_index)
+                .collect(),
+        }
+    }
+
+    // None for groups and despawned entities
+    pub fn details(&self, entity: Entity) -> Option<EntityDetails> {
+        let (entity, sdf, info, parent, material) = self.entities.get(entity).ok()?;
+        let info = info.cloned().unwrap_or_default();
+        let group = parent
+            .and_then(|parent| self.groups.get(parent.parent()).ok())
+            .map(|(_, group_info, _)| group_info.name.clone());
+        let color = material
+            .and_then(|material| self.materials.get(&material.0))
+            .map(|material| material.base_color.to_srgba().to_f32_array());
+        Some(EntityDetails {
+            id: sdf.node_index,
+            name: info.name,
+            tags: info.tags,
+            position: sdf.position.to_array(),
+            shape: SceneShape::from(sdf),
+            color,
+            selected: self.selection.is_selected(entity),
+            group,
+        })
+    }
+}
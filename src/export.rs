@@ -0,0 +1,208 @@
+//! Export helpers for turning the SDF scene into engine-friendly assets
+//!
+//! The collider description is a sphere set derived directly from the entity
+//! positions and scales (ellipsoids use their largest radius, capsules become a
+//! chain of spheres along their segment). Spheres that are fully contained in
+//! another sphere are dropped since they don't contribute to the collision volume.
+
+use bevy::prelude::*;
+use serde::Serialize;
+
+use crate::entity_info::SdfEntityInfo;
+use crate::sdf_render::{SDFRenderEntity, SdfOperation, SdfPrimitive};
+
+/// A single sphere in the exported collider description
+#[derive(Serialize, Clone, Copy, Debug)]
+pub struct ColliderSphere {
+    pub center: [f32; 3],
+    pub radius: f32,
+}
+
+/// Axis-aligned bounds of the collider, handy as a cheap broadphase shape
+#[derive(Serialize, Clone, Copy, Debug)]
+pub struct ColliderBounds {
+    pub min: [f32; 3],
+    pub max: [f32; 3],
+}
+
+/// Collider description stored alongside the exported mesh (JSON or glTF extras)
+#[derive(Serialize, Debug)]
+pub struct ColliderDescription {
+    #[serde(rename = "type")]
+    pub kind: &'static str,
+    pub spheres: Vec<ColliderSphere>,
+    pub bounds: Option<ColliderBounds>,
+}
+
+// Spheres approximating a single instance; capsules become a chain of spheres
+fn instance_spheres(entity: &SDFRenderEntity) -> Vec<(Vec3, f32)> {
+    match entity.primitive {
+        SdfPrimitive::Sphere | SdfPrimitive::Ellipsoid { .. } => {
+            vec![(entity.position, entity.scale)]
+        }
+        SdfPrimitive::Capsule { half_segment } => {
+            let length = half_segment.length() * 2.0;
+            let steps = (length / entity.scale.max(0.001)).ceil().max(1.0) as usize;
+            (0..=steps)
+                .map(|i| {
+                    let t = i as f32 / steps as f32 * 2.0 - 1.0;
+                    (entity.position + half_segment * t, entity.scale)
+                })
+                .collect()
+        }
+    }
+}
+
+/// Build a simplified sphere-set collider from the SDF entities
+pub fn build_sphere_colliders<'a>(
+    entities: impl IntoIterator<Item = &'a SDFRenderEntity>,
+) -> ColliderDescription {
+    // Subtractive entities only carve away volume, so they never become colliders
+    let mut entities: Vec<&SDFRenderEntity> = entities
+        .into_iter()
+        .filter(|e| e.operation == SdfOperation::Union)
+        .collect();
+    entities.sort_by_key(|e| e.node_index);
+    let candidates: Vec<(Vec3, f32)> = entities
+        .iter()
+        .flat_map(|e| e.instances())
+        .flat_map(|instance| instance_spheres(&instance))
+        .collect();
+
+    // Drop spheres that are entirely inside another sphere
+    let spheres: Vec<ColliderSphere> = candidates
+        .iter()
+        .enumerate()
+        .filter(|(i, (inner_center, inner_radius))| {
+            !candidates
+                .iter()
+                .enumerate()
+                .any(|(j, (outer_center, outer_radius))| {
+                    *i != j
+                        && inner_center.distance(*outer_center) + inner_radius <= *outer_radius
+                        && (inner_radius < outer_radius || j < *i)
+                })
+        })
+        .map(|(_, (center, radius))| ColliderSphere {
+            center: center.to_array(),
+            radius: *radius,
+        })
+        .collect();
+
+    let bounds = spheres
+        .iter()
+        .map(|s| {
+            let center = Vec3::from_array(s.center);
+            (center - Vec3::splat(s.radius), center + Vec3::splat(s.radius))
+        })
+        .reduce(|(min_a, max_a), (min_b, max_b)| (min_a.min(min_b), max_a.max(max_b)))
+        .map(|(min, max)| ColliderBounds {
+            min: min.to_array(),
+            max: max.to_array(),
+        });
+
+    ColliderDescription {
+        kind: "sphere_set",
+        spheres,
+        bounds,
+    }
+}
+
+/// Serialize the collider description to JSON
+pub fn colliders_to_json(description: &ColliderDescription) -> String {
+    serde_json::to_string_pretty(description).unwrap_or_else(|err| {
+        warn!("Failed to serialize colliders: {}", err);
+        String::from("{}")
+    })
+}
+
+fn primitive_name(primitive: SdfPrimitive) -> &'static str {
+    match primitive {
+        SdfPrimitive::Sphere => "sphere",
+        SdfPrimitive::Ellipsoid { .. } => "ellipsoid",
+        SdfPrimitive::Capsule { .. } => "capsule",
+    }
+}
+
+fn operation_name(operation: SdfOperation) -> &'static str {
+    match operation {
+        SdfOperation::Union => "union",
+        SdfOperation::Subtract => "subtract",
+    }
+}
+
+/// Build a glTF 2.0 document where every blob is a node
+///
+/// Blobs are grouped into one layer node per operation so the additive and
+/// subtractive parts of the sculpt stay separate in downstream DCC tools.
+/// Blob parameters and tags are stored in the node extras, with the blend
+/// radius that `blend_factor` gives each blob.
+pub fn build_scene_gltf<'a>(
+    entities: impl IntoIterator<Item = (&'a SDFRenderEntity, Option<&'a SdfEntityInfo>)>,
+    blend_factor: f32,
+) -> serde_json::Value {
+    let mut entities: Vec<(&SDFRenderEntity, Option<&SdfEntityInfo>)> =
+        entities.into_iter().collect();
+    entities.sort_by_key(|(e, _)| e.node_index);
+
+    let mut nodes: Vec<serde_json::Value> = Vec::new();
+    let mut layer_indices: Vec<usize> = Vec::new();
+
+    for operation in [SdfOperation::Union, SdfOperation::Subtract] {
+        let layer_entities: Vec<&(&SDFRenderEntity, Option<&SdfEntityInfo>)> = entities
+            .iter()
+            .filter(|(e, _)| e.operation == operation)
+            .collect();
+        if layer_entities.is_empty() {
+            continue;
+        }
+
+        let layer_index = nodes.len();
+        nodes.push(serde_json::Value::Null);
+        layer_indices.push(layer_index);
+
+        let mut children = Vec::new();
+        for (entity, info) in layer_entities {
+            let name = info
+                .map(|info| info.name.clone())
+                .unwrap_or_else(|| format!("Blob {}", entity.node_index));
+            let tags = info.map(|info| info.tags.clone()).unwrap_or_default();
+            children.push(nodes.len());
+            nodes.push(serde_json::json!({
+                "name": name,
+                "translation": entity.position.to_array(),
+                "scale": entity.primitive.half_extents(entity.scale).to_array(),
+                "extras": {
+                    "id": entity.node_index,
+                    "tags": tags,
+                    "primitive": primitive_name(entity.primitive),
+                    "radius": entity.scale,
+                    "blend": entity.blend_radius(blend_factor),
+                    "operation": operation_name(entity.operation),
+                    "displacement": {
+                        "amplitude": entity.displacement.amplitude,
+                        "frequency": entity.displacement.frequency,
+                    },
+                    "shell_thickness": entity.shell_thickness,
+                    "repetition": {
+                        "count": entity.repetition.count,
+                        "spacing": entity.repetition.spacing.to_array(),
+                    },
+                },
+            }));
+        }
+
+        nodes[layer_index] = serde_json::json!({
+            "name": format!("Layer {}", operation_name(operation)),
+            "children": children,
+            "extras": { "operation": operation_name(operation) },
+        });
+    }
+
+    serde_json::json!({
+        "asset": { "version": "2.0", "generator": "bevy_modeller" },
+        "scene": 0,
+        "scenes": [{ "name": "Scene", "nodes": layer_indices }],
+        "nodes": nodes,
+    })
+}
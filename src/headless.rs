@@ -0,0 +1,315 @@
+//! Headless batch mode
+//!
+//! `--headless` runs the app without a window: the render world still runs,
+//! but the camera draws into an offscreen image and a schedule runner drives
+//! the frames. `--scene <file>` loads a scene document or project first and
+//! `--script <file>` then runs one command per line, each waiting for the
+//! previous to finish, before the app exits. `--size <width>x<height>` sets
+//! the image size (1280x720 by default). Output files land in the working
+//! directory like native exports always do. Script commands:
+//!
+//! ```text
+//! # comments and blank lines are skipped
+//! load <scene or project file>
+//! save <scene file>
+//! export mesh [obj|stl|gltf] [at resolution] [<cells>]
+//! export gltf
+//! export colliders
+//! render turntable [<frames>]
+//! screenshot <png file>
+//! estimate volume [<samples>]
+//! wait <frames>
+//! ```
+//!
+//! A script line that doesn't parse ends the run with an error before
+//! anything is done, and so do shaders that fail to compile. A mesh export
+//! or volume estimate that fails or has nothing to do ends it with an error
+//! when it happens.
+
+use std::collections::VecDeque;
+
+use bevy::{
+    prelude::*,
+    render::{
+        camera::RenderTarget,
+        render_asset::RenderAssetUsages,
+        render_resource::{Extent3d, TextureDimension, TextureFormat, TextureUsages},
+        view::screenshot::save_to_disk,
+    },
+};
+
+use crate::command_bridge::{
+    estimate_volume, export_colliders, export_mesh, export_scene_gltf, save_scene,
+    start_turntable_capture,
+};
+use crate::pipeline_warmup::PipelineWarmupState;
+use crate::progress::{AppEvent, Operation, OperationComplete};
+use crate::sdf_render::SdfRenderCamera;
+use crate::startup_options::{open_file, StartupOptions};
+use crate::turntable_capture::{camera_screenshot, TurntableCapture};
+
+// Frames given to loads and saves to be applied, and to screenshots to be read back
+const SETTLE_FRAMES: u32 = 3;
+
+#[derive(Clone, Debug)]
+pub struct HeadlessArgs {
+    pub scene: Option<String>,
+    pub script: Option<String>,
+    pub size: UVec2,
+}
+
+impl From<&StartupOptions> for HeadlessArgs {
+    fn from(options: &StartupOptions) -> Self {
+        Self {
+            scene: options.open.clone(),
+            script: options.script.clone(),
+            size: options.size.unwrap_or(UVec2::new(1280, 720)),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum ScriptCommand {
+    Load(String),
+    Save(String),
+    ExportMesh {
+        format: String,
+        resolution: Option<u32>,
+    },
+    ExportGltf,
+    ExportColliders,
+    Turntable(Option<u32>),
+    Screenshot(String),
+    EstimateVolume(Option<u32>),
+    Wait(u32),
+}
+
+fn parse_script(script: &str) -> Result<Vec<ScriptCommand>, String> {
+    let mut commands = Vec::new();
+    for (number, line) in script.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let words: Vec<&str> = line.split_whitespace().collect();
+        // Rest of the line after the first `n` words, for paths with spaces
+        let rest = |n: usize| {
+            let rest = words[n..].join(" ");
+            (!rest.is_empty()).then_some(rest)
+        };
+        let number_at_end = || words.last().and_then(|word| word.parse().ok());
+
+        let command = match words.as_slice() {
+            ["load", ..] => rest(1).map(ScriptCommand::Load),
+            ["save", ..] => rest(1).map(ScriptCommand::Save),
+            ["export", "mesh", options @ ..] => {
+                let format = options
+                    .iter()
+                    .find(|word| matches!(**word, "obj" | "stl" | "gltf"))
+                    .unwrap_or(&"obj");
+                Some(ScriptCommand::ExportMesh {
+                    format: format.to_string(),
+                    resolution: number_at_end(),
+                })
+            }
+            ["export", "gltf"] => Some(ScriptCommand::ExportGltf),
+            ["export", "colliders"] => Some(ScriptCommand::ExportColliders),
+            ["render", "turntable", ..] => Some(ScriptCommand::Turntable(number_at_end())),
+            ["screenshot", ..] => rest(1).map(ScriptCommand::Screenshot),
+            ["estimate", "volume", ..] => Some(ScriptCommand::EstimateVolume(number_at_end())),
+            ["wait", frames] => frames.parse().ok().map(ScriptCommand::Wait),
+            _ => None,
+        };
+        match command {
+            Some(command) => commands.push(command),
+            None => return Err(format!("line {}: can't make sense of \"{}\"", number + 1, line)),
+        }
+    }
+    Ok(commands)
+}
+
+// What the current command waits for before the next one runs
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Waiting {
+    Nothing,
+    Frames(u32),
+    MeshExport,
+    VolumeEstimate,
+    // Started, but the capture isn't running yet
+    TurntableStart,
+    Turntable,
+}
+
+#[derive(Resource)]
+struct HeadlessScript {
+    commands: VecDeque<ScriptCommand>,
+    waiting: Waiting,
+    error: Option<String>,
+    // Of the image the camera renders to
+    size: UVec2,
+}
+
+pub struct HeadlessPlugin(pub HeadlessArgs);
+
+impl Plugin for HeadlessPlugin {
+    fn build(&self, app: &mut App) {
+        let args = &self.0;
+        let mut commands = VecDeque::new();
+        let mut error = None;
+        if let Some(scene) = &args.scene {
+            commands.push_back(ScriptCommand::Load(scene.clone()));
+        }
+        if let Some(path) = &args.script {
+            match std::fs::read_to_string(path)
+                .map_err(|err| err.to_string())
+                .and_then(|script| parse_script(&script))
+            {
+                Ok(script) => commands.extend(script),
+                Err(err) => error = Some(format!("Failed to read script {}: {}", path, err)),
+            }
+        }
+
+        app.insert_resource(HeadlessScript {
+            commands,
+            waiting: Waiting::Nothing,
+            error,
+            size: args.size,
+        })
+        // The camera is spawned during Startup
+        .add_systems(PostStartup, render_to_image)
+        .add_systems(Update, run_script);
+    }
+}
+
+// Without a window the camera draws into an image, which screenshots read back
+fn render_to_image(
+    mut images: ResMut<Assets<Image>>,
+    script: Res<HeadlessScript>,
+    mut cameras: Query<&mut Camera, With<SdfRenderCamera>>,
+) {
+    let mut image = Image::new_fill(
+        Extent3d {
+            width: script.size.x,
+            height: script.size.y,
+            ..default()
+        },
+        TextureDimension::D2,
+        &[0; 4],
+        TextureFormat::bevy_default(),
+        RenderAssetUsages::default(),
+    );
+    image.texture_descriptor.usage |=
+        TextureUsages::COPY_SRC | TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING;
+    let handle = images.add(image);
+    for mut camera in cameras.iter_mut() {
+        camera.target = RenderTarget::Image(handle.clone().into());
+    }
+}
+
+fn run_script(
+    mut commands: Commands,
+    mut script: ResMut<HeadlessScript>,
+    mut exit: EventWriter<AppExit>,
+    warmup: Res<PipelineWarmupState>,
+    turntable: Res<TurntableCapture>,
+    cameras: Query<&Camera, With<SdfRenderCamera>>,
+    mut app_events: EventReader<AppEvent>,
+) {
+    if let Some(error) = script.error.take() {
+        error!("{}", error);
+        exit.write(AppExit::error());
+        return;
+    }
+    // The warm-up logged which pipeline failed
+    if warmup.has_failed() {
+        exit.write(AppExit::error());
+        return;
+    }
+    if !warmup.is_ready() {
+        return;
+    }
+
+    // Exports and estimates complete, successfully or not, also when they had
+    // nothing to do
+    let completed: Vec<OperationComplete> = app_events
+        .read()
+        .filter_map(|event| match event {
+            AppEvent::Complete(complete) => Some(*complete),
+            AppEvent::Progress(_) => None,
+        })
+        .collect();
+    let awaited = match script.waiting {
+        Waiting::MeshExport => Some(Operation::MeshExport),
+        Waiting::VolumeEstimate => Some(Operation::VolumeEstimate),
+        _ => None,
+    };
+    // The script can't go on from an export or estimate that failed
+    if let Some(failed) = completed
+        .iter()
+        .find(|complete| Some(complete.operation) == awaited && !complete.success)
+    {
+        error!("{:?} failed or had nothing to do", failed.operation);
+        exit.write(AppExit::error());
+        return;
+    }
+    let mesh_exported = completed.iter().any(|c| c.operation == Operation::MeshExport);
+    let volume_estimated = completed.iter().any(|c| c.operation == Operation::VolumeEstimate);
+    script.waiting = match script.waiting {
+        Waiting::Frames(frames) if frames > 1 => Waiting::Frames(frames - 1),
+        Waiting::MeshExport if !mesh_exported => Waiting::MeshExport,
+        Waiting::VolumeEstimate if !volume_estimated => Waiting::VolumeEstimate,
+        Waiting::TurntableStart if turntable.is_active() => Waiting::Turntable,
+        Waiting::TurntableStart => Waiting::TurntableStart,
+        Waiting::Turntable if turntable.is_active() => Waiting::Turntable,
+        _ => Waiting::Nothing,
+    };
+    if script.waiting != Waiting::Nothing {
+        return;
+    }
+
+    let Some(command) = script.commands.pop_front() else {
+        info!("Headless script finished");
+        exit.write(AppExit::Success);
+        return;
+    };
+    info!("Running {:?}", command);
+    script.waiting = match command {
+        ScriptCommand::Load(path) => {
+            open_file(&path);
+            Waiting::Frames(SETTLE_FRAMES)
+        }
+        ScriptCommand::Save(path) => {
+            save_scene(&path);
+            Waiting::Frames(SETTLE_FRAMES)
+        }
+        ScriptCommand::ExportMesh { format, resolution } => {
+            export_mesh(&format, resolution);
+            Waiting::MeshExport
+        }
+        ScriptCommand::ExportGltf => {
+            export_scene_gltf();
+            Waiting::Frames(SETTLE_FRAMES)
+        }
+        ScriptCommand::ExportColliders => {
+            export_colliders();
+            Waiting::Frames(SETTLE_FRAMES)
+        }
+        ScriptCommand::Turntable(frames) => {
+            start_turntable_capture(frames.unwrap_or(120));
+            Waiting::TurntableStart
+        }
+        ScriptCommand::Screenshot(path) => {
+            if let Ok(camera) = cameras.single() {
+                commands
+                    .spawn(camera_screenshot(camera))
+                    .observe(save_to_disk(path));
+            }
+            Waiting::Frames(SETTLE_FRAMES)
+        }
+        ScriptCommand::EstimateVolume(samples) => {
+            estimate_volume(samples);
+            Waiting::VolumeEstimate
+        }
+        ScriptCommand::Wait(frames) => Waiting::Frames(frames.max(1)),
+    };
+}
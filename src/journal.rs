@@ -0,0 +1,717 @@
+//! Journal of every edit, for replaying a session
+//!
+//! While recording, each step the edit history records (spawns, transforms,
+//! brush strokes, deletes, property changes) and every undo and redo is
+//! appended to the journal with the time since recording started. The journal
+//! opens with the scene as it was when recording started, so replaying it
+//! rebuilds the session edit by edit: after a crash, to regenerate a
+//! time-lapse, or to reproduce a bug. Entities are referred to by ids local to
+//! the journal, which replay maps to the entities it spawns.
+//!
+//! Replay applies at most one entry per frame, since entities spawned by an
+//! entry only exist once the frame's commands are applied. With a speed the
+//! entries are spread out like they were recorded; without one they follow
+//! each other as fast as that allows. Natively a journal recording to a path
+//! is rewritten there every second, so little is lost in a crash.
+
+use std::collections::{HashMap, VecDeque};
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::command_bridge::EntityIndexCounter;
+use crate::edit_history::{
+    Edit, EditHistory, EntitySnapshot, HistoryAction, HistoryChange, RenderParts,
+};
+use crate::entity_info::SdfEntityInfo;
+use crate::pivot::PivotOffset;
+use crate::scene_io::{spawn_document, SceneCapture, SceneDocument, SceneShape, SceneTransform};
+use crate::sdf_render::SDFRenderEntity;
+use crate::selection::{deselect, deselect_all, SelectionState};
+
+pub const JOURNAL_FORMAT_VERSION: u32 = 1;
+// Seconds between rewrites of the journal file
+const FLUSH_INTERVAL: f32 = 1.0;
+
+pub struct JournalPlugin;
+
+impl Plugin for JournalPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Journal>()
+            .init_resource::<JournalReplay>()
+            .add_event::<StartJournal>()
+            .add_event::<StopJournal>()
+            .add_event::<ExportJournal>()
+            .add_event::<JournalExported>()
+            .add_event::<ReplayJournal>()
+            .add_systems(Startup, track_history_changes_from_start)
+            .add_systems(
+                Update,
+                (
+                    start_journal,
+                    track_history_changes,
+                    stop_journal,
+                    flush_journal,
+                    export_journal,
+                    start_replay,
+                    advance_replay,
+                )
+                    .chain(),
+            );
+    }
+}
+
+// Starts a new journal from the current scene; natively it is kept written to `path`
+#[derive(Event)]
+pub struct StartJournal {
+    pub path: Option<String>,
+}
+
+#[derive(Event)]
+pub struct StopJournal;
+
+#[derive(Event)]
+pub struct ExportJournal;
+
+#[derive(Event)]
+pub struct JournalExported {
+    pub contents: String,
+}
+
+// Replaces the scene with the journal's and replays its entries; `speed` scales the
+// recorded timing, None replays as fast as possible
+#[derive(Event)]
+pub struct ReplayJournal {
+    pub contents: String,
+    pub speed: Option<f32>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct JournalDocument {
+    pub version: u32,
+    // The scene when recording started
+    pub base: SceneDocument,
+    #[serde(default)]
+    pub entries: Vec<JournalEntry>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct JournalEntry {
+    // Seconds since recording started
+    pub time: f32,
+    pub change: JournalChange,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub enum JournalChange {
+    // Edits recorded as one undo step
+    Step(Vec<JournalEdit>),
+    Undo,
+    Redo,
+}
+
+// An edit with its entities replaced by journal ids, keeping only the new state
+#[derive(Serialize, Deserialize, Debug)]
+pub enum JournalEdit {
+    Spawn {
+        id: u64,
+        parent: Option<u64>,
+        name: String,
+        tags: Vec<String>,
+        transform: SceneTransform,
+        pivot: Option<[f32; 3]>,
+        // sRGB with alpha
+        color: [f32; 4],
+        // None for groups
+        shape: Option<SceneShape>,
+    },
+    Delete {
+        id: u64,
+    },
+    Transform {
+        id: u64,
+        transform: SceneTransform,
+    },
+    Sdf {
+        id: u64,
+        shape: SceneShape,
+    },
+    Info {
+        id: u64,
+        name: String,
+        tags: Vec<String>,
+    },
+    Color {
+        id: u64,
+        color: [f32; 4],
+    },
+}
+
+#[derive(Resource, Default)]
+pub struct Journal {
+    // Some while recording
+    document: Option<JournalDocument>,
+    path: Option<String>,
+    started: f32,
+    ids: HashMap<Entity, u64>,
+    next_id: u64,
+    // Entries added since the file was last written
+    dirty: bool,
+    last_flush: f32,
+}
+
+impl Journal {
+    pub fn is_recording(&self) -> bool {
+        self.document.is_some()
+    }
+
+    fn id(&mut self, entity: Entity) -> u64 {
+        let next_id = &mut self.next_id;
+        *self.ids.entry(entity).or_insert_with(|| {
+            *next_id += 1;
+            *next_id - 1
+        })
+    }
+
+    fn push(&mut self, time: f32, change: JournalChange) {
+        let started = self.started;
+        if let Some(document) = &mut self.document {
+            document.entries.push(JournalEntry {
+                time: time - started,
+                change,
+            });
+            self.dirty = true;
+        }
+    }
+
+    fn to_edit(&mut self, edit: &Edit, materials: &Assets<StandardMaterial>) -> JournalEdit {
+        match edit {
+            Edit::Spawn(snapshot) => {
+                let color = snapshot
+                    .material
+                    .as_ref()
+                    .and_then(|material| materials.get(material))
+                    .map_or(Color::WHITE, |material| material.base_color);
+                let info = snapshot.info.clone().unwrap_or_default();
+                JournalEdit::Spawn {
+                    id: self.id(snapshot.entity),
+                    parent: snapshot.parent.map(|parent| self.id(parent)),
+                    name: info.name,
+                    tags: info.tags,
+                    transform: (&snapshot.transform).into(),
+                    pivot: snapshot.pivot.map(|pivot| pivot.0.to_array()),
+                    color: color.to_srgba().to_f32_array(),
+                    shape: snapshot.sdf.as_ref().map(SceneShape::from),
+                }
+            }
+            Edit::Delete(snapshot) => JournalEdit::Delete {
+                id: self.id(snapshot.entity),
+            },
+            Edit::Transform { entity, after, .. } => JournalEdit::Transform {
+                id: self.id(*entity),
+                transform: after.into(),
+            },
+            Edit::Sdf { entity, after, .. } => JournalEdit::Sdf {
+                id: self.id(*entity),
+                shape: after.into(),
+            },
+            Edit::Info { entity, after, .. } => JournalEdit::Info {
+                id: self.id(*entity),
+                name: after.name.clone(),
+                tags: after.tags.clone(),
+            },
+            Edit::Color { entity, after, .. } => JournalEdit::Color {
+                id: self.id(*entity),
+                color: after.to_srgba().to_f32_array(),
+            },
+        }
+    }
+
+    fn contents(&self) -> Option<Result<String, String>> {
+        let document = self.document.as_ref()?;
+        Some(
+            ron::ser::to_string_pretty(document, ron::ser::PrettyConfig::default())
+                .map_err(|err| err.to_string()),
+        )
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct JournalReplay {
+    entries: VecDeque<JournalEntry>,
+    ids: HashMap<u64, Entity>,
+    speed: Option<f32>,
+    started: f32,
+    // Set after an undo or redo until the history applied or skipped it
+    waiting: bool,
+}
+
+fn track_history_changes_from_start(mut history: ResMut<EditHistory>) {
+    history.track_changes();
+}
+
+fn start_journal(
+    mut start_events: EventReader<StartJournal>,
+    mut journal: ResMut<Journal>,
+    capture: SceneCapture,
+    time: Res<Time<Real>>,
+) {
+    let Some(StartJournal { path }) = start_events.read().last() else {
+        return;
+    };
+    let (base, entities) = capture.capture();
+    *journal = Journal {
+        document: Some(JournalDocument {
+            version: JOURNAL_FORMAT_VERSION,
+            base,
+            entries: Vec::new(),
+        }),
+        path: path.clone(),
+        started: time.elapsed_secs(),
+        ids: HashMap::new(),
+        next_id: 0,
+        dirty: true,
+        last_flush: time.elapsed_secs(),
+    };
+    // The base scene's entities take the first ids, in the order replay spawns them
+    for entity in entities {
+        journal.id(entity);
+    }
+    info!("Started recording a journal");
+}
+
+fn track_history_changes(
+    mut history: ResMut<EditHistory>,
+    mut journal: ResMut<Journal>,
+    mut replay: ResMut<JournalReplay>,
+    materials: Res<Assets<StandardMaterial>>,
+    time: Res<Time<Real>>,
+) {
+    let now = time.elapsed_secs();
+    for change in history.take_changes() {
+        match change {
+            HistoryChange::Recorded(edits) => {
+                if journal.is_recording() {
+                    let edits = edits
+                        .iter()
+                        .map(|edit| journal.to_edit(edit, &materials))
+                        .collect();
+                    journal.push(now, JournalChange::Step(edits));
+                }
+            }
+            HistoryChange::Applied(action) => {
+                let change = match action {
+                    HistoryAction::Undo => JournalChange::Undo,
+                    HistoryAction::Redo => JournalChange::Redo,
+                };
+                journal.push(now, change);
+                replay.waiting = false;
+            }
+            // Nothing changed so nothing is journaled, but a replay waiting on it moves on
+            HistoryChange::Skipped(_) => replay.waiting = false,
+            HistoryChange::Remapped { old, new } => {
+                if let Some(id) = journal.ids.remove(&old) {
+                    journal.ids.insert(new, id);
+                }
+                for entity in replay.ids.values_mut() {
+                    if *entity == old {
+                        *entity = new;
+                    }
+                }
+            }
+            HistoryChange::Cleared => {
+                if journal.is_recording() {
+                    info!("The scene was replaced, the journal stops recording");
+                    journal.document = None;
+                }
+            }
+        }
+    }
+}
+
+fn stop_journal(mut stop_events: EventReader<StopJournal>, mut journal: ResMut<Journal>) {
+    if stop_events.read().last().is_none() {
+        return;
+    }
+    // Written one last time before it is dropped
+    write_journal_file(&mut journal);
+    journal.document = None;
+    info!("Stopped recording the journal");
+}
+
+fn flush_journal(mut journal: ResMut<Journal>, time: Res<Time<Real>>) {
+    if !journal.dirty || time.elapsed_secs() - journal.last_flush < FLUSH_INTERVAL {
+        return;
+    }
+    journal.last_flush = time.elapsed_secs();
+    write_journal_file(&mut journal);
+}
+
+fn write_journal_file(journal: &mut Journal) {
+    journal.dirty = false;
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        let Some(path) = journal.path.clone() else {
+            return;
+        };
+        match journal.contents() {
+            Some(Ok(contents)) => {
+                if let Err(err) = std::fs::write(&path, contents) {
+                    warn!("Failed to write {}: {}", path, err);
+                }
+            }
+            Some(Err(err)) => warn!("Failed to serialize the journal: {}", err),
+            None => {}
+        }
+    }
+}
+
+fn export_journal(
+    mut export_events: EventReader<ExportJournal>,
+    mut exported_events: EventWriter<JournalExported>,
+    journal: Res<Journal>,
+) {
+    if export_events.read().last().is_none() {
+        return;
+    }
+    match journal.contents() {
+        Some(Ok(contents)) => {
+            exported_events.write(JournalExported { contents });
+        }
+        Some(Err(err)) => warn!("Failed to serialize the journal: {}", err),
+        None => warn!("No journal is being recorded"),
+    }
+}
+
+fn start_replay(
+    mut commands: Commands,
+    mut replay_events: EventReader<ReplayJournal>,
+    mut replay: ResMut<JournalReplay>,
+    roots: Query<Entity, (With<SdfEntityInfo>, Without<ChildOf>)>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut history: ResMut<EditHistory>,
+    mut selection: ResMut<SelectionState>,
+    mut entity_index_counter: ResMut<EntityIndexCounter>,
+    time: Res<Time<Real>>,
+) {
+    let Some(ReplayJournal { contents, speed }) = replay_events.read().last() else {
+        return;
+    };
+    let document: JournalDocument = match ron::from_str(contents) {
+        Ok(document) => document,
+        Err(err) => {
+            warn!("Failed to read the journal: {}", err);
+            return;
+        }
+    };
+    if document.version > JOURNAL_FORMAT_VERSION {
+        warn!(
+            "Journal format version {} is newer than the supported version {}",
+            document.version, JOURNAL_FORMAT_VERSION
+        );
+        return;
+    }
+
+    // Replaying over a recording journal would end it anyway, through the cleared history
+    deselect_all(&mut commands, &mut selection);
+    for root in roots.iter() {
+        commands.entity(root).despawn();
+    }
+    history.clear();
+    entity_index_counter.counter = 0;
+    let spawned = spawn_document(
+        &document.base,
+        Vec3::ZERO,
+        1.0,
+        &mut commands,
+        &mut meshes,
+        &mut materials,
+        &mut entity_index_counter,
+    );
+
+    info!("Replaying {} journal entries", document.entries.len());
+    *replay = JournalReplay {
+        entries: document.entries.into(),
+        ids: (0..).zip(spawned.iter().map(|snapshot| snapshot.entity)).collect(),
+        speed: speed.filter(|speed| *speed > 0.0),
+        started: time.elapsed_secs(),
+        waiting: false,
+    };
+}
+
+fn advance_replay(
+    mut commands: Commands,
+    mut replay: ResMut<JournalReplay>,
+    entities: Query<(
+        &Transform,
+        Option<&SDFRenderEntity>,
+        Option<&SdfEntityInfo>,
+        RenderParts,
+    )>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut history: ResMut<EditHistory>,
+    mut selection: ResMut<SelectionState>,
+    mut entity_index_counter: ResMut<EntityIndexCounter>,
+    time: Res<Time<Real>>,
+) {
+    if replay.waiting {
+        return;
+    }
+    let Some(entry) = replay.entries.front() else {
+        return;
+    };
+    if let Some(speed) = replay.speed {
+        if (time.elapsed_secs() - replay.started) * speed < entry.time {
+            return;
+        }
+    }
+    let Some(entry) = replay.entries.pop_front() else {
+        return;
+    };
+
+    let action = match entry.change {
+        JournalChange::Step(edits) => Err(edits),
+        JournalChange::Undo => Ok(HistoryAction::Undo),
+        JournalChange::Redo => Ok(HistoryAction::Redo),
+    };
+    let edits = match action {
+        Ok(action) => {
+            // Later entries may refer to entities the undo or redo respawns
+            history.request(action);
+            replay.waiting = true;
+            return;
+        }
+        Err(edits) => edits,
+    };
+
+    // Entities spawned earlier in this step don't exist yet, their snapshots stand in
+    let mut spawned: HashMap<Entity, EntitySnapshot> = HashMap::new();
+    let current = |entity: Entity, spawned: &HashMap<Entity, EntitySnapshot>| {
+        spawned.get(&entity).cloned().or_else(|| {
+            let (transform, sdf, info, render_parts) = entities.get(entity).ok()?;
+            Some(EntitySnapshot::capture(
+                entity,
+                *transform,
+                sdf,
+                info,
+                render_parts,
+            ))
+        })
+    };
+
+    let mut recorded = Vec::new();
+    for edit in edits {
+        match edit {
+            JournalEdit::Spawn {
+                id,
+                parent,
+                name,
+                tags,
+                transform,
+                pivot,
+                color,
+                shape,
+            } => {
+                let transform: Transform = (&transform).into();
+                let sdf = shape.map(|shape| {
+                    let node_index = entity_index_counter.counter;
+                    entity_index_counter.counter += 1;
+                    shape.to_entity(node_index, transform.translation, 1.0)
+                });
+                let [r, g, b, a] = color;
+                let mut snapshot = EntitySnapshot {
+                    entity: Entity::PLACEHOLDER,
+                    mesh: sdf
+                        .as_ref()
+                        .map(|sdf| meshes.add(sdf.primitive.proxy_mesh(sdf.scale))),
+                    material: sdf.as_ref().map(|_| {
+                        materials.add(StandardMaterial {
+                            base_color: Color::srgba(r, g, b, a),
+                            ..default()
+                        })
+                    }),
+                    sdf,
+                    info: Some(SdfEntityInfo { name, tags }),
+                    transform,
+                    parent: parent.and_then(|parent| replay.ids.get(&parent).copied()),
+                    pivot: pivot.map(|pivot| PivotOffset(Vec3::from_array(pivot))),
+                };
+                snapshot.entity = snapshot.spawn(&mut commands);
+                replay.ids.insert(id, snapshot.entity);
+                spawned.insert(snapshot.entity, snapshot.clone());
+                recorded.push(Edit::Spawn(snapshot));
+            }
+            JournalEdit::Delete { id } => {
+                let Some(entity) = replay.ids.get(&id).copied() else {
+                    continue;
+                };
+                let Some(snapshot) = current(entity, &spawned) else {
+                    continue;
+                };
+                spawned.remove(&entity);
+                if selection.is_selected(entity) {
+                    deselect(&mut commands, &mut selection, entity);
+                }
+                if let Ok(mut entity_commands) = commands.get_entity(entity) {
+                    entity_commands.despawn();
+                }
+                recorded.push(Edit::Delete(snapshot));
+            }
+            JournalEdit::Transform { id, transform } => {
+                let Some(entity) = replay.ids.get(&id).copied() else {
+                    continue;
+                };
+                let Some(before) = current(entity, &spawned) else {
+                    continue;
+                };
+                let after: Transform = (&transform).into();
+                commands.entity(entity).insert(after);
+                recorded.push(Edit::Transform {
+                    entity,
+                    before: before.transform,
+                    after,
+                });
+            }
+            JournalEdit::Sdf { id, shape } => {
+                let Some(entity) = replay.ids.get(&id).copied() else {
+                    continue;
+                };
+                let Some(before) = current(entity, &spawned).and_then(|snapshot| snapshot.sdf)
+                else {
+                    continue;
+                };
+                let after = shape.to_entity(before.node_index, before.position, 1.0);
+                commands.entity(entity).insert(after.clone());
+                recorded.push(Edit::Sdf {
+                    entity,
+                    before,
+                    after,
+                });
+            }
+            JournalEdit::Info { id, name, tags } => {
+                let Some(entity) = replay.ids.get(&id).copied() else {
+                    continue;
+                };
+                let before = current(entity, &spawned)
+                    .and_then(|snapshot| snapshot.info)
+                    .unwrap_or_default();
+                let after = SdfEntityInfo { name, tags };
+                commands.entity(entity).insert(after.clone());
+                recorded.push(Edit::Info {
+                    entity,
+                    before,
+                    after,
+                });
+            }
+            JournalEdit::Color { id, color } => {
+                let Some(entity) = replay.ids.get(&id).copied() else {
+                    continue;
+                };
+                let material = current(entity, &spawned)
+                    .and_then(|snapshot| snapshot.material)
+                    .and_then(|handle| materials.get_mut(&handle));
+                let Some(material) = material else {
+                    continue;
+                };
+                let [r, g, b, a] = color;
+                let before = material.base_color;
+                material.base_color = Color::srgba(r, g, b, a);
+                recorded.push(Edit::Color {
+                    entity,
+                    before,
+                    after: material.base_color,
+                });
+            }
+        }
+    }
+    history.record_all(recorded);
+
+    if replay.entries.is_empty() {
+        info!("Journal replay finished");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::brush_mode::{BrushSettings, BrushToolState};
+    use crate::edit_history::EditHistoryPlugin;
+    use crate::scene_io::SCENE_FORMAT_VERSION;
+    use crate::sdf_render::{SdfBlend, BLEND_FACTOR};
+    use crate::settings::KeyBindings;
+    use crate::symmetry::Symmetry;
+    use crate::translation::DragData;
+
+    fn replay_app() -> App {
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, EditHistoryPlugin, JournalPlugin))
+            .init_resource::<Assets<Mesh>>()
+            .init_resource::<Assets<StandardMaterial>>()
+            .init_resource::<SelectionState>()
+            .init_resource::<EntityIndexCounter>()
+            .init_resource::<DragData>()
+            .init_resource::<KeyBindings>()
+            .init_resource::<ButtonInput<KeyCode>>()
+            .init_resource::<BrushToolState>()
+            .init_resource::<BrushSettings>()
+            .init_resource::<Symmetry>()
+            .init_resource::<SdfBlend>();
+        app
+    }
+
+    // An undo of an edit made before recording started has nothing to undo on replay
+    #[test]
+    fn replay_continues_past_an_undo_with_nothing_to_undo() {
+        let spawn = JournalEdit::Spawn {
+            id: 0,
+            parent: None,
+            name: "replayed".to_string(),
+            tags: Vec::new(),
+            transform: (&Transform::IDENTITY).into(),
+            pivot: None,
+            color: [1.0; 4],
+            shape: None,
+        };
+        let document = JournalDocument {
+            version: JOURNAL_FORMAT_VERSION,
+            base: SceneDocument {
+                version: SCENE_FORMAT_VERSION,
+                groups: Vec::new(),
+                entities: Vec::new(),
+                camera: None,
+                brush: None,
+                blend_factor: BLEND_FACTOR,
+            },
+            entries: vec![
+                JournalEntry {
+                    time: 0.0,
+                    change: JournalChange::Undo,
+                },
+                JournalEntry {
+                    time: 0.0,
+                    change: JournalChange::Step(vec![spawn]),
+                },
+            ],
+        };
+
+        let mut app = replay_app();
+        app.world_mut().send_event(ReplayJournal {
+            contents: ron::to_string(&document).unwrap(),
+            speed: None,
+        });
+        for _ in 0..10 {
+            app.update();
+        }
+
+        assert!(app.world().resource::<JournalReplay>().entries.is_empty());
+        let names: Vec<String> = app
+            .world_mut()
+            .query::<&SdfEntityInfo>()
+            .iter(app.world())
+            .map(|info| info.name.clone())
+            .collect();
+        assert_eq!(names, ["replayed"]);
+    }
+}
@@ -0,0 +1,347 @@
+//! SDF sculpting editor as a set of Bevy plugins
+//!
+//! `ModellerPlugins` adds the renderer, compute, picking, selection,
+//! translation, brush and JavaScript bridge plugins to an app that already
+//! has `DefaultPlugins`, so other Bevy apps can embed the modeller. The
+//! builder methods configure the pieces that differ between hosts:
+//!
+//! ```ignore
+//! App::new()
+//!     .add_plugins(DefaultPlugins)
+//!     .add_plugins(
+//!         ModellerPlugins::default()
+//!             .with_perf_ui(false)
+//!             .with_picking(PickingBackend::Mesh)
+//!             .with_default_camera(false),
+//!     )
+//!     .run();
+//! ```
+//!
+//! Without the default camera the host spawns its own, with `SdfRenderCamera`,
+//! `SDFRenderSettings`, `DepthPrepass`, `Msaa::Off` and a `PanOrbitCamera`.
+//! Like with any plugin group, single plugins the host already has can be
+//! turned off with `.build().disable::<Plugin>()`.
+
+use bevy::{app::PluginGroupBuilder, core_pipeline::prepass::DepthPrepass, prelude::*};
+use bevy_panorbit_camera::{PanOrbitCamera, PanOrbitCameraPlugin};
+use iyes_perf_ui::{prelude::PerfUiDefaultEntries, PerfUiPlugin};
+
+pub mod adaptive_resolution;
+pub mod align;
+pub mod brick_map;
+pub mod clip_plane;
+pub mod coarse_tuning;
+pub mod brush_mode;
+pub mod command_bridge;
+pub mod edit_history;
+pub mod entity_info;
+pub mod export;
+pub mod headless;
+pub mod journal;
+pub mod localization;
+pub mod meshing;
+pub mod mode;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod osc_control;
+pub mod overlay;
+pub mod perf_ui;
+pub mod pivot;
+pub mod preview_mesh;
+pub mod pipeline_warmup;
+pub mod progress;
+pub mod project_file;
+#[cfg(all(feature = "remote", not(target_arch = "wasm32")))]
+pub mod remote;
+pub mod scene_io;
+pub mod scene_snapshots;
+pub mod sdf_compute;
+pub mod sdf_cpu;
+pub mod sdf_picking;
+pub mod sdf_render;
+pub mod sdf_volume_cache;
+pub mod selection;
+pub mod settings;
+pub mod snapping;
+pub mod startup_options;
+pub mod symmetry;
+pub mod temporal_accumulation;
+pub mod texture_bake;
+pub mod translation;
+pub mod turntable_capture;
+pub mod view_culling;
+pub mod view_presets;
+pub mod volume_estimate;
+
+use adaptive_resolution::AdaptiveResolutionPlugin;
+use align::AlignPlugin;
+use brick_map::BrickMapPlugin;
+use clip_plane::ClipPlanePlugin;
+use coarse_tuning::CoarseTuningPlugin;
+use brush_mode::BrushModePlugin;
+pub use command_bridge::spawn_sphere_at_origin;
+use command_bridge::CommandBridgePlugin;
+use edit_history::EditHistoryPlugin;
+use journal::JournalPlugin;
+use localization::LocalizationPlugin;
+use meshing::MeshingPlugin;
+use mode::ModePlugin;
+pub use mode::{switch_to_brush_mode, switch_to_translate_mode, AppMode, AppModeState};
+use overlay::OverlayPlugin;
+use perf_ui::{SdfPerfUiEntries, SdfPerfUiPlugin};
+use pipeline_warmup::PipelineWarmupPlugin;
+use preview_mesh::PreviewMeshPlugin;
+use progress::ProgressPlugin;
+use project_file::ProjectFilePlugin;
+use scene_io::SceneIoPlugin;
+use scene_snapshots::SceneSnapshotsPlugin;
+use sdf_compute::SdfComputePlugin;
+use sdf_picking::SdfPickingPlugin;
+use sdf_render::{
+    SDFRenderEnabled, SDFRenderPlugin, SDFRenderSettings, SdfAmbientOcclusion, SdfDebugView,
+    SdfIsolateMode, SdfRenderCamera,
+};
+use sdf_volume_cache::SdfVolumeCachePlugin;
+use selection::SelectionPlugin;
+use settings::{KeyBindings, SettingsPlugin};
+use snapping::SnappingPlugin;
+use temporal_accumulation::TemporalAccumulationPlugin;
+use translation::TranslationPlugin;
+use turntable_capture::TurntableCapturePlugin;
+use view_culling::ViewCullingPlugin;
+use view_presets::ViewPresetsPlugin;
+use volume_estimate::VolumeEstimatePlugin;
+
+// How clicks in the viewport find the entity under the cursor
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PickingBackend {
+    // Proxy meshes first, then a ray marched against the SDF for entities
+    // without one
+    #[default]
+    MeshAndSdf,
+    // Proxy meshes only
+    Mesh,
+    // The host brings its own picking backend; the gizmo handles still need
+    // one that sees meshes
+    None,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct ModellerPlugins {
+    perf_ui: bool,
+    picking: PickingBackend,
+    default_camera: bool,
+    msaa: Msaa,
+}
+
+impl Default for ModellerPlugins {
+    fn default() -> Self {
+        Self {
+            perf_ui: true,
+            picking: PickingBackend::default(),
+            default_camera: true,
+            msaa: Msaa::Off,
+        }
+    }
+}
+
+impl ModellerPlugins {
+    // The perf UI overlay and the SDF entries in it
+    pub fn with_perf_ui(mut self, enabled: bool) -> Self {
+        self.perf_ui = enabled;
+        self
+    }
+
+    pub fn with_picking(mut self, picking: PickingBackend) -> Self {
+        self.picking = picking;
+        self
+    }
+
+    // The orbiting SDF camera and the light spawned at startup
+    pub fn with_default_camera(mut self, enabled: bool) -> Self {
+        self.default_camera = enabled;
+        self
+    }
+
+    // Anti-aliasing of the default camera. The SDF pipelines aren't multisampled
+    // yet, so anything but Msaa::Off is turned down with a warning
+    pub fn with_msaa(mut self, msaa: Msaa) -> Self {
+        self.msaa = msaa;
+        self
+    }
+}
+
+impl PluginGroup for ModellerPlugins {
+    fn build(self) -> PluginGroupBuilder {
+        let mut group = PluginGroupBuilder::start::<Self>()
+            .add(SDFRenderPlugin::default())
+            .add(bevy::diagnostic::FrameTimeDiagnosticsPlugin::default())
+            .add(bevy::diagnostic::EntityCountDiagnosticsPlugin)
+            .add(bevy::diagnostic::SystemInformationDiagnosticsPlugin)
+            .add(bevy::render::diagnostic::RenderDiagnosticsPlugin)
+            .add(PanOrbitCameraPlugin);
+        if self.perf_ui {
+            group = group.add(PerfUiPlugin).add(SdfPerfUiPlugin);
+        }
+        if self.picking != PickingBackend::None {
+            group = group.add(MeshPickingPlugin);
+        }
+        group = group.add(ModePlugin).add(SelectionPlugin);
+        if self.picking == PickingBackend::MeshAndSdf {
+            group = group.add(SdfPickingPlugin);
+        }
+        group
+            .add(OverlayPlugin)
+            .add(SnappingPlugin)
+            .add(TranslationPlugin)
+            .add(AlignPlugin)
+            .add(SdfComputePlugin)
+            .add(BrushModePlugin)
+            .add(CommandBridgePlugin)
+            .add(EditHistoryPlugin)
+            .add(AdaptiveResolutionPlugin)
+            .add(TemporalAccumulationPlugin)
+            .add(CoarseTuningPlugin)
+            .add(SdfVolumeCachePlugin)
+            .add(BrickMapPlugin)
+            .add(ViewCullingPlugin)
+            .add(ClipPlanePlugin)
+            .add(ViewPresetsPlugin)
+            .add(TurntableCapturePlugin)
+            .add(VolumeEstimatePlugin)
+            .add(MeshingPlugin)
+            .add(PreviewMeshPlugin)
+            .add(SceneIoPlugin)
+            .add(ProjectFilePlugin)
+            .add(SceneSnapshotsPlugin)
+            .add(JournalPlugin)
+            .add(PipelineWarmupPlugin)
+            .add(ProgressPlugin)
+            .add(LocalizationPlugin)
+            .add(SettingsPlugin)
+            .add(ModellerSetupPlugin {
+                perf_ui: self.perf_ui,
+                default_camera: self.default_camera,
+                msaa: self.msaa,
+            })
+    }
+}
+
+// The light of the default camera setup, which OSC parameters move around
+#[derive(Component)]
+pub struct KeyLight;
+
+// Startup entities and the viewport shortcuts, configured by ModellerPlugins
+pub struct ModellerSetupPlugin {
+    perf_ui: bool,
+    default_camera: bool,
+    msaa: Msaa,
+}
+
+impl Plugin for ModellerSetupPlugin {
+    fn build(&self, app: &mut App) {
+        if self.default_camera {
+            if self.msaa != Msaa::Off {
+                warn!("The SDF pass doesn't support MSAA yet, leaving it off");
+            }
+            app.add_systems(Startup, spawn_default_camera);
+        }
+        if self.perf_ui {
+            app.add_systems(Startup, spawn_perf_ui);
+        }
+        app.add_systems(
+            Update,
+            (
+                toggle_sdf_render_system,
+                toggle_ambient_occlusion_system,
+                cycle_debug_view_system,
+                toggle_isolate_mode_system,
+            ),
+        );
+    }
+}
+
+fn spawn_default_camera(mut commands: Commands) {
+    commands.spawn((
+        Camera {
+            order: 0,
+            ..default()
+        },
+        SdfRenderCamera,
+        SDFRenderSettings {
+            near_plane: 0.1,
+            far_plane: 10.,
+            ..default()
+        },
+        DepthPrepass,
+        // The SDF pipelines draw single sampled
+        Msaa::Off,
+        PanOrbitCamera {
+            button_orbit: MouseButton::Right,
+            button_pan: MouseButton::Left,
+            modifier_orbit: None,
+            modifier_pan: Some(KeyCode::SuperLeft),
+            ..default()
+        },
+        Transform::from_xyz(0., 2.0, 5.0).looking_at(Vec3::ZERO, Vec3::Y),
+    ));
+
+    commands.spawn((
+        KeyLight,
+        PointLight {
+            shadows_enabled: true,
+            intensity: 10_000_000.,
+            range: 100.0,
+            shadow_depth_bias: 0.2,
+            ..default()
+        },
+        Transform::from_xyz(8.0, 16.0, 8.0),
+    ));
+}
+
+fn spawn_perf_ui(mut commands: Commands) {
+    commands.spawn((PerfUiDefaultEntries::default(), SdfPerfUiEntries::default()));
+}
+
+fn toggle_sdf_render_system(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    keys: Res<KeyBindings>,
+    mut sdf_render_enabled: ResMut<SDFRenderEnabled>,
+) {
+    if keyboard_input.just_pressed(keys.toggle_sdf_render) {
+        sdf_render_enabled.enabled = !sdf_render_enabled.enabled;
+        info!("Post-process toggled: {}", sdf_render_enabled.enabled);
+    }
+}
+
+fn toggle_ambient_occlusion_system(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    keys: Res<KeyBindings>,
+    mut ambient_occlusion: ResMut<SdfAmbientOcclusion>,
+) {
+    if keyboard_input.just_pressed(keys.toggle_ambient_occlusion) {
+        ambient_occlusion.enabled = !ambient_occlusion.enabled;
+        info!("Ambient occlusion toggled: {}", ambient_occlusion.enabled);
+    }
+}
+
+fn toggle_isolate_mode_system(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    keys: Res<KeyBindings>,
+    mut isolate: ResMut<SdfIsolateMode>,
+) {
+    if keyboard_input.just_pressed(keys.toggle_isolate_mode) {
+        isolate.enabled = !isolate.enabled;
+        info!("Isolate view toggled: {}", isolate.enabled);
+    }
+}
+
+fn cycle_debug_view_system(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    keys: Res<KeyBindings>,
+    mut debug_view: ResMut<SdfDebugView>,
+) {
+    if keyboard_input.just_pressed(keys.cycle_debug_view) {
+        *debug_view = debug_view.next();
+        info!("SDF debug view: {:?}", *debug_view);
+    }
+}
@@ -0,0 +1,108 @@
+//! Localization of UI text shown by the Bevy side of the editor
+//!
+//! Strings are looked up by key in a per-language bundle. Unknown keys fall
+//! back to English, and unknown language codes are ignored so the embedding
+//! page can pass its locale through unchanged.
+
+use bevy::prelude::*;
+
+pub struct LocalizationPlugin;
+
+impl Plugin for LocalizationPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Localization>()
+            .add_systems(Update, update_localized_text);
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Language {
+    English,
+    German,
+}
+
+impl Language {
+    pub fn from_code(code: &str) -> Option<Self> {
+        // Accept full locales like "de-DE" as well as bare language codes
+        let language = code.split(['-', '_']).next().unwrap_or(code);
+        match language.to_ascii_lowercase().as_str() {
+            "en" => Some(Language::English),
+            "de" => Some(Language::German),
+            _ => None,
+        }
+    }
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            Language::English => "en",
+            Language::German => "de",
+        }
+    }
+}
+
+const ENGLISH: &[(&str, &str)] = &[
+    ("loading.shaders", "Compiling shaders..."),
+    ("loading.failed", "Shaders failed to compile, see the log"),
+];
+
+const GERMAN: &[(&str, &str)] = &[
+    ("loading.shaders", "Shader werden kompiliert..."),
+    ("loading.failed", "Shader konnten nicht kompiliert werden, siehe Log"),
+];
+
+fn bundle(language: Language) -> &'static [(&'static str, &'static str)] {
+    match language {
+        Language::English => ENGLISH,
+        Language::German => GERMAN,
+    }
+}
+
+#[derive(Resource)]
+pub struct Localization {
+    pub language: Language,
+}
+
+impl Default for Localization {
+    fn default() -> Self {
+        Self {
+            language: Language::English,
+        }
+    }
+}
+
+impl Localization {
+    /// Look up a string for the current language, falling back to English and then the key
+    pub fn get<'a>(&self, key: &'a str) -> &'a str {
+        let lookup = |language| {
+            bundle(language)
+                .iter()
+                .find(|(k, _)| *k == key)
+                .map(|(_, v)| *v)
+        };
+        lookup(self.language)
+            .or_else(|| lookup(Language::English))
+            .unwrap_or(key)
+    }
+
+    pub fn set_language(&mut self, code: &str) {
+        match Language::from_code(code) {
+            Some(language) => self.language = language,
+            None => warn!("Unknown language requested: {}", code),
+        }
+    }
+}
+
+// Component for UI text that should follow the current language
+#[derive(Component)]
+pub struct LocalizedText(pub &'static str);
+
+fn update_localized_text(
+    localization: Res<Localization>,
+    mut text_query: Query<(&mut Text, Ref<LocalizedText>)>,
+) {
+    for (mut text, localized) in text_query.iter_mut() {
+        if localization.is_changed() || localized.is_changed() {
+            text.0 = localization.get(localized.0).to_string();
+        }
+    }
+}
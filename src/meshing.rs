@@ -0,0 +1,681 @@
+//! Triangle mesh export of the SDF surface
+//!
+//! The scene is sampled on a grid fitted around the entity bounds through the
+//! compute module, a slab of points per request so the buffers stay small, and
+//! the zero crossing of the samples is polygonized on the CPU. Every grid cell
+//! is split into six tetrahedra along its main diagonal, which needs no case
+//! table and leaves no ambiguous faces, so the surface comes out watertight.
+//! Vertices on shared cell edges are welded. The mesh is written as OBJ or
+//! ASCII STL, to disk on native builds or handed to JavaScript on the web.
+//!
+//! glTF exports are shaded as well: vertex normals come from the SDF gradient
+//! on the GPU, and vertex colors blend the material colors of the entities
+//! near each vertex over the same radius their surfaces blend over. Ambient
+//! occlusion and detail finer than the grid are baked into textures over the
+//! mesh, see texture_bake.rs.
+
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use bevy::{
+    prelude::*,
+    render::{
+        mesh::{Indices, PrimitiveTopology},
+        render_asset::RenderAssetUsages,
+    },
+    tasks::{block_on, futures_lite::future, AsyncComputeTaskPool, Task},
+};
+use futures::channel::oneshot;
+
+use crate::pipeline_warmup::PipelineWarmupState;
+use crate::progress::{AppEvent, Operation, SharedProgress};
+use crate::sdf_compute::{project_to_surface_async, sample_sdf_async, SdfEvaluationSender};
+use crate::sdf_cpu::{entity_distance, SceneSdf};
+use crate::sdf_render::{EntityData, SDFRenderEntity, SdfBlend, SdfOperation};
+use crate::texture_bake::{bake_mesh_textures_async, BakedTextures};
+
+// Cells along the longest side of the scene bounds when none are asked for
+pub const DEFAULT_MESH_RESOLUTION: u32 = 96;
+pub const MAX_MESH_RESOLUTION: u32 = 256;
+
+// Points evaluated per compute request
+pub const POINTS_PER_REQUEST: usize = 1 << 18;
+
+// glTF constants
+const GLTF_FLOAT: u32 = 5126;
+const GLTF_UNSIGNED_INT: u32 = 5125;
+const GLTF_ARRAY_BUFFER: u32 = 34962;
+const GLTF_ELEMENT_ARRAY_BUFFER: u32 = 34963;
+const GLTF_LINEAR: u32 = 9729;
+
+// Corners of a cell as x + 2y + 4z offsets, and the six tetrahedra around the
+// diagonal from corner 0 to 7. Neighbouring cells split their shared faces
+// along the same diagonal
+const CELL_TETRAHEDRA: [[usize; 4]; 6] = [
+    [0, 1, 3, 7],
+    [0, 1, 5, 7],
+    [0, 2, 3, 7],
+    [0, 2, 6, 7],
+    [0, 4, 5, 7],
+    [0, 4, 6, 7],
+];
+
+pub struct MeshingPlugin;
+
+impl Plugin for MeshingPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<MeshExport>()
+            .add_event::<ExportMesh>()
+            .add_event::<MeshExported>()
+            .add_systems(Update, run_mesh_export);
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MeshFormat {
+    Obj,
+    Stl,
+    // With vertex normals and colors, and baked occlusion and normal textures
+    Gltf,
+}
+
+impl MeshFormat {
+    pub fn file_name(&self) -> &'static str {
+        match self {
+            MeshFormat::Obj => "sculpt.obj",
+            MeshFormat::Stl => "sculpt.stl",
+            MeshFormat::Gltf => "sculpt.gltf",
+        }
+    }
+
+    // Name of the JavaScript event the file is delivered through
+    pub fn event_name(&self) -> &'static str {
+        match self {
+            MeshFormat::Obj => "meshObjExported",
+            MeshFormat::Stl => "meshStlExported",
+            MeshFormat::Gltf => "meshGltfExported",
+        }
+    }
+}
+
+#[derive(Event)]
+pub struct ExportMesh {
+    pub format: MeshFormat,
+    pub resolution: u32,
+}
+
+// The written file, sent once meshing finished
+#[derive(Event)]
+pub struct MeshExported {
+    pub format: MeshFormat,
+    pub contents: String,
+}
+
+#[derive(Resource, Default)]
+pub struct MeshExport {
+    task: Option<Task<Option<(SdfMesh, MeshFormat)>>>,
+    progress: SharedProgress,
+    // Last progress sent as an AppEvent
+    reported: f32,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct SdfMesh {
+    pub positions: Vec<Vec3>,
+    // Counter-clockwise seen from outside
+    pub triangles: Vec<[u32; 3]>,
+    // Per vertex, empty until the mesh is shaded
+    pub normals: Vec<Vec3>,
+    pub colors: Vec<LinearRgba>,
+    // Per vertex, empty until textures are baked over the mesh
+    pub uvs: Vec<Vec2>,
+    pub tangents: Vec<Vec4>,
+    pub textures: Option<BakedTextures>,
+}
+
+impl SdfMesh {
+    pub fn to_obj(&self) -> String {
+        let mut obj = String::from("# SDF sculpt\no sculpt\n");
+        for p in &self.positions {
+            let _ = writeln!(obj, "v {} {} {}", p.x, p.y, p.z);
+        }
+        for [a, b, c] in &self.triangles {
+            let _ = writeln!(obj, "f {} {} {}", a + 1, b + 1, c + 1);
+        }
+        obj
+    }
+
+    pub fn to_stl(&self) -> String {
+        let mut stl = String::from("solid sculpt\n");
+        for triangle in &self.triangles {
+            let [a, b, c] = triangle.map(|i| self.positions[i as usize]);
+            let normal = (b - a).cross(c - a).normalize_or_zero();
+            let _ = writeln!(stl, "facet normal {} {} {}", normal.x, normal.y, normal.z);
+            stl.push_str("  outer loop\n");
+            for v in [a, b, c] {
+                let _ = writeln!(stl, "    vertex {} {} {}", v.x, v.y, v.z);
+            }
+            stl.push_str("  endloop\nendfacet\n");
+        }
+        stl.push_str("endsolid sculpt\n");
+        stl
+    }
+
+    /// A glTF 2.0 document with the mesh in a single node, its buffer and baked
+    /// textures embedded as data uris. Vertex colors multiply a white, fully
+    /// rough material
+    pub fn to_gltf(&self) -> serde_json::Value {
+        let indices: Vec<u32> = self.triangles.iter().flatten().copied().collect();
+        let normals = self.vertex_normals();
+        let colors: Vec<[f32; 4]> = if self.colors.len() == self.positions.len() {
+            self.colors.iter().map(|c| c.to_f32_array()).collect()
+        } else {
+            vec![[1.0; 4]; self.positions.len()]
+        };
+        let vertex_count = self.positions.len();
+        let textures = self.textures.as_ref().filter(|_| {
+            self.uvs.len() == vertex_count && self.tangents.len() == vertex_count
+        });
+
+        // Vertex attributes by name, with the type of their accessor
+        let mut attributes: Vec<(&str, &str, &[u8])> = vec![
+            ("POSITION", "VEC3", bytemuck::cast_slice(&self.positions)),
+            ("NORMAL", "VEC3", bytemuck::cast_slice(&normals)),
+            ("COLOR_0", "VEC4", bytemuck::cast_slice(&colors)),
+        ];
+        if textures.is_some() {
+            attributes.push(("TEXCOORD_0", "VEC2", bytemuck::cast_slice(&self.uvs)));
+            attributes.push(("TANGENT", "VEC4", bytemuck::cast_slice(&self.tangents)));
+        }
+
+        let (min, max) = self
+            .positions
+            .iter()
+            .fold((Vec3::INFINITY, Vec3::NEG_INFINITY), |(min, max), p| {
+                (min.min(*p), max.max(*p))
+            });
+
+        // A view and an accessor per attribute, then the indices
+        let mut buffer = Vec::new();
+        let mut buffer_views = Vec::new();
+        let mut accessors = Vec::new();
+        let mut primitive_attributes = serde_json::Map::new();
+        for (index, (name, kind, data)) in attributes.iter().enumerate() {
+            buffer_views.push(serde_json::json!({
+                "buffer": 0,
+                "byteOffset": buffer.len(),
+                "byteLength": data.len(),
+                "target": GLTF_ARRAY_BUFFER,
+            }));
+            buffer.extend_from_slice(data);
+            let mut accessor = serde_json::json!({
+                "bufferView": index,
+                "componentType": GLTF_FLOAT,
+                "count": vertex_count,
+                "type": kind,
+            });
+            if *name == "POSITION" {
+                accessor["min"] = serde_json::json!(min.to_array());
+                accessor["max"] = serde_json::json!(max.to_array());
+            }
+            accessors.push(accessor);
+            primitive_attributes.insert(name.to_string(), index.into());
+        }
+        let index_bytes: &[u8] = bytemuck::cast_slice(&indices);
+        buffer_views.push(serde_json::json!({
+            "buffer": 0,
+            "byteOffset": buffer.len(),
+            "byteLength": index_bytes.len(),
+            "target": GLTF_ELEMENT_ARRAY_BUFFER,
+        }));
+        buffer.extend_from_slice(index_bytes);
+        accessors.push(serde_json::json!({
+            "bufferView": attributes.len(),
+            "componentType": GLTF_UNSIGNED_INT,
+            "count": indices.len(),
+            "type": "SCALAR",
+        }));
+
+        let mut document = serde_json::json!({
+            "asset": { "version": "2.0", "generator": "bevy_modeller" },
+            "scene": 0,
+            "scenes": [{ "nodes": [0] }],
+            "nodes": [{ "name": "Sculpt", "mesh": 0 }],
+            "meshes": [{
+                "name": "Sculpt",
+                "primitives": [{
+                    "attributes": primitive_attributes,
+                    "indices": attributes.len(),
+                    "material": 0,
+                }],
+            }],
+            "materials": [{
+                "name": "Sculpt",
+                "pbrMetallicRoughness": {
+                    "baseColorFactor": [1.0, 1.0, 1.0, 1.0],
+                    "metallicFactor": 0.0,
+                    "roughnessFactor": 1.0,
+                },
+            }],
+            "accessors": accessors,
+            "bufferViews": buffer_views,
+            "buffers": [{
+                "byteLength": buffer.len(),
+                "uri": format!("data:application/octet-stream;base64,{}", base64(&buffer)),
+            }],
+        });
+
+        if let Some(textures) = textures {
+            let image = |png: &[u8]| {
+                serde_json::json!({ "uri": format!("data:image/png;base64,{}", base64(png)) })
+            };
+            document["images"] = serde_json::json!([
+                image(&textures.occlusion),
+                image(&textures.normal),
+            ]);
+            // Every texel is baked up to the cell edges, so filtering stays in the cell
+            document["samplers"] = serde_json::json!([{
+                "magFilter": GLTF_LINEAR,
+                "minFilter": GLTF_LINEAR,
+            }]);
+            document["textures"] = serde_json::json!([
+                { "sampler": 0, "source": 0 },
+                { "sampler": 0, "source": 1 },
+            ]);
+            let material = &mut document["materials"][0];
+            material["occlusionTexture"] = serde_json::json!({ "index": 0 });
+            material["normalTexture"] = serde_json::json!({ "index": 1 });
+        }
+        document
+    }
+
+    // With the shaded normals, or smoothed face normals otherwise
+    pub fn to_render_mesh(&self) -> Mesh {
+        let normals = self.vertex_normals();
+        let indices = self.triangles.iter().flatten().copied().collect();
+        Mesh::new(PrimitiveTopology::TriangleList, RenderAssetUsages::RENDER_WORLD)
+            .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, self.positions.clone())
+            .with_inserted_attribute(Mesh::ATTRIBUTE_NORMAL, normals)
+            .with_inserted_indices(Indices::U32(indices))
+    }
+
+    // The shaded normals, or smoothed face normals for meshes that weren't shaded
+    pub fn vertex_normals(&self) -> Vec<Vec3> {
+        if self.normals.len() == self.positions.len() {
+            self.normals.clone()
+        } else {
+            self.flat_normals()
+        }
+    }
+
+    // Area weighted face normals summed per vertex
+    fn flat_normals(&self) -> Vec<Vec3> {
+        let mut normals = vec![Vec3::ZERO; self.positions.len()];
+        for triangle in &self.triangles {
+            let [a, b, c] = triangle.map(|i| self.positions[i as usize]);
+            let normal = (b - a).cross(c - a);
+            for i in triangle {
+                normals[*i as usize] += normal;
+            }
+        }
+        normals.iter().map(|n| n.normalize_or_zero()).collect()
+    }
+
+    pub fn write(&self, format: MeshFormat) -> String {
+        match format {
+            MeshFormat::Obj => self.to_obj(),
+            MeshFormat::Stl => self.to_stl(),
+            MeshFormat::Gltf => serde_json::to_string(&self.to_gltf()).unwrap_or_else(|err| {
+                warn!("Failed to serialize glTF mesh: {}", err);
+                String::from("{}")
+            }),
+        }
+    }
+}
+
+// Additive entity instances with their material color, to color vertices by
+#[derive(Clone, Default)]
+pub struct MeshPalette {
+    entities: Vec<(SDFRenderEntity, LinearRgba)>,
+    // SdfBlend::factor of the scene
+    blend_factor: f32,
+}
+
+impl MeshPalette {
+    pub fn from_entities<'a>(
+        entities: impl IntoIterator<Item = (&'a SDFRenderEntity, Color)>,
+        blend_factor: f32,
+    ) -> Self {
+        Self {
+            entities: entities
+                .into_iter()
+                .filter(|(entity, _)| entity.operation == SdfOperation::Union)
+                .flat_map(|(entity, color)| {
+                    entity.instances().map(move |instance| (instance, color.to_linear()))
+                })
+                .collect(),
+            blend_factor,
+        }
+    }
+
+    // The colors of the closest entities, each fading out once it's further
+    // from the point than the closest one by its blend radius
+    fn color_at(&self, point: Vec3) -> LinearRgba {
+        let distances: Vec<f32> = self
+            .entities
+            .iter()
+            .map(|(entity, _)| entity_distance(entity, point))
+            .collect();
+        let closest = distances.iter().copied().fold(f32::INFINITY, f32::min);
+
+        let (sum, total_weight) = self.entities.iter().zip(&distances).fold(
+            (Vec4::ZERO, 0.0),
+            |(sum, total_weight), ((entity, color), distance)| {
+                let blend = entity.blend_radius(self.blend_factor).max(f32::EPSILON);
+                let weight = (-(distance - closest) / blend).exp();
+                (sum + Vec4::from_array(color.to_f32_array()) * weight, total_weight + weight)
+            },
+        );
+        if total_weight > 0.0 {
+            LinearRgba::from_f32_array((sum / total_weight).to_array())
+        } else {
+            LinearRgba::WHITE
+        }
+    }
+}
+
+pub fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] =
+        b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let b = [chunk[0], *chunk.get(1).unwrap_or(&0), *chunk.get(2).unwrap_or(&0)];
+        let n = ((b[0] as u32) << 16) | ((b[1] as u32) << 8) | b[2] as u32;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(ALPHABET[((n >> (18 - 6 * i)) & 63) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+// Regularly spaced points to sample the SDF at
+pub struct SampleGrid {
+    pub origin: Vec3,
+    pub cell_size: f32,
+    // Points along each axis
+    pub dims: UVec3,
+}
+
+impl SampleGrid {
+    // Points of the bounds, `resolution` cells along the longest side and one
+    // cell of padding all around so the surface closes
+    pub fn around(min: Vec3, max: Vec3, resolution: u32) -> Self {
+        let cell_size = (max - min).max_element().max(f32::EPSILON) / resolution as f32;
+        let cells = ((max - min) / cell_size).ceil().as_uvec3().max(UVec3::ONE);
+        Self {
+            origin: min - Vec3::splat(cell_size),
+            cell_size,
+            dims: cells + UVec3::splat(3),
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.dims.element_product() as usize
+    }
+
+    fn index(&self, x: u32, y: u32, z: u32) -> usize {
+        (x + self.dims.x * (y + self.dims.y * z)) as usize
+    }
+
+    fn position(&self, index: usize) -> Vec3 {
+        let index = index as u32;
+        let x = index % self.dims.x;
+        let y = (index / self.dims.x) % self.dims.y;
+        let z = index / (self.dims.x * self.dims.y);
+        self.origin + UVec3::new(x, y, z).as_vec3() * self.cell_size
+    }
+}
+
+/// Samples the SDF within `min`..`max` and polygonizes its surface (async),
+/// with `resolution` cells along the longest side of the bounds
+pub async fn mesh_sdf_async(
+    min: Vec3,
+    max: Vec3,
+    resolution: u32,
+    sender: &SdfEvaluationSender,
+    progress: Option<&SharedProgress>,
+) -> Result<SdfMesh, oneshot::Canceled> {
+    let grid = SampleGrid::around(min, max, resolution.clamp(1, MAX_MESH_RESOLUTION));
+    let distances = sample_grid_async(&grid, sender, progress).await?;
+    Ok(polygonize(&grid, &distances))
+}
+
+/// Signed distance at every point of the grid (async), reporting the fraction
+/// of points sampled to `progress`
+pub async fn sample_grid_async(
+    grid: &SampleGrid,
+    sender: &SdfEvaluationSender,
+    progress: Option<&SharedProgress>,
+) -> Result<Vec<f32>, oneshot::Canceled> {
+    let mut distances = Vec::with_capacity(grid.len());
+    for start in (0..grid.len()).step_by(POINTS_PER_REQUEST) {
+        let end = (start + POINTS_PER_REQUEST).min(grid.len());
+        let points = (start..end).map(|index| grid.position(index)).collect();
+        let results = sample_sdf_async(points, sender).await?;
+        distances.extend(results.iter().map(|result| result.distance));
+        if let Some(progress) = progress {
+            progress.set(end as f32 / grid.len() as f32);
+        }
+    }
+    Ok(distances)
+}
+
+/// Fills in the vertex normals from the SDF gradient (async) and the vertex
+/// colors from the palette
+pub async fn shade_mesh_async(
+    mesh: &mut SdfMesh,
+    palette: &MeshPalette,
+    sender: &SdfEvaluationSender,
+    progress: Option<&SharedProgress>,
+) -> Result<(), oneshot::Canceled> {
+    let mut normals = Vec::with_capacity(mesh.positions.len());
+    for positions in mesh.positions.chunks(POINTS_PER_REQUEST) {
+        // Vertices are on the surface already, so only the normal is kept
+        let results = project_to_surface_async(positions.to_vec(), sender).await?;
+        normals.extend(results.iter().map(|result| result.normal));
+        if let Some(progress) = progress {
+            progress.set(normals.len() as f32 / mesh.positions.len() as f32);
+        }
+    }
+    mesh.normals = normals;
+    mesh.colors = mesh.positions.iter().map(|p| palette.color_at(*p)).collect();
+    Ok(())
+}
+
+// Surface through the zero crossing of the grid's distances
+pub fn polygonize(grid: &SampleGrid, distances: &[f32]) -> SdfMesh {
+    let mut mesh = SdfMesh::default();
+    // Welded vertices by the grid points of the edge they lie on
+    let mut edge_vertices: HashMap<(usize, usize), u32> = HashMap::new();
+
+    for z in 0..grid.dims.z - 1 {
+        for y in 0..grid.dims.y - 1 {
+            for x in 0..grid.dims.x - 1 {
+                let corners: [usize; 8] = std::array::from_fn(|c| {
+                    let c = c as u32;
+                    grid.index(x + (c & 1), y + ((c >> 1) & 1), z + ((c >> 2) & 1))
+                });
+                for tetrahedron in CELL_TETRAHEDRA {
+                    let points = tetrahedron.map(|c| corners[c]);
+                    polygonize_tetrahedron(
+                        grid,
+                        distances,
+                        points,
+                        &mut mesh,
+                        &mut edge_vertices,
+                    );
+                }
+            }
+        }
+    }
+
+    mesh
+}
+
+fn polygonize_tetrahedron(
+    grid: &SampleGrid,
+    distances: &[f32],
+    points: [usize; 4],
+    mesh: &mut SdfMesh,
+    edge_vertices: &mut HashMap<(usize, usize), u32>,
+) {
+    let (inside, outside): (Vec<usize>, Vec<usize>) =
+        points.into_iter().partition(|&p| distances[p] < 0.0);
+    if inside.is_empty() || outside.is_empty() {
+        return;
+    }
+
+    let mut edge_vertex = |a: usize, b: usize| -> u32 {
+        let key = (a.min(b), a.max(b));
+        *edge_vertices.entry(key).or_insert_with(|| {
+            let (da, db) = (distances[a], distances[b]);
+            let t = (da / (da - db)).clamp(0.0, 1.0);
+            let position = grid.position(a).lerp(grid.position(b), t);
+            mesh.positions.push(position);
+            mesh.positions.len() as u32 - 1
+        })
+    };
+
+    let vertices: Vec<u32> = match (inside.len(), outside.len()) {
+        (1, 3) => outside.iter().map(|&o| edge_vertex(inside[0], o)).collect(),
+        (3, 1) => inside.iter().map(|&i| edge_vertex(i, outside[0])).collect(),
+        _ => vec![
+            edge_vertex(inside[0], outside[0]),
+            edge_vertex(inside[0], outside[1]),
+            edge_vertex(inside[1], outside[1]),
+            edge_vertex(inside[1], outside[0]),
+        ],
+    };
+
+    // Triangles face from the inside corners towards the outside ones
+    let centroid = |corners: &[usize]| {
+        corners.iter().map(|&p| grid.position(p)).sum::<Vec3>() / corners.len() as f32
+    };
+    let outward = centroid(&outside) - centroid(&inside);
+    let mut push_triangle = |a: u32, b: u32, c: u32| {
+        let [pa, pb, pc] = [a, b, c].map(|i| mesh.positions[i as usize]);
+        let normal = (pb - pa).cross(pc - pa);
+        if normal.length_squared() <= f32::EPSILON * f32::EPSILON {
+            return;
+        }
+        if normal.dot(outward) < 0.0 {
+            mesh.triangles.push([a, c, b]);
+        } else {
+            mesh.triangles.push([a, b, c]);
+        }
+    };
+    push_triangle(vertices[0], vertices[1], vertices[2]);
+    if vertices.len() == 4 {
+        push_triangle(vertices[0], vertices[2], vertices[3]);
+    }
+}
+
+fn run_mesh_export(
+    mut export_events: EventReader<ExportMesh>,
+    mut exported_events: EventWriter<MeshExported>,
+    mut app_events: EventWriter<AppEvent>,
+    warmup: Res<PipelineWarmupState>,
+    entity_data: Option<Res<EntityData>>,
+    sdf_sender: Res<SdfEvaluationSender>,
+    sdf_entities: Query<(&SDFRenderEntity, Option<&MeshMaterial3d<StandardMaterial>>)>,
+    materials: Res<Assets<StandardMaterial>>,
+    blend: Res<SdfBlend>,
+    mut export: ResMut<MeshExport>,
+) {
+    if let Some(request) = export_events.read().last() {
+        let bounds = entity_data.as_ref().and_then(|data| data.bounds());
+        if export.task.is_some() || !warmup.is_ready() {
+            info!("Mesh export is busy, try again shortly");
+            app_events.write(AppEvent::complete(Operation::MeshExport, false));
+        } else if let Some((min, max)) = bounds {
+            let sender = sdf_sender.clone();
+            let (format, resolution) = (request.format, request.resolution);
+            let palette = (format == MeshFormat::Gltf).then(|| {
+                let colors = sdf_entities.iter().map(|(entity, material)| {
+                    let color = material
+                        .and_then(|material| materials.get(&material.0))
+                        .map_or(Color::WHITE, |material| material.base_color);
+                    (entity, color)
+                });
+                MeshPalette::from_entities(colors, blend.factor)
+            });
+            let scene_sdf = (format == MeshFormat::Gltf).then(|| {
+                SceneSdf::from_entities(sdf_entities.iter().map(|(entity, _)| entity), blend.factor)
+            });
+            // Shading takes roughly as long as sampling and the texture bake
+            // twice that, when there are any
+            let progress = SharedProgress::default();
+            let [sampled, shaded] = if palette.is_some() { [0.25, 0.5] } else { [1.0, 1.0] };
+            let sampling = progress.span(0., sampled);
+            let shading = progress.span(sampled, shaded);
+            let baking = progress.span(shaded, 1.);
+            export.progress = progress;
+            export.reported = 0.;
+            app_events.write(AppEvent::progress(Operation::MeshExport, 0.));
+            export.task = Some(AsyncComputeTaskPool::get().spawn(async move {
+                let mut mesh =
+                    mesh_sdf_async(min, max, resolution, &sender, Some(&sampling)).await.ok()?;
+                if let Some(palette) = palette {
+                    shade_mesh_async(&mut mesh, &palette, &sender, Some(&shading)).await.ok()?;
+                }
+                if let Some(scene_sdf) = scene_sdf {
+                    match bake_mesh_textures_async(&mesh, &scene_sdf, Some(&baking)).await {
+                        Some(baked) => mesh = baked,
+                        None => warn!("The mesh is too detailed to bake textures for"),
+                    }
+                }
+                Some((mesh, format))
+            }));
+        } else {
+            info!("Nothing to export");
+            app_events.write(AppEvent::complete(Operation::MeshExport, false));
+        }
+    }
+
+    let Some(task) = &mut export.task else {
+        return;
+    };
+    let Some(result) = block_on(future::poll_once(task)) else {
+        let progress = export.progress.get();
+        if progress > export.reported {
+            export.reported = progress;
+            app_events.write(AppEvent::progress(Operation::MeshExport, progress));
+        }
+        return;
+    };
+    export.task = None;
+
+    let Some((mesh, format)) = result else {
+        app_events.write(AppEvent::complete(Operation::MeshExport, false));
+        return;
+    };
+    if mesh.triangles.is_empty() {
+        info!("The sculpt has no surface to export");
+        app_events.write(AppEvent::complete(Operation::MeshExport, false));
+        return;
+    }
+    info!(
+        "Meshed the sculpt into {} triangles ({} vertices)",
+        mesh.triangles.len(),
+        mesh.positions.len()
+    );
+    exported_events.write(MeshExported {
+        format,
+        contents: mesh.write(format),
+    });
+    app_events.write(AppEvent::complete(Operation::MeshExport, true));
+}
@@ -1,4 +1,5 @@
 use bevy::prelude::*;
+use bevy_panorbit_camera::PanOrbitCamera;
 use std::collections::HashSet;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -66,6 +67,31 @@ pub struct ModePlugin;
 
 impl Plugin for ModePlugin {
     fn build(&self, app: &mut App) {
-        app.init_resource::<AppModeState>();
+        app.init_resource::<AppModeState>()
+            .add_systems(Update, arbitrate_camera_buttons);
+    }
+}
+
+// Camera panning shares the left mouse button with the brush, so while in
+// Brush mode panning moves to the middle button. The configured pan button
+// and modifier are remembered and put back when leaving the mode.
+fn arbitrate_camera_buttons(
+    mode_state: Res<AppModeState>,
+    mut pan_orbit_query: Query<&mut PanOrbitCamera>,
+    mut saved_pan: Local<Option<(MouseButton, Option<KeyCode>)>>,
+) {
+    let Ok(mut pan_orbit) = pan_orbit_query.single_mut() else {
+        return;
+    };
+
+    if mode_state.is_mode(AppMode::Brush) {
+        if saved_pan.is_none() && pan_orbit.button_pan == MouseButton::Left {
+            *saved_pan = Some((pan_orbit.button_pan, pan_orbit.modifier_pan));
+            pan_orbit.button_pan = MouseButton::Middle;
+            pan_orbit.modifier_pan = None;
+        }
+    } else if let Some((button, modifier)) = saved_pan.take() {
+        pan_orbit.button_pan = button;
+        pan_orbit.modifier_pan = modifier;
     }
 }
@@ -0,0 +1,258 @@
+//! Live parameter control over OSC
+//!
+//! Listens for Open Sound Control messages on a UDP port and maps their
+//! addresses to editor parameters through the `OscBindings` table, so
+//! control surfaces like TouchOSC can tweak the scene during demos and
+//! installations. Faders send values from 0 to 1, which a binding scales to
+//! the range of its parameter; messages with no binding are ignored. Started
+//! with `--osc [ADDRESS]` or by adding `OscControlPlugin`, on native builds
+//! only since browsers can't open UDP sockets. Only local senders reach the
+//! default address; surfaces on other devices need one like `0.0.0.0:9000`.
+//! MIDI controllers can be bridged in with any MIDI to OSC tool.
+
+use std::{
+    f32::consts::{FRAC_PI_2, PI},
+    net::UdpSocket,
+    thread,
+};
+
+use bevy::prelude::*;
+
+use crate::brush_mode::BrushSettings;
+use crate::sdf_render::{SdfAmbientOcclusion, SdfBlend, SdfShadows};
+use crate::KeyLight;
+
+pub const DEFAULT_OSC_ADDRESS: &str = "127.0.0.1:9000";
+
+// Lowest the light goes at night, so the scene never turns fully dark
+const MIN_LIGHT_ELEVATION: f32 = 0.05;
+
+pub struct OscControlPlugin {
+    pub address: String,
+}
+
+impl Default for OscControlPlugin {
+    fn default() -> Self {
+        Self {
+            address: DEFAULT_OSC_ADDRESS.to_string(),
+        }
+    }
+}
+
+// Parameters OSC messages can drive
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ControlParameter {
+    // Radius of the tools that place primitives
+    BrushRadius,
+    // Direction the key light shines from around the vertical, in degrees
+    LightAngle,
+    // Hour from 0 to 24; the key light rises at 6 and sets at 18
+    TimeOfDay,
+    ShadowSoftness,
+    AmbientOcclusionIntensity,
+    // Blend radius of every entity relative to its scale
+    BlendFactor,
+}
+
+#[derive(Debug, Clone)]
+pub struct OscBinding {
+    pub address: String,
+    pub parameter: ControlParameter,
+    // Values a message of 0 and of 1 set the parameter to
+    pub min: f32,
+    pub max: f32,
+}
+
+impl OscBinding {
+    pub fn new(address: &str, parameter: ControlParameter, min: f32, max: f32) -> Self {
+        Self {
+            address: address.to_string(),
+            parameter,
+            min,
+            max,
+        }
+    }
+}
+
+// Which OSC address drives which parameter; hosts can replace the defaults
+#[derive(Resource, Debug, Clone)]
+pub struct OscBindings(pub Vec<OscBinding>);
+
+impl Default for OscBindings {
+    fn default() -> Self {
+        use ControlParameter::*;
+        Self(vec![
+            OscBinding::new("/brush/radius", BrushRadius, 0.05, 1.0),
+            OscBinding::new("/light/angle", LightAngle, 0.0, 360.0),
+            OscBinding::new("/time", TimeOfDay, 0.0, 24.0),
+            OscBinding::new("/shadows/softness", ShadowSoftness, 0.001, 0.5),
+            OscBinding::new("/ao/intensity", AmbientOcclusionIntensity, 0.0, 2.0),
+            OscBinding::new("/blend", BlendFactor, 0.05, 1.0),
+        ])
+    }
+}
+
+// Address and first numeric argument of a received message
+struct OscMessage {
+    address: String,
+    value: f32,
+}
+
+#[derive(Resource)]
+struct OscReceiver(crossbeam_channel::Receiver<OscMessage>);
+
+impl Plugin for OscControlPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<OscBindings>();
+
+        let socket = match UdpSocket::bind(&self.address) {
+            Ok(socket) => socket,
+            Err(err) => {
+                warn!("Failed to listen for OSC on {}: {}", self.address, err);
+                return;
+            }
+        };
+        info!("Listening for OSC on {}", self.address);
+
+        let (sender, receiver) = crossbeam_channel::unbounded();
+        thread::spawn(move || {
+            let mut buffer = [0u8; 4096];
+            loop {
+                let length = match socket.recv(&mut buffer) {
+                    Ok(length) => length,
+                    Err(err) => {
+                        warn!("Failed to receive OSC: {}", err);
+                        continue;
+                    }
+                };
+                let mut messages = Vec::new();
+                parse_packet(&buffer[..length], &mut messages);
+                for message in messages {
+                    sender.send(message);
+                }
+            }
+        });
+        app.insert_resource(OscReceiver(receiver))
+            .add_systems(Update, apply_osc_messages);
+    }
+}
+
+fn apply_osc_messages(
+    receiver: Res<OscReceiver>,
+    bindings: Res<OscBindings>,
+    mut brush_settings: ResMut<BrushSettings>,
+    mut shadows: ResMut<SdfShadows>,
+    mut ambient_occlusion: ResMut<SdfAmbientOcclusion>,
+    mut light_query: Query<&mut Transform, With<KeyLight>>,
+    mut blend: ResMut<SdfBlend>,
+) {
+    // Every change rebuilds the entity data and BVH, so only the last value of
+    // the frame is applied, and only when it differs
+    let mut blend_factor = None;
+    while let Some(message) = receiver.0.try_recv() {
+        let bound = bindings.0.iter().filter(|binding| binding.address == message.address);
+        for binding in bound {
+            let value = binding.min + (binding.max - binding.min) * message.value.clamp(0., 1.);
+            match binding.parameter {
+                ControlParameter::BrushRadius => brush_settings.radius = Some(value),
+                ControlParameter::LightAngle => {
+                    for mut transform in light_query.iter_mut() {
+                        let (_, elevation, distance) = light_orbit(&transform);
+                        let azimuth = value.to_radians();
+                        transform.translation = orbit_position(azimuth, elevation, distance);
+                    }
+                }
+                ControlParameter::TimeOfDay => {
+                    // Highest at noon, resting just above the horizon at night
+                    let sun = ((value - 6.) / 12. * PI).sin();
+                    let elevation = (sun * FRAC_PI_2).max(MIN_LIGHT_ELEVATION);
+                    for mut transform in light_query.iter_mut() {
+                        let (azimuth, _, distance) = light_orbit(&transform);
+                        transform.translation = orbit_position(azimuth, elevation, distance);
+                    }
+                }
+                ControlParameter::ShadowSoftness => shadows.softness = value,
+                ControlParameter::AmbientOcclusionIntensity => ambient_occlusion.intensity = value,
+                ControlParameter::BlendFactor => blend_factor = Some(value.max(0.)),
+            }
+        }
+    }
+    if let Some(factor) = blend_factor {
+        blend.set_if_neq(SdfBlend { factor });
+    }
+}
+
+// Azimuth, elevation and distance of the light around the origin
+fn light_orbit(transform: &Transform) -> (f32, f32, f32) {
+    let position = transform.translation;
+    let distance = position.length().max(f32::EPSILON);
+    let azimuth = position.z.atan2(position.x);
+    let elevation = (position.y / distance).clamp(-1., 1.).asin();
+    (azimuth, elevation, distance)
+}
+
+fn orbit_position(azimuth: f32, elevation: f32, distance: f32) -> Vec3 {
+    let horizontal = elevation.cos() * distance;
+    Vec3::new(
+        azimuth.cos() * horizontal,
+        elevation.sin() * distance,
+        azimuth.sin() * horizontal,
+    )
+}
+
+// Collects the messages of a packet, descending into bundles. Malformed
+// parts are skipped.
+fn parse_packet(packet: &[u8], messages: &mut Vec<OscMessage>) {
+    if let Some(mut elements) = packet.strip_prefix(b"#bundle\0") {
+        // Skip the time tag; messages apply as soon as they arrive
+        elements = elements.get(8..).unwrap_or_default();
+        while let Some(size) = elements.get(..4) {
+            let size = i32::from_be_bytes(size.try_into().unwrap()).max(0) as usize;
+            let Some(element) = elements.get(4..4 + size) else {
+                return;
+            };
+            parse_packet(element, messages);
+            elements = &elements[4 + size..];
+        }
+    } else if let Some(message) = parse_message(packet) {
+        messages.push(message);
+    }
+}
+
+fn parse_message(packet: &[u8]) -> Option<OscMessage> {
+    let (address, rest) = read_string(packet)?;
+    let (type_tags, mut arguments) = read_string(rest)?;
+    for tag in type_tags.strip_prefix(',')?.chars() {
+        let value = match tag {
+            'f' => f32::from_be_bytes(arguments.get(..4)?.try_into().ok()?),
+            'i' => i32::from_be_bytes(arguments.get(..4)?.try_into().ok()?) as f32,
+            'd' => f64::from_be_bytes(arguments.get(..8)?.try_into().ok()?) as f32,
+            'T' => 1.,
+            'F' => 0.,
+            // Other arguments are skipped when they come before the value
+            's' => {
+                arguments = read_string(arguments)?.1;
+                continue;
+            }
+            'h' | 't' => {
+                arguments = arguments.get(8..)?;
+                continue;
+            }
+            'N' | 'I' => continue,
+            _ => return None,
+        };
+        return Some(OscMessage {
+            address: address.to_string(),
+            value,
+        });
+    }
+    None
+}
+
+// Reads a null terminated string padded to four bytes
+fn read_string(bytes: &[u8]) -> Option<(&str, &[u8])> {
+    let end = bytes.iter().position(|&byte| byte == 0)?;
+    let string = std::str::from_utf8(&bytes[..end]).ok()?;
+    let padded = (end + 4) & !3;
+    Some((string, bytes.get(padded..)?))
+}
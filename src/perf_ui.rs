@@ -0,0 +1,220 @@
+//! SDF entries for the perf UI
+//!
+//! Shows the scene statistics `sdf_render` and `sdf_compute` record as
+//! diagnostics next to the default frame time and system entries: how many
+//! entities and BVH nodes the scene has, how long BVH rebuilds take, how much
+//! entity data is uploaded each frame, the coarse prepass resolution and how
+//! many compute evaluations are waiting for results. The GPU time of the
+//! coarse prepass, the main SDF pass and the compute dispatch comes from the
+//! timestamp spans the render diagnostics resolve, where the adapter supports
+//! timestamp queries.
+
+use std::marker::PhantomData;
+
+use bevy::{
+    diagnostic::{DiagnosticPath, DiagnosticsStore},
+    ecs::system::{lifetimeless::SRes, SystemParam},
+    prelude::*,
+};
+use iyes_perf_ui::{entry::PerfUiEntry, prelude::*};
+
+use crate::sdf_compute::{SDF_COMPUTE_PASS_GPU_TIME, SDF_COMPUTE_QUEUE_DEPTH};
+use crate::sdf_render::{
+    SDF_BVH_NODE_COUNT, SDF_BVH_REBUILD_TIME, SDF_COARSE_PASS_HEIGHT, SDF_COARSE_PASS_WIDTH,
+    SDF_COARSE_PREPASS_GPU_TIME, SDF_ENTITY_COUNT, SDF_ENTITY_UPLOAD_BYTES,
+    SDF_RENDER_PASS_GPU_TIME,
+};
+
+// Sorted after the default entries, in the order they are declared here
+const SORT_KEY_BASE: i32 = 1000;
+
+pub struct SdfPerfUiPlugin;
+
+impl Plugin for SdfPerfUiPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_perf_ui_simple_entry::<PerfUiEntrySdfStat<EntityCount>>()
+            .add_perf_ui_simple_entry::<PerfUiEntrySdfStat<BvhNodeCount>>()
+            .add_perf_ui_simple_entry::<PerfUiEntrySdfStat<BvhRebuildTime>>()
+            .add_perf_ui_simple_entry::<PerfUiEntrySdfStat<EntityUploadBytes>>()
+            .add_perf_ui_simple_entry::<PerfUiEntryCoarseResolution>()
+            .add_perf_ui_simple_entry::<PerfUiEntrySdfStat<ComputeQueueDepth>>()
+            .add_perf_ui_simple_entry::<PerfUiEntrySdfStat<CoarsePrepassGpuTime>>()
+            .add_perf_ui_simple_entry::<PerfUiEntrySdfStat<RenderPassGpuTime>>()
+            .add_perf_ui_simple_entry::<PerfUiEntrySdfStat<ComputePassGpuTime>>();
+    }
+}
+
+// Every SDF entry, to spawn on a perf UI root next to PerfUiDefaultEntries
+#[derive(Bundle, Default)]
+pub struct SdfPerfUiEntries {
+    entity_count: PerfUiEntrySdfStat<EntityCount>,
+    bvh_node_count: PerfUiEntrySdfStat<BvhNodeCount>,
+    bvh_rebuild_time: PerfUiEntrySdfStat<BvhRebuildTime>,
+    entity_upload_bytes: PerfUiEntrySdfStat<EntityUploadBytes>,
+    coarse_resolution: PerfUiEntryCoarseResolution,
+    compute_queue_depth: PerfUiEntrySdfStat<ComputeQueueDepth>,
+    coarse_prepass_gpu_time: PerfUiEntrySdfStat<CoarsePrepassGpuTime>,
+    render_pass_gpu_time: PerfUiEntrySdfStat<RenderPassGpuTime>,
+    compute_pass_gpu_time: PerfUiEntrySdfStat<ComputePassGpuTime>,
+}
+
+// A single valued diagnostic shown as one perf UI entry
+pub trait SdfStat: Send + Sync + 'static {
+    const LABEL: &'static str;
+    const PATH: DiagnosticPath;
+    const SUFFIX: &'static str;
+    const PRECISION: usize;
+    const SORT_KEY: i32;
+    // Shows the smoothed value instead of the latest, for noisy timings
+    const SMOOTHED: bool = false;
+}
+
+pub struct EntityCount;
+pub struct BvhNodeCount;
+pub struct BvhRebuildTime;
+pub struct EntityUploadBytes;
+pub struct ComputeQueueDepth;
+pub struct CoarsePrepassGpuTime;
+pub struct RenderPassGpuTime;
+pub struct ComputePassGpuTime;
+
+impl SdfStat for EntityCount {
+    const LABEL: &'static str = "SDF Entities";
+    const PATH: DiagnosticPath = SDF_ENTITY_COUNT;
+    const SUFFIX: &'static str = "";
+    const PRECISION: usize = 0;
+    const SORT_KEY: i32 = SORT_KEY_BASE;
+}
+
+impl SdfStat for BvhNodeCount {
+    const LABEL: &'static str = "BVH Nodes";
+    const PATH: DiagnosticPath = SDF_BVH_NODE_COUNT;
+    const SUFFIX: &'static str = "";
+    const PRECISION: usize = 0;
+    const SORT_KEY: i32 = SORT_KEY_BASE + 1;
+}
+
+impl SdfStat for BvhRebuildTime {
+    const LABEL: &'static str = "BVH Rebuild";
+    const PATH: DiagnosticPath = SDF_BVH_REBUILD_TIME;
+    const SUFFIX: &'static str = " ms";
+    const PRECISION: usize = 2;
+    const SORT_KEY: i32 = SORT_KEY_BASE + 2;
+}
+
+impl SdfStat for EntityUploadBytes {
+    const LABEL: &'static str = "Entity Upload";
+    const PATH: DiagnosticPath = SDF_ENTITY_UPLOAD_BYTES;
+    const SUFFIX: &'static str = " B/frame";
+    const PRECISION: usize = 0;
+    const SORT_KEY: i32 = SORT_KEY_BASE + 3;
+}
+
+impl SdfStat for ComputeQueueDepth {
+    const LABEL: &'static str = "Compute Queue";
+    const PATH: DiagnosticPath = SDF_COMPUTE_QUEUE_DEPTH;
+    const SUFFIX: &'static str = "";
+    const PRECISION: usize = 0;
+    const SORT_KEY: i32 = SORT_KEY_BASE + 5;
+}
+
+impl SdfStat for CoarsePrepassGpuTime {
+    const LABEL: &'static str = "GPU Coarse Pass";
+    const PATH: DiagnosticPath = SDF_COARSE_PREPASS_GPU_TIME;
+    const SUFFIX: &'static str = " ms";
+    const PRECISION: usize = 2;
+    const SORT_KEY: i32 = SORT_KEY_BASE + 6;
+    const SMOOTHED: bool = true;
+}
+
+impl SdfStat for RenderPassGpuTime {
+    const LABEL: &'static str = "GPU SDF Pass";
+    const PATH: DiagnosticPath = SDF_RENDER_PASS_GPU_TIME;
+    const SUFFIX: &'static str = " ms";
+    const PRECISION: usize = 2;
+    const SORT_KEY: i32 = SORT_KEY_BASE + 7;
+    const SMOOTHED: bool = true;
+}
+
+impl SdfStat for ComputePassGpuTime {
+    const LABEL: &'static str = "GPU Compute Pass";
+    const PATH: DiagnosticPath = SDF_COMPUTE_PASS_GPU_TIME;
+    const SUFFIX: &'static str = " ms";
+    const PRECISION: usize = 2;
+    const SORT_KEY: i32 = SORT_KEY_BASE + 8;
+    const SMOOTHED: bool = true;
+}
+
+#[derive(Component)]
+#[require(PerfUiRoot)]
+pub struct PerfUiEntrySdfStat<S: SdfStat> {
+    marker: PhantomData<S>,
+}
+
+impl<S: SdfStat> Default for PerfUiEntrySdfStat<S> {
+    fn default() -> Self {
+        Self {
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<S: SdfStat> PerfUiEntry for PerfUiEntrySdfStat<S> {
+    type Value = f64;
+    type SystemParam = SRes<DiagnosticsStore>;
+
+    fn label(&self) -> &str {
+        S::LABEL
+    }
+
+    fn sort_key(&self) -> i32 {
+        S::SORT_KEY
+    }
+
+    fn format_value(&self, value: &Self::Value) -> String {
+        format!("{:.*}{}", S::PRECISION, value, S::SUFFIX)
+    }
+
+    fn update_value(
+        &self,
+        diagnostics: &mut <Self::SystemParam as SystemParam>::Item<'_, '_>,
+    ) -> Option<Self::Value> {
+        let diagnostic = diagnostics.get(&S::PATH)?;
+        if S::SMOOTHED {
+            diagnostic.smoothed()
+        } else {
+            diagnostic.value()
+        }
+    }
+}
+
+// Width and height of the coarse prepass texture, which are two diagnostics
+#[derive(Component, Default)]
+#[require(PerfUiRoot)]
+pub struct PerfUiEntryCoarseResolution;
+
+impl PerfUiEntry for PerfUiEntryCoarseResolution {
+    type Value = UVec2;
+    type SystemParam = SRes<DiagnosticsStore>;
+
+    fn label(&self) -> &str {
+        "Coarse Pass"
+    }
+
+    fn sort_key(&self) -> i32 {
+        SORT_KEY_BASE + 4
+    }
+
+    fn format_value(&self, value: &Self::Value) -> String {
+        format!("{}x{}", value.x, value.y)
+    }
+
+    fn update_value(
+        &self,
+        diagnostics: &mut <Self::SystemParam as SystemParam>::Item<'_, '_>,
+    ) -> Option<Self::Value> {
+        let width = diagnostics.get(&SDF_COARSE_PASS_WIDTH)?.value()?;
+        let height = diagnostics.get(&SDF_COARSE_PASS_HEIGHT)?.value()?;
+        Some(UVec2::new(width as u32, height as u32))
+    }
+}
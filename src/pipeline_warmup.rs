@@ -0,0 +1,161 @@
+//! Startup warm-up for the SDF pipelines
+//!
+//! All SDF pipelines (main pass, coarse prepass, upscale and compute) are queued when
+//! the render plugins finish. Compiling them can take a while, especially on
+//! wasm, so a loading indicator is shown until every pipeline is ready and
+//! interaction that depends on them (like the brush) waits for the warm-up.
+//! When a pipeline fails to compile the warm-up latches the failure, the
+//! indicator says so and callers can check `has_failed` to give up instead of
+//! waiting forever.
+//!
+//! Compiled pipelines aren't persisted between launches: Bevy's pipeline cache
+//! creates every pipeline without a wgpu `PipelineCache`, so there is no cache
+//! data to save or seed.
+
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+use bevy::{
+    prelude::*,
+    render::{
+        render_resource::{CachedPipelineState, PipelineCache},
+        Render, RenderApp, RenderSet,
+    },
+};
+
+use crate::localization::LocalizedText;
+use crate::adaptive_resolution::SdfUpscalePipeline;
+use crate::sdf_compute::SdfComputePipeline;
+use crate::sdf_render::{SDFCoarsePrepassPipeline, SDFRenderPipeline};
+
+pub struct PipelineWarmupPlugin;
+
+// Shared between the main and render world, set once all pipelines compiled
+// or one of them failed to
+#[derive(Resource, Clone, Default)]
+pub struct PipelineWarmupState {
+    ready: Arc<AtomicBool>,
+    failed: Arc<AtomicBool>,
+}
+
+impl PipelineWarmupState {
+    pub fn is_ready(&self) -> bool {
+        self.ready.load(Ordering::Relaxed)
+    }
+
+    // Whether a pipeline failed to compile, in which case the warm-up never
+    // becomes ready
+    pub fn has_failed(&self) -> bool {
+        self.failed.load(Ordering::Relaxed)
+    }
+}
+
+#[derive(Component)]
+struct LoadingIndicator;
+
+impl Plugin for PipelineWarmupPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<PipelineWarmupState>()
+            .add_systems(Startup, spawn_loading_indicator)
+            .add_systems(Update, hide_loading_indicator);
+    }
+
+    fn finish(&self, app: &mut App) {
+        let state = app.world().resource::<PipelineWarmupState>().clone();
+
+        let Some(render_app) = app.get_sub_app_mut(RenderApp) else {
+            return;
+        };
+
+        render_app
+            .insert_resource(state)
+            .add_systems(Render, check_pipelines_ready.in_set(RenderSet::Cleanup));
+    }
+}
+
+fn spawn_loading_indicator(mut commands: Commands) {
+    commands.spawn((
+        Text::default(),
+        LocalizedText("loading.shaders"),
+        Node {
+            position_type: PositionType::Absolute,
+            bottom: Val::Px(12.),
+            right: Val::Px(12.),
+            ..default()
+        },
+        LoadingIndicator,
+    ));
+}
+
+fn hide_loading_indicator(
+    state: Res<PipelineWarmupState>,
+    mut indicator_query: Query<(Entity, &mut LocalizedText), With<LoadingIndicator>>,
+    mut commands: Commands,
+) {
+    if state.has_failed() {
+        for (_, mut text) in indicator_query.iter_mut() {
+            if text.0 != "loading.failed" {
+                text.0 = "loading.failed";
+            }
+        }
+        return;
+    }
+    if !state.is_ready() {
+        return;
+    }
+
+    for (entity, _) in indicator_query.iter() {
+        info!("SDF pipelines ready");
+        commands.entity(entity).despawn();
+    }
+}
+
+// Runs in the render world once per frame until every pipeline has compiled or
+// one failed to
+fn check_pipelines_ready(
+    state: Res<PipelineWarmupState>,
+    pipeline_cache: Res<PipelineCache>,
+    render_pipeline: Option<Res<SDFRenderPipeline>>,
+    coarse_pipeline: Option<Res<SDFCoarsePrepassPipeline>>,
+    compute_pipeline: Option<Res<SdfComputePipeline>>,
+    upscale_pipeline: Option<Res<SdfUpscalePipeline>>,
+) {
+    if state.is_ready() || state.has_failed() {
+        return;
+    }
+
+    let (
+        Some(render_pipeline),
+        Some(coarse_pipeline),
+        Some(compute_pipeline),
+        Some(upscale_pipeline),
+    ) = (render_pipeline, coarse_pipeline, compute_pipeline, upscale_pipeline)
+    else {
+        return;
+    };
+
+    let states = [
+        pipeline_cache.get_render_pipeline_state(render_pipeline.pipeline_id),
+        pipeline_cache.get_render_pipeline_state(coarse_pipeline.pipeline_id),
+        pipeline_cache.get_render_pipeline_state(render_pipeline.scaled_pipeline_id),
+        pipeline_cache.get_render_pipeline_state(render_pipeline.temporal_pipeline_id),
+        pipeline_cache.get_render_pipeline_state(upscale_pipeline.pipeline_id),
+        pipeline_cache.get_compute_pipeline_state(compute_pipeline.pipeline),
+    ];
+
+    for pipeline_state in states {
+        match pipeline_state {
+            CachedPipelineState::Ok(_) => {}
+            CachedPipelineState::Err(err) => {
+                error!("SDF pipeline failed to compile: {:?}", err);
+                state.failed.store(true, Ordering::Relaxed);
+                return;
+            }
+            _ => return,
+        }
+    }
+
+    state.ready.store(true, Ordering::Relaxed);
+}
@@ -0,0 +1,35 @@
+use bevy::prelude::*;
+
+use crate::sdf_render::SDFRenderEntity;
+
+// Point the gizmo sits at and scaling happens around, in the entity's local
+// space so it stays attached to the entity when it is scaled. Entities without
+// one pivot around their origin.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Default)]
+pub struct PivotOffset(pub Vec3);
+
+// World position of an entity's pivot
+pub fn pivot_world(transform: &GlobalTransform, pivot: Option<&PivotOffset>) -> Vec3 {
+    transform.transform_point(pivot.map_or(Vec3::ZERO, |p| p.0))
+}
+
+// Local offset that puts the pivot of an entity at `point`
+pub fn pivot_offset_at(transform: &Transform, point: Vec3) -> PivotOffset {
+    PivotOffset(transform.compute_affine().inverse().transform_point3(point))
+}
+
+// Min and max corner of the bounding box of the given SDF entities
+pub fn bounds<'a>(entities: impl IntoIterator<Item = &'a SDFRenderEntity>) -> Option<(Vec3, Vec3)> {
+    entities
+        .into_iter()
+        .map(|e| {
+            let half_size = e.primitive.half_extents(e.scale);
+            (e.position - half_size, e.position + half_size)
+        })
+        .reduce(|(min_a, max_a), (min_b, max_b)| (min_a.min(min_b), max_a.max(max_b)))
+}
+
+// Center of the bounding box of the given SDF entities
+pub fn bounds_center<'a>(entities: impl IntoIterator<Item = &'a SDFRenderEntity>) -> Option<Vec3> {
+    bounds(entities).map(|(min, max)| (min + max) * 0.5)
+}
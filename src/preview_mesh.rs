@@ -0,0 +1,255 @@
+//! Low resolution mesh of the scene, kept up to date in the background
+//!
+//! Space is split into world aligned chunks of `CHUNK_CELLS` cells on a side.
+//! Whenever the entity data changes, only the chunks overlapping the old or
+//! new bounds of the entities that changed are sampled and polygonized again,
+//! a handful of chunks per compute round trip so the rest of the app keeps
+//! its evaluations flowing. Shown instead of the ray-marched SDF, the mesh is
+//! far cheaper to draw, which helps on weak GPUs. The proxy meshes stay for
+//! picking; they sit inside the surface. F6 toggles the preview.
+
+use std::collections::{HashMap, HashSet};
+
+use bevy::{
+    prelude::*,
+    tasks::{block_on, futures_lite::future, AsyncComputeTaskPool, Task},
+};
+use bvh::aabb::Bounded;
+use futures::future::join_all;
+
+use crate::meshing::{polygonize, sample_grid_async, SampleGrid, SdfMesh};
+use crate::pipeline_warmup::PipelineWarmupState;
+use crate::sdf_compute::SdfEvaluationSender;
+use crate::sdf_render::{EntityData, GpuSdfEntity, SDFRenderEnabled};
+use crate::settings::KeyBindings;
+
+// Cells along each side of a chunk
+const CHUNK_CELLS: u32 = 16;
+// Chunks remeshed per round trip
+const CHUNKS_PER_ROUND: usize = 16;
+
+pub struct PreviewMeshPlugin;
+
+impl Plugin for PreviewMeshPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SdfPreviewMesh>()
+            .init_resource::<PreviewChunks>()
+            .add_systems(
+                Update,
+                (toggle_preview_mesh, mark_dirty_chunks, remesh_dirty_chunks).chain(),
+            );
+    }
+}
+
+#[derive(Resource, Clone)]
+pub struct SdfPreviewMesh {
+    // Shows the mesh instead of the ray-marched SDF
+    pub enabled: bool,
+    // Edge length of a grid cell in world units
+    pub cell_size: f32,
+}
+
+impl Default for SdfPreviewMesh {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            cell_size: 0.1,
+        }
+    }
+}
+
+// Marks the entities holding the mesh of a chunk
+#[derive(Component)]
+struct PreviewChunk;
+
+#[derive(Resource, Default)]
+struct PreviewChunks {
+    chunks: HashMap<IVec3, Entity>,
+    dirty: HashSet<IVec3>,
+    // The entity data the chunks are being brought up to date with
+    meshed_entities: Vec<GpuSdfEntity>,
+    // Remeshed chunks, None where the evaluation didn't come back
+    task: Option<Task<Vec<(IVec3, Option<SdfMesh>)>>>,
+    material: Option<Handle<StandardMaterial>>,
+}
+
+impl PreviewChunks {
+    fn clear(&mut self, commands: &mut Commands) {
+        for (_, chunk) in self.chunks.drain() {
+            commands.entity(chunk).despawn();
+        }
+        self.dirty.clear();
+        self.meshed_entities.clear();
+        self.task = None;
+    }
+
+    // Chunks overlapping the entity's bounds, which include its blend radius
+    fn mark_dirty(&mut self, entity: &GpuSdfEntity, chunk_size: f32) {
+        let aabb = entity.aabb();
+        let min = (Vec3::new(aabb.min.x, aabb.min.y, aabb.min.z) / chunk_size)
+            .floor()
+            .as_ivec3();
+        let max = (Vec3::new(aabb.max.x, aabb.max.y, aabb.max.z) / chunk_size)
+            .floor()
+            .as_ivec3();
+        for z in min.z..=max.z {
+            for y in min.y..=max.y {
+                for x in min.x..=max.x {
+                    self.dirty.insert(IVec3::new(x, y, z));
+                }
+            }
+        }
+    }
+}
+
+// Selection flags change the entity data without changing the surface
+fn same_surface(a: &GpuSdfEntity, b: &GpuSdfEntity) -> bool {
+    a.position_scale == b.position_scale
+        && a.shape == b.shape
+        && a.modifiers == b.modifiers
+        && a.operation == b.operation
+        && a.primitive == b.primitive
+}
+
+fn toggle_preview_mesh(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    keys: Res<KeyBindings>,
+    mut preview: ResMut<SdfPreviewMesh>,
+    mut sdf_render_enabled: ResMut<SDFRenderEnabled>,
+) {
+    if keyboard_input.just_pressed(keys.toggle_preview_mesh) {
+        preview.enabled = !preview.enabled;
+        info!("Preview mesh toggled: {}", preview.enabled);
+    }
+    if preview.is_changed() && !preview.is_added() {
+        sdf_render_enabled.enabled = !preview.enabled;
+    }
+}
+
+fn mark_dirty_chunks(
+    mut commands: Commands,
+    preview: Res<SdfPreviewMesh>,
+    entity_data: Option<Res<EntityData>>,
+    mut chunks: ResMut<PreviewChunks>,
+) {
+    if !preview.enabled {
+        if !chunks.chunks.is_empty() || chunks.task.is_some() {
+            chunks.clear(&mut commands);
+        }
+        return;
+    }
+    // Enabled or resized, every chunk starts over
+    if preview.is_changed() {
+        chunks.clear(&mut commands);
+    }
+
+    let Some(entity_data) = entity_data else {
+        return;
+    };
+    if !(entity_data.is_changed() || preview.is_changed()) {
+        return;
+    }
+
+    let chunk_size = preview.cell_size.max(0.001) * CHUNK_CELLS as f32;
+    let entities = entity_data.entities();
+    let previous = std::mem::take(&mut chunks.meshed_entities);
+    for index in 0..entities.len().max(previous.len()) {
+        match (previous.get(index), entities.get(index)) {
+            (Some(old), Some(new)) if same_surface(old, new) => {}
+            (old, new) => {
+                for entity in old.into_iter().chain(new) {
+                    chunks.mark_dirty(entity, chunk_size);
+                }
+            }
+        }
+    }
+    chunks.meshed_entities = entities.to_vec();
+}
+
+fn remesh_dirty_chunks(
+    mut commands: Commands,
+    preview: Res<SdfPreviewMesh>,
+    warmup: Res<PipelineWarmupState>,
+    sdf_sender: Res<SdfEvaluationSender>,
+    mut chunks: ResMut<PreviewChunks>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    if let Some(task) = &mut chunks.task {
+        let Some(remeshed) = block_on(future::poll_once(task)) else {
+            return;
+        };
+        chunks.task = None;
+
+        let material = chunks
+            .material
+            .get_or_insert_with(|| {
+                materials.add(StandardMaterial {
+                    base_color: Color::srgb(0.8, 0.8, 0.8),
+                    perceptual_roughness: 0.8,
+                    ..default()
+                })
+            })
+            .clone();
+        for (key, mesh) in remeshed {
+            let Some(mesh) = mesh else {
+                chunks.dirty.insert(key);
+                continue;
+            };
+            let mesh = Some(mesh).filter(|mesh| !mesh.triangles.is_empty());
+            match (chunks.chunks.get(&key).copied(), mesh) {
+                (Some(chunk), Some(mesh)) => {
+                    commands
+                        .entity(chunk)
+                        .insert(Mesh3d(meshes.add(mesh.to_render_mesh())));
+                }
+                (Some(chunk), None) => {
+                    commands.entity(chunk).despawn();
+                    chunks.chunks.remove(&key);
+                }
+                (None, Some(mesh)) => {
+                    let chunk = commands
+                        .spawn((
+                            PreviewChunk,
+                            Mesh3d(meshes.add(mesh.to_render_mesh())),
+                            MeshMaterial3d(material.clone()),
+                            Transform::default(),
+                            // Clicks go through to the proxy meshes of the entities
+                            Pickable::IGNORE,
+                        ))
+                        .id();
+                    chunks.chunks.insert(key, chunk);
+                }
+                (None, None) => {}
+            }
+        }
+    }
+
+    if chunks.dirty.is_empty() || !warmup.is_ready() {
+        return;
+    }
+
+    let cell_size = preview.cell_size.max(0.001);
+    let keys: Vec<IVec3> = chunks.dirty.iter().take(CHUNKS_PER_ROUND).copied().collect();
+    for key in &keys {
+        chunks.dirty.remove(key);
+    }
+    let sender = sdf_sender.clone();
+    chunks.task = Some(AsyncComputeTaskPool::get().spawn(async move {
+        // Requested together, so they share a dispatch
+        join_all(keys.into_iter().map(|key| {
+            let sender = sender.clone();
+            async move {
+                // One extra point per axis so neighbouring chunks meet
+                let grid = SampleGrid {
+                    origin: key.as_vec3() * cell_size * CHUNK_CELLS as f32,
+                    cell_size,
+                    dims: UVec3::splat(CHUNK_CELLS + 1),
+                };
+                let distances = sample_grid_async(&grid, &sender, None).await.ok();
+                (key, distances.map(|distances| polygonize(&grid, &distances)))
+            }
+        }))
+        .await
+    }));
+}
@@ -0,0 +1,106 @@
+//! Progress reports of long operations
+//!
+//! Mesh exports, BVH rebuilds, turntable captures and volume estimates write
+//! `AppEvent`s while they run and when they finish. Other systems can read them like any Bevy
+//! event, and the command bridge forwards them to JavaScript as
+//! `exportProgress` and `exportComplete`, so the host page can show progress
+//! bars. Work running on other threads reports through a `SharedProgress`,
+//! which the system polling the task turns into events.
+
+use std::sync::{
+    atomic::{AtomicU32, Ordering},
+    Arc,
+};
+
+use bevy::prelude::*;
+use serde::Serialize;
+
+pub struct ProgressPlugin;
+
+impl Plugin for ProgressPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<AppEvent>();
+    }
+}
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum Operation {
+    MeshExport,
+    BvhRebuild,
+    TurntableCapture,
+    VolumeEstimate,
+}
+
+#[derive(Serialize, Clone, Copy, Debug)]
+pub struct ProgressUpdate {
+    pub operation: Operation,
+    // Fraction done, from 0 to 1
+    pub progress: f32,
+}
+
+#[derive(Serialize, Clone, Copy, Debug)]
+pub struct OperationComplete {
+    pub operation: Operation,
+    // False when the operation failed or had nothing to do
+    pub success: bool,
+}
+
+#[derive(Event, Clone, Copy, Debug)]
+pub enum AppEvent {
+    Progress(ProgressUpdate),
+    Complete(OperationComplete),
+}
+
+impl AppEvent {
+    pub fn progress(operation: Operation, progress: f32) -> Self {
+        AppEvent::Progress(ProgressUpdate {
+            operation,
+            progress: progress.clamp(0., 1.),
+        })
+    }
+
+    pub fn complete(operation: Operation, success: bool) -> Self {
+        AppEvent::Complete(OperationComplete { operation, success })
+    }
+}
+
+// Fraction done of work on another thread. A span reports into part of the
+// range of the progress it was made from, for work done in phases.
+#[derive(Clone, Debug)]
+pub struct SharedProgress {
+    // Thousandths, so the value fits an atomic
+    done: Arc<AtomicU32>,
+    start: f32,
+    end: f32,
+}
+
+impl Default for SharedProgress {
+    fn default() -> Self {
+        Self {
+            done: Arc::default(),
+            start: 0.,
+            end: 1.,
+        }
+    }
+}
+
+impl SharedProgress {
+    pub fn span(&self, start: f32, end: f32) -> Self {
+        let length = self.end - self.start;
+        Self {
+            done: self.done.clone(),
+            start: self.start + length * start,
+            end: self.start + length * end,
+        }
+    }
+
+    pub fn set(&self, fraction: f32) {
+        let value = self.start + (self.end - self.start) * fraction.clamp(0., 1.);
+        self.done.store((value * 1000.).round() as u32, Ordering::Relaxed);
+    }
+
+    pub fn get(&self) -> f32 {
+        self.done.load(Ordering::Relaxed) as f32 / 1000.
+    }
+}
@@ -0,0 +1,553 @@
+//! Project files: the scene document, a thumbnail and metadata in one zip
+//!
+//! A project is a zip archive with three entries: `scene.ron`, the document
+//! `scene_io` writes, `thumbnail.png`, a small render of the viewport taken
+//! when saving, and `project.json`, the author, creation and modification
+//! times and the version of the app that saved it. Entries are written without
+//! compression, which keeps the writer small; the PNG is compressed already
+//! and documents are tiny. Deflated entries are read too, so archives repacked
+//! by other zip tools still open.
+//!
+//! Projects saved or opened are remembered for the recent files list. Their
+//! paths are kept with the user settings, and natively the metadata and
+//! thumbnails are read back from the archives at startup. On the web the page
+//! keeps the archives, so restored entries only have their path until they are
+//! opened again.
+
+use std::io::Cursor;
+
+use bevy::{
+    prelude::*,
+    render::view::screenshot::{Screenshot, ScreenshotCaptured},
+};
+use serde::{Deserialize, Serialize};
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen::prelude::*;
+
+use crate::meshing::base64;
+use crate::scene_io::{write_scene, LoadScene, SceneCapture, SceneFormat};
+
+pub const PROJECT_FORMAT_VERSION: u32 = 1;
+// Longest side of the thumbnail in pixels
+const THUMBNAIL_SIZE: u32 = 256;
+const MAX_RECENT_PROJECTS: usize = 10;
+
+const SCENE_ENTRY: &str = "scene.ron";
+const THUMBNAIL_ENTRY: &str = "thumbnail.png";
+const METADATA_ENTRY: &str = "project.json";
+
+pub struct ProjectFilePlugin;
+
+impl Plugin for ProjectFilePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<RecentProjects>()
+            .add_event::<SaveProject>()
+            .add_event::<OpenProject>()
+            .add_event::<ProjectSaved>()
+            .add_event::<ListRecentProjects>()
+            .add_event::<RecentProjectsListed>()
+            .add_systems(Update, (save_projects, open_projects, list_recent_projects));
+    }
+}
+
+// Saves the scene with a thumbnail of the next rendered frame
+#[derive(Event)]
+pub struct SaveProject {
+    pub path: String,
+    pub author: Option<String>,
+}
+
+// Replaces the scene with the one in a project archive
+#[derive(Event)]
+pub struct OpenProject {
+    pub path: String,
+    pub bytes: Vec<u8>,
+}
+
+#[derive(Event)]
+pub struct ProjectSaved {
+    pub path: String,
+    pub bytes: Vec<u8>,
+}
+
+#[derive(Event)]
+pub struct ListRecentProjects;
+
+#[derive(Event)]
+pub struct RecentProjectsListed(pub Vec<RecentProjectSummary>);
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ProjectMetadata {
+    pub format_version: u32,
+    pub app_version: String,
+    #[serde(default)]
+    pub author: Option<String>,
+    // Milliseconds since the Unix epoch, like JavaScript dates
+    pub created: f64,
+    pub modified: f64,
+    #[serde(default)]
+    pub entity_count: usize,
+}
+
+pub struct Project {
+    pub metadata: ProjectMetadata,
+    pub scene: String,
+    // PNG, empty when no thumbnail could be captured
+    pub thumbnail: Vec<u8>,
+}
+
+impl Project {
+    pub fn to_zip(&self) -> Result<Vec<u8>, String> {
+        let metadata = serde_json::to_string_pretty(&self.metadata).map_err(|e| e.to_string())?;
+        let mut entries = vec![
+            (METADATA_ENTRY, metadata.as_bytes()),
+            (SCENE_ENTRY, self.scene.as_bytes()),
+        ];
+        if !self.thumbnail.is_empty() {
+            entries.push((THUMBNAIL_ENTRY, self.thumbnail.as_slice()));
+        }
+        Ok(write_zip(&entries))
+    }
+
+    pub fn from_zip(bytes: &[u8]) -> Result<Self, String> {
+        let entries = read_zip(bytes)?;
+        let entry = |name: &str| {
+            entries
+                .iter()
+                .find(|(entry, _)| entry == name)
+                .map(|(_, data)| data.clone())
+        };
+
+        let metadata = entry(METADATA_ENTRY).ok_or("the project has no metadata")?;
+        let metadata: ProjectMetadata =
+            serde_json::from_slice(&metadata).map_err(|err| err.to_string())?;
+        if metadata.format_version > PROJECT_FORMAT_VERSION {
+            return Err(format!(
+                "project format version {} is newer than the supported version {}",
+                metadata.format_version, PROJECT_FORMAT_VERSION
+            ));
+        }
+        let scene = entry(SCENE_ENTRY).ok_or("the project has no scene")?;
+        Ok(Self {
+            metadata,
+            scene: String::from_utf8(scene).map_err(|err| err.to_string())?,
+            thumbnail: entry(THUMBNAIL_ENTRY).unwrap_or_default(),
+        })
+    }
+}
+
+pub struct RecentProject {
+    pub path: String,
+    // None for entries restored on the web, whose archive can't be read back
+    pub metadata: Option<ProjectMetadata>,
+    pub thumbnail: Vec<u8>,
+}
+
+// An entry of the recent files list as JavaScript gets it
+#[derive(Serialize, Clone, Debug)]
+pub struct RecentProjectSummary {
+    pub path: String,
+    #[serde(flatten)]
+    pub metadata: Option<ProjectMetadata>,
+    // data: URL of the PNG, None without a thumbnail
+    pub thumbnail: Option<String>,
+}
+
+// Most recently saved or opened first
+#[derive(Resource, Default)]
+pub struct RecentProjects {
+    pub projects: Vec<RecentProject>,
+}
+
+impl RecentProjects {
+    pub fn get(&self, path: &str) -> Option<&RecentProject> {
+        self.projects.iter().find(|project| project.path == path)
+    }
+
+    pub fn paths(&self) -> Vec<String> {
+        self.projects.iter().map(|project| project.path.clone()).collect()
+    }
+
+    // Rebuilds the list from stored paths, natively dropping archives that no longer open
+    pub fn restore(&mut self, paths: &[String]) {
+        self.projects = paths
+            .iter()
+            .filter_map(|path| read_recent_project(path))
+            .take(MAX_RECENT_PROJECTS)
+            .collect();
+    }
+
+    fn touch(&mut self, path: &str, project: &Project) {
+        self.projects.retain(|recent| recent.path != path);
+        self.projects.insert(
+            0,
+            RecentProject {
+                path: path.to_string(),
+                metadata: Some(project.metadata.clone()),
+                thumbnail: project.thumbnail.clone(),
+            },
+        );
+        self.projects.truncate(MAX_RECENT_PROJECTS);
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn read_recent_project(path: &str) -> Option<RecentProject> {
+    let bytes = std::fs::read(path).ok()?;
+    let project = Project::from_zip(&bytes).ok()?;
+    Some(RecentProject {
+        path: path.to_string(),
+        metadata: Some(project.metadata),
+        thumbnail: project.thumbnail,
+    })
+}
+
+#[cfg(target_arch = "wasm32")]
+fn read_recent_project(path: &str) -> Option<RecentProject> {
+    Some(RecentProject {
+        path: path.to_string(),
+        metadata: None,
+        thumbnail: Vec::new(),
+    })
+}
+
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen]
+extern "C" {
+    #[wasm_bindgen(js_namespace = Date, js_name = now)]
+    fn date_now() -> f64;
+}
+
+pub fn now_millis() -> f64 {
+    #[cfg(target_arch = "wasm32")]
+    {
+        date_now()
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0.0, |elapsed| elapsed.as_secs_f64() * 1000.0)
+    }
+}
+
+// Scales a captured frame down and encodes it as PNG, dropping the alpha channel,
+// which holds brightness rather than coverage with HDR
+fn encode_thumbnail(frame: &Image) -> Result<Vec<u8>, String> {
+    let frame = frame.clone().try_into_dynamic().map_err(|err| err.to_string())?;
+    let thumbnail = frame.thumbnail(THUMBNAIL_SIZE, THUMBNAIL_SIZE).to_rgb8();
+    let mut png = Cursor::new(Vec::new());
+    thumbnail
+        .write_to(&mut png, image::ImageFormat::Png)
+        .map_err(|err| err.to_string())?;
+    Ok(png.into_inner())
+}
+
+fn save_projects(
+    mut commands: Commands,
+    mut save_events: EventReader<SaveProject>,
+    capture: SceneCapture,
+    recent: Res<RecentProjects>,
+) {
+    for SaveProject { path, author } in save_events.read() {
+        let document = capture.document();
+        let scene = match write_scene(&document, SceneFormat::Ron) {
+            Ok(scene) => scene,
+            Err(err) => {
+                warn!("Failed to serialize scene: {}", err);
+                continue;
+            }
+        };
+        let modified = now_millis();
+        let previous = recent.get(path).and_then(|project| project.metadata.as_ref());
+        let mut project = Some(Project {
+            metadata: ProjectMetadata {
+                format_version: PROJECT_FORMAT_VERSION,
+                app_version: env!("CARGO_PKG_VERSION").to_string(),
+                // Saving over a known project keeps its author and creation time
+                author: author
+                    .clone()
+                    .or_else(|| previous.and_then(|metadata| metadata.author.clone())),
+                created: previous.map_or(modified, |metadata| metadata.created),
+                modified,
+                entity_count: document.entities.len(),
+            },
+            scene,
+            thumbnail: Vec::new(),
+        });
+
+        let path = path.clone();
+        commands.spawn(Screenshot::primary_window()).observe(
+            move |trigger: Trigger<ScreenshotCaptured>,
+                  mut saved_events: EventWriter<ProjectSaved>,
+                  mut recent: ResMut<RecentProjects>| {
+                let Some(mut project) = project.take() else {
+                    return;
+                };
+                match encode_thumbnail(trigger.event()) {
+                    Ok(thumbnail) => project.thumbnail = thumbnail,
+                    Err(err) => warn!("Failed to capture a project thumbnail: {}", err),
+                }
+                match project.to_zip() {
+                    Ok(bytes) => {
+                        info!("Saved project {}", path);
+                        recent.touch(&path, &project);
+                        saved_events.write(ProjectSaved {
+                            path: path.clone(),
+                            bytes,
+                        });
+                    }
+                    Err(err) => warn!("Failed to write project {}: {}", path, err),
+                }
+            },
+        );
+    }
+}
+
+fn open_projects(
+    mut open_events: EventReader<OpenProject>,
+    mut load_events: EventWriter<LoadScene>,
+    mut recent: ResMut<RecentProjects>,
+) {
+    for OpenProject { path, bytes } in open_events.read() {
+        match Project::from_zip(bytes) {
+            Ok(project) => {
+                recent.touch(path, &project);
+                load_events.write(LoadScene {
+                    contents: project.scene,
+                });
+            }
+            Err(err) => warn!("Failed to open project {}: {}", path, err),
+        }
+    }
+}
+
+fn list_recent_projects(
+    mut list_events: EventReader<ListRecentProjects>,
+    mut listed_events: EventWriter<RecentProjectsListed>,
+    recent: Res<RecentProjects>,
+) {
+    if list_events.read().last().is_none() {
+        return;
+    }
+    let summaries = recent
+        .projects
+        .iter()
+        .map(|project| RecentProjectSummary {
+            path: project.path.clone(),
+            metadata: project.metadata.clone(),
+            thumbnail: (!project.thumbnail.is_empty())
+                .then(|| format!("data:image/png;base64,{}", base64(&project.thumbnail))),
+        })
+        .collect();
+    listed_events.write(RecentProjectsListed(summaries));
+}
+
+// CRC-32 as zip archives use it (IEEE polynomial, reflected)
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+// Zip archive with every entry stored uncompressed
+fn write_zip(entries: &[(&str, &[u8])]) -> Vec<u8> {
+    let mut archive = Vec::new();
+    let mut directory = Vec::new();
+    for (name, data) in entries {
+        let offset = archive.len() as u32;
+        let crc = crc32(data);
+        // Local header: signature, version needed, flags, method (stored), time and
+        // date (1980-01-01), crc, sizes, name and extra field lengths
+        archive.extend_from_slice(&0x0403_4b50u32.to_le_bytes());
+        archive.extend_from_slice(&[20, 0, 0, 0, 0, 0, 0, 0, 0x21, 0]);
+        archive.extend_from_slice(&crc.to_le_bytes());
+        archive.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        archive.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        archive.extend_from_slice(&(name.len() as u16).to_le_bytes());
+        archive.extend_from_slice(&0u16.to_le_bytes());
+        archive.extend_from_slice(name.as_bytes());
+        archive.extend_from_slice(data);
+
+        // Central directory record: the same fields plus version made by, comment
+        // length, disk number, attributes and the local header offset
+        directory.extend_from_slice(&0x0201_4b50u32.to_le_bytes());
+        directory.extend_from_slice(&[20, 0, 20, 0, 0, 0, 0, 0, 0, 0, 0x21, 0]);
+        directory.extend_from_slice(&crc.to_le_bytes());
+        directory.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        directory.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        directory.extend_from_slice(&(name.len() as u16).to_le_bytes());
+        directory.extend_from_slice(&[0; 12]);
+        directory.extend_from_slice(&offset.to_le_bytes());
+        directory.extend_from_slice(name.as_bytes());
+    }
+
+    let directory_offset = archive.len() as u32;
+    archive.extend_from_slice(&directory);
+    // End of central directory
+    archive.extend_from_slice(&0x0605_4b50u32.to_le_bytes());
+    archive.extend_from_slice(&[0; 4]);
+    archive.extend_from_slice(&(entries.len() as u16).to_le_bytes());
+    archive.extend_from_slice(&(entries.len() as u16).to_le_bytes());
+    archive.extend_from_slice(&(directory.len() as u32).to_le_bytes());
+    archive.extend_from_slice(&directory_offset.to_le_bytes());
+    archive.extend_from_slice(&0u16.to_le_bytes());
+    archive
+}
+
+// Reads the entries of a zip archive, stored or deflated
+fn read_zip(bytes: &[u8]) -> Result<Vec<(String, Vec<u8>)>, String> {
+    // Offsets come from the archive, so they are added without overflowing
+    let field = |at: usize, offset: usize, length: usize| {
+        at.checked_add(offset)
+            .and_then(|start| bytes.get(start..)?.get(..length))
+            .ok_or("the archive is truncated")
+    };
+    let u16_at = |at: usize, offset: usize| {
+        field(at, offset, 2).map(|b| u16::from_le_bytes([b[0], b[1]]) as usize)
+    };
+    let u32_at = |at: usize, offset: usize| {
+        field(at, offset, 4).map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as usize)
+    };
+
+    // The end of central directory record sits before an optional comment
+    let end = (0..bytes.len().saturating_sub(21))
+        .rev()
+        .find(|&at| u32_at(at, 0) == Ok(0x0605_4b50))
+        .ok_or("not a zip archive")?;
+    let count = u16_at(end, 10)?;
+    let mut record = u32_at(end, 16)?;
+
+    let mut entries = Vec::with_capacity(count);
+    for _ in 0..count {
+        if u32_at(record, 0)? != 0x0201_4b50 {
+            return Err("the central directory is corrupt".into());
+        }
+        let method = u16_at(record, 10)?;
+        let crc = u32_at(record, 16)?;
+        let compressed_size = u32_at(record, 20)?;
+        let size = u32_at(record, 24)?;
+        let name_length = u16_at(record, 28)?;
+        let extra_length = u16_at(record, 30)?;
+        let comment_length = u16_at(record, 32)?;
+        let local = u32_at(record, 42)?;
+        let name = field(record, 46, name_length)?;
+        let name = String::from_utf8_lossy(name).into_owned();
+        record += 46 + name_length + extra_length + comment_length;
+
+        let header_length = 30 + u16_at(local, 26)? + u16_at(local, 28)?;
+        let raw = field(local, header_length, compressed_size)?;
+        let data = match method {
+            0 => raw.to_vec(),
+            8 => miniz_oxide::inflate::decompress_to_vec_with_limit(raw, size)
+                .map_err(|err| format!("{} can't be decompressed: {}", name, err))?,
+            _ => return Err(format!("{} uses an unsupported compression method", name)),
+        };
+        if crc32(&data) as usize != crc {
+            return Err(format!("{} is corrupt", name));
+        }
+        entries.push((name, data));
+    }
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn project() -> Project {
+        Project {
+            metadata: ProjectMetadata {
+                format_version: PROJECT_FORMAT_VERSION,
+                app_version: "0.1.0".to_string(),
+                author: Some("Someone".to_string()),
+                created: 1.0,
+                modified: 2.0,
+                entity_count: 3,
+            },
+            scene: "(version: 1)".to_string(),
+            thumbnail: vec![0x89, b'P', b'N', b'G'],
+        }
+    }
+
+    // Single entry archive with the entry deflated, as most zip tools write them
+    fn deflated_zip(name: &str, data: &[u8]) -> Vec<u8> {
+        let compressed = miniz_oxide::deflate::compress_to_vec(data, 6);
+        let sizes = [compressed.len() as u32, data.len() as u32];
+        let mut archive = Vec::new();
+        archive.extend_from_slice(&0x0403_4b50u32.to_le_bytes());
+        archive.extend_from_slice(&[20, 0, 0, 0, 8, 0, 0, 0, 0x21, 0]);
+        archive.extend_from_slice(&crc32(data).to_le_bytes());
+        sizes.iter().for_each(|size| archive.extend_from_slice(&size.to_le_bytes()));
+        archive.extend_from_slice(&(name.len() as u16).to_le_bytes());
+        archive.extend_from_slice(&0u16.to_le_bytes());
+        archive.extend_from_slice(name.as_bytes());
+        archive.extend_from_slice(&compressed);
+
+        let directory_offset = archive.len() as u32;
+        archive.extend_from_slice(&0x0201_4b50u32.to_le_bytes());
+        archive.extend_from_slice(&[20, 0, 20, 0, 0, 0, 8, 0, 0, 0, 0x21, 0]);
+        archive.extend_from_slice(&crc32(data).to_le_bytes());
+        sizes.iter().for_each(|size| archive.extend_from_slice(&size.to_le_bytes()));
+        archive.extend_from_slice(&(name.len() as u16).to_le_bytes());
+        archive.extend_from_slice(&[0; 16]);
+        archive.extend_from_slice(name.as_bytes());
+        let directory_length = archive.len() as u32 - directory_offset;
+
+        archive.extend_from_slice(&0x0605_4b50u32.to_le_bytes());
+        archive.extend_from_slice(&[0, 0, 0, 0, 1, 0, 1, 0]);
+        archive.extend_from_slice(&directory_length.to_le_bytes());
+        archive.extend_from_slice(&directory_offset.to_le_bytes());
+        archive.extend_from_slice(&0u16.to_le_bytes());
+        archive
+    }
+
+    #[test]
+    fn project_round_trips_through_zip() {
+        let bytes = project().to_zip().unwrap();
+        let read = Project::from_zip(&bytes).unwrap();
+        assert_eq!(read.scene, project().scene);
+        assert_eq!(read.thumbnail, project().thumbnail);
+        assert_eq!(read.metadata.author.as_deref(), Some("Someone"));
+        assert_eq!(read.metadata.entity_count, 3);
+    }
+
+    #[test]
+    fn reads_deflated_entries() {
+        let data = "a scene that compresses well ".repeat(20);
+        let entries = read_zip(&deflated_zip(SCENE_ENTRY, data.as_bytes())).unwrap();
+        assert_eq!(entries, [(SCENE_ENTRY.to_string(), data.into_bytes())]);
+    }
+
+    #[test]
+    fn rejects_malformed_archives() {
+        let bytes = project().to_zip().unwrap();
+        assert!(read_zip(b"not a zip archive at all").is_err());
+        assert!(read_zip(&bytes[..bytes.len() / 2]).is_err());
+
+        // A changed byte in the first entry's data fails its checksum
+        let mut corrupt = bytes.clone();
+        corrupt[30 + METADATA_ENTRY.len()] ^= 0xff;
+        assert!(read_zip(&corrupt).is_err());
+
+        // Offsets past the end of the archive, near the end of the address space
+        let mut offset = bytes.clone();
+        let at = offset.len() - 6;
+        offset[at..at + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(read_zip(&offset).is_err());
+        let mut size = bytes;
+        let record = u32::from_le_bytes(size[at..at + 4].try_into().unwrap()) as usize;
+        size[record + 20..record + 24].copy_from_slice(&(u32::MAX - 8).to_le_bytes());
+        assert!(read_zip(&size).is_err());
+    }
+}
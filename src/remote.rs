@@ -0,0 +1,456 @@
+//! Remote control over WebSocket
+//!
+//! With the `remote` feature, native builds can listen for WebSocket clients
+//! that drive the modeller like the JavaScript UI does in the browser. Every
+//! text message is one bridge call:
+//!
+//! ```json
+//! { "command": "spawn_blob", "args": [0, 1, 0, 8, 0.3, 0.5] }
+//! ```
+//!
+//! The command names and arguments are those of the wasm bindings, with
+//! `null` or a left out trailing argument for optional ones and arrays of
+//! numbers for slices. The events the bridge dispatches to JavaScript go to
+//! all connected clients as `{ "event": name, "detail": ... }`, with the
+//! detail parsed from JSON where JavaScript gets a JSON string. Commands that
+//! fail to parse are answered with a `remoteError` event to that client only.
+//! Started with `--remote [ADDRESS]` or by adding `RemotePlugin`.
+//!
+//! Commands can write files anywhere, so the server only listens on loopback
+//! by default and turns down handshakes with an `Origin` header: browsers
+//! always send one, and any web page could reach a local server otherwise.
+//! With `--remote-token TOKEN` clients also have to connect with
+//! `?token=TOKEN` in the URL.
+
+use std::{
+    io::ErrorKind,
+    net::{TcpListener, TcpStream},
+    sync::{mpsc, LazyLock, Mutex},
+    thread,
+    time::Duration,
+};
+
+use bevy::prelude::*;
+use serde::Deserialize;
+use serde_json::Value;
+use tungstenite::{
+    handshake::server::{ErrorResponse, Request, Response},
+    http::StatusCode,
+    Message, WebSocket,
+};
+
+use crate::command_bridge::*;
+
+pub const DEFAULT_REMOTE_ADDRESS: &str = "127.0.0.1:9001";
+
+// How long a client thread waits for a command before sending queued events
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+// Outgoing messages of every connected client
+static CLIENTS: LazyLock<Mutex<Vec<mpsc::Sender<String>>>> = LazyLock::new(Mutex::default);
+
+pub struct RemotePlugin {
+    pub address: String,
+    // Clients must pass it as the token query parameter when set
+    pub token: Option<String>,
+}
+
+impl Default for RemotePlugin {
+    fn default() -> Self {
+        Self {
+            address: DEFAULT_REMOTE_ADDRESS.to_string(),
+            token: None,
+        }
+    }
+}
+
+impl Plugin for RemotePlugin {
+    fn build(&self, _app: &mut App) {
+        let listener = match TcpListener::bind(&self.address) {
+            Ok(listener) => listener,
+            Err(err) => {
+                warn!("Failed to start remote control on {}: {}", self.address, err);
+                return;
+            }
+        };
+        info!("Remote control listening on ws://{}", self.address);
+        let loopback = listener.local_addr().is_ok_and(|addr| addr.ip().is_loopback());
+        if !loopback {
+            warn!(
+                "Remote control on {} is reachable from other machines, and remote commands \
+                 can write files anywhere this user can{}",
+                self.address,
+                if self.token.is_some() { "" } else { ". Set --remote-token to require a token" }
+            );
+        }
+        let token = self.token.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                match stream {
+                    Ok(stream) => {
+                        let token = token.clone();
+                        thread::spawn(move || serve_client(stream, token));
+                    }
+                    Err(err) => warn!("Failed to accept remote client: {}", err),
+                }
+            }
+        });
+    }
+}
+
+// Sends an event to all connected clients, dropping those that disconnected
+pub fn broadcast(event_name: &str, detail: Value) {
+    let mut clients = CLIENTS.lock().unwrap();
+    if clients.is_empty() {
+        return;
+    }
+    let message = event_message(event_name, detail);
+    clients.retain(|client| client.send(message.clone()).is_ok());
+}
+
+fn event_message(event_name: &str, detail: Value) -> String {
+    serde_json::json!({ "event": event_name, "detail": detail }).to_string()
+}
+
+// Turns down browsers and, with a token set, clients that don't pass it
+fn check_handshake(
+    request: &Request,
+    response: Response,
+    token: Option<&str>,
+) -> Result<Response, ErrorResponse> {
+    let reject = |reason: &str| {
+        let mut response = ErrorResponse::new(Some(reason.to_string()));
+        *response.status_mut() = StatusCode::FORBIDDEN;
+        response
+    };
+    if request.headers().contains_key("origin") {
+        return Err(reject("browser clients are not accepted"));
+    }
+    if let Some(token) = token {
+        let given = request
+            .uri()
+            .query()
+            .into_iter()
+            .flat_map(|query| query.split('&'))
+            .find_map(|pair| pair.strip_prefix("token="));
+        if !given.is_some_and(|given| tokens_match(given, token)) {
+            return Err(reject("missing or wrong token"));
+        }
+    }
+    Ok(response)
+}
+
+// Compares every byte regardless of where the first mismatch is, so response
+// timing doesn't reveal how much of a guessed token was right
+fn tokens_match(given: &str, expected: &str) -> bool {
+    let (given, expected) = (given.as_bytes(), expected.as_bytes());
+    given.len() == expected.len()
+        && given.iter().zip(expected).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+fn serve_client(stream: TcpStream, token: Option<String>) {
+    let peer = stream
+        .peer_addr()
+        .map_or_else(|_| "unknown".to_string(), |addr| addr.to_string());
+    let accepted = tungstenite::accept_hdr(stream, |request: &Request, response| {
+        check_handshake(request, response, token.as_deref())
+    });
+    let mut socket = match accepted {
+        Ok(socket) => socket,
+        Err(err) => {
+            warn!("Remote handshake with {} failed: {}", peer, err);
+            return;
+        }
+    };
+    // Short reads, so events go out while the client is quiet
+    if let Err(err) = socket.get_ref().set_read_timeout(Some(POLL_INTERVAL)) {
+        warn!("Failed to configure remote client {}: {}", peer, err);
+        return;
+    }
+
+    info!("Remote client {} connected", peer);
+    let (sender, events) = mpsc::channel();
+    CLIENTS.lock().unwrap().push(sender);
+
+    loop {
+        match socket.read() {
+            Ok(Message::Text(text)) => {
+                if let Err(err) = run_command(text.as_str()) {
+                    warn!("Remote command from {} failed: {}", peer, err);
+                    let message = event_message("remoteError", Value::String(err));
+                    if !send(&mut socket, message) {
+                        break;
+                    }
+                }
+            }
+            Ok(Message::Close(_)) => break,
+            Ok(_) => {}
+            Err(tungstenite::Error::Io(err))
+                if matches!(err.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {}
+            Err(_) => break,
+        }
+        if !events.try_iter().all(|message| send(&mut socket, message)) {
+            break;
+        }
+    }
+    // The sender is dropped from CLIENTS by the next broadcast
+    info!("Remote client {} disconnected", peer);
+}
+
+fn send(socket: &mut WebSocket<TcpStream>, message: String) -> bool {
+    socket.send(Message::text(message)).is_ok()
+}
+
+#[derive(Deserialize)]
+struct RemoteCommand {
+    command: String,
+    #[serde(default)]
+    args: Vec<Value>,
+}
+
+// Calls the bridge function the command names, which queues it for the next frame
+fn run_command(text: &str) -> Result<(), String> {
+    let RemoteCommand { command, args } =
+        serde_json::from_str(text).map_err(|err| format!("invalid command: {}", err))?;
+    let args = Args {
+        command: &command,
+        values: &args,
+    };
+
+    match command.as_str() {
+        "spawn_sphere_at_origin" => spawn_sphere_at_origin(),
+        "spawn_blob" => spawn_blob(
+            args.f32(0)?,
+            args.f32(1)?,
+            args.f32(2)?,
+            args.u32(3)?,
+            args.f32(4)?,
+            args.f32(5)?,
+        ),
+        "set_mode" => set_mode(args.str(0)?),
+        "set_brush_tool" => set_brush_tool(args.str(0)?),
+        "set_brush_color" => set_brush_color(args.f32(0)?, args.f32(1)?, args.f32(2)?),
+        "set_pressure_curve" => set_pressure_curve(args.f32(0)?, args.f32(1)?, args.f32(2)?),
+        "set_brush_jitter" => set_brush_jitter(args.f32(0)?, args.f32(1)?, args.opt_u32(2)?),
+        "set_brush_symmetry" => {
+            set_brush_symmetry(args.str(0)?, args.u32(1)?, args.opt_string(2)?)
+        }
+        "set_curve_profile" => set_curve_profile(args.f32(0)?, args.f32(1)?, args.f32(2)?),
+        "rename_entity" => rename_entity(args.usize(0)?, args.str(1)?),
+        "tag_entity" => tag_entity(args.usize(0)?, args.str(1)?),
+        "find_entities_by_tag" => find_entities_by_tag(args.str(0)?),
+        "delete_entity" => delete_entity(args.usize(0)?),
+        "delete_selected" => delete_selected(),
+        "clear_scene" => clear_scene(),
+        "get_scene_summary" => get_scene_summary(),
+        "get_entity" => get_entity(args.usize(0)?),
+        "select_entity" => select_entity(args.usize(0)?),
+        "set_post_process_enabled" => set_post_process_enabled(args.bool(0)?),
+        "set_language" => set_language(args.str(0)?),
+        "set_ground_plane" => set_ground_plane(args.bool(0)?, args.f32(1)?),
+        "set_background" => set_background(
+            args.f32(0)?,
+            args.f32(1)?,
+            args.f32(2)?,
+            args.f32(3)?,
+            args.f32(4)?,
+            args.f32(5)?,
+        ),
+        "set_ground_grid" => set_ground_grid(
+            args.bool(0)?,
+            args.f32(1)?,
+            args.f32(2)?,
+            args.f32(3)?,
+            args.f32(4)?,
+            args.f32(5)?,
+        ),
+        "set_shadows" => set_shadows(args.bool(0)?, args.f32(1)?),
+        "set_ambient_occlusion" => {
+            set_ambient_occlusion(args.bool(0)?, args.f32(1)?, args.f32(2)?)
+        }
+        "set_isolate_mode" => set_isolate_mode(args.bool(0)?, args.f32(1)?),
+        "set_fog" => set_fog(
+            args.bool(0)?,
+            args.f32(1)?,
+            args.f32(2)?,
+            args.f32(3)?,
+            args.f32(4)?,
+            args.f32(5)?,
+        ),
+        "set_adaptive_resolution" => {
+            set_adaptive_resolution(args.bool(0)?, args.f32(1)?, args.f32(2)?)
+        }
+        "set_coarse_tuning" => set_coarse_tuning(args.bool(0)?, args.f32(1)?),
+        "set_volume_cache" => set_volume_cache(args.bool(0)?, args.u32(1)?),
+        "set_acceleration_structure" => set_acceleration_structure(args.str(0)?),
+        "benchmark_acceleration_structures" => benchmark_acceleration_structures(),
+        "estimate_volume" => estimate_volume(args.opt_u32(0)?),
+        "set_view_culling" => set_view_culling(args.bool(0)?, args.f32(1)?),
+        "set_preview_mesh" => set_preview_mesh(args.bool(0)?, args.f32(1)?),
+        "save_scene" => save_scene(args.str(0)?),
+        "load_scene" => load_scene(args.str(0)?),
+        "load_scene_from_string" => load_scene_from_string(args.str(0)?),
+        "import_scene" => import_scene(
+            args.str(0)?,
+            args.f32(1)?,
+            args.f32(2)?,
+            args.f32(3)?,
+            args.opt_f32(4)?,
+        ),
+        "import_scene_from_string" => import_scene_from_string(
+            args.str(0)?,
+            args.f32(1)?,
+            args.f32(2)?,
+            args.f32(3)?,
+            args.opt_f32(4)?,
+        ),
+        "save_project" => save_project(args.str(0)?, args.opt_string(1)?),
+        "open_project" => open_project(args.str(0)?),
+        "open_project_from_bytes" => open_project_from_bytes(args.str(0)?, &args.bytes(1)?),
+        "list_recent_projects" => list_recent_projects(),
+        "create_snapshot" => create_snapshot(args.str(0)?),
+        "restore_snapshot" => restore_snapshot(args.str(0)?),
+        "delete_snapshot" => delete_snapshot(args.str(0)?),
+        "list_snapshots" => list_snapshots(),
+        "start_journal" => start_journal(args.opt_string(0)?),
+        "stop_journal" => stop_journal(),
+        "export_journal" => export_journal(),
+        "replay_journal" => replay_journal(args.str(0)?, args.opt_f32(1)?),
+        "replay_journal_file" => replay_journal_file(args.str(0)?, args.opt_f32(1)?),
+        "set_temporal_accumulation" => set_temporal_accumulation(args.bool(0)?, args.f32(1)?),
+        "set_clip_plane" => {
+            set_clip_plane(args.bool(0)?, args.f32(1)?, args.f32(2)?, args.f32(3)?)
+        }
+        "set_clip_cap" => set_clip_cap(args.bool(0)?, args.f32(1)?, args.f32(2)?, args.f32(3)?),
+        "start_turntable_capture" => start_turntable_capture(args.u32(0)?),
+        "capture_screenshot" => capture_screenshot(args.str(0)?.to_string()),
+        "set_environment_map" => set_environment_map(args.opt_string(0)?, args.f32(1)?),
+        "set_gizmo_occlusion" => set_gizmo_occlusion(args.bool(0)?),
+        "set_selected_displacement" => set_selected_displacement(args.f32(0)?, args.f32(1)?),
+        "set_selected_shell" => set_selected_shell(args.f32(0)?),
+        "set_selected_repetition" => {
+            set_selected_repetition(args.u32(0)?, args.str(1)?, args.f32(2)?)
+        }
+        "translate_selected" => translate_selected(args.str(0)?, args.f32(1)?),
+        "align_selected" => align_selected(args.str(0)?, args.str(1)?),
+        "distribute_selected" => distribute_selected(args.str(0)?),
+        "set_selected_pivot" => set_selected_pivot(args.f32(0)?, args.f32(1)?, args.f32(2)?),
+        "set_camera_pose" => set_camera_pose(&args.f32s(0)?, &args.f32s(1)?),
+        "frame_selected" => frame_selected(),
+        "set_projection" => set_projection(args.str(0)?),
+        "set_entity_transform" => set_entity_transform(
+            args.usize(0)?,
+            &args.f32s(1)?,
+            &args.f32s(2)?,
+            &args.f32s(3)?,
+        ),
+        "center_selected_pivot" => center_selected_pivot(),
+        "undo" => undo(),
+        "redo" => redo(),
+        "export_colliders" => export_colliders(),
+        "export_scene_gltf" => export_scene_gltf(),
+        "export_mesh" => export_mesh(args.str(0)?, args.opt_u32(1)?),
+        _ => return Err(format!("unknown command {}", command)),
+    }
+    Ok(())
+}
+
+// Arguments of a command, converted to the types of the bridge function
+struct Args<'a> {
+    command: &'a str,
+    values: &'a [Value],
+}
+
+impl Args<'_> {
+    fn required(&self, index: usize) -> Result<&Value, String> {
+        match self.values.get(index) {
+            Some(Value::Null) | None => {
+                Err(format!("{} is missing argument {}", self.command, index))
+            }
+            Some(value) => Ok(value),
+        }
+    }
+
+    fn optional(&self, index: usize) -> Option<&Value> {
+        self.values.get(index).filter(|value| !value.is_null())
+    }
+
+    fn invalid(&self, index: usize, expected: &str) -> String {
+        format!("argument {} of {} should be {}", index, self.command, expected)
+    }
+
+    fn f32(&self, index: usize) -> Result<f32, String> {
+        let value = self.required(index)?;
+        value
+            .as_f64()
+            .map(|number| number as f32)
+            .ok_or_else(|| self.invalid(index, "a number"))
+    }
+
+    fn u32(&self, index: usize) -> Result<u32, String> {
+        let value = self.required(index)?;
+        value
+            .as_u64()
+            .and_then(|number| u32::try_from(number).ok())
+            .ok_or_else(|| self.invalid(index, "an unsigned integer"))
+    }
+
+    fn usize(&self, index: usize) -> Result<usize, String> {
+        let value = self.required(index)?;
+        value
+            .as_u64()
+            .and_then(|number| usize::try_from(number).ok())
+            .ok_or_else(|| self.invalid(index, "an entity id"))
+    }
+
+    fn bool(&self, index: usize) -> Result<bool, String> {
+        let value = self.required(index)?;
+        value.as_bool().ok_or_else(|| self.invalid(index, "a boolean"))
+    }
+
+    fn str(&self, index: usize) -> Result<&str, String> {
+        let value = self.required(index)?;
+        value.as_str().ok_or_else(|| self.invalid(index, "a string"))
+    }
+
+    fn f32s(&self, index: usize) -> Result<Vec<f32>, String> {
+        let value = self.required(index)?;
+        value
+            .as_array()
+            .and_then(|values| {
+                values
+                    .iter()
+                    .map(|value| value.as_f64().map(|number| number as f32))
+                    .collect()
+            })
+            .ok_or_else(|| self.invalid(index, "an array of numbers"))
+    }
+
+    // Bytes come as an array of numbers, JSON having no binary type
+    fn bytes(&self, index: usize) -> Result<Vec<u8>, String> {
+        let value = self.required(index)?;
+        value
+            .as_array()
+            .and_then(|values| {
+                values
+                    .iter()
+                    .map(|value| value.as_u64().and_then(|byte| u8::try_from(byte).ok()))
+                    .collect()
+            })
+            .ok_or_else(|| self.invalid(index, "an array of bytes"))
+    }
+
+    fn opt_f32(&self, index: usize) -> Result<Option<f32>, String> {
+        self.optional(index).map(|_| self.f32(index)).transpose()
+    }
+
+    fn opt_u32(&self, index: usize) -> Result<Option<u32>, String> {
+        self.optional(index).map(|_| self.u32(index)).transpose()
+    }
+
+    fn opt_string(&self, index: usize) -> Result<Option<String>, String> {
+        self.optional(index)
+            .map(|_| self.str(index).map(str::to_string))
+            .transpose()
+    }
+}
@@ -0,0 +1,649 @@
+//! Saving and loading scenes as RON or JSON documents
+//!
+//! A document holds every entity with its shape, transform, material color,
+//! name and tags, the groups (blob clusters) entities belong to, the camera
+//! orbit, the brush settings and the blend factor. Blend radii follow from the
+//! entity radius and that factor, so they aren't stored. Entities are listed
+//! in node order, which loading keeps, since the order decides how blends
+//! combine. Documents carry a format version; newer versions than this build
+//! knows are rejected. Loading replaces the whole scene and forgets the undo
+//! history. Importing instead merges a document into the scene as one
+//! undoable step, moved and scaled by an offset, so saved scenes double as
+//! libraries of reusable parts.
+
+use std::collections::HashMap;
+
+use bevy::{ecs::system::SystemParam, prelude::*};
+use bevy_panorbit_camera::PanOrbitCamera;
+use serde::{Deserialize, Serialize};
+
+use crate::brush_mode::{
+    BrushJitter, BrushSettings, BrushTool, BrushToolState, CurveProfile, PressureCurve,
+};
+use crate::command_bridge::EntityIndexCounter;
+use crate::edit_history::{Edit, EditHistory, EntitySnapshot, RenderParts};
+use crate::entity_info::SdfEntityInfo;
+use crate::pivot::PivotOffset;
+use crate::sdf_render::{
+    NoiseDisplacement, Repetition, SDFRenderEntity, SdfBlend, SdfOperation, SdfPrimitive,
+    SdfRenderCamera, BLEND_FACTOR, MAX_REPETITIONS,
+};
+use crate::selection::{deselect_all, select, SelectionState};
+use crate::symmetry::Symmetry;
+use crate::view_presets::set_orthographic;
+
+pub const SCENE_FORMAT_VERSION: u32 = 1;
+
+pub struct SceneIoPlugin;
+
+impl Plugin for SceneIoPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<SaveScene>()
+            .add_event::<LoadScene>()
+            .add_event::<ImportScene>()
+            .add_event::<SceneSaved>()
+            .add_systems(Update, (save_scenes, load_scenes, import_scenes).chain());
+    }
+}
+
+// Serializes the scene; the extension of `path` picks the format
+#[derive(Event)]
+pub struct SaveScene {
+    pub path: String,
+}
+
+// Replaces the scene with the one in a RON or JSON document
+#[derive(Event)]
+pub struct LoadScene {
+    pub contents: String,
+}
+
+// Adds the entities of a document to the scene, leaving the camera and brush as they
+// are. Everything is scaled by `scale` about the document origin, which then moves
+// to `translation`.
+#[derive(Event)]
+pub struct ImportScene {
+    pub contents: String,
+    pub translation: Vec3,
+    pub scale: f32,
+}
+
+#[derive(Event)]
+pub struct SceneSaved {
+    pub path: String,
+    pub contents: String,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SceneFormat {
+    Ron,
+    Json,
+}
+
+impl SceneFormat {
+    pub fn from_path(path: &str) -> Self {
+        if path.to_lowercase().ends_with(".ron") {
+            SceneFormat::Ron
+        } else {
+            SceneFormat::Json
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SceneDocument {
+    pub version: u32,
+    #[serde(default)]
+    pub groups: Vec<SceneGroup>,
+    #[serde(default)]
+    pub entities: Vec<SceneEntity>,
+    #[serde(default)]
+    pub camera: Option<SceneCamera>,
+    #[serde(default)]
+    pub brush: Option<SceneBrush>,
+    // SdfBlend::factor, files from before it was saved used the default
+    #[serde(default = "default_blend_factor")]
+    pub blend_factor: f32,
+}
+
+fn default_blend_factor() -> f32 {
+    BLEND_FACTOR
+}
+
+// An entity that only holds others, like a blob cluster
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SceneGroup {
+    pub name: String,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    pub transform: SceneTransform,
+    #[serde(default)]
+    pub pivot: Option<[f32; 3]>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SceneEntity {
+    pub name: String,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    // Index into the groups; the transform is relative to the group then
+    #[serde(default)]
+    pub group: Option<usize>,
+    pub transform: SceneTransform,
+    #[serde(default)]
+    pub pivot: Option<[f32; 3]>,
+    // sRGB with alpha
+    pub color: [f32; 4],
+    pub shape: SceneShape,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SceneTransform {
+    pub translation: [f32; 3],
+    pub rotation: [f32; 4],
+    pub scale: [f32; 3],
+}
+
+impl From<&Transform> for SceneTransform {
+    fn from(transform: &Transform) -> Self {
+        Self {
+            translation: transform.translation.to_array(),
+            rotation: transform.rotation.to_array(),
+            scale: transform.scale.to_array(),
+        }
+    }
+}
+
+impl From<&SceneTransform> for Transform {
+    fn from(transform: &SceneTransform) -> Self {
+        Transform {
+            translation: Vec3::from_array(transform.translation),
+            rotation: Quat::from_array(transform.rotation).normalize(),
+            scale: Vec3::from_array(transform.scale),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub enum ScenePrimitive {
+    Sphere,
+    Ellipsoid { radii: [f32; 3] },
+    Capsule { half_segment: [f32; 3] },
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SceneShape {
+    pub primitive: ScenePrimitive,
+    // Radius, or the largest radius of ellipsoids
+    pub radius: f32,
+    #[serde(default)]
+    pub operation: SdfOperation,
+    #[serde(default)]
+    pub noise_amplitude: f32,
+    #[serde(default)]
+    pub noise_frequency: f32,
+    #[serde(default)]
+    pub shell_thickness: f32,
+    #[serde(default = "default_repeat_count")]
+    pub repeat_count: u32,
+    #[serde(default)]
+    pub repeat_spacing: [f32; 3],
+}
+
+fn default_repeat_count() -> u32 {
+    1
+}
+
+impl From<&SDFRenderEntity> for SceneShape {
+    fn from(entity: &SDFRenderEntity) -> Self {
+        Self {
+            primitive: match entity.primitive {
+                SdfPrimitive::Sphere => ScenePrimitive::Sphere,
+                SdfPrimitive::Ellipsoid { radii } => ScenePrimitive::Ellipsoid {
+                    radii: radii.to_array(),
+                },
+                SdfPrimitive::Capsule { half_segment } => ScenePrimitive::Capsule {
+                    half_segment: half_segment.to_array(),
+                },
+            },
+            radius: entity.scale,
+            operation: entity.operation,
+            noise_amplitude: entity.displacement.amplitude,
+            noise_frequency: entity.displacement.frequency,
+            shell_thickness: entity.shell_thickness,
+            repeat_count: entity.repetition.count,
+            repeat_spacing: entity.repetition.spacing.to_array(),
+        }
+    }
+}
+
+impl SceneShape {
+    // Lengths are multiplied by `scale`
+    pub fn to_entity(&self, node_index: usize, position: Vec3, scale: f32) -> SDFRenderEntity {
+        SDFRenderEntity {
+            node_index,
+            position,
+            scale: self.radius * scale,
+            operation: self.operation,
+            primitive: match self.primitive {
+                ScenePrimitive::Sphere => SdfPrimitive::Sphere,
+                ScenePrimitive::Ellipsoid { radii } => SdfPrimitive::Ellipsoid {
+                    radii: Vec3::from_array(radii) * scale,
+                },
+                ScenePrimitive::Capsule { half_segment } => SdfPrimitive::Capsule {
+                    half_segment: Vec3::from_array(half_segment) * scale,
+                },
+            },
+            displacement: NoiseDisplacement {
+                amplitude: self.noise_amplitude * scale,
+                frequency: self.noise_frequency / scale,
+            },
+            shell_thickness: self.shell_thickness * scale,
+            repetition: Repetition {
+                count: self.repeat_count.clamp(1, MAX_REPETITIONS),
+                spacing: Vec3::from_array(self.repeat_spacing) * scale,
+            },
+        }
+    }
+}
+
+// Where the orbit camera looks from
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SceneCamera {
+    pub focus: [f32; 3],
+    pub radius: f32,
+    pub yaw: f32,
+    pub pitch: f32,
+    #[serde(default)]
+    pub orthographic: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SceneBrush {
+    pub tool: BrushTool,
+    pub color: [f32; 4],
+    pub pressure_curve: PressureCurve,
+    pub jitter: BrushJitter,
+    pub curve: CurveProfile,
+    #[serde(default)]
+    pub radius: Option<f32>,
+    #[serde(default)]
+    pub operation: SdfOperation,
+    pub symmetry: SceneSymmetry,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SceneSymmetry {
+    pub center: [f32; 3],
+    pub axis: [f32; 3],
+    pub fold: u32,
+    #[serde(default)]
+    pub mirror_normal: Option<[f32; 3]>,
+}
+
+pub fn write_scene(document: &SceneDocument, format: SceneFormat) -> Result<String, String> {
+    match format {
+        SceneFormat::Ron => {
+            ron::ser::to_string_pretty(document, ron::ser::PrettyConfig::default())
+                .map_err(|err| err.to_string())
+        }
+        SceneFormat::Json => serde_json::to_string_pretty(document).map_err(|err| err.to_string()),
+    }
+}
+
+// JSON documents start with an object, anything else is read as RON
+pub fn read_scene(contents: &str) -> Result<SceneDocument, String> {
+    let document: SceneDocument = if contents.trim_start().starts_with('{') {
+        serde_json::from_str(contents).map_err(|err| err.to_string())?
+    } else {
+        ron::from_str(contents).map_err(|err| err.to_string())?
+    };
+    if document.version > SCENE_FORMAT_VERSION {
+        return Err(format!(
+            "scene format version {} is newer than the supported version {}",
+            document.version, SCENE_FORMAT_VERSION
+        ));
+    }
+    Ok(document)
+}
+
+// Everything a scene document is captured from
+#[derive(SystemParam)]
+pub struct SceneCapture<'w, 's> {
+    entities: Query<
+        'w,
+        's,
+        (
+            Entity,
+            &'static Transform,
+            Option<&'static SDFRenderEntity>,
+            &'static SdfEntityInfo,
+            RenderParts<'static>,
+        ),
+    >,
+    materials: Res<'w, Assets<StandardMaterial>>,
+    cameras: Query<'w, 's, (&'static PanOrbitCamera, &'static Projection), With<SdfRenderCamera>>,
+    brush_tool: Res<'w, BrushToolState>,
+    brush_settings: Res<'w, BrushSettings>,
+    symmetry: Res<'w, Symmetry>,
+    blend: Res<'w, SdfBlend>,
+}
+
+impl SceneCapture<'_, '_> {
+    pub fn document(&self) -> SceneDocument {
+        self.capture().0
+    }
+
+    // The document and the entities it was captured from, groups first, in the order
+    // spawn_document spawns them
+    pub fn capture(&self) -> (SceneDocument, Vec<Entity>) {
+        let mut groups = Vec::new();
+        let mut group_entities = Vec::new();
+        let mut group_indices = HashMap::new();
+        let mut shapes = Vec::new();
+        for (entity, transform, sdf, info, (parent, _, material, pivot)) in self.entities.iter() {
+            let pivot = pivot.map(|pivot| pivot.0.to_array());
+            match sdf {
+                Some(sdf) => shapes.push((entity, sdf, transform, info, parent, material, pivot)),
+                None => {
+                    group_indices.insert(entity, groups.len());
+                    group_entities.push(entity);
+                    groups.push(SceneGroup {
+                        name: info.name.clone(),
+                        tags: info.tags.clone(),
+                        transform: transform.into(),
+                        pivot,
+                    });
+                }
+            }
+        }
+        shapes.sort_by_key(|(_, sdf, ..)| sdf.node_index);
+
+        let captured = group_entities
+            .into_iter()
+            .chain(shapes.iter().map(|(entity, ..)| *entity))
+            .collect();
+        let entities = shapes
+            .into_iter()
+            .map(|(_, sdf, transform, info, parent, material, pivot)| {
+                let color = material
+                    .and_then(|material| self.materials.get(&material.0))
+                    .map_or(Color::WHITE, |material| material.base_color);
+                SceneEntity {
+                    name: info.name.clone(),
+                    tags: info.tags.clone(),
+                    group: parent.and_then(|parent| group_indices.get(&parent.parent()).copied()),
+                    transform: transform.into(),
+                    pivot,
+                    color: color.to_srgba().to_f32_array(),
+                    shape: sdf.into(),
+                }
+            })
+            .collect();
+
+        let camera = self.cameras.iter().next().map(|(pan_orbit, projection)| SceneCamera {
+            focus: pan_orbit.target_focus.to_array(),
+            radius: pan_orbit.target_radius,
+            yaw: pan_orbit.target_yaw,
+            pitch: pan_orbit.target_pitch,
+            orthographic: matches!(projection, Projection::Orthographic(_)),
+        });
+
+        let symmetry = &self.symmetry;
+        let brush = SceneBrush {
+            tool: self.brush_tool.tool,
+            color: self.brush_tool.color.to_srgba().to_f32_array(),
+            pressure_curve: self.brush_settings.pressure_curve,
+            jitter: self.brush_settings.jitter,
+            curve: self.brush_settings.curve,
+            radius: self.brush_settings.radius,
+            operation: self.brush_settings.operation,
+            symmetry: SceneSymmetry {
+                center: symmetry.center.to_array(),
+                axis: symmetry.axis.to_array(),
+                fold: symmetry.fold,
+                mirror_normal: symmetry.mirror_normal.map(|normal| normal.to_array()),
+            },
+        };
+
+        let document = SceneDocument {
+            version: SCENE_FORMAT_VERSION,
+            groups,
+            entities,
+            camera,
+            brush: Some(brush),
+            blend_factor: self.blend.factor,
+        };
+        (document, captured)
+    }
+}
+
+fn save_scenes(
+    mut save_events: EventReader<SaveScene>,
+    mut saved_events: EventWriter<SceneSaved>,
+    capture: SceneCapture,
+) {
+    for SaveScene { path } in save_events.read() {
+        let document = capture.document();
+        match write_scene(&document, SceneFormat::from_path(path)) {
+            Ok(contents) => {
+                info!("Saved {} entities to {}", document.entities.len(), path);
+                saved_events.write(SceneSaved {
+                    path: path.clone(),
+                    contents,
+                });
+            }
+            Err(err) => warn!("Failed to serialize scene: {}", err),
+        }
+    }
+}
+
+fn load_scenes(
+    mut commands: Commands,
+    mut load_events: EventReader<LoadScene>,
+    existing: Query<Entity, (With<SdfEntityInfo>, Without<ChildOf>)>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut history: ResMut<EditHistory>,
+    mut selection: ResMut<SelectionState>,
+    mut entity_index_counter: ResMut<EntityIndexCounter>,
+    mut cameras: Query<(&mut PanOrbitCamera, &mut Projection), With<SdfRenderCamera>>,
+    mut brush_tool: ResMut<BrushToolState>,
+    mut brush_settings: ResMut<BrushSettings>,
+    mut symmetry: ResMut<Symmetry>,
+    mut blend: ResMut<SdfBlend>,
+) {
+    // Only the last scene requested this frame matters
+    let Some(LoadScene { contents }) = load_events.read().last() else {
+        return;
+    };
+    let document = match read_scene(contents) {
+        Ok(document) => document,
+        Err(err) => {
+            warn!("Failed to load scene: {}", err);
+            return;
+        }
+    };
+
+    deselect_all(&mut commands, &mut selection);
+    for entity in existing.iter() {
+        commands.entity(entity).despawn();
+    }
+    history.clear();
+    entity_index_counter.counter = 0;
+
+    spawn_document(
+        &document,
+        Vec3::ZERO,
+        1.0,
+        &mut commands,
+        &mut meshes,
+        &mut materials,
+        &mut entity_index_counter,
+    );
+
+    blend.set_if_neq(SdfBlend {
+        factor: document.blend_factor.max(0.),
+    });
+
+    if let Some(camera) = &document.camera {
+        for (mut pan_orbit, mut projection) in cameras.iter_mut() {
+            let focus = Vec3::from_array(camera.focus);
+            pan_orbit.focus = focus;
+            pan_orbit.target_focus = focus;
+            pan_orbit.radius = Some(camera.radius);
+            pan_orbit.target_radius = camera.radius;
+            pan_orbit.yaw = Some(camera.yaw);
+            pan_orbit.target_yaw = camera.yaw;
+            pan_orbit.pitch = Some(camera.pitch);
+            pan_orbit.target_pitch = camera.pitch;
+            pan_orbit.force_update = true;
+            set_orthographic(&mut projection, camera.orthographic);
+        }
+    }
+
+    if let Some(brush) = &document.brush {
+        let [r, g, b, a] = brush.color;
+        brush_tool.tool = brush.tool;
+        brush_tool.color = Color::srgba(r, g, b, a);
+        brush_settings.pressure_curve = brush.pressure_curve;
+        brush_settings.jitter = brush.jitter;
+        brush_settings.curve = brush.curve;
+        brush_settings.radius = brush.radius;
+        brush_settings.operation = brush.operation;
+        *symmetry = Symmetry {
+            center: Vec3::from_array(brush.symmetry.center),
+            axis: Vec3::from_array(brush.symmetry.axis),
+            fold: brush.symmetry.fold.max(1),
+            mirror_normal: brush.symmetry.mirror_normal.map(Vec3::from_array),
+        };
+    }
+
+    info!(
+        "Loaded a scene with {} entities in {} groups",
+        document.entities.len(),
+        document.groups.len()
+    );
+}
+
+fn import_scenes(
+    mut commands: Commands,
+    mut import_events: EventReader<ImportScene>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut history: ResMut<EditHistory>,
+    mut selection: ResMut<SelectionState>,
+    mut entity_index_counter: ResMut<EntityIndexCounter>,
+) {
+    for ImportScene {
+        contents,
+        translation,
+        scale,
+    } in import_events.read()
+    {
+        let document = match read_scene(contents) {
+            Ok(document) => document,
+            Err(err) => {
+                warn!("Failed to import scene: {}", err);
+                continue;
+            }
+        };
+
+        let snapshots = spawn_document(
+            &document,
+            *translation,
+            scale.max(0.001),
+            &mut commands,
+            &mut meshes,
+            &mut materials,
+            &mut entity_index_counter,
+        );
+        // The imported parts end up selected, ready to be moved into place
+        deselect_all(&mut commands, &mut selection);
+        for snapshot in snapshots.iter().filter(|snapshot| snapshot.parent.is_none()) {
+            select(&mut commands, &mut selection, snapshot.entity);
+        }
+        history.record_all(snapshots.into_iter().map(Edit::Spawn).collect());
+
+        info!(
+            "Imported {} entities in {} groups",
+            document.entities.len(),
+            document.groups.len()
+        );
+    }
+}
+
+// Spawns the groups and entities of a document, scaled by `scale` and moved by
+// `translation`. Entities get node indices after the existing ones, in document order.
+pub fn spawn_document(
+    document: &SceneDocument,
+    translation: Vec3,
+    scale: f32,
+    commands: &mut Commands,
+    meshes: &mut Assets<Mesh>,
+    materials: &mut Assets<StandardMaterial>,
+    entity_index_counter: &mut EntityIndexCounter,
+) -> Vec<EntitySnapshot> {
+    // Children follow their group, so only top level entities are moved. Scale goes into
+    // the positions and shapes rather than the transforms, which would scale proxy meshes
+    // a second time.
+    let place = |transform: &SceneTransform, top_level: bool| {
+        let mut transform: Transform = transform.into();
+        transform.translation *= scale;
+        if top_level {
+            transform.translation += translation;
+        }
+        transform
+    };
+    let pivot = |pivot: Option<[f32; 3]>| pivot.map(|p| PivotOffset(Vec3::from_array(p) * scale));
+
+    let mut snapshots = Vec::new();
+    let mut groups = Vec::new();
+    for group in &document.groups {
+        let mut snapshot = EntitySnapshot {
+            entity: Entity::PLACEHOLDER,
+            sdf: None,
+            info: Some(SdfEntityInfo {
+                name: group.name.clone(),
+                tags: group.tags.clone(),
+            }),
+            transform: place(&group.transform, true),
+            mesh: None,
+            material: None,
+            parent: None,
+            pivot: pivot(group.pivot),
+        };
+        snapshot.entity = snapshot.spawn(commands);
+        groups.push(snapshot.entity);
+        snapshots.push(snapshot);
+    }
+
+    for entity in &document.entities {
+        let parent = entity.group.and_then(|group| groups.get(group).copied());
+        let transform = place(&entity.transform, parent.is_none());
+        let sdf = entity
+            .shape
+            .to_entity(entity_index_counter.counter, transform.translation, scale);
+        entity_index_counter.counter += 1;
+        let [r, g, b, a] = entity.color;
+        let mut snapshot = EntitySnapshot {
+            entity: Entity::PLACEHOLDER,
+            mesh: Some(meshes.add(sdf.primitive.proxy_mesh(sdf.scale))),
+            sdf: Some(sdf),
+            info: Some(SdfEntityInfo {
+                name: entity.name.clone(),
+                tags: entity.tags.clone(),
+            }),
+            transform,
+            material: Some(materials.add(StandardMaterial {
+                base_color: Color::srgba(r, g, b, a),
+                ..default()
+            })),
+            parent,
+            pivot: pivot(entity.pivot),
+        };
+        snapshot.entity = snapshot.spawn(commands);
+        snapshots.push(snapshot);
+    }
+    snapshots
+}
@@ -0,0 +1,216 @@
+//! Named snapshots of the scene
+//!
+//! A snapshot ("before head rework") keeps the entity set as a scene document
+//! written through `scene_io`, so it survives any number of later edits,
+//! unlike the steps of the undo history. Restoring one swaps the current
+//! entities for the snapshot's as a single undo step, so a restore can itself
+//! be undone. The camera and brush are left alone. Snapshots only live for
+//! the session.
+
+use bevy::prelude::*;
+use serde::Serialize;
+
+use crate::command_bridge::EntityIndexCounter;
+use crate::edit_history::{Edit, EditHistory, EntitySnapshot, RenderParts};
+use crate::entity_info::SdfEntityInfo;
+use crate::project_file::now_millis;
+use crate::scene_io::{read_scene, spawn_document, write_scene, SceneCapture, SceneFormat};
+use crate::sdf_render::SDFRenderEntity;
+use crate::selection::{deselect_all, SelectionState};
+
+pub struct SceneSnapshotsPlugin;
+
+impl Plugin for SceneSnapshotsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SceneSnapshots>()
+            .add_event::<CreateSnapshot>()
+            .add_event::<RestoreSnapshot>()
+            .add_event::<DeleteSnapshot>()
+            .add_event::<ListSnapshots>()
+            .add_event::<SnapshotsListed>()
+            .add_systems(
+                Update,
+                (
+                    create_snapshots,
+                    restore_snapshots,
+                    delete_snapshots,
+                    list_snapshots,
+                )
+                    .chain(),
+            );
+    }
+}
+
+// Takes a snapshot, replacing any with the same name
+#[derive(Event)]
+pub struct CreateSnapshot {
+    pub name: String,
+}
+
+#[derive(Event)]
+pub struct RestoreSnapshot {
+    pub name: String,
+}
+
+#[derive(Event)]
+pub struct DeleteSnapshot {
+    pub name: String,
+}
+
+#[derive(Event)]
+pub struct ListSnapshots;
+
+#[derive(Event)]
+pub struct SnapshotsListed(pub Vec<SnapshotSummary>);
+
+pub struct SceneSnapshot {
+    pub name: String,
+    // Milliseconds since the Unix epoch
+    pub created: f64,
+    pub entity_count: usize,
+    // RON scene document without camera and brush
+    pub contents: String,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct SnapshotSummary {
+    pub name: String,
+    pub created: f64,
+    pub entity_count: usize,
+}
+
+// Oldest first
+#[derive(Resource, Default)]
+pub struct SceneSnapshots {
+    pub snapshots: Vec<SceneSnapshot>,
+}
+
+impl SceneSnapshots {
+    pub fn get(&self, name: &str) -> Option<&SceneSnapshot> {
+        self.snapshots.iter().find(|snapshot| snapshot.name == name)
+    }
+}
+
+fn create_snapshots(
+    mut create_events: EventReader<CreateSnapshot>,
+    capture: SceneCapture,
+    mut snapshots: ResMut<SceneSnapshots>,
+) {
+    for CreateSnapshot { name } in create_events.read() {
+        let mut document = capture.document();
+        document.camera = None;
+        document.brush = None;
+        let contents = match write_scene(&document, SceneFormat::Ron) {
+            Ok(contents) => contents,
+            Err(err) => {
+                warn!("Failed to take snapshot {}: {}", name, err);
+                continue;
+            }
+        };
+        snapshots.snapshots.retain(|snapshot| snapshot.name != *name);
+        snapshots.snapshots.push(SceneSnapshot {
+            name: name.clone(),
+            created: now_millis(),
+            entity_count: document.entities.len(),
+            contents,
+        });
+        info!("Took snapshot {} of {} entities", name, document.entities.len());
+    }
+}
+
+fn restore_snapshots(
+    mut commands: Commands,
+    mut restore_events: EventReader<RestoreSnapshot>,
+    snapshots: Res<SceneSnapshots>,
+    roots: Query<Entity, (With<SdfEntityInfo>, Without<ChildOf>)>,
+    children: Query<&Children>,
+    entities: Query<(
+        &Transform,
+        Option<&SDFRenderEntity>,
+        Option<&SdfEntityInfo>,
+        RenderParts,
+    )>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut history: ResMut<EditHistory>,
+    mut selection: ResMut<SelectionState>,
+    mut entity_index_counter: ResMut<EntityIndexCounter>,
+) {
+    // Restoring twice in a frame would delete entities spawned by the first restore,
+    // which only exist once the commands are applied
+    let Some(RestoreSnapshot { name }) = restore_events.read().last() else {
+        return;
+    };
+    let Some(snapshot) = snapshots.get(name) else {
+        warn!("No snapshot named {}", name);
+        return;
+    };
+    let document = match read_scene(&snapshot.contents) {
+        Ok(document) => document,
+        Err(err) => {
+            warn!("Failed to restore snapshot {}: {}", name, err);
+            return;
+        }
+    };
+
+    deselect_all(&mut commands, &mut selection);
+
+    // Children are recorded before their parents, so undo respawns parents first
+    let mut edits = Vec::new();
+    for root in roots.iter() {
+        for entity in children.iter_descendants(root).chain(std::iter::once(root)) {
+            if let Ok((transform, sdf, info, render_parts)) = entities.get(entity) {
+                edits.push(Edit::Delete(EntitySnapshot::capture(
+                    entity,
+                    *transform,
+                    sdf,
+                    info,
+                    render_parts,
+                )));
+            }
+        }
+        commands.entity(root).despawn();
+    }
+
+    let spawned = spawn_document(
+        &document,
+        Vec3::ZERO,
+        1.0,
+        &mut commands,
+        &mut meshes,
+        &mut materials,
+        &mut entity_index_counter,
+    );
+    edits.extend(spawned.into_iter().map(Edit::Spawn));
+    history.record_all(edits);
+    info!("Restored snapshot {}", name);
+}
+
+fn delete_snapshots(
+    mut delete_events: EventReader<DeleteSnapshot>,
+    mut snapshots: ResMut<SceneSnapshots>,
+) {
+    for DeleteSnapshot { name } in delete_events.read() {
+        snapshots.snapshots.retain(|snapshot| snapshot.name != *name);
+    }
+}
+
+fn list_snapshots(
+    mut list_events: EventReader<ListSnapshots>,
+    mut listed_events: EventWriter<SnapshotsListed>,
+    snapshots: Res<SceneSnapshots>,
+) {
+    if list_events.read().last().is_none() {
+        return;
+    }
+    let summaries = snapshots
+        .snapshots
+        .iter()
+        .map(|snapshot| SnapshotSummary {
+            name: snapshot.name.clone(),
+            created: snapshot.created,
+            entity_count: snapshot.entity_count,
+        })
+        .collect();
+    listed_events.write(SnapshotsListed(summaries));
+}
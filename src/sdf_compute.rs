@@ -4,11 +4,19 @@
 //! using compute shaders. It's designed to work with the existing SDF rendering pipeline
 //! and shares the same scene data (entity transforms and settings).
 
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+
 use bevy::{
     core_pipeline::core_3d::graph::{Core3d, Node3d},
+    diagnostic::{Diagnostic, DiagnosticPath, Diagnostics, RegisterDiagnostic},
     prelude::*,
     render::{
+        diagnostic::RecordDiagnostics,
         extract_component::ComponentUniforms,
+        extract_resource::{ExtractResource, ExtractResourcePlugin},
         render_graph::{self, RenderGraphApp, RenderLabel},
         render_resource::{
             binding_types::*, BindGroupLayoutEntry, BindingType, BufferBindingType, ShaderStages, *,
@@ -22,23 +30,137 @@ use futures::channel::oneshot;
 
 const SHADER_ASSET_PATH: &str = "shaders/sdf_compute.wgsl";
 
+/// Evaluation requests sent but not yet answered
+pub const SDF_COMPUTE_QUEUE_DEPTH: DiagnosticPath =
+    DiagnosticPath::const_new("sdf_compute/queue_depth");
+
+/// GPU time of the compute dispatch, resolved from its timestamp span by the render diagnostics
+pub const SDF_COMPUTE_PASS_GPU_TIME: DiagnosticPath =
+    DiagnosticPath::const_new("render/sdf_compute_pass/elapsed_gpu");
+
 /// Result of SDF evaluation matching the WGSL SceneSdfResult struct
 #[repr(C)]
 #[derive(
     Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable, bevy::render::render_resource::ShaderType,
 )]
 pub struct SdfResult {
+    /// Surface normal (the normalized SDF gradient) at the hit point, zero if
+    /// the ray missed or the request didn't ask for it
+    pub normal: Vec3,
+    /// Distance along the ray for ray marches and raycasts, the signed
+    /// distance of the query point for projections and samples
     pub distance: f32,
-    // pub position: Vec3,
+    /// Where the ray march ended (the hit point on hits), the closest surface
+    /// point for projections, or the sampled point
+    pub position: Vec3,
+    _padding: f32,
+}
+
+/// What a request evaluates its points for
+#[derive(Debug)]
+pub enum SdfQuery {
+    /// Viewport uvs, marched along the camera ray to the first hit. The
+    /// gradient costs six more scene evaluations per hit
+    Raymarch { uvs: Vec<Vec2>, with_gradient: bool },
+    /// World space rays, marched up to `max_distance` with the normal of every hit
+    Raycast { rays: Vec<Ray3d>, max_distance: f32 },
+    /// World positions, moved onto the closest point of the surface
+    Project { points: Vec<Vec3> },
+    /// World positions, evaluated for their signed distance only
+    Sample { points: Vec<Vec3> },
+}
+
+/// One query point as uploaded (QueryPoint in sdf_compute.wgsl)
+#[repr(C)]
+#[derive(
+    Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable, bevy::render::render_resource::ShaderType,
+)]
+struct GpuQueryPoint {
+    /// The uv in xy, the ray origin or the position to project in xyz
+    a: Vec4,
+    /// Ray direction in xyz and max distance in w, unused otherwise
+    b: Vec4,
+}
+
+impl GpuQueryPoint {
+    fn new(a: Vec4) -> Self {
+        Self { a, b: Vec4::ZERO }
+    }
+}
+
+impl SdfQuery {
+    fn len(&self) -> usize {
+        match self {
+            SdfQuery::Raymarch { uvs, .. } => uvs.len(),
+            SdfQuery::Raycast { rays, .. } => rays.len(),
+            SdfQuery::Project { points } | SdfQuery::Sample { points } => points.len(),
+        }
+    }
+
+    /// Where the query's points go in a batch. The shader tells the kinds
+    /// apart by index, so ray marches with gradient come first, then the other
+    /// ray marches, then raycasts, then projections and samples
+    fn batch_order(&self) -> u8 {
+        match self {
+            SdfQuery::Raymarch {
+                with_gradient: true,
+                ..
+            } => 0,
+            SdfQuery::Raymarch { .. } => 1,
+            SdfQuery::Raycast { .. } => 2,
+            SdfQuery::Project { .. } => 3,
+            SdfQuery::Sample { .. } => 4,
+        }
+    }
+
+    fn gpu_points(&self) -> Vec<GpuQueryPoint> {
+        match self {
+            SdfQuery::Raymarch { uvs, .. } => uvs
+                .iter()
+                .map(|uv| GpuQueryPoint::new(uv.extend(0.).extend(0.)))
+                .collect(),
+            SdfQuery::Raycast { rays, max_distance } => rays
+                .iter()
+                .map(|ray| GpuQueryPoint {
+                    a: ray.origin.extend(0.),
+                    b: ray.direction.extend(*max_distance),
+                })
+                .collect(),
+            SdfQuery::Project { points } | SdfQuery::Sample { points } => points
+                .iter()
+                .map(|point| GpuQueryPoint::new(point.extend(0.)))
+                .collect(),
+        }
+    }
 }
 
 /// Request for SDF evaluation
 #[derive(Debug)]
 pub struct SdfEvaluationRequest {
-    pub points: Vec<Vec2>,
+    pub query: SdfQuery,
     pub response_tx: oneshot::Sender<Vec<SdfResult>>,
 }
 
+/// Per-dispatch parameters matching ComputeParams in sdf_compute.wgsl
+#[repr(C)]
+#[derive(
+    Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable, bevy::render::render_resource::ShaderType,
+)]
+struct SdfComputeParams {
+    points_count: u32,
+    /// Points at the start of the batch whose gradient was requested
+    gradient_count: u32,
+    /// Points that are ray marched from the camera
+    raymarch_count: u32,
+    /// Points that are ray marched or raycast
+    raycast_end: u32,
+    /// Points that are ray marched, raycast or projected, the rest are sampled
+    project_end: u32,
+    _padding0: u32,
+    _padding1: u32,
+    _padding2: u32,
+}
+
 /// Resource for sending SDF evaluation requests to render world
 #[derive(Resource, Clone)]
 pub struct SdfEvaluationSender(pub crossbeam_channel::Sender<SdfEvaluationRequest>);
@@ -47,6 +169,33 @@ pub struct SdfEvaluationSender(pub crossbeam_channel::Sender<SdfEvaluationReques
 #[derive(Resource, Deref)]
 struct RenderWorldReceiver(crossbeam_channel::Receiver<SdfEvaluationRequest>);
 
+/// Requests the render world has received but not answered yet, shared with the main world
+#[derive(Resource, Clone, Default)]
+struct SdfComputeInFlight(Arc<AtomicUsize>);
+
+/// Limits on GPU evaluation, extracted to the render world
+#[derive(Resource, Clone)]
+pub struct SdfComputeSettings {
+    /// Batches that may wait for their readback at once, each copying its
+    /// results into its own readback buffer. Requests beyond that are queued
+    /// and packed into the next batch
+    pub max_in_flight: usize,
+}
+
+impl Default for SdfComputeSettings {
+    fn default() -> Self {
+        Self { max_in_flight: 2 }
+    }
+}
+
+impl ExtractResource for SdfComputeSettings {
+    type Source = SdfComputeSettings;
+
+    fn extract_resource(source: &Self::Source) -> Self {
+        source.clone()
+    }
+}
+
 /// GPU-aligned Vec3 for proper buffer alignment (16 bytes instead of 12)
 #[repr(C)]
 #[derive(
@@ -63,16 +212,24 @@ pub struct GpuVec3 {
 pub struct SdfComputePlugin;
 
 impl Plugin for SdfComputePlugin {
-    fn build(&self, _app: &mut App) {}
+    fn build(&self, app: &mut App) {
+        app.register_diagnostic(Diagnostic::new(SDF_COMPUTE_QUEUE_DEPTH))
+            .init_resource::<SdfComputeSettings>()
+            .add_plugins(ExtractResourcePlugin::<SdfComputeSettings>::default());
+    }
 
     fn finish(&self, app: &mut App) {
         let (request_sender, request_receiver) = crossbeam_channel::unbounded();
+        let in_flight = SdfComputeInFlight::default();
 
-        app.insert_resource(SdfEvaluationSender(request_sender));
+        app.insert_resource(SdfEvaluationSender(request_sender))
+            .insert_resource(in_flight.clone())
+            .add_systems(Update, record_compute_queue_depth);
 
         let render_app = app.sub_app_mut(RenderApp);
         render_app
             .insert_resource(RenderWorldReceiver(request_receiver))
+            .insert_resource(in_flight)
             .init_resource::<SdfComputePipeline>()
             .init_resource::<SdfComputeBuffers>()
             .init_resource::<PendingSdfRequests>()
@@ -86,8 +243,7 @@ impl Plugin for SdfComputePlugin {
                                 ComponentUniforms<crate::sdf_render::SDFRenderSettings>,
                             >,
                         ),
-                    process_sdf_requests.before(RenderSet::Render),
-                    initiate_gpu_readback.after(RenderSet::Render),
+                    process_sdf_requests.in_set(RenderSet::PrepareResources),
                     perform_delayed_readback.in_set(RenderSet::PostCleanup),
                 ),
             );
@@ -106,10 +262,30 @@ impl Plugin for SdfComputePlugin {
 struct SdfComputeBuffers {
     query_points_buffer: Buffer,
     results_buffer: Buffer,
-    readback_buffer: Buffer,
+    params_buffer: Buffer,
+    // Created as batches need them, up to SdfComputeSettings::max_in_flight
+    readback_slots: Vec<ReadbackSlot>,
     current_capacity: usize,
 }
 
+/// A readback buffer, mapped by at most one batch at a time
+struct ReadbackSlot {
+    buffer: Buffer,
+    capacity: usize,
+}
+
+impl ReadbackSlot {
+    fn new(render_device: &RenderDevice, capacity: usize) -> Self {
+        let buffer = render_device.create_buffer(&BufferDescriptor {
+            label: Some("sdf_readback_buffer"),
+            size: (capacity * std::mem::size_of::<SdfResult>()) as u64,
+            usage: BufferUsages::MAP_READ | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        Self { buffer, capacity }
+    }
+}
+
 impl FromWorld for SdfComputeBuffers {
     fn from_world(world: &mut World) -> Self {
         let render_device = world.resource::<RenderDevice>();
@@ -118,7 +294,7 @@ impl FromWorld for SdfComputeBuffers {
         info!("create buffers");
         let query_points_buffer = render_device.create_buffer(&BufferDescriptor {
             label: Some("sdf_query_points_buffer"),
-            size: (initial_capacity * std::mem::size_of::<Vec2>()) as u64,
+            size: (initial_capacity * std::mem::size_of::<GpuQueryPoint>()) as u64,
             usage: BufferUsages::STORAGE | BufferUsages::COPY_DST,
             mapped_at_creation: false,
         });
@@ -130,17 +306,18 @@ impl FromWorld for SdfComputeBuffers {
             mapped_at_creation: false,
         });
 
-        let readback_buffer = render_device.create_buffer(&BufferDescriptor {
-            label: Some("sdf_readback_buffer"),
-            size: (initial_capacity * std::mem::size_of::<SdfResult>()) as u64,
-            usage: BufferUsages::MAP_READ | BufferUsages::COPY_DST,
+        let params_buffer = render_device.create_buffer(&BufferDescriptor {
+            label: Some("sdf_compute_params_buffer"),
+            size: std::mem::size_of::<SdfComputeParams>() as u64,
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
             mapped_at_creation: false,
         });
 
         Self {
             query_points_buffer,
             results_buffer,
-            readback_buffer,
+            params_buffer,
+            readback_slots: Vec::new(),
             current_capacity: initial_capacity,
         }
     }
@@ -168,6 +345,7 @@ fn prepare_sdf_bind_groups(
         &BindGroupEntries::sequential((
             buffers.query_points_buffer.as_entire_binding(),
             buffers.results_buffer.as_entire_binding(),
+            buffers.params_buffer.as_entire_binding(),
         )),
     );
 
@@ -195,10 +373,10 @@ fn prepare_sdf_bind_groups(
 }
 
 #[derive(Resource)]
-struct SdfComputePipeline {
+pub(crate) struct SdfComputePipeline {
     compute_layout: BindGroupLayout,
     sdf_layout: BindGroupLayout,
-    pipeline: CachedComputePipelineId,
+    pub(crate) pipeline: CachedComputePipelineId,
 }
 
 impl FromWorld for SdfComputePipeline {
@@ -212,9 +390,11 @@ impl FromWorld for SdfComputePipeline {
                 ShaderStages::COMPUTE,
                 (
                     // Query points buffer
-                    storage_buffer_read_only::<Vec2>(false),
+                    storage_buffer_read_only::<GpuQueryPoint>(false),
                     // Results buffer
                     storage_buffer::<SdfResult>(false),
+                    // Point and gradient counts of the batch
+                    uniform_buffer::<SdfComputeParams>(false),
                 ),
             ),
         );
@@ -227,8 +407,17 @@ impl FromWorld for SdfComputePipeline {
                 (
                     // PostProcessSettings uniform
                     uniform_buffer::<crate::sdf_render::SDFRenderSettings>(true),
-                    // Entity transforms storage buffer
-                    storage_buffer_read_only::<Mat4>(false),
+                    // Entity storage buffer
+                    BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: ShaderStages::COMPUTE,
+                        ty: BindingType::Buffer {
+                            ty: BufferBindingType::Storage { read_only: true },
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
                     // BVH nodes storage buffer
                     BindGroupLayoutEntry {
                         binding: 2,
@@ -264,13 +453,77 @@ impl FromWorld for SdfComputePipeline {
     }
 }
 
-/// Pending SDF requests waiting for GPU readback
+/// Requests evaluated in a single dispatch, with their points laid out back to
+/// back in the order the requests came in
+#[derive(Debug, Default)]
+struct SdfBatch {
+    requests: Vec<SdfEvaluationRequest>,
+}
+
+impl SdfBatch {
+    fn points_count(&self) -> usize {
+        self.requests.iter().map(|req| req.query.len()).sum()
+    }
+
+    /// Points of the requests matching `filter`
+    fn count_points(&self, filter: impl Fn(&SdfQuery) -> bool) -> u32 {
+        self.requests
+            .iter()
+            .filter(|req| filter(&req.query))
+            .map(|req| req.query.len() as u32)
+            .sum()
+    }
+
+    fn points(&self) -> Vec<GpuQueryPoint> {
+        self.requests
+            .iter()
+            .flat_map(|req| req.query.gpu_points())
+            .collect()
+    }
+
+    /// Hands every request its slice of the batch results
+    fn respond(self, results: Vec<SdfResult>) {
+        let mut results = results.into_iter();
+        for request in self.requests {
+            let slice = results.by_ref().take(request.query.len()).collect();
+            let _ = request.response_tx.send(slice);
+        }
+    }
+}
+
+/// Pending SDF requests waiting for GPU readback. Each batch on the GPU holds
+/// a readback slot until its results are read, and everything received while
+/// all slots are taken is packed into the next batch
 #[derive(Resource, Default)]
 struct PendingSdfRequests {
-    requests: Vec<SdfEvaluationRequest>,
-    completed_requests: Vec<SdfEvaluationRequest>,
-    pending_mapping: Option<(SdfEvaluationRequest, crossbeam_channel::Receiver<()>)>, // (request, receiver)
-    ready_for_mapping: Vec<SdfEvaluationRequest>,
+    /// Received, but waiting for a free readback slot
+    queued: SdfBatch,
+    /// Uploaded this frame, dispatched and copied into its slot by the node
+    dispatched: Option<(SdfBatch, usize)>,
+    pending_mappings: Vec<PendingMapping>,
+}
+
+/// A batch whose results are being mapped from its readback slot
+struct PendingMapping {
+    batch: SdfBatch,
+    slot: usize,
+    mapped: crossbeam_channel::Receiver<()>,
+}
+
+impl PendingSdfRequests {
+    /// Requests across every stage, which are all still waiting for their results
+    fn in_flight(&self) -> usize {
+        let batches = std::iter::once(&self.queued)
+            .chain(self.dispatched.iter().map(|(batch, _)| batch))
+            .chain(self.pending_mappings.iter().map(|mapping| &mapping.batch));
+        batches.map(|batch| batch.requests.len()).sum()
+    }
+
+    /// A readback slot no batch is mapping
+    fn free_slot(&self, max_in_flight: usize) -> Option<usize> {
+        (0..max_in_flight.max(1))
+            .find(|slot| !self.pending_mappings.iter().any(|mapping| mapping.slot == *slot))
+    }
 }
 
 fn process_sdf_requests(
@@ -279,136 +532,164 @@ fn process_sdf_requests(
     mut buffers: ResMut<SdfComputeBuffers>,
     mut pending_requests: ResMut<PendingSdfRequests>,
     receiver: ResMut<RenderWorldReceiver>,
+    pipeline: Res<SdfComputePipeline>,
+    pipeline_cache: Res<PipelineCache>,
+    bind_groups: Option<Res<SdfComputeBindGroups>>,
+    settings: Res<SdfComputeSettings>,
 ) {
-    // Process new incoming requests
+    // Queue everything that came in since the last frame, so requests sent
+    // while a batch is on the GPU are packed together instead of serializing
     while let Some(request) = receiver.try_recv() {
         // info!(
         //     "Received SDF request with ID: {} for {} points",
         //     request.id,
-        //     request.points.len()
+        //     request.query.len()
         // );
-        let points_count = request.points.len();
-        if points_count == 0 {
+        if request.query.len() == 0 {
             info!("Skipping empty SDF request");
+            let _ = request.response_tx.send(vec![]);
             continue;
         }
+        pending_requests.queued.requests.push(request);
+    }
 
-        // Resize buffers if needed
-        if points_count > buffers.current_capacity {
-            let new_capacity = (points_count * 2).max(1024);
+    // The node can only dispatch once the pipeline and bind groups are ready
+    let gpu_ready =
+        pipeline_cache.get_compute_pipeline(pipeline.pipeline).is_some() && bind_groups.is_some();
+    if !gpu_ready {
+        return;
+    }
 
-            buffers.query_points_buffer = render_device.create_buffer(&BufferDescriptor {
-                label: Some("sdf_query_points_buffer"),
-                size: (new_capacity * std::mem::size_of::<Vec2>()) as u64,
-                usage: BufferUsages::STORAGE | BufferUsages::COPY_DST,
-                mapped_at_creation: false,
-            });
+    // With every readback buffer still mapped the requests wait for the next frame
+    let Some(slot) = pending_requests.free_slot(settings.max_in_flight) else {
+        return;
+    };
 
-            buffers.results_buffer = render_device.create_buffer(&BufferDescriptor {
-                label: Some("sdf_results_buffer"),
-                size: (new_capacity * std::mem::size_of::<SdfResult>()) as u64,
-                usage: BufferUsages::STORAGE | BufferUsages::COPY_SRC,
-                mapped_at_creation: false,
-            });
+    let points_count = pending_requests.queued.points_count();
+    if points_count == 0 {
+        return;
+    }
 
-            buffers.readback_buffer = render_device.create_buffer(&BufferDescriptor {
-                label: Some("sdf_readback_buffer"),
-                size: (new_capacity * std::mem::size_of::<SdfResult>()) as u64,
-                usage: BufferUsages::MAP_READ | BufferUsages::COPY_DST,
-                mapped_at_creation: false,
-            });
+    // Resize buffers if needed
+    if points_count > buffers.current_capacity {
+        let new_capacity = (points_count * 2).max(1024);
 
-            buffers.current_capacity = new_capacity;
-        }
+        buffers.query_points_buffer = render_device.create_buffer(&BufferDescriptor {
+            label: Some("sdf_query_points_buffer"),
+            size: (new_capacity * std::mem::size_of::<GpuQueryPoint>()) as u64,
+            usage: BufferUsages::STORAGE | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
 
-        // Upload query points to GPU
-        let points_data = bytemuck::cast_slice(&request.points);
-        render_queue.write_buffer(&buffers.query_points_buffer, 0, points_data);
+        buffers.results_buffer = render_device.create_buffer(&BufferDescriptor {
+            label: Some("sdf_results_buffer"),
+            size: (new_capacity * std::mem::size_of::<SdfResult>()) as u64,
+            usage: BufferUsages::STORAGE | BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
 
-        // Add to pending requests for GPU readback after compute dispatch
-        // info!("Adding SDF request ID: {} to pending queue", request.id);
-        pending_requests.requests.push(request);
+        buffers.current_capacity = new_capacity;
     }
-}
 
-fn initiate_gpu_readback(mut pending_requests: ResMut<PendingSdfRequests>) {
-    // Move completed requests from the main queue if needed
-    if pending_requests.completed_requests.is_empty() && !pending_requests.requests.is_empty() {
-        let requests_to_move: Vec<_> = pending_requests.requests.drain(..).collect();
-        pending_requests.completed_requests.extend(requests_to_move);
+    // The free slot isn't mapped, so it can be created or grown right away
+    let capacity = buffers.current_capacity;
+    if slot >= buffers.readback_slots.len() {
+        buffers.readback_slots.push(ReadbackSlot::new(&render_device, capacity));
+    } else if buffers.readback_slots[slot].capacity < points_count {
+        buffers.readback_slots[slot] = ReadbackSlot::new(&render_device, capacity);
     }
 
-    // Move completed requests to ready_for_mapping queue for delayed processing
-    if !pending_requests.completed_requests.is_empty() {
-        let requests_to_map: Vec<_> = pending_requests.completed_requests.drain(..).collect();
-        pending_requests.ready_for_mapping.extend(requests_to_map);
-    }
+    // Upload every queued request's points to GPU, back to back, grouped by
+    // query kind (the sort is stable) so the shader can tell them apart by index
+    let mut batch = std::mem::take(&mut pending_requests.queued);
+    batch.requests.sort_by_key(|request| request.query.batch_order());
+    let points = batch.points();
+    let points_data = bytemuck::cast_slice(&points);
+    render_queue.write_buffer(&buffers.query_points_buffer, 0, points_data);
+
+    let params = SdfComputeParams {
+        points_count: points_count as u32,
+        gradient_count: batch.count_points(|query| query.batch_order() == 0),
+        raymarch_count: batch
+            .count_points(|query| matches!(query, SdfQuery::Raymarch { .. })),
+        raycast_end: batch.count_points(|query| query.batch_order() < 3),
+        project_end: batch.count_points(|query| query.batch_order() < 4),
+        _padding0: 0,
+        _padding1: 0,
+        _padding2: 0,
+    };
+    render_queue.write_buffer(&buffers.params_buffer, 0, bytemuck::bytes_of(&params));
+
+    // Dispatched by the compute node this frame, then mapped for readback
+    pending_requests.dispatched = Some((batch, slot));
+}
+
+fn record_compute_queue_depth(
+    sender: Res<SdfEvaluationSender>,
+    in_flight: Res<SdfComputeInFlight>,
+    mut diagnostics: Diagnostics,
+) {
+    diagnostics.add_measurement(&SDF_COMPUTE_QUEUE_DEPTH, || {
+        (sender.0.len() + in_flight.0.load(Ordering::Relaxed)) as f64
+    });
 }
 
 fn perform_delayed_readback(
-    _render_device: Res<RenderDevice>,
     buffers: Res<SdfComputeBuffers>,
     mut pending_requests: ResMut<PendingSdfRequests>,
+    in_flight: Res<SdfComputeInFlight>,
 ) {
-    // Check if we have a pending mapping
-    if let Some((_request, rx)) = &pending_requests.pending_mapping {
-        // Check if mapping is complete (non-blocking)
-        match rx.try_recv() {
-            Some(_) => {
-                // Take the request to process it
-                let (request, _) = pending_requests.pending_mapping.take().unwrap();
-
-                // Read the data - wrap in a closure to ensure cleanup on error
-                let read_result = (|| -> Result<Vec<SdfResult>, &'static str> {
-                    let buffer_slice = buffers.readback_buffer.slice(..);
-                    let mapped_range = buffer_slice.get_mapped_range();
-
-                    const RESULT_SIZE: usize = std::mem::size_of::<SdfResult>();
-                    let points_count = request.points.len();
-
-                    let mut results_data = Vec::new();
-                    for chunk in mapped_range.chunks_exact(RESULT_SIZE).take(points_count) {
-                        let bytes: [u8; RESULT_SIZE] = chunk
-                            .try_into()
-                            .map_err(|_| "Failed to convert chunk to byte array")?;
-
-                        results_data.push(bytemuck::from_bytes::<SdfResult>(&bytes).clone());
-                    }
-
-                    Ok(results_data)
-                })();
-
-                // Always unmap the buffer regardless of success/failure
-                buffers.readback_buffer.unmap();
-
-                // Send results through oneshot channel
-                match read_result {
-                    Ok(results_data) => {
-                        let _ = request.response_tx.send(results_data);
-                    }
-                    Err(err) => {
-                        eprintln!("Failed to read buffer data: {:?}", err);
-                        // Send empty results on error
-                        let _ = request.response_tx.send(vec![]);
-                    }
-                }
+    // Answer every batch whose results finished mapping (non-blocking)
+    let mut index = 0;
+    while index < pending_requests.pending_mappings.len() {
+        if pending_requests.pending_mappings[index].mapped.try_recv().is_none() {
+            // Mapping not ready yet, keep waiting
+            index += 1;
+            continue;
+        }
+
+        let mapping = pending_requests.pending_mappings.remove(index);
+        let readback_buffer = &buffers.readback_slots[mapping.slot].buffer;
+
+        // Read the data - wrap in a closure to ensure cleanup on error
+        let read_result = (|| -> Result<Vec<SdfResult>, &'static str> {
+            let buffer_slice = readback_buffer.slice(..);
+            let mapped_range = buffer_slice.get_mapped_range();
+
+            const RESULT_SIZE: usize = std::mem::size_of::<SdfResult>();
+            let points_count = mapping.batch.points_count();
+
+            let mut results_data = Vec::new();
+            for chunk in mapped_range.chunks_exact(RESULT_SIZE).take(points_count) {
+                let bytes: [u8; RESULT_SIZE] = chunk
+                    .try_into()
+                    .map_err(|_| "Failed to convert chunk to byte array")?;
+
+                results_data.push(bytemuck::from_bytes::<SdfResult>(&bytes).clone());
             }
-            None => {
-                // Mapping not ready yet, keep waiting
+
+            Ok(results_data)
+        })();
+
+        // Always unmap the buffer regardless of success/failure
+        readback_buffer.unmap();
+
+        // Send results through oneshot channel
+        match read_result {
+            Ok(results_data) => mapping.batch.respond(results_data),
+            Err(err) => {
+                eprintln!("Failed to read buffer data: {:?}", err);
+                // Send empty results on error
+                mapping.batch.respond(vec![]);
             }
         }
     }
 
-    // Start a new mapping if we have no pending mapping and requests are ready
-    if pending_requests.pending_mapping.is_none() && !pending_requests.ready_for_mapping.is_empty()
-    {
-        // Process one request at a time to minimize GPU impact
-        let request = pending_requests.ready_for_mapping.remove(0);
-
+    // Start mapping the results of the batch dispatched this frame
+    if let Some((batch, slot)) = pending_requests.dispatched.take() {
         // Map the readback buffer to read results
-        let buffer_slice = buffers.readback_buffer.slice(..);
+        let buffer_slice = buffers.readback_slots[slot].buffer.slice(..);
         let (tx, rx) = crossbeam_channel::unbounded::<()>();
-        info!("MAP ASYNC!");
         buffer_slice.map_async(MapMode::Read, move |result| match result {
             Ok(_) => {
                 let _ = tx.send(());
@@ -420,9 +701,15 @@ fn perform_delayed_readback(
             }
         });
 
-        // Store the pending mapping for next frame
-        pending_requests.pending_mapping = Some((request, rx));
+        // Checked from the next frame on, while later batches use the other slots
+        pending_requests.pending_mappings.push(PendingMapping {
+            batch,
+            slot,
+            mapped: rx,
+        });
     }
+
+    in_flight.0.store(pending_requests.in_flight(), Ordering::Relaxed);
 }
 
 /// Label to identify the SDF compute node in the render graph
@@ -445,6 +732,7 @@ impl render_graph::Node for SdfComputeNode {
 
         if let Some(bind_groups) = world.get_resource::<SdfComputeBindGroups>() {
             if let Some(compute_pipeline) = pipeline_cache.get_compute_pipeline(pipeline.pipeline) {
+                let diagnostics = render_context.diagnostic_recorder();
                 let mut pass =
                     render_context
                         .command_encoder()
@@ -452,6 +740,7 @@ impl render_graph::Node for SdfComputeNode {
                             label: Some("sdf_compute_pass"),
                             ..default()
                         });
+                let pass_span = diagnostics.pass_span(&mut pass, "sdf_compute_pass");
 
                 let settings_index = 0;
 
@@ -459,21 +748,16 @@ impl render_graph::Node for SdfComputeNode {
                 pass.set_bind_group(1, &bind_groups.sdf_bind_group, &[settings_index]);
                 pass.set_pipeline(compute_pipeline);
 
-                // Dispatch workgroups based on pending requests
+                // One invocation per point of the whole batch
                 let pending_requests = world.resource::<PendingSdfRequests>();
-                if !pending_requests.requests.is_empty()
-                    && pending_requests.pending_mapping.is_none()
-                {
-                    let max_points = pending_requests
-                        .requests
-                        .iter()
-                        .map(|req| req.points.len())
-                        .max()
-                        .unwrap_or(0);
-                    let workgroups = (max_points as u32 + 63) / 64; // 64 threads per workgroup
+                if let Some((batch, _)) = &pending_requests.dispatched {
+                    let points_count = batch.points_count() as u32;
+                    let workgroups = points_count.div_ceil(64); // 64 threads per workgroup
 
                     pass.dispatch_workgroups(workgroups, 1, 1);
                 }
+
+                pass_span.end(&mut pass);
             }
         }
 
@@ -481,13 +765,13 @@ impl render_graph::Node for SdfComputeNode {
         let buffers = world.resource::<SdfComputeBuffers>();
         let pending_requests = world.resource::<PendingSdfRequests>();
 
-        if !pending_requests.requests.is_empty() && pending_requests.pending_mapping.is_none() {
+        if let Some((batch, slot)) = &pending_requests.dispatched {
             render_context.command_encoder().copy_buffer_to_buffer(
                 &buffers.results_buffer,
                 0,
-                &buffers.readback_buffer,
+                &buffers.readback_slots[*slot].buffer,
                 0,
-                (buffers.current_capacity * std::mem::size_of::<SdfResult>()) as u64,
+                (batch.points_count() * std::mem::size_of::<SdfResult>()) as u64,
             );
         }
 
@@ -495,16 +779,69 @@ impl render_graph::Node for SdfComputeNode {
     }
 }
 
-/// Public API function to evaluate SDF at given points (async)
+/// Public API function to evaluate SDF at given points (async). The results
+/// carry distances only, their normals are zero
 pub async fn evaluate_sdf_async(
     points: Vec<Vec2>,
     sender: &SdfEvaluationSender,
 ) -> Result<Vec<SdfResult>, oneshot::Canceled> {
-    let (response_tx, response_rx) = oneshot::channel();
-    let request = SdfEvaluationRequest {
-        points,
-        response_tx,
+    let query = SdfQuery::Raymarch {
+        uvs: points,
+        with_gradient: false,
     };
+    request_evaluation(query, sender).await
+}
+
+/// Like [`evaluate_sdf_async`], but also computes the surface normal of every
+/// hit. Misses keep a zero normal, which tells them apart from hits
+pub async fn evaluate_sdf_with_gradient_async(
+    points: Vec<Vec2>,
+    sender: &SdfEvaluationSender,
+) -> Result<Vec<SdfResult>, oneshot::Canceled> {
+    let query = SdfQuery::Raymarch {
+        uvs: points,
+        with_gradient: true,
+    };
+    request_evaluation(query, sender).await
+}
+
+/// Casts world space rays against the SDF scene (async), independent of the
+/// camera. Hits carry their distance along the ray, position and normal,
+/// misses a zero normal and `max_distance`
+pub async fn raycast_sdf_async(
+    rays: Vec<Ray3d>,
+    max_distance: f32,
+    sender: &SdfEvaluationSender,
+) -> Result<Vec<SdfResult>, oneshot::Canceled> {
+    request_evaluation(SdfQuery::Raycast { rays, max_distance }, sender).await
+}
+
+/// Moves world points onto the closest point of the SDF surface (async), for
+/// tools that stick things to the sculpt. Each result holds the surface point
+/// in `position`, its normal, and the query point's signed distance
+pub async fn project_to_surface_async(
+    points: Vec<Vec3>,
+    sender: &SdfEvaluationSender,
+) -> Result<Vec<SdfResult>, oneshot::Canceled> {
+    request_evaluation(SdfQuery::Project { points }, sender).await
+}
+
+/// Signed distance of the scene at each world point (async), without any
+/// marching. Cheap enough for many thousands of points, e.g. to integrate over
+/// the volume
+pub async fn sample_sdf_async(
+    points: Vec<Vec3>,
+    sender: &SdfEvaluationSender,
+) -> Result<Vec<SdfResult>, oneshot::Canceled> {
+    request_evaluation(SdfQuery::Sample { points }, sender).await
+}
+
+async fn request_evaluation(
+    query: SdfQuery,
+    sender: &SdfEvaluationSender,
+) -> Result<Vec<SdfResult>, oneshot::Canceled> {
+    let (response_tx, response_rx) = oneshot::channel();
+    let request = SdfEvaluationRequest { query, response_tx };
 
     let _ = sender.0.send(request);
 
@@ -0,0 +1,195 @@
+//! CPU mirror of the scene SDF in sdf_common.wgsl
+//!
+//! Used by exports and bakes that need to sample the scene without a GPU
+//! round trip. Keep the combine step in sync with `evaluate_scene_sdf_with_bvh`.
+
+use bevy::prelude::*;
+
+use crate::sdf_render::{SDFRenderEntity, SdfOperation, SdfPrimitive};
+
+// Must match SUBTRACT_SMOOTHING in sdf_common.wgsl
+const SUBTRACT_SMOOTHING: f32 = 0.05;
+
+const NORMAL_EPSILON: f32 = 0.001;
+const AO_SAMPLES: usize = 5;
+const AO_STEP: f32 = 0.06;
+
+fn quadratic_smin(a: f32, b: f32, k: f32) -> f32 {
+    let k4 = k * 4.0;
+    let h = (k4 - (a - b).abs()).max(0.0) / k4;
+    a.min(b) - h * h * k4 * 0.25
+}
+
+fn quadratic_smax(a: f32, b: f32, k: f32) -> f32 {
+    -quadratic_smin(-a, -b, k)
+}
+
+fn hash3(p: Vec3) -> f32 {
+    // WGSL fract is x - floor(x), which differs from f32::fract for negatives
+    let x = p.dot(Vec3::new(127.1, 311.7, 74.7)).sin() * 43758.5453;
+    x - x.floor()
+}
+
+/// Value noise in [-1, 1] (mirrors `value_noise`)
+pub fn value_noise(p: Vec3) -> f32 {
+    let i = p.floor();
+    let f = p - i;
+    let u = f * f * (Vec3::splat(3.0) - 2.0 * f);
+
+    let n = |x: f32, y: f32, z: f32| hash3(i + Vec3::new(x, y, z));
+    let lerp = |a: f32, b: f32, t: f32| a + (b - a) * t;
+
+    let nx00 = lerp(n(0., 0., 0.), n(1., 0., 0.), u.x);
+    let nx10 = lerp(n(0., 1., 0.), n(1., 1., 0.), u.x);
+    let nx01 = lerp(n(0., 0., 1.), n(1., 0., 1.), u.x);
+    let nx11 = lerp(n(0., 1., 1.), n(1., 1., 1.), u.x);
+    let nxy0 = lerp(nx00, nx10, u.y);
+    let nxy1 = lerp(nx01, nx11, u.y);
+    lerp(nxy0, nxy1, u.z) * 2.0 - 1.0
+}
+
+/// Distance to a single entity's primitive (mirrors `entity_sdf`)
+pub fn entity_distance(entity: &SDFRenderEntity, point: Vec3) -> f32 {
+    let q = point - entity.position;
+    let mut distance = match entity.primitive {
+        SdfPrimitive::Sphere => q.length() - entity.scale,
+        SdfPrimitive::Ellipsoid { radii } => {
+            let k0 = (q / radii).length();
+            let k1 = (q / (radii * radii)).length();
+            k0 * (k0 - 1.0) / k1.max(0.000001)
+        }
+        SdfPrimitive::Capsule { half_segment } => {
+            let pa = q + half_segment;
+            let ba = half_segment * 2.0;
+            let h = (pa.dot(ba) / ba.length_squared().max(0.000001)).clamp(0.0, 1.0);
+            (pa - ba * h).length() - entity.scale
+        }
+    };
+
+    let displacement = entity.displacement;
+    if displacement.amplitude != 0.0 {
+        distance += displacement.amplitude * value_noise(q * displacement.frequency);
+    }
+
+    if entity.shell_thickness > 0.0 {
+        distance = distance.abs() - entity.shell_thickness;
+    }
+
+    distance
+}
+
+/// Snapshot of the scene entities that can be sampled on the CPU
+#[derive(Clone, Default)]
+pub struct SceneSdf {
+    entities: Vec<SDFRenderEntity>,
+    // SdfBlend::factor of the scene
+    blend_factor: f32,
+}
+
+impl SceneSdf {
+    pub fn from_entities<'a>(
+        entities: impl IntoIterator<Item = &'a SDFRenderEntity>,
+        blend_factor: f32,
+    ) -> Self {
+        let mut entities: Vec<&SDFRenderEntity> = entities.into_iter().collect();
+        entities.sort_by_key(|e| e.node_index);
+        Self {
+            entities: entities.iter().flat_map(|e| e.instances()).collect(),
+            blend_factor,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entities.is_empty()
+    }
+
+    /// Signed distance from `point` to the scene surface
+    pub fn distance(&self, point: Vec3) -> f32 {
+        let mut distance = 999999.0;
+        let mut carve_distance: f32 = 999999.0;
+        let mut processed_any = false;
+
+        for entity in &self.entities {
+            let entity_distance = entity_distance(entity, point);
+            match entity.operation {
+                SdfOperation::Subtract => {
+                    carve_distance = carve_distance.min(entity_distance);
+                }
+                SdfOperation::Union => {
+                    distance = if processed_any {
+                        let blend_radius = entity.blend_radius(self.blend_factor);
+                        quadratic_smin(distance, entity_distance, blend_radius)
+                    } else {
+                        entity_distance
+                    };
+                    processed_any = true;
+                }
+            }
+        }
+
+        quadratic_smax(distance, -carve_distance, SUBTRACT_SMOOTHING)
+    }
+
+    /// Surface normal from central differences of the distance field
+    pub fn normal(&self, point: Vec3) -> Vec3 {
+        let e = NORMAL_EPSILON;
+        Vec3::new(
+            self.distance(point + Vec3::X * e) - self.distance(point - Vec3::X * e),
+            self.distance(point + Vec3::Y * e) - self.distance(point - Vec3::Y * e),
+            self.distance(point + Vec3::Z * e) - self.distance(point - Vec3::Z * e),
+        )
+        .normalize_or_zero()
+    }
+
+    /// Ambient occlusion in [0, 1] (1 = unoccluded) by stepping along the normal
+    pub fn ambient_occlusion(&self, point: Vec3, normal: Vec3) -> f32 {
+        let mut occlusion = 0.0;
+        let mut weight = 1.0;
+        for i in 1..=AO_SAMPLES {
+            let step = AO_STEP * i as f32;
+            let distance = self.distance(point + normal * step);
+            occlusion += (step - distance).max(0.0) * weight;
+            weight *= 0.5;
+        }
+        (1.0 - occlusion * 3.0).clamp(0.0, 1.0)
+    }
+
+    /// Bounds of the additive entities, padded by their blend radius
+    pub fn bounds(&self) -> Option<(Vec3, Vec3)> {
+        self.entities
+            .iter()
+            .filter(|e| e.operation == SdfOperation::Union)
+            .map(|e| {
+                let padding = e.blend_radius(self.blend_factor)
+                    + e.displacement.amplitude.abs()
+                    + e.shell_thickness;
+                let half_size = e.primitive.half_extents(e.scale) + Vec3::splat(padding);
+                (e.position - half_size, e.position + half_size)
+            })
+            .reduce(|(min_a, max_a), (min_b, max_b)| (min_a.min(min_b), max_a.max(max_b)))
+    }
+}
+
+/// Shading detail baked for a set of surface samples
+pub struct BakedShading {
+    pub ambient_occlusion: Vec<f32>,
+    pub normals: Vec<Vec3>,
+}
+
+/// Evaluate AO and SDF normals for every sample position
+///
+/// The samples are expected to lie on (or very near) the surface, e.g. texel
+/// centers of an exported mesh projected into world space.
+pub fn bake_shading(sdf: &SceneSdf, positions: &[Vec3]) -> BakedShading {
+    let normals: Vec<Vec3> = positions.iter().map(|p| sdf.normal(*p)).collect();
+    let ambient_occlusion = positions
+        .iter()
+        .zip(normals.iter())
+        .map(|(p, n)| sdf.ambient_occlusion(*p, *n))
+        .collect();
+
+    BakedShading {
+        ambient_occlusion,
+        normals,
+    }
+}
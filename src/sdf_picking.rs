@@ -0,0 +1,145 @@
+//! Picking for entities without a proxy mesh
+//!
+//! `MeshPickingPlugin` only sees entities that have a `Mesh3d`. For clicks that
+//! hit no mesh, the cursor ray is cast against the SDF scene on the GPU through the compute
+//! module and the entity whose surface is closest to the hit point is selected.
+
+use bevy::picking::{hover::HoverMap, pointer::PointerId};
+use bevy::prelude::*;
+use bevy::tasks::{block_on, futures_lite::future, Task};
+use bevy::window::PrimaryWindow;
+
+use crate::mode::AppModeState;
+use crate::overlay::OverlayCamera;
+use crate::pipeline_warmup::PipelineWarmupState;
+use crate::sdf_compute::{raycast_sdf_async, SdfEvaluationSender};
+use crate::sdf_cpu::entity_distance;
+use crate::sdf_render::SDFRenderEntity;
+use crate::selection::{click_select, SelectionState};
+use crate::translation::{DragData, Translatable};
+
+// Cursor travel in pixels beyond which a press is a camera pan, not a click
+const CLICK_TOLERANCE: f32 = 4.0;
+
+// How far from an entity's own surface the scene hit may be, since blending
+// pulls the combined surface away from the individual primitives
+const PICK_TOLERANCE: f32 = 0.1;
+
+// Furthest a click can pick along the cursor ray
+const PICK_DISTANCE: f32 = 50.0;
+
+pub struct SdfPickingPlugin;
+
+impl Plugin for SdfPickingPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SdfPick>()
+            .add_systems(Update, pick_sdf_entities);
+    }
+}
+
+#[derive(Resource, Default)]
+struct SdfPick {
+    // Cursor position when the left button went down
+    press_position: Option<Vec2>,
+    // Pending ray-march for the clicked point, and whether Shift was held
+    task: Option<Task<Option<Vec3>>>,
+    shift: bool,
+}
+
+fn pick_sdf_entities(
+    mode_state: Res<AppModeState>,
+    window: Single<&Window, With<PrimaryWindow>>,
+    buttons: Res<ButtonInput<MouseButton>>,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    sdf_sender: Res<SdfEvaluationSender>,
+    camera_query: Query<(&Camera, &GlobalTransform, &OverlayCamera)>,
+    warmup: Res<PipelineWarmupState>,
+    hover_map: Res<HoverMap>,
+    drag_data: Res<DragData>,
+    mut pick: ResMut<SdfPick>,
+    mut commands: Commands,
+    mut selection_state: ResMut<SelectionState>,
+    sdf_entities: Query<(Entity, &SDFRenderEntity, Has<Mesh3d>)>,
+    translatables: Query<(), With<Translatable>>,
+    parents: Query<&ChildOf>,
+) {
+    if !mode_state.is_selection_enabled() || !warmup.is_ready() {
+        *pick = SdfPick::default();
+        return;
+    }
+
+    if buttons.just_pressed(MouseButton::Left) {
+        pick.press_position = window.cursor_position();
+    }
+
+    if buttons.just_released(MouseButton::Left) && pick.task.is_none() {
+        let press_position = pick.press_position.take();
+        // Meshes under the cursor are handled by mesh picking
+        let over_mesh = hover_map
+            .get(&PointerId::Mouse)
+            .is_some_and(|hits| !hits.is_empty());
+        let is_click = matches!(
+            (press_position, window.cursor_position()),
+            (Some(pressed), Some(released)) if pressed.distance(released) < CLICK_TOLERANCE
+        );
+
+        if is_click && !over_mesh && matches!(*drag_data, DragData::Idle) {
+            let Some(viewport_position) = window.cursor_position() else {
+                return;
+            };
+            let Ok((camera, camera_transform, _)) = camera_query.single() else {
+                return;
+            };
+            let Ok(ray) = camera.viewport_to_world(camera_transform, viewport_position) else {
+                return;
+            };
+            let sender_clone = sdf_sender.clone();
+            pick.shift = keyboard_input.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+            pick.task = Some(bevy::tasks::AsyncComputeTaskPool::get().spawn(async move {
+                let results = raycast_sdf_async(vec![ray], PICK_DISTANCE, &sender_clone).await;
+                // Misses come back without a normal
+                results
+                    .ok()?
+                    .first()
+                    .filter(|r| r.normal != Vec3::ZERO)
+                    .map(|r| r.position)
+            }));
+        }
+    }
+
+    let Some(task) = &mut pick.task else {
+        return;
+    };
+    let Some(hit) = block_on(future::poll_once(task)) else {
+        return;
+    };
+    pick.task = None;
+
+    let Some(hit) = hit else {
+        return;
+    };
+
+    let closest = sdf_entities
+        .iter()
+        .map(|(entity, sdf_entity, has_mesh)| {
+            (entity, entity_distance(sdf_entity, hit).abs(), has_mesh)
+        })
+        .filter(|(_, distance, _)| *distance < PICK_TOLERANCE)
+        .min_by(|a, b| a.1.total_cmp(&b.1));
+
+    // Entities with a mesh would have been hit by mesh picking already
+    let Some((entity, _, false)) = closest else {
+        return;
+    };
+
+    // Children of groups select their group
+    let Some(target) = std::iter::once(entity)
+        .chain(parents.iter_ancestors(entity))
+        .find(|e| translatables.contains(*e))
+    else {
+        return;
+    };
+
+    let shift = pick.shift;
+    click_select(&mut commands, &mut selection_state, target, shift);
+}
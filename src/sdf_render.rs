@@ -1,10 +1,18 @@
 use bevy::{
     core_pipeline::{
-        core_3d::graph::{Core3d, Node3d},
+        core_3d::{
+            graph::{Core3d, Node3d},
+            CORE_3D_DEPTH_FORMAT,
+        },
         fullscreen_vertex_shader::fullscreen_shader_vertex_state,
+        fxaa::{Fxaa, Sensitivity},
         prepass::ViewPrepassTextures,
     },
-    ecs::query::QueryItem,
+    diagnostic::{Diagnostic, DiagnosticPath, Diagnostics, RegisterDiagnostic},
+    ecs::{
+        component::HookContext, query::QueryItem, system::SystemParam, world::DeferredWorld,
+    },
+    platform::time::Instant,
     prelude::*,
     render::{
         extract_component::{
@@ -16,11 +24,19 @@ use bevy::{
             NodeRunError, RenderGraphApp, RenderGraphContext, RenderLabel, ViewNode, ViewNodeRunner,
         },
         render_resource::{
-            binding_types::{sampler, texture_2d, uniform_buffer},
+            binding_types::{
+                sampler, storage_buffer_read_only_sized, texture_2d, texture_3d, uniform_buffer,
+            },
             Buffer, BufferDescriptor, BufferUsages, *,
         },
+        render_asset::RenderAssets,
+        render_phase::TrackedRenderPass,
+        storage::GpuShaderStorageBuffer,
         renderer::{RenderContext, RenderDevice, RenderQueue},
-        view::ViewTarget,
+        texture::{FallbackImage, GpuImage},
+        camera::{CameraMainTextureUsages, CameraProjection, ExtractedCamera},
+        diagnostic::RecordDiagnostics,
+        view::{RenderLayers, ViewDepthTexture, ViewTarget},
         Render, RenderApp, RenderSet,
     },
 };
@@ -32,18 +48,57 @@ use bvh::{
 };
 use bytemuck::Pod;
 use nalgebra::{Point3, Vector3};
+use std::ops::Range;
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc,
+};
+use std::time::Duration;
+
+use crate::adaptive_resolution::{ScaledSdfTargets, SdfUpscalePipeline, SCALED_DEPTH_FORMAT};
+use crate::brick_map::BrickMapBuffers;
+use crate::coarse_tuning::SdfMarchStatsBuffer;
+use crate::progress::{AppEvent, Operation};
+use crate::sdf_cpu::entity_distance;
+use crate::sdf_volume_cache::SdfVolumeTextures;
+use crate::selection::SelectionState;
+use crate::temporal_accumulation::{SdfHistoryTextures, HISTORY_DEPTH_FORMAT};
+use crate::view_culling::SdfViewEntitiesBuffer;
 
 /// This example uses a shader source file from the assets subdirectory
 const SHADER_ASSET_PATH: &str = "shaders/sdf_render.wgsl";
 
+// Scene and upload statistics, shown in the perf UI
+pub const SDF_ENTITY_COUNT: DiagnosticPath = DiagnosticPath::const_new("sdf/entity_count");
+pub const SDF_BVH_NODE_COUNT: DiagnosticPath = DiagnosticPath::const_new("sdf/bvh_node_count");
+pub const SDF_BVH_REBUILD_TIME: DiagnosticPath =
+    DiagnosticPath::const_new("sdf/bvh_rebuild_time");
+pub const SDF_ENTITY_UPLOAD_BYTES: DiagnosticPath =
+    DiagnosticPath::const_new("sdf/entity_upload_bytes");
+pub const SDF_COARSE_PASS_WIDTH: DiagnosticPath =
+    DiagnosticPath::const_new("sdf/coarse_pass_width");
+pub const SDF_COARSE_PASS_HEIGHT: DiagnosticPath =
+    DiagnosticPath::const_new("sdf/coarse_pass_height");
+
+// GPU time of the passes, resolved from their timestamp spans by the render diagnostics
+pub const SDF_RENDER_PASS_GPU_TIME: DiagnosticPath =
+    DiagnosticPath::const_new("render/sdf_render_pass/elapsed_gpu");
+pub const SDF_COARSE_PREPASS_GPU_TIME: DiagnosticPath =
+    DiagnosticPath::const_new("render/sdf_coarse_prepass/elapsed_gpu");
+
 // Resource to hold transform data in the render world
 #[derive(Resource)]
 pub struct EntityBuffer {
     pub buffer: Option<Buffer>,
-    pub data: Vec<Vec4>,
+    pub data: Vec<GpuSdfEntity>,
     pub capacity: usize,
 }
 
+// Bytes of entity data written to the GPU since the main world last recorded
+// them, shared between both worlds
+#[derive(Resource, Clone, Default)]
+struct EntityUploadBytes(Arc<AtomicU64>);
+
 // Buffer for BVH data
 #[derive(Resource)]
 pub struct BVHBuffer {
@@ -52,6 +107,13 @@ pub struct BVHBuffer {
     pub capacity: usize,
 }
 
+// Buffer for the lights the SDF is shaded with
+#[derive(Resource, Default)]
+pub struct LightBuffer {
+    pub buffer: Option<Buffer>,
+    pub capacity: usize,
+}
+
 impl Default for EntityBuffer {
     fn default() -> Self {
         Self {
@@ -72,26 +134,310 @@ impl Default for BVHBuffer {
     }
 }
 
+// How an entity is combined with the rest of the scene
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, serde::Serialize, serde::Deserialize)]
+pub enum SdfOperation {
+    #[default]
+    Union,
+    Subtract,
+}
+
+impl SdfOperation {
+    // Must match the OPERATION_* constants in sdf_common.wgsl
+    pub fn as_gpu(&self) -> u32 {
+        match self {
+            SdfOperation::Union => 0,
+            SdfOperation::Subtract => 1,
+        }
+    }
+
+    pub fn from_gpu(value: u32) -> Self {
+        match value {
+            1 => SdfOperation::Subtract,
+            _ => SdfOperation::Union,
+        }
+    }
+}
+
+// Shape of an entity. For spheres and capsules `scale` on the entity is the radius,
+// for ellipsoids it is the largest radius.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum SdfPrimitive {
+    #[default]
+    Sphere,
+    Ellipsoid {
+        radii: Vec3,
+    },
+    // Segment from `position - half_segment` to `position + half_segment`
+    Capsule {
+        half_segment: Vec3,
+    },
+}
+
+impl SdfPrimitive {
+    // Must match the PRIMITIVE_* constants in sdf_common.wgsl
+    pub fn as_gpu(&self) -> u32 {
+        match self {
+            SdfPrimitive::Sphere => 0,
+            SdfPrimitive::Ellipsoid { .. } => 1,
+            SdfPrimitive::Capsule { .. } => 2,
+        }
+    }
+
+    // Primitive-specific parameters packed into GpuSdfEntity::shape
+    pub fn gpu_shape(&self) -> Vec3 {
+        match self {
+            SdfPrimitive::Sphere => Vec3::ZERO,
+            SdfPrimitive::Ellipsoid { radii } => *radii,
+            SdfPrimitive::Capsule { half_segment } => *half_segment,
+        }
+    }
+
+    pub fn from_gpu(value: u32, shape: Vec3) -> Self {
+        match value {
+            1 => SdfPrimitive::Ellipsoid { radii: shape },
+            2 => SdfPrimitive::Capsule {
+                half_segment: shape,
+            },
+            _ => SdfPrimitive::Sphere,
+        }
+    }
+
+    // Scale and shape after stretching by `factor` along each axis. Spheres
+    // stretched unevenly turn into ellipsoids.
+    pub fn scaled(&self, scale: f32, factor: Vec3) -> (f32, SdfPrimitive) {
+        match self {
+            SdfPrimitive::Sphere if factor.min_element() == factor.max_element() => {
+                (scale * factor.x, SdfPrimitive::Sphere)
+            }
+            SdfPrimitive::Sphere => {
+                let radii = Vec3::splat(scale) * factor;
+                (radii.max_element(), SdfPrimitive::Ellipsoid { radii })
+            }
+            SdfPrimitive::Ellipsoid { radii } => {
+                let radii = *radii * factor;
+                (radii.max_element(), SdfPrimitive::Ellipsoid { radii })
+            }
+            SdfPrimitive::Capsule { half_segment } => (
+                scale * factor.min_element(),
+                SdfPrimitive::Capsule {
+                    half_segment: *half_segment * factor,
+                },
+            ),
+        }
+    }
+
+    // Shape after turning by `rotation` about the entity's position. Ellipsoids
+    // stay axis aligned, so they take the extents of the turned ellipsoid, which
+    // is exact for quarter turns.
+    pub fn rotated(&self, rotation: Quat) -> SdfPrimitive {
+        match self {
+            SdfPrimitive::Sphere => SdfPrimitive::Sphere,
+            SdfPrimitive::Ellipsoid { radii } => {
+                let m = Mat3::from_quat(rotation);
+                let squared =
+                    Mat3::from_cols(m.x_axis * m.x_axis, m.y_axis * m.y_axis, m.z_axis * m.z_axis);
+                SdfPrimitive::Ellipsoid {
+                    radii: (squared * (*radii * *radii)).sqrt(),
+                }
+            }
+            SdfPrimitive::Capsule { half_segment } => SdfPrimitive::Capsule {
+                half_segment: rotation * *half_segment,
+            },
+        }
+    }
+
+    // Mesh standing in for the shape in mesh picking, centered on the entity
+    pub fn proxy_mesh(&self, scale: f32) -> Mesh {
+        match self {
+            SdfPrimitive::Sphere => Mesh::from(Sphere { radius: scale }),
+            SdfPrimitive::Ellipsoid { radii } => {
+                Mesh::from(Sphere { radius: 1. }).scaled_by(*radii)
+            }
+            SdfPrimitive::Capsule { half_segment } => {
+                Mesh::from(Capsule3d::new(scale, half_segment.length() * 2.)).rotated_by(
+                    Quat::from_rotation_arc(Vec3::Y, half_segment.normalize_or(Vec3::Y)),
+                )
+            }
+        }
+    }
+
+    // Half extents of the shape for an entity with the given scale
+    pub fn half_extents(&self, scale: f32) -> Vec3 {
+        match self {
+            SdfPrimitive::Sphere => Vec3::splat(scale),
+            SdfPrimitive::Ellipsoid { radii } => *radii,
+            SdfPrimitive::Capsule { half_segment } => half_segment.abs() + Vec3::splat(scale),
+        }
+    }
+}
+
+// Noise added to the distance of an entity, e.g. to make spheres look like rocks
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct NoiseDisplacement {
+    pub amplitude: f32,
+    pub frequency: f32,
+}
+
+// Most instances a repetition expands to; every instance is a GPU entity, so
+// larger counts are clamped
+pub const MAX_REPETITIONS: u32 = 64;
+
+// Repeats an entity `count` times, each instance offset by `spacing` from the previous.
+// The ECS entity is the base instance, so moving it with the gizmo moves the whole array.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Repetition {
+    pub count: u32,
+    pub spacing: Vec3,
+}
+
+impl Default for Repetition {
+    fn default() -> Self {
+        Self {
+            count: 1,
+            spacing: Vec3::ZERO,
+        }
+    }
+}
+
+// Default radius over which an entity blends into the ones before it,
+// relative to its scale
+pub const BLEND_FACTOR: f32 = 0.5;
+
+// Blend factor of the scene, saved with it and changed live by OSC controls.
+// The shaders get the resulting radius through GpuSdfEntity, the CPU SDF, mesh
+// colors and exports are handed the factor.
+#[derive(Resource, Clone, Copy, Debug, PartialEq)]
+pub struct SdfBlend {
+    pub factor: f32,
+}
+
+impl Default for SdfBlend {
+    fn default() -> Self {
+        Self {
+            factor: BLEND_FACTOR,
+        }
+    }
+}
+
 // Component to mark entities whose transforms should be sent to the shader
 #[derive(Component, Clone, Debug, PartialEq)]
 pub struct SDFRenderEntity {
     pub node_index: usize,
     pub position: Vec3,
     pub scale: f32,
+    pub operation: SdfOperation,
+    pub primitive: SdfPrimitive,
+    pub displacement: NoiseDisplacement,
+    // Turns the entity into a hollow shell of this thickness when > 0
+    pub shell_thickness: f32,
+    pub repetition: Repetition,
+}
+
+impl SDFRenderEntity {
+    // Rebuild an (already expanded) instance from its GPU representation
+    pub fn from_gpu(node_index: usize, e: &GpuSdfEntity) -> Self {
+        Self {
+            position: e.position_scale.truncate(),
+            scale: e.position_scale.w,
+            operation: SdfOperation::from_gpu(e.operation),
+            primitive: SdfPrimitive::from_gpu(e.primitive, e.shape.truncate()),
+            displacement: NoiseDisplacement {
+                amplitude: e.modifiers.x,
+                frequency: e.modifiers.y,
+            },
+            shell_thickness: e.modifiers.z,
+            repetition: Repetition::default(),
+            node_index,
+        }
+    }
+
+    pub fn blend_radius(&self, blend_factor: f32) -> f32 {
+        self.scale * blend_factor
+    }
+
+    // Expand the repetition modifier into the individual instances seen by the BVH
+    pub fn instances(&self) -> impl Iterator<Item = SDFRenderEntity> + '_ {
+        (0..self.repetition.count.clamp(1, MAX_REPETITIONS)).map(move |i| SDFRenderEntity {
+            position: self.position + self.repetition.spacing * i as f32,
+            repetition: Repetition::default(),
+            ..self.clone()
+        })
+    }
+}
+
+// Per-entity data as laid out on the GPU (must match SdfEntity in sdf_common.wgsl)
+#[repr(C)]
+#[derive(Clone, Copy, Pod, bytemuck::Zeroable, Debug)]
+pub struct GpuSdfEntity {
+    pub position_scale: Vec4,
+    // xyz: ellipsoid radii or capsule half segment, w: blend radius
+    pub shape: Vec4,
+    // x: noise amplitude, y: noise frequency, z: shell thickness, w: unused
+    pub modifiers: Vec4,
+    pub operation: u32,
+    pub primitive: u32,
+    // ENTITY_FLAG_* bits
+    pub flags: u32,
+    pub __padding: u32,
+}
+
+// Set on instances of selected entities (must match sdf_common.wgsl)
+pub const ENTITY_FLAG_SELECTED: u32 = 1;
+
+impl GpuSdfEntity {
+    pub fn new(entity: &SDFRenderEntity, blend_factor: f32) -> Self {
+        Self {
+            position_scale: entity.position.extend(entity.scale),
+            shape: entity.primitive.gpu_shape().extend(entity.blend_radius(blend_factor)),
+            modifiers: Vec4::new(
+                entity.displacement.amplitude,
+                entity.displacement.frequency,
+                entity.shell_thickness,
+                0.,
+            ),
+            operation: entity.operation.as_gpu(),
+            primitive: entity.primitive.as_gpu(),
+            flags: 0,
+            __padding: 0,
+        }
+    }
 }
 
-impl Bounded<f32, 3> for SDFRenderEntity {
+// Bounds from the GPU data, which carries the blend radius (must match
+// entity_half_extents in sdf_common.wgsl)
+impl Bounded<f32, 3> for GpuSdfEntity {
     fn aabb(&self) -> Aabb<f32, 3> {
-        let half_size = self.scale + 0.5; // add .5 for smoothing factor - parameterize this?
-        let half_size_v3 = Vector3::new(half_size, half_size, half_size);
-        let pos = Point3::new(self.position.x, self.position.y, self.position.z);
+        let scale = self.position_scale.w;
+        let primitive = SdfPrimitive::from_gpu(self.primitive, self.shape.truncate());
+        // At least .5 for smoothing, more when the blend radius is larger.
+        // Noise and shells can push the surface out by up to their amplitude/thickness
+        let padding = self.shape.w.max(0.5);
+        let half_size = primitive.half_extents(scale)
+            + Vec3::splat(padding + self.modifiers.x.abs() + self.modifiers.z);
+        let half_size_v3 = Vector3::new(half_size.x, half_size.y, half_size.z);
+        let position = self.position_scale.truncate();
+        let pos = Point3::new(position.x, position.y, position.z);
         let min = pos - half_size_v3;
         let max = pos + half_size_v3;
         Aabb::with_bounds(min, max)
     }
 }
 
-impl BHShape<f32, 3> for SDFRenderEntity {
+// An entity instance as a leaf of the BVH
+#[derive(Clone, Copy)]
+struct BvhShape {
+    entity: GpuSdfEntity,
+    node_index: usize,
+}
+
+impl Bounded<f32, 3> for BvhShape {
+    fn aabb(&self) -> Aabb<f32, 3> {
+        self.entity.aabb()
+    }
+}
+
+impl BHShape<f32, 3> for BvhShape {
     fn set_bh_node_index(&mut self, index: usize) {
         self.node_index = index;
     }
@@ -103,7 +449,62 @@ impl BHShape<f32, 3> for SDFRenderEntity {
 
 // Resource to transfer data from main world to render world
 #[derive(Resource, Clone)]
-struct EntityData(Vec<Vec4>);
+pub struct EntityData(Vec<GpuSdfEntity>);
+
+impl EntityData {
+    pub fn entities(&self) -> &[GpuSdfEntity] {
+        &self.0
+    }
+
+    // Min and max corner of the bounds around every entity, None for an empty scene
+    pub fn bounds(&self) -> Option<(Vec3, Vec3)> {
+        if self.0.is_empty() {
+            return None;
+        }
+        Some(
+            self.0
+                .iter()
+                .map(|e| e.aabb())
+                .fold((Vec3::INFINITY, Vec3::NEG_INFINITY), |(min, max), aabb| {
+                    (
+                        min.min(Vec3::new(aabb.min.x, aabb.min.y, aabb.min.z)),
+                        max.max(Vec3::new(aabb.max.x, aabb.max.y, aabb.max.z)),
+                    )
+                }),
+        )
+    }
+}
+
+// Light kinds (must match the LIGHT_* constants in sdf_render.wgsl)
+const LIGHT_POINT: f32 = 0.0;
+const LIGHT_DIRECTIONAL: f32 = 1.0;
+
+// Converts light intensities to shading values the way Bevy's default camera
+// exposure and Lambertian BRDF do, so the SDF and meshes respond to lights alike
+const LIGHT_EXPOSURE: f32 = 1.0 / (1000.0 * std::f32::consts::PI);
+
+// Per-light data as laid out on the GPU (must match SdfLight in sdf_render.wgsl)
+#[repr(C)]
+#[derive(Clone, Copy, Pod, bytemuck::Zeroable, Debug)]
+pub struct GpuSdfLight {
+    // xyz: position of a point light or direction towards a directional light,
+    // w: light kind
+    pub position: Vec4,
+    // rgb: color times exposed intensity, w: range of a point light
+    pub color: Vec4,
+}
+
+// Lights in the scene, collected every frame for the render world
+#[derive(Resource, Clone, Default)]
+pub struct SdfLightData(Vec<GpuSdfLight>);
+
+impl ExtractResource for SdfLightData {
+    type Source = SdfLightData;
+
+    fn extract_resource(source: &Self::Source) -> Self {
+        source.clone()
+    }
+}
 
 #[repr(C)]
 #[derive(Clone, Pod, bytemuck::Zeroable, std::marker::Copy, Debug)]
@@ -118,7 +519,148 @@ pub struct BVHNode {
 
 // Resource for flattened BVH
 #[derive(Resource, Clone)]
-struct FlattenedBVH(Vec<BVHNode>);
+pub struct FlattenedBVH(Vec<BVHNode>);
+
+impl FlattenedBVH {
+    /// Indices into the entity data of all leaves whose bounds overlap the sphere
+    pub fn query_sphere(&self, center: Vec3, radius: f32) -> Vec<usize> {
+        let overlaps = |node: &BVHNode| {
+            let closest = center.clamp(node.min.truncate(), node.max.truncate());
+            closest.distance_squared(center) <= radius * radius
+        };
+
+        let mut shapes = Vec::new();
+        let mut index = 0;
+        // Same stackless traversal as bvh_traverse_for_entities in sdf_common.wgsl
+        while let Some(node) = self.0.get(index) {
+            if !overlaps(node) {
+                index = node.exit_index as usize;
+            } else if node.shape_index != u32::MAX {
+                shapes.push(node.shape_index as usize);
+                index = node.exit_index as usize;
+            } else {
+                index = node.entry_index as usize;
+            }
+        }
+        shapes
+    }
+
+    // Recomputes the bounds of every node bottom-up from the shapes' current
+    // AABBs, keeping the tree structure. Fails if the tree has a different
+    // number of leaves than there are shapes, which needs a rebuild instead
+    fn refit(&mut self, shapes: &[GpuSdfEntity]) -> bool {
+        let leaves = self.0.iter().filter(|n| n.shape_index != u32::MAX).count();
+        if leaves != shapes.len() {
+            return false;
+        }
+
+        // Subtrees are flattened after the node that bounds them, so walking
+        // backwards refits children before their parents
+        for index in (0..self.0.len()).rev() {
+            let node = self.0[index];
+            if node.shape_index != u32::MAX {
+                continue;
+            }
+            let Some(child) = self.0.get(node.entry_index as usize).copied() else {
+                return false;
+            };
+            let (min, max) = if child.shape_index != u32::MAX {
+                // A leaf carries no bounds of its own, the node above it does
+                let Some(shape) = shapes.get(child.shape_index as usize) else {
+                    return false;
+                };
+                let aabb = shape.aabb();
+                (
+                    Vec3::new(aabb.min.x, aabb.min.y, aabb.min.z),
+                    Vec3::new(aabb.max.x, aabb.max.y, aabb.max.z),
+                )
+            } else {
+                // Otherwise the child is the first of two sibling bounds nodes
+                let Some(sibling) = self.0.get(child.exit_index as usize) else {
+                    return false;
+                };
+                (
+                    child.min.truncate().min(sibling.min.truncate()),
+                    child.max.truncate().max(sibling.max.truncate()),
+                )
+            };
+            self.0[index].min = min.map(gpu_friendly_f32).extend(0.);
+            self.0[index].max = max.map(gpu_friendly_f32).extend(0.);
+        }
+        true
+    }
+
+    // Summed surface area of all bounds, a cheap measure of how loose the tree is
+    fn surface_area(&self) -> f32 {
+        self.0
+            .iter()
+            .filter(|n| n.shape_index == u32::MAX)
+            .map(|n| {
+                let size = (n.max - n.min).truncate().max(Vec3::ZERO);
+                2.0 * (size.x * size.y + size.y * size.z + size.z * size.x)
+            })
+            .sum()
+    }
+}
+
+// Limits on full BVH rebuilds. While only positions and scales change the
+// existing tree is refit in place, which gets looser as shapes move apart
+#[derive(Resource, Clone)]
+pub struct BvhRebuildBudget {
+    // Minimum time between rebuilds caused by the tree getting loose
+    pub min_rebuild_interval: Duration,
+    // Rebuild once the refit tree's surface area exceeds the freshly built
+    // tree's by this factor
+    pub max_area_growth: f32,
+}
+
+// Rebuilds run within a frame, so only their completion is reported, and only
+// for ones slow enough to notice since every spawn rebuilds
+const REPORTED_BVH_REBUILD_TIME: Duration = Duration::from_millis(50);
+
+impl Default for BvhRebuildBudget {
+    fn default() -> Self {
+        Self {
+            min_rebuild_interval: Duration::from_millis(250),
+            max_area_growth: 1.5,
+        }
+    }
+}
+
+// ECS entity that owns each instance in EntityData (repetitions share an owner)
+#[derive(Resource, Clone, Default)]
+pub struct EntityInstanceOwners(Vec<Entity>);
+
+/// Main-world queries against the scene as it was last uploaded to the GPU
+#[derive(SystemParam)]
+pub struct SdfSceneQuery<'w> {
+    bvh: Res<'w, FlattenedBVH>,
+    entity_data: Option<Res<'w, EntityData>>,
+    owners: Option<Res<'w, EntityInstanceOwners>>,
+}
+
+impl SdfSceneQuery<'_> {
+    /// ECS entities with at least one instance whose surface lies within `radius` of `center`
+    pub fn entities_overlapping_sphere(&self, center: Vec3, radius: f32) -> Vec<Entity> {
+        let (Some(entity_data), Some(owners)) = (&self.entity_data, &self.owners) else {
+            return Vec::new();
+        };
+
+        let mut entities: Vec<Entity> = Vec::new();
+        for shape_index in self.bvh.query_sphere(center, radius) {
+            let (Some(gpu_entity), Some(owner)) =
+                (entity_data.0.get(shape_index), owners.0.get(shape_index))
+            else {
+                continue;
+            };
+            let instance = SDFRenderEntity::from_gpu(shape_index, gpu_entity);
+            if entity_distance(&instance, center) <= radius && !entities.contains(owner) {
+                entities.push(*owner);
+            }
+        }
+        entities
+    }
+}
 
 impl FromWorld for FlattenedBVH {
     fn from_world(_: &mut World) -> Self {
@@ -146,7 +688,42 @@ impl ExtractResource for EntityData {
 }
 
 /// It is generally encouraged to set up post processing effects as a plugin
-pub struct SDFRenderPlugin;
+#[derive(Default)]
+pub struct SDFRenderPlugin {
+    pub placement: SdfPassPlacement,
+    // Whether the SDF camera renders to an HDR target (`Camera::hdr`), which
+    // decides the color format the pass writes
+    pub hdr: bool,
+}
+
+// Where in the 3d render graph the SDF pass runs
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SdfPassPlacement {
+    // On the tonemapped image, the pass writes display-ready colors
+    #[default]
+    AfterTonemapping,
+    // Ahead of bloom and tonemapping, which then treat the SDF surface like the
+    // rest of the scene; meant for HDR cameras
+    BeforeTonemapping,
+}
+
+// Render world copy of the SDFRenderPlugin configuration
+#[derive(Resource, Clone, Copy)]
+pub struct SdfPassConfig {
+    pub placement: SdfPassPlacement,
+    pub hdr: bool,
+}
+
+impl SdfPassConfig {
+    // Format of the view target, and of the color targets standing in for it
+    pub fn color_format(&self) -> TextureFormat {
+        if self.hdr {
+            ViewTarget::TEXTURE_FORMAT_HDR
+        } else {
+            TextureFormat::bevy_default()
+        }
+    }
+}
 
 impl Plugin for SDFRenderPlugin {
     fn build(&self, app: &mut App) {
@@ -158,6 +735,7 @@ impl Plugin for SDFRenderPlugin {
             // It's important to derive [`ExtractComponent`] on [`SDFRenderSettings`]
             // for this plugin to work correctly.
             ExtractComponentPlugin::<SDFRenderSettings>::default(),
+            ExtractComponentPlugin::<SdfRenderCamera>::default(),
             // The settings will also be the data used in the shader.
             // This plugin will prepare the component for the GPU by creating a uniform buffer
             // and writing the data to that buffer every frame.
@@ -168,11 +746,34 @@ impl Plugin for SDFRenderPlugin {
             ExtractResourcePlugin::<SDFRenderEnabled>::default(),
             // Extract the FlattenedBVH from main world to render world
             ExtractResourcePlugin::<FlattenedBVH>::default(),
+            // Extract the environment map handle from main world to render world
+            ExtractResourcePlugin::<SdfEnvironment>::default(),
+            // Extract the scene lights from main world to render world
+            ExtractResourcePlugin::<SdfLightData>::default(),
         ))
         // Initialize the PostProcessEnabled resource
         .init_resource::<SDFRenderEnabled>()
+        .init_resource::<SdfGroundPlane>()
+        .init_resource::<ViewportAppearance>()
+        .init_resource::<SdfShadows>()
+        .init_resource::<SdfBlend>()
+        .init_resource::<SdfAntiAliasing>()
+        .init_resource::<SdfDebugView>()
+        .init_resource::<SdfLightData>()
+        .init_resource::<SdfAmbientOcclusion>()
+        .init_resource::<SdfFog>()
+        .init_resource::<SdfIsolateMode>()
+        .init_resource::<SdfEnvironment>()
         // Initialize the FlattenedBVH resource
         .init_resource::<FlattenedBVH>()
+        .init_resource::<BvhRebuildBudget>()
+        .init_resource::<EntityUploadBytes>()
+        .register_diagnostic(Diagnostic::new(SDF_ENTITY_COUNT))
+        .register_diagnostic(Diagnostic::new(SDF_BVH_NODE_COUNT))
+        .register_diagnostic(Diagnostic::new(SDF_BVH_REBUILD_TIME).with_suffix("ms"))
+        .register_diagnostic(Diagnostic::new(SDF_ENTITY_UPLOAD_BYTES).with_suffix("B"))
+        .register_diagnostic(Diagnostic::new(SDF_COARSE_PASS_WIDTH))
+        .register_diagnostic(Diagnostic::new(SDF_COARSE_PASS_HEIGHT))
         // Add the system to collect transform data
         .add_systems(
             Update,
@@ -183,9 +784,21 @@ impl Plugin for SDFRenderPlugin {
                 update_entity_count_in_settings,
                 update_bvh_node_count_in_settings,
                 update_time_in_settings,
+                update_ground_plane_in_settings,
+                update_viewport_appearance_in_settings,
+                collect_light_data,
+                update_ambient_occlusion_in_settings,
+                update_fog_in_settings,
+                update_isolate_in_settings,
+                update_environment_in_settings,
+                update_anti_aliasing,
+                update_debug_view_in_settings,
                 build_entity_bvh.after(collect_entity_data),
             ),
-        );
+        )
+        .add_systems(Update, record_sdf_diagnostics.after(build_entity_bvh));
+
+        let upload_bytes = app.world().resource::<EntityUploadBytes>().clone();
 
         // We need to get the render app from the main app
         let Some(render_app) = app.get_sub_app_mut(RenderApp) else {
@@ -193,15 +806,24 @@ impl Plugin for SDFRenderPlugin {
         };
 
         render_app
+            .insert_resource(upload_bytes)
+            .insert_resource(SdfPassConfig {
+                placement: self.placement,
+                hdr: self.hdr,
+            })
             .init_resource::<EntityBuffer>()
             // BVH
             .init_resource::<FlattenedBVH>()
             .init_resource::<BVHBuffer>()
+            .init_resource::<LightBuffer>()
             .add_systems(
                 Render,
                 (
                     manage_coarse_pass_texture.in_set(RenderSet::PrepareResources),
-                    update_transform_buffer.in_set(RenderSet::PrepareResources),
+                    update_transform_buffer
+                        .in_set(RenderSet::PrepareResources)
+                        .run_if(resource_exists_and_changed::<EntityData>),
+                    update_light_buffer.in_set(RenderSet::PrepareResources),
                     update_render_world_entity_count
                         .in_set(RenderSet::PrepareResources)
                         .after(update_transform_buffer),
@@ -212,7 +834,9 @@ impl Plugin for SDFRenderPlugin {
             )
             .add_systems(
                 Render,
-                update_bvh_buffer.in_set(RenderSet::PrepareResources),
+                update_bvh_buffer
+                    .in_set(RenderSet::PrepareResources)
+                    .run_if(resource_changed::<FlattenedBVH>),
             )
             .add_render_graph_node::<ViewNodeRunner<SDFCoarsePrepassNode>>(
                 Core3d,
@@ -223,17 +847,40 @@ impl Plugin for SDFRenderPlugin {
                 Core3d,
                 // It also needs the label of the node
                 SDFRenderLabel,
-            )
-            .add_render_graph_edges(
-                Core3d,
-                // Specify the node ordering: Tonemapping -> Coarse Prepass -> Main SDF -> End
-                (
-                    Node3d::Tonemapping,
-                    SDFCoarsePrepassLabel,
-                    SDFRenderLabel,
-                    Node3d::EndMainPassPostProcessing,
-                ),
             );
+
+        match self.placement {
+            SdfPassPlacement::AfterTonemapping => {
+                render_app.add_render_graph_edges(
+                    Core3d,
+                    // Specify the node ordering: Tonemapping -> Coarse Prepass -> Main SDF -> End
+                    (
+                        Node3d::Tonemapping,
+                        SDFCoarsePrepassLabel,
+                        SDFRenderLabel,
+                        Node3d::EndMainPassPostProcessing,
+                    ),
+                );
+            }
+            SdfPassPlacement::BeforeTonemapping => {
+                render_app
+                    .add_render_graph_edges(
+                        Core3d,
+                        // Main pass -> Coarse Prepass -> Main SDF -> Tonemapping
+                        (
+                            Node3d::EndMainPass,
+                            SDFCoarsePrepassLabel,
+                            SDFRenderLabel,
+                            Node3d::Tonemapping,
+                        ),
+                    )
+                    // Bloom has to pick up bright SDF surfaces as well
+                    .add_render_graph_edge(Core3d, SDFRenderLabel, Node3d::Bloom);
+            }
+        }
+
+        // FXAA has to smooth the SDF silhouettes, so it runs after the SDF pass
+        render_app.add_render_graph_edge(Core3d, SDFRenderLabel, Node3d::Fxaa);
     }
 
     fn finish(&self, app: &mut App) {
@@ -253,17 +900,26 @@ impl Plugin for SDFRenderPlugin {
 // System that runs in the main world to collect transform data
 fn collect_entity_data(
     changed_entities: Query<&SDFRenderEntity, Changed<SDFRenderEntity>>,
-    all_entities: Query<&SDFRenderEntity>,
+    mut removed_entities: RemovedComponents<SDFRenderEntity>,
+    all_entities: Query<(Entity, &SDFRenderEntity)>,
+    selection: Res<SelectionState>,
+    blend: Res<SdfBlend>,
     mut commands: Commands,
     entity_data: Option<Res<EntityData>>,
+    instance_owners: Option<Res<EntityInstanceOwners>>,
 ) {
+    // Despawned entities have to disappear from the buffer and BVH as well
+    let any_removed = removed_entities.read().count() > 0;
+    // The buffer flags selected entities for the isolate view
+    let selection_changed = selection.is_changed();
+
     // Check if we need to collect data
     let needs_update = if entity_data.is_none() {
         // First time - collect all entities
         true
     } else {
-        // Only update if entities have changed
-        !changed_entities.is_empty()
+        // Only update if entities or the blend radius have changed, or entities were removed
+        !changed_entities.is_empty() || any_removed || selection_changed || blend.is_changed()
     };
 
     if !needs_update {
@@ -275,24 +931,44 @@ fn collect_entity_data(
         all_entities.iter().count()
     );
 
-    let mut entities: Vec<&SDFRenderEntity> = all_entities.iter().collect();
-    entities.sort_by_key(|e| e.node_index);
+    let mut entities: Vec<(Entity, &SDFRenderEntity)> = all_entities.iter().collect();
+    entities.sort_by_key(|(_, e)| e.node_index);
 
-    let transforms: Vec<Vec4> = entities
-        .iter()
-        .map(|entity| {
-            let translation = entity.position;
-            let scale = entity.scale;
-            Vec4::new(translation.x, translation.y, translation.z, scale)
-        })
-        .collect();
-    // Send the data to the render world
-    commands.insert_resource(EntityData(transforms));
+    let mut transforms: Vec<GpuSdfEntity> = Vec::new();
+    let mut owners: Vec<Entity> = Vec::new();
+    for (owner, entity) in entities {
+        let flags = if selection.is_selected(owner) {
+            ENTITY_FLAG_SELECTED
+        } else {
+            0
+        };
+        for instance in entity.instances() {
+            transforms.push(GpuSdfEntity {
+                flags,
+                ..GpuSdfEntity::new(&instance, blend.factor)
+            });
+            owners.push(owner);
+        }
+    }
+
+    // Replacing the data marks it changed, which re-extracts and re-uploads it
+    // and refits the BVH, so identical data (say a selection change with the
+    // isolate flag unaffected) keeps the existing resources
+    let data_unchanged = entity_data.is_some_and(|data| {
+        bytemuck::cast_slice::<_, u8>(&data.0) == bytemuck::cast_slice::<_, u8>(&transforms)
+    });
+    if !data_unchanged {
+        // Send the data to the render world
+        commands.insert_resource(EntityData(transforms));
+    }
+    if !instance_owners.is_some_and(|existing| existing.0 == owners) {
+        commands.insert_resource(EntityInstanceOwners(owners));
+    }
 }
 
 // System to update BVH node count in render world settings
 fn update_render_world_bvh_count(
-    mut settings_query: Query<&mut SDFRenderSettings>,
+    mut settings_query: Query<&mut SDFRenderSettings, With<SdfRenderCamera>>,
     bvh_buffer: Option<Res<BVHBuffer>>,
 ) {
     for mut settings in settings_query.iter_mut() {
@@ -307,7 +983,7 @@ fn update_render_world_bvh_count(
 
 // System to update BVH node count in main world settings
 fn update_bvh_node_count_in_settings(
-    mut settings_query: Query<&mut SDFRenderSettings>,
+    mut settings_query: Query<&mut SDFRenderSettings, With<SdfRenderCamera>>,
     bvh_data: Option<Res<FlattenedBVH>>,
 ) {
     for mut settings in settings_query.iter_mut() {
@@ -326,26 +1002,80 @@ fn gpu_friendly_f32(f: f32) -> f32 {
     f
 }
 
-// System that runs in the main world to collect transform data
-fn build_entity_bvh(mut commands: Commands, entity_data: ResMut<EntityData>) {
+// Surface area of the BVH when it was last fully built, and when that was
+#[derive(Default)]
+struct BvhBuildState {
+    built_area: f32,
+    built_at: Duration,
+}
+
+// System that runs in the main world to keep the BVH in sync with the entity
+// data, refitting it in place when it can and rebuilding it when it must
+fn build_entity_bvh(
+    entity_data: Res<EntityData>,
+    budget: Res<BvhRebuildBudget>,
+    time: Res<Time>,
+    mut flattened_bvh: ResMut<FlattenedBVH>,
+    mut state: Local<BvhBuildState>,
+    mut diagnostics: Diagnostics,
+    mut app_events: EventWriter<AppEvent>,
+) {
     if !entity_data.is_changed() {
         return;
     }
 
-    let entities: Vec<Vec4> = entity_data.into_inner().to_owned().0;
-    info!("Building BVH for {} entities", entities.len());
+    // Inserts and deletes change the leaf count, so the refit fails for those
+    if flattened_bvh.refit(&entity_data.0) {
+        let loose = flattened_bvh.surface_area() > state.built_area * budget.max_area_growth;
+        let can_rebuild = time.elapsed() >= state.built_at + budget.min_rebuild_interval;
+        if !(loose && can_rebuild) {
+            return;
+        }
+    }
 
-    let mut sdf_entities: Vec<SDFRenderEntity> = entities
+    info!("Building BVH for {} entities", entity_data.0.len());
+    let started = Instant::now();
+    let mut shapes: Vec<BvhShape> = entity_data
+        .0
         .iter()
         .enumerate()
-        .map(|(i, v)| SDFRenderEntity {
-            position: Vec3::new(v.x, v.y, v.z),
-            scale: v.w,
-            node_index: i,
-        })
+        .map(|(node_index, &entity)| BvhShape { entity, node_index })
         .collect();
+    *flattened_bvh = flatten_entity_bvh(&mut shapes);
+    diagnostics.add_measurement(&SDF_BVH_REBUILD_TIME, || {
+        started.elapsed().as_secs_f64() * 1000.
+    });
+    if started.elapsed() >= REPORTED_BVH_REBUILD_TIME {
+        app_events.write(AppEvent::complete(Operation::BvhRebuild, true));
+    }
+    state.built_area = flattened_bvh.surface_area();
+    state.built_at = time.elapsed();
+}
+
+// Records the scene statistics the perf UI shows, once per frame
+fn record_sdf_diagnostics(
+    entity_data: Res<EntityData>,
+    flattened_bvh: Res<FlattenedBVH>,
+    upload_bytes: Res<EntityUploadBytes>,
+    camera_query: Query<&SDFRenderSettings, With<SdfRenderCamera>>,
+    mut diagnostics: Diagnostics,
+) {
+    diagnostics.add_measurement(&SDF_ENTITY_COUNT, || entity_data.0.len() as f64);
+    diagnostics.add_measurement(&SDF_BVH_NODE_COUNT, || flattened_bvh.0.len() as f64);
+    diagnostics.add_measurement(&SDF_ENTITY_UPLOAD_BYTES, || {
+        upload_bytes.0.swap(0, Ordering::Relaxed) as f64
+    });
+
+    // Same size manage_coarse_pass_texture creates the coarse texture with
+    if let Ok(settings) = camera_query.single() {
+        let coarse_size = settings.viewport_size * settings.coarse_resolution_factor;
+        diagnostics.add_measurement(&SDF_COARSE_PASS_WIDTH, || coarse_size.x.floor() as f64);
+        diagnostics.add_measurement(&SDF_COARSE_PASS_HEIGHT, || coarse_size.y.floor() as f64);
+    }
+}
 
-    let bvh = Bvh::build_par(&mut sdf_entities);
+fn flatten_entity_bvh(shapes: &mut [BvhShape]) -> FlattenedBVH {
+    let bvh = Bvh::build_par(shapes);
 
     let flat = bvh.flatten();
 
@@ -371,7 +1101,7 @@ fn build_entity_bvh(mut commands: Commands, entity_data: ResMut<EntityData>) {
         })
         .collect();
 
-    commands.insert_resource(FlattenedBVH(as_bvh_nodes));
+    FlattenedBVH(as_bvh_nodes)
 }
 
 fn update_bvh_buffer(
@@ -380,10 +1110,6 @@ fn update_bvh_buffer(
     mut bvh_buffer: ResMut<BVHBuffer>,
     flattened_bvh: Res<FlattenedBVH>,
 ) {
-    if !flattened_bvh.is_changed() {
-        return;
-    }
-
     let bvh_data = &flattened_bvh.0;
 
     bvh_buffer.data = bvh_data.clone();
@@ -416,36 +1142,31 @@ fn sync_entity_positions(
     mut entity_query: Query<(&mut SDFRenderEntity, &GlobalTransform), Changed<GlobalTransform>>,
 ) {
     for (mut entity, transform) in entity_query.iter_mut() {
-        entity.position = transform.translation();
+        // Propagation can touch transforms without moving them
+        let position = transform.translation();
+        if entity.position != position {
+            entity.position = position;
+        }
     }
 }
 
-// System that runs in the render world to update the buffer
+// System that runs in the render world to update the buffer, only on frames
+// the entity data was extracted again
 fn update_transform_buffer(
     mut transform_buffer: ResMut<EntityBuffer>,
-    transform_data: Option<Res<EntityData>>,
+    data: Res<EntityData>,
     render_device: Res<RenderDevice>,
     render_queue: Res<RenderQueue>,
+    upload_bytes: Res<EntityUploadBytes>,
 ) {
-    let Some(data) = transform_data else {
-        info!("no data");
-        return;
-    };
-
-    // Only update if the data has changed
-    if !data.is_changed() {
-        return;
-    }
-
-    info!("Updating entity buffer - {} entities", data.0.len());
-
-    // Update our CPU-side data
-    transform_buffer.data = data.0.clone();
-    let data_size = transform_buffer.data.len() * std::mem::size_of::<Vec4>();
+    let entity_size = std::mem::size_of::<GpuSdfEntity>();
+    let data_size = data.0.len() * entity_size;
 
-    // Create or resize buffer if needed
+    // Create or resize buffer if needed, which then needs everything uploaded
+    let mut dirty = dirty_entity_ranges(&transform_buffer.data, &data.0);
     if transform_buffer.buffer.is_none() || transform_buffer.capacity < data_size {
         info!("resize transform buffer");
+        dirty = vec![0..data.0.len()];
         transform_buffer.capacity = (data_size * 2).max(1024); // Buffer with some extra space
 
         transform_buffer.buffer = Some(render_device.create_buffer(&BufferDescriptor {
@@ -456,18 +1177,79 @@ fn update_transform_buffer(
         }));
     }
 
-    // Write data to buffer
+    // Write only the entities that changed since the last upload
     if let Some(buffer) = &transform_buffer.buffer {
-        if !transform_buffer.data.is_empty() {
-            let data_bytes = bytemuck::cast_slice(&transform_buffer.data);
-            render_queue.write_buffer(buffer, 0, data_bytes);
+        for range in dirty.iter().filter(|r| !r.is_empty()) {
+            let offset = (range.start * entity_size) as u64;
+            let data_bytes = bytemuck::cast_slice(&data.0[range.clone()]);
+            render_queue.write_buffer(buffer, offset, data_bytes);
+            upload_bytes.0.fetch_add(data_bytes.len() as u64, Ordering::Relaxed);
+        }
+    }
+
+    // Update our CPU-side data, which mirrors what the buffer holds
+    transform_buffer.data.clone_from(&data.0);
+}
+
+// Unchanged entities between two dirty ones below which both are written in a
+// single range, trading a few redundant bytes for fewer write_buffer calls
+const ENTITY_UPLOAD_MERGE_GAP: usize = 16;
+
+// Index ranges of `new` that differ from `old`. Entities past the end of `old`
+// are always dirty, while ones that were removed need no upload since the
+// shaders only read up to the entity count
+fn dirty_entity_ranges(old: &[GpuSdfEntity], new: &[GpuSdfEntity]) -> Vec<Range<usize>> {
+    let mut ranges: Vec<Range<usize>> = Vec::new();
+    for (index, entity) in new.iter().enumerate() {
+        let unchanged = old
+            .get(index)
+            .is_some_and(|o| bytemuck::bytes_of(o) == bytemuck::bytes_of(entity));
+        if unchanged {
+            continue;
+        }
+        match ranges.last_mut() {
+            Some(last) if index - last.end < ENTITY_UPLOAD_MERGE_GAP => last.end = index + 1,
+            _ => ranges.push(index..index + 1),
+        }
+    }
+    ranges
+}
+
+fn update_light_buffer(
+    mut light_buffer: ResMut<LightBuffer>,
+    light_data: Option<Res<SdfLightData>>,
+    render_device: Res<RenderDevice>,
+    render_queue: Res<RenderQueue>,
+) {
+    let Some(data) = light_data else {
+        return;
+    };
+    if !data.is_changed() && light_buffer.buffer.is_some() {
+        return;
+    }
+
+    // Keep a buffer around even without lights, since the bind group needs one
+    let data_size = data.0.len() * std::mem::size_of::<GpuSdfLight>();
+    if light_buffer.buffer.is_none() || light_buffer.capacity < data_size {
+        light_buffer.capacity = (data_size * 2).max(256);
+        light_buffer.buffer = Some(render_device.create_buffer(&BufferDescriptor {
+            label: Some("sdf_light_buffer"),
+            size: light_buffer.capacity as u64,
+            usage: BufferUsages::STORAGE | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        }));
+    }
+
+    if let Some(buffer) = &light_buffer.buffer {
+        if !data.0.is_empty() {
+            render_queue.write_buffer(buffer, 0, bytemuck::cast_slice(&data.0));
         }
     }
 }
 
 // System to update entity count in main world settings
 fn update_entity_count_in_settings(
-    mut settings_query: Query<&mut SDFRenderSettings>,
+    mut settings_query: Query<&mut SDFRenderSettings, With<SdfRenderCamera>>,
     transform_data: Option<Res<EntityData>>,
 ) {
     for mut settings in settings_query.iter_mut() {
@@ -482,7 +1264,7 @@ fn update_entity_count_in_settings(
 
 // System to update entity count in render world settings
 fn update_render_world_entity_count(
-    mut settings_query: Query<&mut SDFRenderSettings>,
+    mut settings_query: Query<&mut SDFRenderSettings, With<SdfRenderCamera>>,
     transform_buffer: Option<Res<EntityBuffer>>,
 ) {
     for mut settings in settings_query.iter_mut() {
@@ -518,10 +1300,15 @@ impl ViewNode for SDFRenderNode {
     // This query will only run on the view entity
     type ViewQuery = (
         &'static ViewTarget,
+        // For the viewport of cameras that only cover part of their target
+        &'static ExtractedCamera,
+        // The main depth texture, which the marched surface depth is written to so
+        // passes after this one can depth test against the SDF scene
+        &'static ViewDepthTexture,
         // prepass textures
         &'static ViewPrepassTextures,
-        // This makes sure the node only runs on cameras with the SDFRenderSettings component
-        &'static SDFRenderSettings,
+        // This makes sure the node only runs on cameras marked with SdfRenderCamera
+        &'static SdfRenderCamera,
         // As there could be multiple sdf render components sent to the GPU (one per camera),
         // we need to get the index of the one that is associated with the current view.
         &'static DynamicUniformIndex<SDFRenderSettings>,
@@ -538,9 +1325,14 @@ impl ViewNode for SDFRenderNode {
         &self,
         _graph: &mut RenderGraphContext,
         render_context: &mut RenderContext,
-        (view_target, prepass_textures, _sdf_render_settings, settings_index): QueryItem<
-            Self::ViewQuery,
-        >,
+        (
+            view_target,
+            camera,
+            view_depth,
+            prepass_textures,
+            _sdf_render_camera,
+            settings_index,
+        ): QueryItem<Self::ViewQuery>,
         world: &World,
     ) -> Result<(), NodeRunError> {
         // Check if sdf rendering is enabled, if not skip the entire pass
@@ -550,6 +1342,12 @@ impl ViewNode for SDFRenderNode {
             }
         }
 
+        // The pipelines are built for the target format SDFRenderPlugin was set up with
+        if view_target.main_texture_format() != world.resource::<SdfPassConfig>().color_format() {
+            warn_once!("SDF pass skipped, SDFRenderPlugin::hdr doesn't match the camera");
+            return Ok(());
+        }
+
         // Get the pipeline resource that contains the global data we need
         // to create the render pipeline
         let sdf_render_pipeline = world.resource::<SDFRenderPipeline>();
@@ -608,6 +1406,16 @@ impl ViewNode for SDFRenderNode {
             return Ok(()); // Skip rendering if no BVH buffer
         };
 
+        let Some(light_binding) = world
+            .resource::<LightBuffer>()
+            .buffer
+            .as_ref()
+            .map(|b| b.as_entire_binding())
+        else {
+            info!("no light binding");
+            return Ok(());
+        };
+
         // This will start a new "sdf render write", obtaining two texture
         // views from the view target - a `source` and a `destination`.
         // `source` is the "current" main texture and you _must_ write into
@@ -630,7 +1438,30 @@ impl ViewNode for SDFRenderNode {
             return Ok(());
         };
 
-        let bind_group = render_context.render_device().create_bind_group(
+        // Environment map, or a placeholder while none is loaded; the shader
+        // ignores it when the environment intensity is zero
+        let environment_view = world
+            .get_resource::<SdfEnvironment>()
+            .and_then(|environment| environment.image.as_ref())
+            .and_then(|image| world.resource::<RenderAssets<GpuImage>>().get(image))
+            .map(|image| &image.texture_view)
+            .unwrap_or(&world.resource::<FallbackImage>().d2.texture_view);
+
+        // Temporal accumulation reads last frame from one half of the history and
+        // writes this frame into the other; placeholders are bound while it's off
+        let temporal = world.get_resource::<SdfHistoryTextures>().and_then(|history| {
+            Some((
+                history,
+                pipeline_cache.get_render_pipeline(sdf_render_pipeline.temporal_pipeline_id)?,
+            ))
+        });
+        let fallback_view = &world.resource::<FallbackImage>().d2.texture_view;
+        let (history_color_view, history_depth_view) = match temporal {
+            Some((history, _)) => (&history.previous().color_view, &history.previous().depth_view),
+            None => (fallback_view, fallback_view),
+        };
+
+        let bind_group = render_context.render_device().create_bind_group(
             "sdf_render_bind_group",
             &sdf_render_pipeline.layout,
             &BindGroupEntries::sequential((
@@ -642,9 +1473,42 @@ impl ViewNode for SDFRenderNode {
                 &coarse_texture.view,
                 // Coarse pass sampler
                 &sdf_render_pipeline.coarse_sampler,
+                // Environment map
+                environment_view,
+                &sdf_render_pipeline.environment_sampler,
+                // Temporal history
+                history_color_view,
+                history_depth_view,
             )),
         );
 
+        // Iteration counters the coarse prepass is tuned by
+        let stats_buffer = world
+            .get_resource::<SdfMarchStatsBuffer>()
+            .and_then(|stats| {
+                world
+                    .resource::<RenderAssets<GpuShaderStorageBuffer>>()
+                    .get(&stats.0)
+            })
+            .map(|stats| &stats.buffer)
+            .unwrap_or(&sdf_render_pipeline.fallback_stats_buffer);
+
+        // Baked distance volume, or a placeholder without the volume cache plugin;
+        // the shader ignores it while the volume is disabled
+        let volume_view = world
+            .get_resource::<SdfVolumeTextures>()
+            .map(|textures| textures.sample_view())
+            .unwrap_or(&world.resource::<FallbackImage>().d3.texture_view);
+
+        // Brick map lists, only read while it is the selected acceleration structure
+        let (brick_counts, brick_entities) = world
+            .get_resource::<BrickMapBuffers>()
+            .map(|bricks| (&bricks.counts, &bricks.entities))
+            .unwrap_or((
+                &sdf_render_pipeline.fallback_brick_buffer,
+                &sdf_render_pipeline.fallback_brick_buffer,
+            ));
+
         // Create SDF scene bind group (group 1)
         let sdf_bind_group = render_context.render_device().create_bind_group(
             "sdf_scene_bind_group",
@@ -656,27 +1520,103 @@ impl ViewNode for SDFRenderNode {
                 transform_binding,
                 // BVH storage buffer
                 bvh_binding,
+                // Light storage buffer
+                light_binding,
+                // March statistics storage buffer
+                stats_buffer.as_entire_binding(),
+                // Baked distance volume
+                volume_view,
+                &sdf_render_pipeline.volume_sampler,
+                // Brick map
+                brick_counts.as_entire_binding(),
+                brick_entities.as_entire_binding(),
             )),
         );
 
+        // With adaptive resolution below full scale, the surface is marched into
+        // the smaller targets and upscaled into the view afterwards
+        let upscale_pipeline = world.resource::<SdfUpscalePipeline>();
+        let scaled = world.get_resource::<ScaledSdfTargets>().and_then(|targets| {
+            Some((
+                targets,
+                pipeline_cache.get_render_pipeline(sdf_render_pipeline.scaled_pipeline_id)?,
+                pipeline_cache.get_render_pipeline(upscale_pipeline.pipeline_id)?,
+            ))
+        });
+
+        // Passes limited to a viewport only draw inside it, so the rest of the
+        // destination is loaded, after carrying it over from the source
+        let destination = if camera.viewport.is_some() {
+            let usage = post_process.destination_texture.usage();
+            if usage.contains(TextureUsages::COPY_DST) {
+                render_context.command_encoder().copy_texture_to_texture(
+                    post_process.source_texture.as_image_copy(),
+                    post_process.destination_texture.as_image_copy(),
+                    post_process.source_texture.size(),
+                );
+            }
+            Some(RenderPassColorAttachment {
+                view: post_process.destination,
+                resolve_target: None,
+                ops: Operations {
+                    load: LoadOp::Load,
+                    store: StoreOp::Store,
+                },
+            })
+        } else {
+            color_attachment(post_process.destination)
+        };
+
+        let (pass_pipeline, color_attachments, depth_stencil_attachment) = match (scaled, temporal)
+        {
+            (Some((targets, scaled_pipeline, _)), _) => (
+                scaled_pipeline,
+                vec![
+                    color_attachment(&targets.color_view),
+                    color_attachment(&targets.depth_view),
+                ],
+                None,
+            ),
+            (None, Some((history, temporal_pipeline))) => (
+                temporal_pipeline,
+                vec![
+                    destination.clone(),
+                    color_attachment(&history.current().color_view),
+                    color_attachment(&history.current().depth_view),
+                ],
+                Some(view_depth.get_attachment(StoreOp::Store)),
+            ),
+            // We need to specify the sdf render destination view here
+            // to make sure we write to the appropriate texture.
+            // The prepass depth bound above is a copy, so the main depth texture can
+            // be written while it is sampled
+            (None, None) => (
+                pipeline,
+                vec![destination.clone()],
+                Some(view_depth.get_attachment(StoreOp::Store)),
+            ),
+        };
+
+        // Timed, so adaptive resolution can follow the GPU cost of the pass
+        let diagnostics = render_context.diagnostic_recorder();
+
         // Begin the render pass
         let mut render_pass = render_context.begin_tracked_render_pass(RenderPassDescriptor {
             label: Some("sdf_render_pass"),
-            color_attachments: &[Some(RenderPassColorAttachment {
-                // We need to specify the sdf render destination view here
-                // to make sure we write to the appropriate texture.
-                view: post_process.destination,
-                resolve_target: None,
-                ops: Operations::default(),
-            })],
-            depth_stencil_attachment: None,
+            color_attachments: &color_attachments,
+            depth_stencil_attachment,
             timestamp_writes: None,
             occlusion_query_set: None,
         });
+        let pass_span = diagnostics.pass_span(render_pass.wgpu_pass(), "sdf_render_pass");
 
         // This is mostly just wgpu boilerplate for drawing a fullscreen triangle,
         // using the pipeline/bind_group created above
-        render_pass.set_render_pipeline(pipeline);
+        render_pass.set_render_pipeline(pass_pipeline);
+        // The scaled targets only hold the viewport, everything else is target sized
+        if scaled.is_none() {
+            set_view_viewport(&mut render_pass, camera);
+        }
         // By passing in the index of the sdf render settings on this view, we ensure
         // that in the event that multiple settings were sent to the GPU (as would be the
         // case with multiple cameras), we use the correct one.
@@ -684,22 +1624,79 @@ impl ViewNode for SDFRenderNode {
         render_pass.set_bind_group(1, &sdf_bind_group, &[settings_index.index()]);
         render_pass.draw(0..3, 0..1);
 
+        pass_span.end(render_pass.wgpu_pass());
+        drop(render_pass);
+
+        let Some((targets, _, upscale_render_pipeline)) = scaled else {
+            return Ok(());
+        };
+
+        let upscale_bind_group = render_context.render_device().create_bind_group(
+            "sdf_upscale_bind_group",
+            &upscale_pipeline.layout,
+            &BindGroupEntries::sequential((
+                &targets.color_view,
+                &upscale_pipeline.sampler,
+                &targets.depth_view,
+            )),
+        );
+
+        let mut upscale_pass = render_context.begin_tracked_render_pass(RenderPassDescriptor {
+            label: Some("sdf_upscale_pass"),
+            color_attachments: &[destination],
+            depth_stencil_attachment: Some(view_depth.get_attachment(StoreOp::Store)),
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+
+        upscale_pass.set_render_pipeline(upscale_render_pipeline);
+        set_view_viewport(&mut upscale_pass, camera);
+        upscale_pass.set_bind_group(0, &upscale_bind_group, &[]);
+        upscale_pass.draw(0..3, 0..1);
+
         Ok(())
     }
 }
 
+// Restricts a fullscreen pass to the camera's viewport, leaving the rest of the
+// target to other cameras
+fn set_view_viewport(render_pass: &mut TrackedRenderPass, camera: &ExtractedCamera) {
+    let Some(viewport) = camera.viewport.as_ref() else {
+        return;
+    };
+    render_pass.set_camera_viewport(viewport);
+    render_pass.set_scissor_rect(
+        viewport.physical_position.x,
+        viewport.physical_position.y,
+        viewport.physical_size.x,
+        viewport.physical_size.y,
+    );
+}
+
+// Attachment that overwrites every pixel of `view`
+fn color_attachment(view: &TextureView) -> Option<RenderPassColorAttachment<'_>> {
+    Some(RenderPassColorAttachment {
+        view,
+        resolve_target: None,
+        ops: Operations::default(),
+    })
+}
+
 impl ViewNode for SDFCoarsePrepassNode {
     type ViewQuery = (
         &'static ViewPrepassTextures,
-        &'static SDFRenderSettings,
+        &'static SdfRenderCamera,
         &'static DynamicUniformIndex<SDFRenderSettings>,
+        Option<&'static SdfViewEntitiesBuffer>,
     );
 
     fn run(
         &self,
         _graph: &mut RenderGraphContext,
         render_context: &mut RenderContext,
-        (prepass_textures, _sdf_render_settings, settings_index): QueryItem<Self::ViewQuery>,
+        (prepass_textures, _sdf_render_camera, settings_index, view_entities): QueryItem<
+            Self::ViewQuery,
+        >,
         world: &World,
     ) -> Result<(), NodeRunError> {
         // Check if sdf rendering is enabled
@@ -746,6 +1743,11 @@ impl ViewNode for SDFCoarsePrepassNode {
             return Ok(());
         };
 
+        // The view's culled entity list is uploaded a frame after the camera appears
+        let Some(view_entities) = view_entities else {
+            return Ok(());
+        };
+
         // Create a dummy screen texture view for the coarse pass
         let dummy_texture = render_context
             .render_device()
@@ -765,6 +1767,8 @@ impl ViewNode for SDFCoarsePrepassNode {
             });
         let dummy_view = dummy_texture.create_view(&TextureViewDescriptor::default());
 
+        let diagnostics = render_context.diagnostic_recorder();
+
         let bind_group = render_context.render_device().create_bind_group(
             "sdf_coarse_prepass_bind_group",
             &coarse_pipeline.layout,
@@ -779,10 +1783,11 @@ impl ViewNode for SDFCoarsePrepassNode {
         let sdf_bind_group = render_context.render_device().create_bind_group(
             "sdf_coarse_scene_bind_group",
             &coarse_pipeline.sdf_layout,
-            &BindGroupEntries::sequential((
-                settings_binding.clone(),
-                transform_binding,
-                bvh_binding,
+            &BindGroupEntries::with_indices((
+                (0, settings_binding.clone()),
+                (1, transform_binding),
+                (2, bvh_binding),
+                (9, view_entities.buffer.as_entire_binding()),
             )),
         );
 
@@ -797,29 +1802,49 @@ impl ViewNode for SDFCoarsePrepassNode {
             timestamp_writes: None,
             occlusion_query_set: None,
         });
+        let pass_span = diagnostics.pass_span(render_pass.wgpu_pass(), "sdf_coarse_prepass");
 
         render_pass.set_render_pipeline(pipeline);
         render_pass.set_bind_group(0, &bind_group, &[]);
         render_pass.set_bind_group(1, &sdf_bind_group, &[settings_index.index()]);
         render_pass.draw(0..3, 0..1);
 
+        pass_span.end(render_pass.wgpu_pass());
+
         Ok(())
     }
 }
 
 // This contains global data used by the render pipeline. This will be created once on startup.
 #[derive(Resource)]
-struct SDFRenderPipeline {
+pub(crate) struct SDFRenderPipeline {
     layout: BindGroupLayout,
     sdf_layout: BindGroupLayout,
     sampler: Sampler,
     depth_sampler: Sampler,
     coarse_sampler: Sampler,
-    pipeline_id: CachedRenderPipelineId,
+    environment_sampler: Sampler,
+    // Bound in place of the march statistics until their buffer is uploaded
+    fallback_stats_buffer: Buffer,
+    // Bound in place of the brick map without the brick map plugin
+    fallback_brick_buffer: Buffer,
+    volume_sampler: Sampler,
+    pub(crate) pipeline_id: CachedRenderPipelineId,
+    pub(crate) scaled_pipeline_id: CachedRenderPipelineId,
+    pub(crate) temporal_pipeline_id: CachedRenderPipelineId,
+}
+
+// Targets a variant of the main SDF pipeline renders into
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum SdfPassOutput {
+    View,
+    Scaled,
+    Temporal,
 }
 
 impl FromWorld for SDFRenderPipeline {
     fn from_world(world: &mut World) -> Self {
+        let color_format = world.resource::<SdfPassConfig>().color_format();
         let render_device = world.resource::<RenderDevice>();
 
         // We need to define the bind group layout used for our pipeline
@@ -841,6 +1866,13 @@ impl FromWorld for SDFRenderPipeline {
                     texture_2d(TextureSampleType::Float { filterable: true }),
                     // The coarse pass sampler
                     sampler(SamplerBindingType::Filtering),
+                    // The equirectangular environment map
+                    texture_2d(TextureSampleType::Float { filterable: true }),
+                    // The environment map sampler
+                    sampler(SamplerBindingType::Filtering),
+                    // Last frame's color and marched depth for temporal accumulation
+                    texture_2d(TextureSampleType::Float { filterable: true }),
+                    texture_2d(TextureSampleType::Float { filterable: false }),
                 ),
             ),
         );
@@ -875,46 +1907,136 @@ impl FromWorld for SDFRenderPipeline {
                         },
                         count: None,
                     },
+                    // Storage buffer for the scene lights
+                    BindGroupLayoutEntry {
+                        binding: 3,
+                        visibility: ShaderStages::FRAGMENT,
+                        ty: BindingType::Buffer {
+                            ty: BufferBindingType::Storage { read_only: true },
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                    // Counters of the fine march iterations, see coarse_tuning.rs
+                    BindGroupLayoutEntry {
+                        binding: 4,
+                        visibility: ShaderStages::FRAGMENT,
+                        ty: BindingType::Buffer {
+                            ty: BufferBindingType::Storage { read_only: false },
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                    // Baked distance volume, see sdf_volume_cache.rs
+                    texture_3d(TextureSampleType::Float { filterable: true }),
+                    sampler(SamplerBindingType::Filtering),
+                    // Brick counts and entity lists, see brick_map.rs
+                    storage_buffer_read_only_sized(false, None),
+                    storage_buffer_read_only_sized(false, None),
                 ),
             ),
         );
 
+        let fallback_stats_buffer = render_device.create_buffer(&BufferDescriptor {
+            label: Some("sdf_fallback_march_stats_buffer"),
+            size: 8,
+            usage: BufferUsages::STORAGE,
+            mapped_at_creation: false,
+        });
+        let fallback_brick_buffer = render_device.create_buffer(&BufferDescriptor {
+            label: Some("sdf_fallback_brick_buffer"),
+            size: 4,
+            usage: BufferUsages::STORAGE,
+            mapped_at_creation: false,
+        });
+
         // We can create the sampler here since it won't change at runtime and doesn't depend on the view
         let sampler = render_device.create_sampler(&SamplerDescriptor::default());
         let depth_sampler = render_device.create_sampler(&SamplerDescriptor { ..default() });
         let coarse_sampler = render_device.create_sampler(&SamplerDescriptor::default());
+        // Longitude wraps around, latitude stops at the poles
+        let environment_sampler = render_device.create_sampler(&SamplerDescriptor {
+            address_mode_u: AddressMode::Repeat,
+            address_mode_v: AddressMode::ClampToEdge,
+            mag_filter: FilterMode::Linear,
+            min_filter: FilterMode::Linear,
+            ..default()
+        });
+        // Interpolated between voxels, and clamped at the edges of the volume
+        let volume_sampler = render_device.create_sampler(&SamplerDescriptor {
+            mag_filter: FilterMode::Linear,
+            min_filter: FilterMode::Linear,
+            ..default()
+        });
 
         // Get the shader handle
         let shader = world.load_asset(SHADER_ASSET_PATH);
 
-        let pipeline_id = world
-            .resource_mut::<PipelineCache>()
-            // This will add the pipeline to the cache and queue its creation
-            .queue_render_pipeline(RenderPipelineDescriptor {
-                label: Some("sdf_render_pipeline".into()),
-                layout: vec![layout.clone(), sdf_layout.clone()],
-                // This will setup a fullscreen triangle for the vertex state
-                vertex: fullscreen_shader_vertex_state(),
-                fragment: Some(FragmentState {
-                    shader,
-                    shader_defs: vec![],
-                    // Make sure this matches the entry point of your shader.
-                    // It can be anything as long as it matches here and in the shader.
-                    entry_point: "fragment".into(),
-                    targets: vec![Some(ColorTargetState {
-                        format: TextureFormat::bevy_default(),
+        // Below full resolution (see adaptive_resolution.rs) a variant of the pipeline
+        // writes color and marched depth into the scaled targets instead of the view,
+        // another also writes them into the history of temporal_accumulation.rs
+        let descriptor = |output: SdfPassOutput| RenderPipelineDescriptor {
+            label: Some(match output {
+                SdfPassOutput::View => "sdf_render_pipeline".into(),
+                SdfPassOutput::Scaled => "sdf_scaled_render_pipeline".into(),
+                SdfPassOutput::Temporal => "sdf_temporal_render_pipeline".into(),
+            }),
+            layout: vec![layout.clone(), sdf_layout.clone()],
+            // This will setup a fullscreen triangle for the vertex state
+            vertex: fullscreen_shader_vertex_state(),
+            fragment: Some(FragmentState {
+                shader: shader.clone(),
+                shader_defs: match output {
+                    SdfPassOutput::View => vec![],
+                    SdfPassOutput::Scaled => vec!["SCALED_OUTPUT".into()],
+                    SdfPassOutput::Temporal => vec!["TEMPORAL_ACCUMULATION".into()],
+                },
+                // Make sure this matches the entry point of your shader.
+                // It can be anything as long as it matches here and in the shader.
+                entry_point: "fragment".into(),
+                targets: match output {
+                    SdfPassOutput::View => vec![Some(ColorTargetState {
+                        format: color_format,
                         blend: None,
                         write_mask: ColorWrites::ALL,
                     })],
-                }),
-                // All of the following properties are not important for this effect so just use the default values.
-                // This struct doesn't have the Default trait implemented because not all fields can have a default value.
-                primitive: PrimitiveState::default(),
-                depth_stencil: None,
-                multisample: MultisampleState::default(),
-                push_constant_ranges: vec![],
-                zero_initialize_workgroup_memory: false,
-            });
+                    SdfPassOutput::Scaled => vec![
+                        Some(color_format.into()),
+                        Some(SCALED_DEPTH_FORMAT.into()),
+                    ],
+                    SdfPassOutput::Temporal => vec![
+                        Some(color_format.into()),
+                        Some(color_format.into()),
+                        Some(HISTORY_DEPTH_FORMAT.into()),
+                    ],
+                },
+            }),
+            // All of the following properties are not important for this effect so just use the default values.
+            // This struct doesn't have the Default trait implemented because not all fields can have a default value.
+            primitive: PrimitiveState::default(),
+            // Every pixel is overwritten with the depth of the marched surface,
+            // like the color
+            depth_stencil: (output != SdfPassOutput::Scaled).then(|| DepthStencilState {
+                format: CORE_3D_DEPTH_FORMAT,
+                depth_write_enabled: true,
+                depth_compare: CompareFunction::Always,
+                stencil: StencilState::default(),
+                bias: DepthBiasState::default(),
+            }),
+            multisample: MultisampleState::default(),
+            push_constant_ranges: vec![],
+            zero_initialize_workgroup_memory: false,
+        };
+
+        // This will add the pipelines to the cache and queue their creation
+        let mut pipeline_cache = world.resource_mut::<PipelineCache>();
+        let pipeline_id = pipeline_cache.queue_render_pipeline(descriptor(SdfPassOutput::View));
+        let scaled_pipeline_id =
+            pipeline_cache.queue_render_pipeline(descriptor(SdfPassOutput::Scaled));
+        let temporal_pipeline_id =
+            pipeline_cache.queue_render_pipeline(descriptor(SdfPassOutput::Temporal));
 
         Self {
             layout,
@@ -922,18 +2044,24 @@ impl FromWorld for SDFRenderPipeline {
             sampler,
             depth_sampler,
             coarse_sampler,
+            environment_sampler,
+            fallback_stats_buffer,
+            fallback_brick_buffer,
+            volume_sampler,
             pipeline_id,
+            scaled_pipeline_id,
+            temporal_pipeline_id,
         }
     }
 }
 
 #[derive(Resource)]
-struct SDFCoarsePrepassPipeline {
+pub(crate) struct SDFCoarsePrepassPipeline {
     layout: BindGroupLayout,
     sdf_layout: BindGroupLayout,
     sampler: Sampler,
     depth_sampler: Sampler,
-    pipeline_id: CachedRenderPipelineId,
+    pub(crate) pipeline_id: CachedRenderPipelineId,
 }
 
 impl FromWorld for SDFCoarsePrepassPipeline {
@@ -961,33 +2089,17 @@ impl FromWorld for SDFCoarsePrepassPipeline {
         // Separate bind group layout for SDF scene data (group 1) - reuse from main pass
         let sdf_layout = render_device.create_bind_group_layout(
             "sdf_coarse_scene_bind_group_layout",
-            &BindGroupLayoutEntries::sequential(
+            &BindGroupLayoutEntries::with_indices(
                 ShaderStages::FRAGMENT,
                 (
                     // SDF settings uniform
-                    uniform_buffer::<SDFRenderSettings>(true),
+                    (0, uniform_buffer::<SDFRenderSettings>(true)),
                     // Storage buffer for entity transforms
-                    BindGroupLayoutEntry {
-                        binding: 1,
-                        visibility: ShaderStages::FRAGMENT,
-                        ty: BindingType::Buffer {
-                            ty: BufferBindingType::Storage { read_only: true },
-                            has_dynamic_offset: false,
-                            min_binding_size: None,
-                        },
-                        count: None,
-                    },
+                    (1, storage_buffer_read_only_sized(false, None)),
                     // BVH Buffer
-                    BindGroupLayoutEntry {
-                        binding: 2,
-                        visibility: ShaderStages::FRAGMENT,
-                        ty: BindingType::Buffer {
-                            ty: BufferBindingType::Storage { read_only: true },
-                            has_dynamic_offset: false,
-                            min_binding_size: None,
-                        },
-                        count: None,
-                    },
+                    (2, storage_buffer_read_only_sized(false, None)),
+                    // The entities within this view's frustum
+                    (9, storage_buffer_read_only_sized(false, None)),
                 ),
             ),
         );
@@ -1007,7 +2119,7 @@ impl FromWorld for SDFCoarsePrepassPipeline {
                     vertex: fullscreen_shader_vertex_state(),
                     fragment: Some(FragmentState {
                         shader,
-                        shader_defs: vec![],
+                        shader_defs: vec!["SDF_VIEW_CULLING".into()],
                         entry_point: "fragment".into(),
                         targets: vec![Some(ColorTargetState {
                             format: TextureFormat::R32Float,
@@ -1032,8 +2144,25 @@ impl FromWorld for SDFCoarsePrepassPipeline {
     }
 }
 
+// Marks the cameras the SDF scene is ray-marched for. Only these get their
+// settings synced and extracted and run the SDF nodes, so other cameras (the
+// overlay, UI) never pay for the passes.
+#[derive(Component, Clone, Copy, Default, ExtractComponent)]
+#[require(SDFRenderSettings)]
+#[component(on_add = allow_viewport_copies)]
+pub struct SdfRenderCamera;
+
+// With a viewport, the SDF pass copies the rest of the target into the texture
+// it draws to, which needs COPY_DST on the camera's main textures
+fn allow_viewport_copies(mut world: DeferredWorld, context: HookContext) {
+    if let Some(mut usages) = world.get_mut::<CameraMainTextureUsages>(context.entity) {
+        usages.0 |= TextureUsages::COPY_DST;
+    }
+}
+
 // This is the component that will get passed to the shader
 #[derive(Component, Clone, Copy, ExtractComponent, ShaderType)]
+#[extract_component_filter(With<SdfRenderCamera>)]
 pub struct SDFRenderSettings {
     pub near_plane: f32,
     pub far_plane: f32,
@@ -1047,6 +2176,64 @@ pub struct SDFRenderSettings {
     pub coarse_resolution_factor: f32,
     pub coarse_distance_multiplier: f32,
     pub coarse_max_steps: u32,
+    pub ground_height: f32,
+    pub ground_enabled: u32,
+    // Bevy's (reverse-z) projection times the view matrix, to turn marched hit
+    // points into values for the depth buffer
+    pub clip_from_world: Mat4,
+    pub light_count: u32,
+    // Penumbra size of the ray-marched shadows, 0 disables them
+    pub shadow_softness: f32,
+    // Darkening of creases, 0 disables ambient occlusion
+    pub ao_intensity: f32,
+    // Distance along the normal over which occluders are sampled
+    pub ao_radius: f32,
+    // Strength of the environment map's ambient light and reflections, 0
+    // when no environment map is loaded
+    pub environment_intensity: f32,
+    // Physical size of the camera's viewport in pixels
+    pub viewport_size: Vec2,
+    // Last frame's clip_from_world, to reproject the temporal history
+    pub previous_clip_from_world: Mat4,
+    // Weight of the history in temporal accumulation, 0 when it's off
+    pub temporal_blend: f32,
+    // Sub-pixel offset (in pixels) of this frame's rays for temporal accumulation
+    pub temporal_jitter: Vec2,
+    // SdfDebugView::as_gpu of the output to show
+    pub debug_view: u32,
+    // Cut-away plane (xyz: unit normal, w: offset along it), see clip_plane.rs
+    pub clip_plane: Vec4,
+    pub clip_enabled: u32,
+    // Flat color of the cut face, alpha 0 shades it like the rest of the surface
+    pub clip_cap_color: Vec4,
+    // Backdrop colors in linear RGB, see ViewportAppearance
+    pub background_zenith: Vec4,
+    pub background_horizon: Vec4,
+    // Alpha is the opacity of the grid lines
+    pub grid_color: Vec4,
+    pub grid_spacing: f32,
+    pub grid_enabled: u32,
+    // Exponential distance fog in linear RGB, density 0 disables it
+    pub fog_color: Vec4,
+    pub fog_density: f32,
+    // Distance from the camera where the fog begins
+    pub fog_start: f32,
+    // Physical position of the camera's viewport within its render target
+    pub viewport_offset: Vec2,
+    // Unselected entities are ghosted while set, see SdfIsolateMode
+    pub isolate_enabled: u32,
+    pub isolate_opacity: f32,
+    // Bounds of the baked distance volume, see SdfVolumeCache
+    pub volume_min: Vec3,
+    pub volume_enabled: u32,
+    pub volume_max: Vec3,
+    // Bounds of the entities edited since the volume was baked
+    pub volume_dirty_min: Vec3,
+    pub volume_dirty_max: Vec3,
+    // AccelerationStructure::as_gpu, and the grid of the brick map
+    pub acceleration_structure: u32,
+    pub brick_grid_min: Vec3,
+    pub brick_grid_max: Vec3,
 }
 
 impl Default for SDFRenderSettings {
@@ -1064,6 +2251,41 @@ impl Default for SDFRenderSettings {
             coarse_resolution_factor: 0.0625, // 1/16 resolution
             coarse_distance_multiplier: 10.,  // 10x higher threshold
             coarse_max_steps: 24,             // Reduced steps for performance
+            ground_height: 0.0,
+            ground_enabled: 0,
+            clip_from_world: Mat4::IDENTITY,
+            light_count: 0,
+            shadow_softness: 0.0,
+            ao_intensity: 0.0,
+            ao_radius: 0.2,
+            environment_intensity: 0.0,
+            viewport_size: Vec2::new(1920.0, 1080.0),
+            previous_clip_from_world: Mat4::IDENTITY,
+            temporal_blend: 0.0,
+            temporal_jitter: Vec2::ZERO,
+            debug_view: 0,
+            clip_plane: Vec4::new(1.0, 0.0, 0.0, 0.0),
+            clip_enabled: 0,
+            clip_cap_color: Vec4::ZERO,
+            background_zenith: Vec4::ZERO,
+            background_horizon: Vec4::ZERO,
+            grid_color: Vec4::ZERO,
+            grid_spacing: 1.0,
+            grid_enabled: 0,
+            fog_color: Vec4::ZERO,
+            fog_density: 0.0,
+            fog_start: 0.0,
+            viewport_offset: Vec2::ZERO,
+            isolate_enabled: 0,
+            isolate_opacity: 0.25,
+            volume_min: Vec3::ZERO,
+            volume_enabled: 0,
+            volume_max: Vec3::ZERO,
+            volume_dirty_min: Vec3::ZERO,
+            volume_dirty_max: Vec3::ZERO,
+            acceleration_structure: 0,
+            brick_grid_min: Vec3::ZERO,
+            brick_grid_max: Vec3::ZERO,
         }
     }
 }
@@ -1075,6 +2297,199 @@ pub struct CoarsePassTexture {
     pub size: Extent3d,
 }
 
+// Infinite floor that is always evaluated by the SDF but kept out of the BVH
+#[derive(Resource, Clone)]
+pub struct SdfGroundPlane {
+    pub height: f32,
+    pub enabled: bool,
+}
+
+impl Default for SdfGroundPlane {
+    fn default() -> Self {
+        Self {
+            height: -1.0,
+            enabled: false,
+        }
+    }
+}
+
+// What the SDF pass shows where rays miss the scene: a gradient from the
+// horizon up to the zenith, and a reference grid at the ground plane height
+// (drawn whether or not the ground plane itself is enabled)
+#[derive(Resource, Clone)]
+pub struct ViewportAppearance {
+    pub zenith_color: Color,
+    pub horizon_color: Color,
+    pub grid_enabled: bool,
+    // Distance between grid lines in world units
+    pub grid_spacing: f32,
+    // Alpha is the opacity of the lines
+    pub grid_color: Color,
+}
+
+impl Default for ViewportAppearance {
+    fn default() -> Self {
+        Self {
+            zenith_color: Color::srgb(0.16, 0.17, 0.19),
+            horizon_color: Color::srgb(0.35, 0.36, 0.38),
+            grid_enabled: true,
+            grid_spacing: 1.0,
+            grid_color: Color::srgba(0.6, 0.6, 0.6, 0.5),
+        }
+    }
+}
+
+// Shadows the scene light casts onto the SDF surface
+#[derive(Resource, Clone)]
+pub struct SdfShadows {
+    pub enabled: bool,
+    // Larger values give wider, softer penumbras
+    pub softness: f32,
+}
+
+impl Default for SdfShadows {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            softness: 0.0625,
+        }
+    }
+}
+
+// Ambient occlusion of the SDF surface, sampled along the normal
+#[derive(Resource, Clone)]
+pub struct SdfAmbientOcclusion {
+    pub enabled: bool,
+    pub intensity: f32,
+    pub radius: f32,
+}
+
+impl Default for SdfAmbientOcclusion {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            intensity: 1.0,
+            radius: 0.2,
+        }
+    }
+}
+
+// Focus on the selection by drawing every other entity as a faded, desaturated
+// ghost. Does nothing while the selection is empty.
+#[derive(Resource, Clone)]
+pub struct SdfIsolateMode {
+    pub enabled: bool,
+    // How much of the ghosted surface shows over the backdrop
+    pub opacity: f32,
+}
+
+impl Default for SdfIsolateMode {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            opacity: 0.25,
+        }
+    }
+}
+
+// Exponential fog over the marched surface and backdrop, so distant geometry
+// fades out instead of ending at the far plane
+#[derive(Resource, Clone)]
+pub struct SdfFog {
+    pub enabled: bool,
+    pub color: Color,
+    // Fraction of the remaining light absorbed per world unit
+    pub density: f32,
+    // Distance from the camera before which there is no fog
+    pub start: f32,
+}
+
+impl Default for SdfFog {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            color: Color::srgb(0.35, 0.36, 0.38),
+            density: 0.05,
+            start: 10.0,
+        }
+    }
+}
+
+// Edge anti-aliasing of the SDF pass, done by FXAA after the pass since MSAA
+// doesn't apply to ray-marched silhouettes. Quality 0 turns it off, 1 to 5 go
+// from the sharpest to the smoothest result.
+#[derive(Resource, Clone)]
+pub struct SdfAntiAliasing {
+    pub quality: u32,
+}
+
+impl Default for SdfAntiAliasing {
+    fn default() -> Self {
+        Self { quality: 3 }
+    }
+}
+
+// What the SDF pass outputs, the non-shaded views help diagnosing slow frames
+#[derive(Resource, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SdfDebugView {
+    #[default]
+    Shaded,
+    Normals,
+    // Ray-march iterations per pixel as a heatmap
+    Steps,
+    Depth,
+    // BVH nodes visited per pixel as a heatmap
+    BvhVisits,
+}
+
+impl SdfDebugView {
+    // Must match the DEBUG_VIEW_* constants in sdf_render.wgsl
+    pub fn as_gpu(&self) -> u32 {
+        match self {
+            SdfDebugView::Shaded => 0,
+            SdfDebugView::Normals => 1,
+            SdfDebugView::Steps => 2,
+            SdfDebugView::Depth => 3,
+            SdfDebugView::BvhVisits => 4,
+        }
+    }
+
+    pub fn next(&self) -> Self {
+        match self {
+            SdfDebugView::Shaded => SdfDebugView::Normals,
+            SdfDebugView::Normals => SdfDebugView::Steps,
+            SdfDebugView::Steps => SdfDebugView::Depth,
+            SdfDebugView::Depth => SdfDebugView::BvhVisits,
+            SdfDebugView::BvhVisits => SdfDebugView::Shaded,
+        }
+    }
+}
+
+// Equirectangular (HDR) image the SDF surface picks up ambient light and
+// reflections from
+#[derive(Resource, Clone)]
+pub struct SdfEnvironment {
+    pub image: Option<Handle<Image>>,
+    pub intensity: f32,
+}
+
+impl Default for SdfEnvironment {
+    fn default() -> Self {
+        Self {
+            image: None,
+            intensity: 1.0,
+        }
+    }
+}
+
+impl ExtractResource for SdfEnvironment {
+    type Source = SdfEnvironment;
+
+    fn extract_resource(source: &Self::Source) -> Self {
+        source.clone()
+    }
+}
+
 #[derive(Resource, Clone)]
 pub struct SDFRenderEnabled {
     pub enabled: bool,
@@ -1096,9 +2511,19 @@ impl ExtractResource for SDFRenderEnabled {
 
 // System to update SDFRenderSettings with current camera data
 fn update_camera_settings(
-    mut camera_query: Query<(&mut SDFRenderSettings, &GlobalTransform, &Projection), With<Camera>>,
+    mut camera_query: Query<
+        (&mut SDFRenderSettings, &Camera, &GlobalTransform, &Projection),
+        With<SdfRenderCamera>,
+    >,
 ) {
-    for (mut settings, global_transform, projection) in camera_query.iter_mut() {
+    for (mut settings, camera, global_transform, projection) in camera_query.iter_mut() {
+        // Track the viewport so the coarse pass texture can follow resizes, and
+        // where it sits in the render target for cameras with a sub-rect viewport
+        if let Some(rect) = camera.physical_viewport_rect() {
+            settings.viewport_offset = rect.min.as_vec2();
+            settings.viewport_size = rect.size().as_vec2();
+        }
+
         // Update camera position
         settings.camera_position = global_transform.translation();
 
@@ -1150,12 +2575,177 @@ fn update_camera_settings(
         // Compute and store the inverse view-projection matrix on CPU
         let view_proj = settings.projection_matrix * settings.view_matrix;
         settings.inverse_view_projection = view_proj.inverse();
+
+        // The depth buffer follows Bevy's projection rather than the one above
+        settings.previous_clip_from_world = settings.clip_from_world;
+        settings.clip_from_world = projection.get_clip_from_view() * settings.view_matrix;
+    }
+}
+
+fn update_ground_plane_in_settings(
+    ground_plane: Res<SdfGroundPlane>,
+    mut camera_query: Query<&mut SDFRenderSettings, With<SdfRenderCamera>>,
+) {
+    for mut settings in camera_query.iter_mut() {
+        settings.ground_height = ground_plane.height;
+        settings.ground_enabled = ground_plane.enabled as u32;
+    }
+}
+
+fn update_viewport_appearance_in_settings(
+    appearance: Res<ViewportAppearance>,
+    mut camera_query: Query<&mut SDFRenderSettings, With<SdfRenderCamera>>,
+) {
+    for mut settings in camera_query.iter_mut() {
+        settings.background_zenith = appearance.zenith_color.to_linear().to_vec4();
+        settings.background_horizon = appearance.horizon_color.to_linear().to_vec4();
+        settings.grid_color = appearance.grid_color.to_linear().to_vec4();
+        settings.grid_spacing = appearance.grid_spacing.max(0.001);
+        settings.grid_enabled = appearance.grid_enabled as u32;
+    }
+}
+
+// The SDF is lit by the point and directional lights on the main render
+// layer; the overlay camera has a light of its own
+fn collect_light_data(
+    shadows: Res<SdfShadows>,
+    point_lights: Query<(&PointLight, &GlobalTransform, Option<&RenderLayers>)>,
+    directional_lights: Query<(&DirectionalLight, &GlobalTransform, Option<&RenderLayers>)>,
+    mut light_data: ResMut<SdfLightData>,
+    mut camera_query: Query<&mut SDFRenderSettings, With<SdfRenderCamera>>,
+) {
+    let on_main_layer = |layers: Option<&RenderLayers>| {
+        layers.is_none_or(|l| l.intersects(&RenderLayers::default()))
+    };
+
+    let points = point_lights
+        .iter()
+        .filter(|(_, _, layers)| on_main_layer(*layers))
+        .map(|(light, transform, _)| {
+            // Luminous intensity per steradian; the shader divides by distance squared
+            let intensity = light.intensity / (4.0 * std::f32::consts::PI) * LIGHT_EXPOSURE;
+            GpuSdfLight {
+                position: transform.translation().extend(LIGHT_POINT),
+                color: (light.color.to_linear().to_vec3() * intensity).extend(light.range),
+            }
+        });
+    let directionals = directional_lights
+        .iter()
+        .filter(|(_, _, layers)| on_main_layer(*layers))
+        .map(|(light, transform, _)| GpuSdfLight {
+            // Directional lights shine along their forward direction
+            position: (-transform.forward().as_vec3()).extend(LIGHT_DIRECTIONAL),
+            color: (light.color.to_linear().to_vec3() * light.illuminance * LIGHT_EXPOSURE)
+                .extend(0.0),
+        });
+    light_data.0 = points.chain(directionals).collect();
+
+    for mut settings in camera_query.iter_mut() {
+        settings.light_count = light_data.0.len() as u32;
+        settings.shadow_softness = if shadows.enabled {
+            shadows.softness.max(0.001)
+        } else {
+            0.0
+        };
+    }
+}
+
+fn update_ambient_occlusion_in_settings(
+    ambient_occlusion: Res<SdfAmbientOcclusion>,
+    mut camera_query: Query<&mut SDFRenderSettings, With<SdfRenderCamera>>,
+) {
+    for mut settings in camera_query.iter_mut() {
+        settings.ao_intensity = if ambient_occlusion.enabled {
+            ambient_occlusion.intensity
+        } else {
+            0.0
+        };
+        settings.ao_radius = ambient_occlusion.radius.max(0.001);
+    }
+}
+
+fn update_fog_in_settings(
+    fog: Res<SdfFog>,
+    mut camera_query: Query<&mut SDFRenderSettings, With<SdfRenderCamera>>,
+) {
+    for mut settings in camera_query.iter_mut() {
+        settings.fog_color = fog.color.to_linear().to_vec4();
+        settings.fog_density = if fog.enabled { fog.density } else { 0.0 };
+        settings.fog_start = fog.start.max(0.0);
+    }
+}
+
+fn update_isolate_in_settings(
+    isolate: Res<SdfIsolateMode>,
+    selection: Res<SelectionState>,
+    mut camera_query: Query<&mut SDFRenderSettings, With<SdfRenderCamera>>,
+) {
+    for mut settings in camera_query.iter_mut() {
+        settings.isolate_enabled = (isolate.enabled && !selection.is_empty()) as u32;
+        settings.isolate_opacity = isolate.opacity.clamp(0.0, 1.0);
+    }
+}
+
+fn update_debug_view_in_settings(
+    debug_view: Res<SdfDebugView>,
+    mut camera_query: Query<&mut SDFRenderSettings, With<SdfRenderCamera>>,
+) {
+    for mut settings in camera_query.iter_mut() {
+        settings.debug_view = debug_view.as_gpu();
+    }
+}
+
+// Mirrors the anti-aliasing quality into the camera's FXAA
+fn update_anti_aliasing(
+    mut commands: Commands,
+    anti_aliasing: Res<SdfAntiAliasing>,
+    camera_query: Query<(Entity, Option<&Fxaa>), With<SdfRenderCamera>>,
+) {
+    let sensitivity = match anti_aliasing.quality {
+        0 => None,
+        1 => Some(Sensitivity::Low),
+        2 => Some(Sensitivity::Medium),
+        3 => Some(Sensitivity::High),
+        4 => Some(Sensitivity::Ultra),
+        _ => Some(Sensitivity::Extreme),
+    };
+
+    for (entity, fxaa) in camera_query.iter() {
+        let up_to_date = match (fxaa, sensitivity) {
+            (Some(fxaa), Some(sensitivity)) => fxaa.enabled && fxaa.edge_threshold == sensitivity,
+            (Some(fxaa), None) => !fxaa.enabled,
+            (None, sensitivity) => sensitivity.is_none(),
+        };
+        if up_to_date {
+            continue;
+        }
+
+        commands.entity(entity).insert(Fxaa {
+            enabled: sensitivity.is_some(),
+            edge_threshold: sensitivity.unwrap_or(Sensitivity::High),
+            edge_threshold_min: sensitivity.unwrap_or(Sensitivity::High),
+        });
+    }
+}
+
+// The environment only contributes once its image has finished loading
+fn update_environment_in_settings(
+    environment: Res<SdfEnvironment>,
+    asset_server: Res<AssetServer>,
+    mut camera_query: Query<&mut SDFRenderSettings, With<SdfRenderCamera>>,
+) {
+    let loaded = environment
+        .image
+        .as_ref()
+        .is_some_and(|image| asset_server.is_loaded_with_dependencies(image));
+    for mut settings in camera_query.iter_mut() {
+        settings.environment_intensity = if loaded { environment.intensity } else { 0.0 };
     }
 }
 
 fn update_time_in_settings(
     time: Res<Time>,
-    mut camera_query: Query<&mut SDFRenderSettings, With<Camera>>,
+    mut camera_query: Query<&mut SDFRenderSettings, With<SdfRenderCamera>>,
 ) {
     for mut settings in camera_query.iter_mut() {
         settings.time = time.elapsed().as_secs_f32();
@@ -1166,19 +2756,18 @@ fn manage_coarse_pass_texture(
     mut commands: Commands,
     render_device: Res<RenderDevice>,
     coarse_texture: Option<ResMut<CoarsePassTexture>>,
-    camera_query: Query<&SDFRenderSettings, With<Camera>>,
+    camera_query: Query<&SDFRenderSettings, With<SdfRenderCamera>>,
 ) {
     // Get the first camera's settings to determine texture size
     let Ok(settings) = camera_query.single() else {
         return;
     };
 
-    // Calculate coarse texture size based on resolution factor
-    // For now, use a base size - this should be updated based on actual viewport size
-    let base_width = 1920u32;
-    let base_height = 1080u32;
-    let coarse_width = (base_width as f32 * settings.coarse_resolution_factor) as u32;
-    let coarse_height = (base_height as f32 * settings.coarse_resolution_factor) as u32;
+    // Calculate coarse texture size from the viewport and the resolution factor
+    // (the shader derives the same size from the settings)
+    let coarse_size = settings.viewport_size * settings.coarse_resolution_factor;
+    let coarse_width = coarse_size.x as u32;
+    let coarse_height = coarse_size.y as u32;
 
     let desired_size = Extent3d {
         width: coarse_width.max(1),
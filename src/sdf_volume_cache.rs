@@ -0,0 +1,537 @@
+//! Baked distance volume for large static scenes
+//!
+//! Once the entities have been left alone for a moment, a compute pass bakes
+//! the distance to them into a 3D texture spanning the scene, a few slices per
+//! frame. The SDF pass then takes long steps through the baked volume wherever
+//! it is far from any surface, and only evaluates the entities analytically
+//! close to surfaces and around entities edited since the bake. Bakes go into
+//! whichever of two textures the SDF pass isn't sampling, so it never sees a
+//! half-baked volume.
+
+use std::time::Duration;
+
+use bevy::{
+    core_pipeline::core_3d::graph::Core3d,
+    prelude::*,
+    render::{
+        extract_resource::{ExtractResource, ExtractResourcePlugin},
+        render_graph::{self, RenderGraphApp, RenderLabel},
+        render_resource::{binding_types::*, *},
+        renderer::{RenderContext, RenderDevice},
+        Render, RenderApp, RenderSet,
+    },
+};
+use bvh::aabb::Bounded;
+use bytemuck::{Pod, Zeroable};
+
+use crate::sdf_render::{
+    EntityBuffer, EntityData, GpuSdfEntity, SDFCoarsePrepassLabel, SDFRenderSettings,
+    SdfRenderCamera,
+};
+
+const SHADER_ASSET_PATH: &str = "shaders/sdf_volume_bake.wgsl";
+
+// Voxels baked per invocation group along each axis (must match sdf_volume_bake.wgsl)
+const BAKE_WORKGROUP_SIZE: u32 = 4;
+
+// Fraction of the scene's extent the volume reaches past it on each side, so
+// rays can skip towards the scene before they reach it
+const VOLUME_PADDING: f32 = 0.1;
+
+// Stored in the settings while nothing has been edited since the bake, far
+// enough away that no step is ever limited by it
+const EMPTY_DIRTY_MIN: Vec3 = Vec3::splat(1.0e9);
+const EMPTY_DIRTY_MAX: Vec3 = Vec3::splat(-1.0e9);
+
+pub struct SdfVolumeCachePlugin;
+
+#[derive(Resource, Clone)]
+pub struct SdfVolumeCache {
+    pub enabled: bool,
+    // Scenes with fewer entities are cheap enough to march analytically
+    pub min_entities: usize,
+    // Voxels along each axis of the volume
+    pub resolution: u32,
+    // Slices of the volume baked per frame, which bounds the cost of a bake
+    pub slices_per_frame: u32,
+    // How long the entities have to stay unchanged before they are baked
+    pub settle_time: Duration,
+}
+
+impl Default for SdfVolumeCache {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            min_entities: 64,
+            resolution: 64,
+            slices_per_frame: 8,
+            settle_time: Duration::from_millis(500),
+        }
+    }
+}
+
+// Volume over the entities as they were when its bake started
+struct VolumeBake {
+    // Which of the two volume textures it is baked into
+    target: usize,
+    min: Vec3,
+    max: Vec3,
+    entities: Vec<GpuSdfEntity>,
+    slices_done: u32,
+}
+
+#[derive(Resource, Default)]
+struct SdfVolumeCacheState {
+    resolution: u32,
+    // Completely baked volume the SDF pass samples
+    ready: Option<VolumeBake>,
+    baking: Option<VolumeBake>,
+    // When the entity data last changed
+    last_change: Duration,
+    // Bounds of the entities that differ from the ready volume, old and new
+    dirty: Option<(Vec3, Vec3)>,
+}
+
+// Slices of a volume to bake this frame
+#[derive(Clone)]
+struct VolumeBakeSlices {
+    target: usize,
+    min: Vec3,
+    max: Vec3,
+    entity_count: u32,
+    first_slice: u32,
+    slices: u32,
+}
+
+// What the render world does with the volume textures this frame
+#[derive(Resource, Clone, Default)]
+struct SdfVolumeFrame {
+    resolution: u32,
+    // Texture holding the ready volume
+    sample: Option<usize>,
+    bake: Option<VolumeBakeSlices>,
+}
+
+impl ExtractResource for SdfVolumeFrame {
+    type Source = SdfVolumeFrame;
+
+    fn extract_resource(source: &Self::Source) -> Self {
+        source.clone()
+    }
+}
+
+impl Plugin for SdfVolumeCachePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SdfVolumeCache>()
+            .init_resource::<SdfVolumeCacheState>()
+            .init_resource::<SdfVolumeFrame>()
+            .add_plugins(ExtractResourcePlugin::<SdfVolumeFrame>::default())
+            // After the entity data of the frame has been collected
+            .add_systems(
+                PostUpdate,
+                (update_volume_cache, update_volume_in_settings).chain(),
+            );
+
+        let Some(render_app) = app.get_sub_app_mut(RenderApp) else {
+            return;
+        };
+
+        render_app
+            .add_systems(
+                Render,
+                (
+                    prepare_volume_textures.in_set(RenderSet::PrepareResources),
+                    prepare_volume_bake.in_set(RenderSet::PrepareBindGroups),
+                ),
+            )
+            .add_render_graph_node::<SdfVolumeBakeNode>(Core3d, SdfVolumeBakeLabel)
+            .add_render_graph_edge(Core3d, SdfVolumeBakeLabel, SDFCoarsePrepassLabel);
+    }
+
+    fn finish(&self, app: &mut App) {
+        let Some(render_app) = app.get_sub_app_mut(RenderApp) else {
+            return;
+        };
+
+        render_app.init_resource::<SdfVolumeBakePipeline>();
+    }
+}
+
+// Only the shape of an entity is baked, not e.g. whether it is selected
+fn same_geometry(a: &GpuSdfEntity, b: &GpuSdfEntity) -> bool {
+    a.position_scale == b.position_scale
+        && a.shape == b.shape
+        && a.modifiers == b.modifiers
+        && a.operation == b.operation
+        && a.primitive == b.primitive
+}
+
+fn entity_bounds(entity: &GpuSdfEntity) -> (Vec3, Vec3) {
+    let aabb = entity.aabb();
+    (
+        Vec3::new(aabb.min.x, aabb.min.y, aabb.min.z),
+        Vec3::new(aabb.max.x, aabb.max.y, aabb.max.z),
+    )
+}
+
+fn union_bounds(bounds: Option<(Vec3, Vec3)>, (min, max): (Vec3, Vec3)) -> Option<(Vec3, Vec3)> {
+    Some(bounds.map_or((min, max), |(a, b)| (a.min(min), b.max(max))))
+}
+
+// Bounds of every entity that differs between the baked and current entities
+fn dirty_bounds(baked: &[GpuSdfEntity], current: &[GpuSdfEntity]) -> Option<(Vec3, Vec3)> {
+    let mut dirty = None;
+    for index in 0..baked.len().max(current.len()) {
+        let (old, new) = (baked.get(index), current.get(index));
+        if let (Some(old), Some(new)) = (old, new) {
+            if same_geometry(old, new) {
+                continue;
+            }
+        }
+        for entity in [old, new].into_iter().flatten() {
+            dirty = union_bounds(dirty, entity_bounds(entity));
+        }
+    }
+    dirty
+}
+
+// Starts a bake once the entities have settled and advances it a few slices
+// per frame, swapping it in for the ready volume once every slice is done
+fn update_volume_cache(
+    cache: Res<SdfVolumeCache>,
+    entity_data: Option<Res<EntityData>>,
+    time: Res<Time>,
+    mut state: ResMut<SdfVolumeCacheState>,
+    mut frame: ResMut<SdfVolumeFrame>,
+) {
+    let Some(entity_data) = entity_data else {
+        return;
+    };
+    let entities = entity_data.entities();
+    let resolution = cache.resolution.max(1).next_multiple_of(BAKE_WORKGROUP_SIZE);
+    let usable = cache.enabled && entities.len() >= cache.min_entities.max(1);
+
+    if entity_data.is_changed() {
+        state.last_change = time.elapsed();
+        // A bake of entities that have changed since is of no use anymore
+        state.baking = None;
+    }
+    if !usable || state.resolution != resolution {
+        state.ready = None;
+        state.baking = None;
+        state.resolution = resolution;
+    }
+
+    let settled = time.elapsed() >= state.last_change + cache.settle_time;
+    let up_to_date = state.ready.as_ref().is_some_and(|ready| {
+        ready.entities.len() == entities.len()
+            && ready.entities.iter().zip(entities).all(|(a, b)| same_geometry(a, b))
+    });
+    if usable && settled && !up_to_date && state.baking.is_none() {
+        let bounds = entities
+            .iter()
+            .fold(None, |bounds, e| union_bounds(bounds, entity_bounds(e)));
+        if let Some((min, max)) = bounds {
+            let padding = (max - min) * VOLUME_PADDING;
+            state.baking = Some(VolumeBake {
+                target: state.ready.as_ref().map_or(0, |ready| 1 - ready.target),
+                min: min - padding,
+                max: max + padding,
+                entities: entities.to_vec(),
+                slices_done: 0,
+            });
+        }
+    }
+
+    frame.bake = None;
+    let mut completed = false;
+    if let Some(bake) = &mut state.baking {
+        let slices = cache
+            .slices_per_frame
+            .next_multiple_of(BAKE_WORKGROUP_SIZE)
+            .clamp(BAKE_WORKGROUP_SIZE, resolution - bake.slices_done);
+        frame.bake = Some(VolumeBakeSlices {
+            target: bake.target,
+            min: bake.min,
+            max: bake.max,
+            entity_count: bake.entities.len() as u32,
+            first_slice: bake.slices_done,
+            slices,
+        });
+        bake.slices_done += slices;
+        completed = bake.slices_done >= resolution;
+    }
+    if completed {
+        // The last slices are baked before the SDF pass of the same frame runs
+        state.ready = state.baking.take();
+    }
+
+    if entity_data.is_changed() || completed || state.ready.is_none() {
+        state.dirty = state
+            .ready
+            .as_ref()
+            .and_then(|ready| dirty_bounds(&ready.entities, entities));
+    }
+    frame.resolution = resolution;
+    frame.sample = state.ready.as_ref().map(|ready| ready.target);
+}
+
+fn update_volume_in_settings(
+    state: Res<SdfVolumeCacheState>,
+    mut camera_query: Query<&mut SDFRenderSettings, With<SdfRenderCamera>>,
+) {
+    let (dirty_min, dirty_max) = state.dirty.unwrap_or((EMPTY_DIRTY_MIN, EMPTY_DIRTY_MAX));
+    for mut settings in camera_query.iter_mut() {
+        settings.volume_enabled = state.ready.is_some() as u32;
+        if let Some(ready) = &state.ready {
+            settings.volume_min = ready.min;
+            settings.volume_max = ready.max;
+        }
+        settings.volume_dirty_min = dirty_min;
+        settings.volume_dirty_max = dirty_max;
+    }
+}
+
+// The pair of volume textures in the render world
+#[derive(Resource)]
+pub struct SdfVolumeTextures {
+    resolution: u32,
+    views: [TextureView; 2],
+    // Whether every slice of the volume in each texture was actually baked,
+    // which isn't the case while the bake pipeline is still compiling
+    complete: [bool; 2],
+    sample: Option<usize>,
+    // Reads as zero distance, so the SDF pass marches everything analytically
+    // until a volume is ready
+    empty_view: TextureView,
+}
+
+impl SdfVolumeTextures {
+    // Volume the SDF pass samples this frame
+    pub fn sample_view(&self) -> &TextureView {
+        match self.sample {
+            Some(target) if self.complete[target] => &self.views[target],
+            _ => &self.empty_view,
+        }
+    }
+}
+
+fn create_volume_texture(render_device: &RenderDevice, size: u32, usage: TextureUsages) -> Texture {
+    render_device.create_texture(&TextureDescriptor {
+        label: Some("sdf_volume_texture"),
+        size: Extent3d {
+            width: size,
+            height: size,
+            depth_or_array_layers: size,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: TextureDimension::D3,
+        format: TextureFormat::Rgba16Float,
+        usage,
+        view_formats: &[],
+    })
+}
+
+fn prepare_volume_textures(
+    mut commands: Commands,
+    frame: Option<Res<SdfVolumeFrame>>,
+    textures: Option<ResMut<SdfVolumeTextures>>,
+    render_device: Res<RenderDevice>,
+) {
+    let Some(frame) = frame else {
+        return;
+    };
+
+    if let Some(mut textures) = textures {
+        if textures.resolution == frame.resolution {
+            textures.sample = frame.sample;
+            return;
+        }
+    }
+
+    let usage = TextureUsages::STORAGE_BINDING | TextureUsages::TEXTURE_BINDING;
+    let views = [0, 1].map(|_| {
+        create_volume_texture(&render_device, frame.resolution, usage).create_view(&default())
+    });
+    let empty_view = create_volume_texture(&render_device, 1, TextureUsages::TEXTURE_BINDING)
+        .create_view(&default());
+    commands.insert_resource(SdfVolumeTextures {
+        resolution: frame.resolution,
+        views,
+        complete: [false; 2],
+        sample: None,
+        empty_view,
+    });
+}
+
+// Where the bake of this frame goes (must match VolumeBakeParams in sdf_volume_bake.wgsl)
+#[repr(C)]
+#[derive(Clone, Copy, Pod, Zeroable)]
+struct VolumeBakeParams {
+    volume_min: Vec3,
+    entity_count: u32,
+    volume_max: Vec3,
+    first_slice: u32,
+}
+
+#[derive(Resource)]
+struct SdfVolumeBakeBindGroups {
+    volume_bind_group: BindGroup,
+    entity_bind_group: BindGroup,
+    workgroups: UVec3,
+}
+
+fn prepare_volume_bake(
+    mut commands: Commands,
+    frame: Option<Res<SdfVolumeFrame>>,
+    textures: Option<ResMut<SdfVolumeTextures>>,
+    pipeline: Res<SdfVolumeBakePipeline>,
+    pipeline_cache: Res<PipelineCache>,
+    entity_buffer: Res<EntityBuffer>,
+    render_device: Res<RenderDevice>,
+) {
+    commands.remove_resource::<SdfVolumeBakeBindGroups>();
+    let (Some(frame), Some(mut textures)) = (frame, textures) else {
+        return;
+    };
+    let Some(bake) = &frame.bake else {
+        return;
+    };
+
+    // A volume is complete if all of its slices were baked, starting from the first
+    if bake.first_slice == 0 {
+        textures.complete[bake.target] = true;
+    }
+    let ready = pipeline_cache.get_compute_pipeline(pipeline.pipeline).is_some();
+    let Some(entity_buffer) = entity_buffer.buffer.as_ref().filter(|_| ready) else {
+        textures.complete[bake.target] = false;
+        return;
+    };
+
+    let params = VolumeBakeParams {
+        volume_min: bake.min,
+        entity_count: bake.entity_count,
+        volume_max: bake.max,
+        first_slice: bake.first_slice,
+    };
+    let params_buffer = render_device.create_buffer_with_data(&BufferInitDescriptor {
+        label: Some("sdf_volume_bake_params"),
+        contents: bytemuck::bytes_of(&params),
+        usage: BufferUsages::UNIFORM,
+    });
+
+    let volume_bind_group = render_device.create_bind_group(
+        Some("sdf_volume_bake_bind_group"),
+        &pipeline.volume_layout,
+        &BindGroupEntries::sequential((
+            &textures.views[bake.target],
+            params_buffer.as_entire_binding(),
+        )),
+    );
+    let entity_bind_group = render_device.create_bind_group(
+        Some("sdf_volume_bake_entity_bind_group"),
+        &pipeline.entity_layout,
+        &BindGroupEntries::with_indices(((1, entity_buffer.as_entire_binding()),)),
+    );
+
+    let groups = textures.resolution / BAKE_WORKGROUP_SIZE;
+    commands.insert_resource(SdfVolumeBakeBindGroups {
+        volume_bind_group,
+        entity_bind_group,
+        workgroups: UVec3::new(groups, groups, bake.slices / BAKE_WORKGROUP_SIZE),
+    });
+}
+
+#[derive(Resource)]
+struct SdfVolumeBakePipeline {
+    volume_layout: BindGroupLayout,
+    entity_layout: BindGroupLayout,
+    pipeline: CachedComputePipelineId,
+}
+
+impl FromWorld for SdfVolumeBakePipeline {
+    fn from_world(world: &mut World) -> Self {
+        let render_device = world.resource::<RenderDevice>();
+
+        let volume_layout = render_device.create_bind_group_layout(
+            Some("sdf_volume_bake_layout"),
+            &BindGroupLayoutEntries::sequential(
+                ShaderStages::COMPUTE,
+                (
+                    texture_storage_3d(TextureFormat::Rgba16Float, StorageTextureAccess::WriteOnly),
+                    uniform_buffer_sized(false, None),
+                ),
+            ),
+        );
+
+        // Only the entities of the shared scene group are read by the bake
+        let entity_layout = render_device.create_bind_group_layout(
+            Some("sdf_volume_bake_entity_layout"),
+            &BindGroupLayoutEntries::with_indices(
+                ShaderStages::COMPUTE,
+                ((1, storage_buffer_read_only_sized(false, None)),),
+            ),
+        );
+
+        let shader = world.load_asset(SHADER_ASSET_PATH);
+        let pipeline_cache = world.resource::<PipelineCache>();
+        let pipeline = pipeline_cache.queue_compute_pipeline(ComputePipelineDescriptor {
+            label: Some("sdf_volume_bake_pipeline".into()),
+            layout: vec![volume_layout.clone(), entity_layout.clone()],
+            push_constant_ranges: Vec::new(),
+            shader,
+            shader_defs: Vec::new(),
+            entry_point: "bake".into(),
+            zero_initialize_workgroup_memory: false,
+        });
+
+        Self {
+            volume_layout,
+            entity_layout,
+            pipeline,
+        }
+    }
+}
+
+#[derive(Debug, Hash, PartialEq, Eq, Clone, RenderLabel)]
+struct SdfVolumeBakeLabel;
+
+// Bakes this frame's slices ahead of the SDF passes. The graph runs it for
+// every view, which rewrites the same slices but is otherwise harmless
+#[derive(Default)]
+struct SdfVolumeBakeNode;
+
+impl render_graph::Node for SdfVolumeBakeNode {
+    fn run(
+        &self,
+        _graph: &mut render_graph::RenderGraphContext,
+        render_context: &mut RenderContext,
+        world: &World,
+    ) -> Result<(), render_graph::NodeRunError> {
+        let Some(bind_groups) = world.get_resource::<SdfVolumeBakeBindGroups>() else {
+            return Ok(());
+        };
+        let pipeline = world.resource::<SdfVolumeBakePipeline>();
+        let Some(compute_pipeline) = world
+            .resource::<PipelineCache>()
+            .get_compute_pipeline(pipeline.pipeline)
+        else {
+            return Ok(());
+        };
+
+        let mut pass = render_context
+            .command_encoder()
+            .begin_compute_pass(&ComputePassDescriptor {
+                label: Some("sdf_volume_bake_pass"),
+                ..default()
+            });
+        pass.set_pipeline(compute_pipeline);
+        pass.set_bind_group(0, &bind_groups.volume_bind_group, &[]);
+        pass.set_bind_group(1, &bind_groups.entity_bind_group, &[]);
+        let workgroups = bind_groups.workgroups;
+        pass.dispatch_workgroups(workgroups.x, workgroups.y, workgroups.z);
+
+        Ok(())
+    }
+}
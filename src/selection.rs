@@ -1,5 +1,10 @@
+use crate::edit_history::{Edit, EditHistory, EntitySnapshot, RenderParts};
+use crate::entity_info::SdfEntityInfo;
 use crate::mode::AppModeState;
+use crate::sdf_render::SDFRenderEntity;
+use crate::translation::DragData;
 use bevy::prelude::*;
+use std::collections::HashSet;
 
 // Plugin for the selection system
 pub struct SelectionPlugin;
@@ -9,18 +14,43 @@ impl Plugin for SelectionPlugin {
         app.init_resource::<SelectionState>()
             .add_event::<EntitySelectedEvent>()
             .add_event::<EntityDeselectedEvent>()
-            .add_systems(Update, on_change_app_mode);
+            .add_event::<EntityDeletedEvent>()
+            .add_event::<DeleteEntities>()
+            .add_event::<EntitiesDeleted>()
+            .add_systems(
+                Update,
+                (on_change_app_mode, delete_selected_entities, delete_entities).chain(),
+            );
     }
 }
 
-// Component to mark the currently selected entity
+// Component to mark the currently selected entities
 #[derive(Component)]
 pub struct Selected;
 
-// Resource to track the currently selected entity
+// Resource to track the currently selected entities, in selection order
 #[derive(Resource, Default)]
 pub struct SelectionState {
-    pub selected_entity: Option<Entity>,
+    pub selected_entities: Vec<Entity>,
+}
+
+impl SelectionState {
+    pub fn is_selected(&self, entity: Entity) -> bool {
+        self.selected_entities.contains(&entity)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.selected_entities.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = Entity> + '_ {
+        self.selected_entities.iter().copied()
+    }
+
+    // Forget an entity without emitting events, e.g. because it was despawned
+    pub fn remove(&mut self, entity: Entity) {
+        self.selected_entities.retain(|e| *e != entity);
+    }
 }
 
 // Events for selection changes
@@ -30,12 +60,63 @@ pub struct EntitySelectedEvent;
 #[derive(Event)]
 pub struct EntityDeselectedEvent;
 
-// Observer system to handle selection logic using the Bevy picking system
+#[derive(Event)]
+pub struct EntityDeletedEvent;
+
+// What a DeleteEntities request despawns
+#[derive(Clone, Copy, Debug)]
+pub enum DeleteTarget {
+    // The SDF entity with this node index
+    Id(usize),
+    Selected,
+    // Every entity and group in the scene
+    All,
+}
+
+// Despawns entities as a single undo step, groups together with their children
+#[derive(Event)]
+pub struct DeleteEntities(pub DeleteTarget);
+
+// Node indices of the SDF entities a DeleteEntities request despawned, children
+// of deleted groups included
+#[derive(Event)]
+pub struct EntitiesDeleted(pub Vec<usize>);
+
+// Adds the entity to the selection, leaving the rest selected
+pub fn select(commands: &mut Commands, selection_state: &mut SelectionState, entity: Entity) {
+    commands.entity(entity).insert(Selected);
+    selection_state.selected_entities.push(entity);
+    commands.trigger_targets(EntitySelectedEvent, entity);
+}
+
+// Also for entities about to be despawned, so observers like the gizmo can clean up
+pub fn deselect(commands: &mut Commands, selection_state: &mut SelectionState, entity: Entity) {
+    // The entity may have been despawned since it was selected
+    if let Ok(mut entity_commands) = commands.get_entity(entity) {
+        entity_commands.remove::<Selected>();
+    }
+    selection_state.remove(entity);
+    commands.trigger_targets(EntityDeselectedEvent, entity);
+}
+
+// Deselect everything, e.g. before the scene is replaced. Call it before
+// despawning the selected entities, so the deselect observers still find them
+pub fn deselect_all(commands: &mut Commands, selection_state: &mut SelectionState) {
+    let selected: Vec<Entity> = selection_state.iter().collect();
+    for entity in selected {
+        deselect(commands, selection_state, entity);
+    }
+}
+
+// Observer system to handle selection logic using the Bevy picking system.
+// A plain click selects only the clicked entity, shift-click adds it to or
+// removes it from the selection.
 pub fn handle_selection(
     click: Trigger<Pointer<Click>>,
     mut commands: Commands,
     mut selection_state: ResMut<SelectionState>,
     mode_state: Res<AppModeState>,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
 ) {
     // Early return if selection is not enabled for the current mode
     if !mode_state.is_selection_enabled() {
@@ -44,29 +125,136 @@ pub fn handle_selection(
 
     // Get entity from pointer interactions
     let entity = click.target();
+    let shift = keyboard_input.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+    click_select(&mut commands, &mut selection_state, entity, shift);
+}
 
-    // Check if the clicked entity is already selected
-    if selection_state.selected_entity == Some(entity) {
-        return;
-    } else {
-        // Deselect any currently selected entity
-        if let Some(selected_entity) = selection_state.selected_entity {
-            commands.entity(selected_entity).remove::<Selected>();
-            commands.trigger_targets(EntityDeselectedEvent, selected_entity);
+// Shared by mesh picking and SDF picking
+pub fn click_select(
+    commands: &mut Commands,
+    selection_state: &mut SelectionState,
+    entity: Entity,
+    shift: bool,
+) {
+    if shift {
+        if selection_state.is_selected(entity) {
+            deselect(commands, selection_state, entity);
+        } else {
+            select(commands, selection_state, entity);
         }
+        return;
+    }
 
-        // Select the new entity
-        commands.entity(entity).insert(Selected);
-        selection_state.selected_entity = Some(entity);
-        commands.trigger_targets(EntitySelectedEvent, entity);
+    // Deselect everything except the clicked entity
+    let others: Vec<Entity> = selection_state.iter().filter(|e| *e != entity).collect();
+    for other in others {
+        deselect(commands, selection_state, other);
+    }
+
+    if !selection_state.is_selected(entity) {
+        select(commands, selection_state, entity);
     }
 }
+
 pub fn on_change_app_mode(
     app_mode: Res<AppModeState>,
+    mut commands: Commands,
     mut selection_state: ResMut<SelectionState>,
 ) {
     if !app_mode.is_changed() || app_mode.is_selection_enabled() {
         return;
     }
-    selection_state.selected_entity = None;
+    deselect_all(&mut commands, &mut selection_state);
+}
+
+// Deletes the selected entities on Delete/Backspace
+pub fn delete_selected_entities(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mode_state: Res<AppModeState>,
+    drag_data: Res<DragData>,
+    selection_state: Res<SelectionState>,
+    mut delete_events: EventWriter<DeleteEntities>,
+) {
+    if !mode_state.is_selection_enabled() || selection_state.is_empty() {
+        return;
+    }
+    // Backspace edits typed offsets while dragging
+    if !matches!(*drag_data, DragData::Idle) {
+        return;
+    }
+    if keyboard_input.any_just_pressed([KeyCode::Delete, KeyCode::Backspace]) {
+        delete_events.write(DeleteEntities(DeleteTarget::Selected));
+    }
+}
+
+// Children are recorded before their parents so undo restores a group before
+// them. Entities inside a group that is deleted too are only recorded once.
+fn delete_entities(
+    mut delete_events: EventReader<DeleteEntities>,
+    mut deleted_events: EventWriter<EntitiesDeleted>,
+    mut commands: Commands,
+    mut selection_state: ResMut<SelectionState>,
+    mut history: ResMut<EditHistory>,
+    entities: Query<(
+        &Transform,
+        Option<&SDFRenderEntity>,
+        Option<&SdfEntityInfo>,
+        RenderParts,
+    )>,
+    sdf_entities: Query<(Entity, &SDFRenderEntity)>,
+    roots: Query<Entity, (With<SdfEntityInfo>, Without<ChildOf>)>,
+    children: Query<&Children>,
+) {
+    // Despawns only take effect once the commands are applied
+    let mut deleted = HashSet::new();
+    for DeleteEntities(target) in delete_events.read() {
+        let targets: Vec<Entity> = match *target {
+            DeleteTarget::Id(id) => {
+                let found = sdf_entities
+                    .iter()
+                    .find(|(_, sdf_entity)| sdf_entity.node_index == id)
+                    .map(|(entity, _)| entity);
+                if found.is_none() {
+                    warn!("No entity with id {} to delete", id);
+                }
+                found.into_iter().collect()
+            }
+            DeleteTarget::Selected => selection_state.iter().collect(),
+            DeleteTarget::All => roots.iter().collect(),
+        };
+
+        let mut edits = Vec::new();
+        let mut ids = Vec::new();
+        for target in targets {
+            if deleted.contains(&target) {
+                continue;
+            }
+            let descendants: Vec<Entity> = children.iter_descendants(target).collect();
+            for entity in descendants.into_iter().chain(std::iter::once(target)) {
+                if !deleted.insert(entity) {
+                    continue;
+                }
+                if selection_state.is_selected(entity) {
+                    deselect(&mut commands, &mut selection_state, entity);
+                }
+                let Ok((transform, sdf, info, render_parts)) = entities.get(entity) else {
+                    continue;
+                };
+                if let Some(sdf) = sdf {
+                    ids.push(sdf.node_index);
+                }
+                edits.push(Edit::Delete(EntitySnapshot::capture(
+                    entity,
+                    *transform,
+                    sdf,
+                    info,
+                    render_parts,
+                )));
+            }
+            commands.trigger_targets(EntityDeletedEvent, target);
+            commands.entity(target).despawn();
+        }
+        history.record_all(edits);
+        deleted_events.write(EntitiesDeleted(ids));
+    }
 }
@@ -0,0 +1,349 @@
+//! User settings that persist between sessions
+//!
+//! Keybindings, brush defaults, render toggles, camera sensitivities and the
+//! paths of recent projects are kept in a TOML file: `settings.toml` in the
+//! platform config directory natively, a localStorage item on the web. The
+//! file is read when the plugin is built and applied once the camera exists.
+//! From then on the settings are compared with what was last written every
+//! second and saved when they differ, so dragging a slider doesn't rewrite the
+//! file every frame. Missing keys keep their defaults, so files from older
+//! versions still load.
+
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use bevy_panorbit_camera::PanOrbitCamera;
+use serde::{Deserialize, Serialize};
+
+use crate::adaptive_resolution::SdfAdaptiveResolution;
+use crate::brush_mode::{
+    BrushJitter, BrushSettings, BrushTool, BrushToolState, CurveProfile, PressureCurve,
+};
+use crate::coarse_tuning::SdfCoarseTuning;
+use crate::project_file::RecentProjects;
+use crate::sdf_render::{SdfAmbientOcclusion, SdfOperation, SdfRenderCamera};
+use crate::temporal_accumulation::SdfTemporalAccumulation;
+
+// Seconds between checks for changed settings
+const SAVE_INTERVAL: f32 = 1.0;
+
+#[cfg(target_arch = "wasm32")]
+const STORAGE_KEY: &str = "bevy_modeller.settings";
+
+pub struct SettingsPlugin;
+
+impl Plugin for SettingsPlugin {
+    fn build(&self, app: &mut App) {
+        let settings = load_settings();
+        app.insert_resource(settings.keys.clone())
+            .insert_resource(SettingsStore {
+                saved: settings,
+                timer: Timer::from_seconds(SAVE_INTERVAL, TimerMode::Repeating),
+            })
+            // The camera is spawned during Startup
+            .add_systems(PostStartup, apply_settings)
+            .add_systems(Update, save_changed_settings);
+    }
+}
+
+// Keys of the global shortcuts
+#[derive(Resource, Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct KeyBindings {
+    pub toggle_sdf_render: KeyCode,
+    pub toggle_ambient_occlusion: KeyCode,
+    pub toggle_isolate_mode: KeyCode,
+    pub cycle_debug_view: KeyCode,
+    pub toggle_preview_mesh: KeyCode,
+    pub estimate_volume: KeyCode,
+    pub turntable_capture: KeyCode,
+    // Pressed with Ctrl, and with Ctrl+Shift to redo
+    pub undo: KeyCode,
+    pub grab: KeyCode,
+    // Held while brushing to carve instead of add, or the other way round
+    pub carve: KeyCode,
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        Self {
+            toggle_sdf_render: KeyCode::KeyP,
+            toggle_ambient_occlusion: KeyCode::KeyL,
+            toggle_isolate_mode: KeyCode::KeyI,
+            cycle_debug_view: KeyCode::F3,
+            toggle_preview_mesh: KeyCode::F6,
+            estimate_volume: KeyCode::F7,
+            turntable_capture: KeyCode::F12,
+            undo: KeyCode::KeyZ,
+            grab: KeyCode::KeyG,
+            carve: KeyCode::ControlLeft,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct BrushDefaults {
+    pub tool: BrushTool,
+    pub operation: SdfOperation,
+    // Hex sRGB of the paint color
+    pub color: String,
+    // Unset uses each tool's own radius
+    pub radius: Option<f32>,
+    pub pressure_curve: PressureCurve,
+    pub jitter: BrushJitter,
+    pub curve: CurveProfile,
+}
+
+impl Default for BrushDefaults {
+    fn default() -> Self {
+        let tool = BrushToolState::default();
+        let settings = BrushSettings::default();
+        Self {
+            tool: tool.tool,
+            operation: settings.operation,
+            color: tool.color.to_srgba().to_hex(),
+            radius: settings.radius,
+            pressure_curve: settings.pressure_curve,
+            jitter: settings.jitter,
+            curve: settings.curve,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct RenderPreferences {
+    pub ambient_occlusion: bool,
+    pub ambient_occlusion_intensity: f32,
+    pub ambient_occlusion_radius: f32,
+    pub adaptive_resolution: bool,
+    pub adaptive_min_scale: f32,
+    pub temporal_accumulation: bool,
+    pub temporal_blend: f32,
+    pub coarse_tuning: bool,
+}
+
+impl Default for RenderPreferences {
+    fn default() -> Self {
+        let ambient_occlusion = SdfAmbientOcclusion::default();
+        let adaptive = SdfAdaptiveResolution::default();
+        let temporal = SdfTemporalAccumulation::default();
+        Self {
+            ambient_occlusion: ambient_occlusion.enabled,
+            ambient_occlusion_intensity: ambient_occlusion.intensity,
+            ambient_occlusion_radius: ambient_occlusion.radius,
+            adaptive_resolution: adaptive.enabled,
+            adaptive_min_scale: adaptive.min_scale,
+            temporal_accumulation: temporal.enabled,
+            temporal_blend: temporal.blend,
+            coarse_tuning: SdfCoarseTuning::default().enabled,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct CameraPreferences {
+    pub orbit_sensitivity: f32,
+    pub pan_sensitivity: f32,
+    pub zoom_sensitivity: f32,
+}
+
+impl Default for CameraPreferences {
+    fn default() -> Self {
+        Self {
+            orbit_sensitivity: 1.0,
+            pan_sensitivity: 1.0,
+            zoom_sensitivity: 1.0,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Default)]
+#[serde(default)]
+pub struct UserSettings {
+    pub keys: KeyBindings,
+    pub brush: BrushDefaults,
+    pub render: RenderPreferences,
+    pub camera: CameraPreferences,
+    // Most recently saved or opened first
+    pub recent_projects: Vec<String>,
+}
+
+#[derive(Resource)]
+struct SettingsStore {
+    // As last loaded or written
+    saved: UserSettings,
+    timer: Timer,
+}
+
+// The resources the settings are read from and applied to
+#[derive(SystemParam)]
+struct SettingsTargets<'w, 's> {
+    keys: ResMut<'w, KeyBindings>,
+    brush_tool: ResMut<'w, BrushToolState>,
+    brush: ResMut<'w, BrushSettings>,
+    ambient_occlusion: ResMut<'w, SdfAmbientOcclusion>,
+    adaptive: ResMut<'w, SdfAdaptiveResolution>,
+    temporal: ResMut<'w, SdfTemporalAccumulation>,
+    coarse: ResMut<'w, SdfCoarseTuning>,
+    cameras: Query<'w, 's, &'static mut PanOrbitCamera, With<SdfRenderCamera>>,
+    recent_projects: ResMut<'w, RecentProjects>,
+}
+
+impl SettingsTargets<'_, '_> {
+    fn current(&self) -> UserSettings {
+        let camera = self.cameras.iter().next().map_or_else(CameraPreferences::default, |camera| {
+            CameraPreferences {
+                orbit_sensitivity: camera.orbit_sensitivity,
+                pan_sensitivity: camera.pan_sensitivity,
+                zoom_sensitivity: camera.zoom_sensitivity,
+            }
+        });
+        UserSettings {
+            keys: self.keys.clone(),
+            brush: BrushDefaults {
+                tool: self.brush_tool.tool,
+                operation: self.brush.operation,
+                color: self.brush_tool.color.to_srgba().to_hex(),
+                radius: self.brush.radius,
+                pressure_curve: self.brush.pressure_curve,
+                jitter: self.brush.jitter,
+                curve: self.brush.curve,
+            },
+            render: RenderPreferences {
+                ambient_occlusion: self.ambient_occlusion.enabled,
+                ambient_occlusion_intensity: self.ambient_occlusion.intensity,
+                ambient_occlusion_radius: self.ambient_occlusion.radius,
+                adaptive_resolution: self.adaptive.enabled,
+                adaptive_min_scale: self.adaptive.min_scale,
+                temporal_accumulation: self.temporal.enabled,
+                temporal_blend: self.temporal.blend,
+                coarse_tuning: self.coarse.enabled,
+            },
+            camera,
+            recent_projects: self.recent_projects.paths(),
+        }
+    }
+
+    fn apply(&mut self, settings: &UserSettings) {
+        *self.keys = settings.keys.clone();
+
+        let brush = &settings.brush;
+        self.brush_tool.tool = brush.tool;
+        match Srgba::hex(&brush.color) {
+            Ok(color) => self.brush_tool.color = color.into(),
+            Err(err) => warn!("Ignoring brush color {}: {:?}", brush.color, err),
+        }
+        self.brush.operation = brush.operation;
+        self.brush.radius = brush.radius;
+        self.brush.pressure_curve = brush.pressure_curve;
+        self.brush.jitter = brush.jitter;
+        self.brush.curve = brush.curve;
+
+        let render = &settings.render;
+        self.ambient_occlusion.enabled = render.ambient_occlusion;
+        self.ambient_occlusion.intensity = render.ambient_occlusion_intensity;
+        self.ambient_occlusion.radius = render.ambient_occlusion_radius;
+        self.adaptive.enabled = render.adaptive_resolution;
+        self.adaptive.min_scale = render.adaptive_min_scale.clamp(0.1, 1.0);
+        self.temporal.enabled = render.temporal_accumulation;
+        self.temporal.blend = render.temporal_blend.clamp(0.0, 1.0);
+        self.coarse.enabled = render.coarse_tuning;
+
+        for mut camera in self.cameras.iter_mut() {
+            camera.orbit_sensitivity = settings.camera.orbit_sensitivity;
+            camera.pan_sensitivity = settings.camera.pan_sensitivity;
+            camera.zoom_sensitivity = settings.camera.zoom_sensitivity;
+        }
+
+        self.recent_projects.restore(&settings.recent_projects);
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn settings_path() -> Option<std::path::PathBuf> {
+    use std::env::var_os;
+    use std::path::PathBuf;
+
+    let home = || var_os("HOME").map(PathBuf::from);
+    let config_dir = if cfg!(target_os = "windows") {
+        var_os("APPDATA").map(PathBuf::from)
+    } else if cfg!(target_os = "macos") {
+        home().map(|home| home.join("Library/Application Support"))
+    } else {
+        var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| home().map(|home| home.join(".config")))
+    }?;
+    Some(config_dir.join(env!("CARGO_PKG_NAME")).join("settings.toml"))
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn read_stored_settings() -> Option<String> {
+    std::fs::read_to_string(settings_path()?).ok()
+}
+
+#[cfg(target_arch = "wasm32")]
+fn read_stored_settings() -> Option<String> {
+    let storage = web_sys::window()?.local_storage().ok()??;
+    storage.get_item(STORAGE_KEY).ok()?
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn write_stored_settings(contents: &str) -> Result<(), String> {
+    let path = settings_path().ok_or("no config directory")?;
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).map_err(|err| err.to_string())?;
+    }
+    std::fs::write(&path, contents).map_err(|err| err.to_string())
+}
+
+#[cfg(target_arch = "wasm32")]
+fn write_stored_settings(contents: &str) -> Result<(), String> {
+    let storage = web_sys::window()
+        .and_then(|window| window.local_storage().ok().flatten())
+        .ok_or("localStorage is unavailable")?;
+    storage
+        .set_item(STORAGE_KEY, contents)
+        .map_err(|err| format!("{:?}", err))
+}
+
+// Defaults when nothing is stored yet or the stored settings don't parse
+fn load_settings() -> UserSettings {
+    let Some(contents) = read_stored_settings() else {
+        return UserSettings::default();
+    };
+    toml::from_str(&contents).unwrap_or_else(|err| {
+        warn!("Failed to parse settings, using defaults: {}", err);
+        UserSettings::default()
+    })
+}
+
+fn apply_settings(store: Res<SettingsStore>, mut targets: SettingsTargets) {
+    targets.apply(&store.saved);
+}
+
+fn save_changed_settings(
+    time: Res<Time>,
+    mut store: ResMut<SettingsStore>,
+    targets: SettingsTargets,
+) {
+    if !store.timer.tick(time.delta()).just_finished() {
+        return;
+    }
+    let current = targets.current();
+    if current == store.saved {
+        return;
+    }
+    let written = toml::to_string_pretty(&current)
+        .map_err(|err| err.to_string())
+        .and_then(|contents| write_stored_settings(&contents));
+    match written {
+        Ok(()) => info!("Saved settings"),
+        Err(err) => warn!("Failed to save settings: {}", err),
+    }
+    // Not retried until something changes again, so a read-only config
+    // directory doesn't warn every second
+    store.saved = current;
+}
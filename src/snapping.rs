@@ -0,0 +1,60 @@
+use bevy::prelude::*;
+
+// Plugin for grid and angle snapping while transforming entities
+pub struct SnappingPlugin;
+
+impl Plugin for SnappingPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SnapSettings>()
+            .add_systems(PreUpdate, update_snap_enabled);
+    }
+}
+
+// Resource holding the snapping increments; snapping is active while Ctrl is held
+#[derive(Resource, Debug, Clone)]
+pub struct SnapSettings {
+    pub enabled: bool,
+    // World grid spacing translations snap to
+    pub grid_size: f32,
+    // Angle increment rotations snap to, in radians
+    pub angle_increment: f32,
+}
+
+impl Default for SnapSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            grid_size: 0.25,
+            angle_increment: 15f32.to_radians(),
+        }
+    }
+}
+
+impl SnapSettings {
+    // Snap the components of `position` selected by the non-zero components of `axes`
+    // to the world grid, leaving the others untouched
+    pub fn snap_position(&self, position: Vec3, axes: Vec3) -> Vec3 {
+        if !self.enabled || self.grid_size <= 0. {
+            return position;
+        }
+        let snapped = (position / self.grid_size).round() * self.grid_size;
+        Vec3::select(axes.cmpne(Vec3::ZERO), snapped, position)
+    }
+
+    pub fn snap_angle(&self, angle: f32) -> f32 {
+        if !self.enabled || self.angle_increment <= 0. {
+            return angle;
+        }
+        (angle / self.angle_increment).round() * self.angle_increment
+    }
+}
+
+fn update_snap_enabled(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut snap_settings: ResMut<SnapSettings>,
+) {
+    let enabled = keyboard_input.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]);
+    if snap_settings.enabled != enabled {
+        snap_settings.enabled = enabled;
+    }
+}
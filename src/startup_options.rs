@@ -0,0 +1,126 @@
+//! Command line options
+//!
+//! Parsed once in `main` into the `StartupOptions` resource, which the setup
+//! systems read: the window, camera and coarse prepass are configured from it
+//! and the scene given with `--open` replaces the default sphere. Browsers
+//! have no command line, so wasm builds always get the defaults. `--help`
+//! lists everything.
+
+use bevy::prelude::*;
+use clap::Parser;
+
+use crate::command_bridge::{load_scene, open_project};
+
+#[derive(Parser, Resource, Clone, Debug, Default)]
+#[command(version, about = "SDF sculpting editor")]
+pub struct StartupOptions {
+    /// Scene document or project to open instead of the default sphere
+    #[arg(long, alias = "scene", value_name = "FILE")]
+    pub open: Option<String>,
+
+    /// Initial window size
+    #[arg(long, value_name = "WIDTHxHEIGHT", value_parser = parse_size)]
+    pub window_size: Option<UVec2>,
+
+    /// Multisample anti-aliasing samples for the camera: 1 (off), 2, 4 or 8.
+    /// The SDF pass doesn't support MSAA yet, so it stays off with a warning
+    #[arg(long, value_name = "SAMPLES")]
+    pub msaa: Option<u32>,
+
+    /// Leaves out the perf UI overlay
+    #[arg(long)]
+    pub disable_perf_ui: bool,
+
+    /// Coarse prepass resolution relative to the viewport, 0.0625 by default
+    #[arg(long, value_name = "FACTOR")]
+    pub coarse_factor: Option<f32>,
+
+    /// Quits three seconds after starting
+    #[arg(long)]
+    pub auto_close: bool,
+
+    /// Runs without a window, see --script
+    #[arg(long)]
+    pub headless: bool,
+
+    /// Batch script to run in headless mode
+    #[arg(long, value_name = "FILE", requires = "headless")]
+    pub script: Option<String>,
+
+    /// Size of the image headless mode renders to
+    #[arg(long, value_name = "WIDTHxHEIGHT", value_parser = parse_size, requires = "headless")]
+    pub size: Option<UVec2>,
+
+    /// Maps OSC messages to editor parameters, listening on 127.0.0.1:9000 by default
+    #[cfg(not(target_arch = "wasm32"))]
+    #[arg(
+        long,
+        value_name = "ADDRESS",
+        num_args = 0..=1,
+        default_missing_value = crate::osc_control::DEFAULT_OSC_ADDRESS
+    )]
+    pub osc: Option<String>,
+
+    /// Accepts bridge commands from WebSocket clients, on 127.0.0.1:9001 by default
+    #[cfg(all(feature = "remote", not(target_arch = "wasm32")))]
+    #[arg(
+        long,
+        value_name = "ADDRESS",
+        num_args = 0..=1,
+        default_missing_value = crate::remote::DEFAULT_REMOTE_ADDRESS
+    )]
+    pub remote: Option<String>,
+
+    /// Secret remote clients have to pass as `?token=` in the WebSocket URL
+    #[cfg(all(feature = "remote", not(target_arch = "wasm32")))]
+    #[arg(long, value_name = "TOKEN", requires = "remote")]
+    pub remote_token: Option<String>,
+}
+
+impl StartupOptions {
+    // Exits with usage information when the arguments don't parse
+    pub fn from_env() -> Self {
+        #[cfg(target_arch = "wasm32")]
+        {
+            Self::default()
+        }
+
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            Self::parse()
+        }
+    }
+
+    pub fn msaa(&self) -> Msaa {
+        match self.msaa {
+            None | Some(1) => Msaa::Off,
+            Some(2) => Msaa::Sample2,
+            Some(4) => Msaa::Sample4,
+            Some(8) => Msaa::Sample8,
+            Some(samples) => {
+                warn!("Unsupported MSAA sample count {}, leaving MSAA off", samples);
+                Msaa::Off
+            }
+        }
+    }
+}
+
+fn parse_size(size: &str) -> Result<UVec2, String> {
+    let parsed = size.split_once('x').and_then(|(width, height)| {
+        Some(UVec2::new(width.parse().ok()?, height.parse().ok()?))
+    });
+    match parsed {
+        Some(size) if size.x > 0 && size.y > 0 => Ok(size),
+        _ => Err(format!("expected WIDTHxHEIGHT, got \"{}\"", size)),
+    }
+}
+
+// Opens a scene document or, when the file is a zip archive, a project
+pub fn open_file(path: &str) {
+    let is_project = std::fs::read(path).is_ok_and(|bytes| bytes.starts_with(b"PK"));
+    if is_project {
+        open_project(path);
+    } else {
+        load_scene(path);
+    }
+}
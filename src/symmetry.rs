@@ -0,0 +1,51 @@
+use bevy::prelude::*;
+use std::f32::consts::TAU;
+
+// Radial symmetry for brush placements: everything a stroke places is
+// repeated `fold` times around `axis` through `center`, and once more for
+// each of those reflected across the plane with normal `mirror_normal`
+#[derive(Resource, Debug, Clone, Copy)]
+pub struct Symmetry {
+    pub center: Vec3,
+    pub axis: Vec3,
+    pub fold: u32,
+    pub mirror_normal: Option<Vec3>,
+}
+
+impl Default for Symmetry {
+    fn default() -> Self {
+        Self {
+            center: Vec3::ZERO,
+            axis: Vec3::Y,
+            fold: 1,
+            mirror_normal: None,
+        }
+    }
+}
+
+impl Symmetry {
+    // Every symmetric position of `point`, starting with the point itself.
+    // Positions come in the same order for every point, so the images of the
+    // two ends of a capsule can be zipped.
+    pub fn images(&self, point: Vec3) -> Vec<Vec3> {
+        let fold = self.fold.max(1);
+        let axis = self.axis.normalize_or(Vec3::Y);
+        let local = point - self.center;
+
+        let mut sources = vec![local];
+        if let Some(normal) = self.mirror_normal {
+            let normal = normal.normalize_or(Vec3::X);
+            sources.push(local - 2. * local.dot(normal) * normal);
+        }
+
+        sources
+            .into_iter()
+            .flat_map(|source| {
+                (0..fold).map(move |i| {
+                    let rotation = Quat::from_axis_angle(axis, TAU * i as f32 / fold as f32);
+                    self.center + rotation * source
+                })
+            })
+            .collect()
+    }
+}
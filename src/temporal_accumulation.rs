@@ -0,0 +1,217 @@
+//! Temporal accumulation for the SDF pass
+//!
+//! When enabled, every frame marches rays through a slightly different
+//! sub-pixel offset and blends the result with the previous frame, reprojected
+//! through last frame's view-projection. The previous color and marched depth
+//! are kept in a pair of history textures that swap roles each frame; history
+//! whose depth doesn't match the reprojected surface is discarded, so moved
+//! geometry doesn't smear. Accumulation is skipped while adaptive resolution
+//! renders below full size.
+
+use bevy::{
+    prelude::*,
+    render::{
+        camera::ExtractedCamera,
+        extract_resource::{ExtractResource, ExtractResourcePlugin},
+        render_resource::*,
+        renderer::RenderDevice,
+        Render, RenderApp, RenderSet,
+    },
+};
+
+use crate::adaptive_resolution::SdfAdaptiveResolution;
+use crate::sdf_render::{SDFRenderSettings, SdfPassConfig, SdfRenderCamera};
+
+// Format of the history depth (the marched depth, not a depth buffer)
+pub const HISTORY_DEPTH_FORMAT: TextureFormat = TextureFormat::R32Float;
+
+// Length of the sub-pixel jitter sequence
+const JITTER_SEQUENCE_LENGTH: u32 = 16;
+
+pub struct TemporalAccumulationPlugin;
+
+#[derive(Resource, Clone)]
+pub struct SdfTemporalAccumulation {
+    pub enabled: bool,
+    // Weight of the reprojected history in each new frame
+    pub blend: f32,
+}
+
+impl Default for SdfTemporalAccumulation {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            blend: 0.9,
+        }
+    }
+}
+
+impl ExtractResource for SdfTemporalAccumulation {
+    type Source = SdfTemporalAccumulation;
+
+    fn extract_resource(source: &Self::Source) -> Self {
+        source.clone()
+    }
+}
+
+pub struct HistoryTarget {
+    pub color_texture: Texture,
+    pub color_view: TextureView,
+    pub depth_texture: Texture,
+    pub depth_view: TextureView,
+}
+
+// Color and marched depth of the last two frames, one is read while the other is written
+#[derive(Resource)]
+pub struct SdfHistoryTextures {
+    pub targets: [HistoryTarget; 2],
+    // Index of the target written this frame
+    pub current: usize,
+    pub size: Extent3d,
+}
+
+impl SdfHistoryTextures {
+    pub fn current(&self) -> &HistoryTarget {
+        &self.targets[self.current]
+    }
+
+    pub fn previous(&self) -> &HistoryTarget {
+        &self.targets[1 - self.current]
+    }
+}
+
+impl Plugin for TemporalAccumulationPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SdfTemporalAccumulation>()
+            .add_plugins(ExtractResourcePlugin::<SdfTemporalAccumulation>::default())
+            .add_systems(Update, update_temporal_in_settings);
+
+        let Some(render_app) = app.get_sub_app_mut(RenderApp) else {
+            return;
+        };
+
+        render_app.add_systems(
+            Render,
+            manage_history_textures.in_set(RenderSet::PrepareResources),
+        );
+    }
+}
+
+// Element `index` of the Halton low-discrepancy sequence in `base`
+fn halton(mut index: u32, base: u32) -> f32 {
+    let mut fraction = 1.0;
+    let mut result = 0.0;
+    while index > 0 {
+        fraction /= base as f32;
+        result += fraction * (index % base) as f32;
+        index /= base;
+    }
+    result
+}
+
+fn update_temporal_in_settings(
+    temporal: Res<SdfTemporalAccumulation>,
+    mut frame: Local<u32>,
+    mut camera_query: Query<&mut SDFRenderSettings, With<SdfRenderCamera>>,
+) {
+    // Offsets in pixels within [-0.5, 0.5], starting at 1 since Halton(0) is 0
+    let jitter = if temporal.enabled {
+        *frame = (*frame + 1) % JITTER_SEQUENCE_LENGTH;
+        Vec2::new(halton(*frame + 1, 2), halton(*frame + 1, 3)) - 0.5
+    } else {
+        Vec2::ZERO
+    };
+
+    for mut settings in camera_query.iter_mut() {
+        settings.temporal_blend = if temporal.enabled {
+            temporal.blend
+        } else {
+            0.0
+        };
+        settings.temporal_jitter = jitter;
+    }
+}
+
+fn create_history_target(
+    render_device: &RenderDevice,
+    size: Extent3d,
+    color_format: TextureFormat,
+) -> HistoryTarget {
+    let create = |label: &'static str, format: TextureFormat| {
+        render_device.create_texture(&TextureDescriptor {
+            label: Some(label),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format,
+            usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        })
+    };
+
+    // New textures start zeroed, a depth of 0 (the far plane) never matches a
+    // reprojected surface, so the first frame ignores the history
+    let color_texture = create("sdf_history_color_texture", color_format);
+    let depth_texture = create("sdf_history_depth_texture", HISTORY_DEPTH_FORMAT);
+
+    HistoryTarget {
+        color_view: color_texture.create_view(&TextureViewDescriptor::default()),
+        depth_view: depth_texture.create_view(&TextureViewDescriptor::default()),
+        color_texture,
+        depth_texture,
+    }
+}
+
+fn manage_history_textures(
+    mut commands: Commands,
+    render_device: Res<RenderDevice>,
+    config: Res<SdfPassConfig>,
+    temporal: Option<Res<SdfTemporalAccumulation>>,
+    adaptive: Option<Res<SdfAdaptiveResolution>>,
+    history: Option<ResMut<SdfHistoryTextures>>,
+    camera_query: Query<&ExtractedCamera, With<SdfRenderCamera>>,
+) {
+    let enabled = temporal.is_some_and(|temporal| temporal.enabled)
+        && adaptive.is_none_or(|adaptive| adaptive.scale >= 1.0);
+    let Ok(camera) = camera_query.single() else {
+        return;
+    };
+
+    // Drop the history while accumulation is off, so it never resumes from stale frames
+    if !enabled {
+        if history.is_some() {
+            commands.remove_resource::<SdfHistoryTextures>();
+        }
+        return;
+    }
+
+    // Sized like the render target, the pass writes the history under the same
+    // viewport as the view target
+    let Some(size) = camera.physical_target_size else {
+        return;
+    };
+    let size = size.max(UVec2::ONE);
+    let desired_size = Extent3d {
+        width: size.x,
+        height: size.y,
+        depth_or_array_layers: 1,
+    };
+
+    match history {
+        Some(mut history) if history.size == desired_size => {
+            // Last frame's output becomes the history read this frame
+            history.current = 1 - history.current;
+        }
+        _ => {
+            commands.insert_resource(SdfHistoryTextures {
+                targets: [
+                    create_history_target(&render_device, desired_size, config.color_format()),
+                    create_history_target(&render_device, desired_size, config.color_format()),
+                ],
+                current: 0,
+                size: desired_size,
+            });
+        }
+    }
+}
@@ -0,0 +1,245 @@
+//! Texture-space bake of the SDF shading for glTF exports
+//!
+//! Marching the SDF loses detail smaller than a grid cell, like noise
+//! displacement and tight creases, so glTF exports carry it in textures:
+//! ambient occlusion, and the SDF normal relative to the mesh as a
+//! tangent-space normal map, both sampled on the CPU with `SceneSdf`.
+//!
+//! The unwrap is the simplest charting there is. Triangles are unwelded and
+//! every pair of them gets a square cell of the atlas, one on each side of the
+//! cell's diagonal. That needs no chart search or overlap checks, and the
+//! seams it puts between all triangles don't show since every texel of a cell
+//! is baked, up to its edges.
+
+use std::io::Cursor;
+
+use bevy::{prelude::*, tasks::futures_lite::future};
+
+use crate::meshing::SdfMesh;
+use crate::progress::SharedProgress;
+use crate::sdf_cpu::{bake_shading, SceneSdf};
+
+// Side of the atlas in texels, doubled from the minimum until the cells fit
+const MIN_ATLAS_SIZE: u32 = 1024;
+const MAX_ATLAS_SIZE: u32 = 2048;
+// Smallest cell that still leaves both of its triangles a texel inside it
+const MIN_CELL_SIZE: u32 = 4;
+// Cells baked between yields, so web builds keep rendering during the bake
+const CELLS_PER_CHUNK: usize = 256;
+
+/// PNG encoded textures of a baked mesh
+#[derive(Clone, Debug)]
+pub struct BakedTextures {
+    // Ambient occlusion in the red channel, as glTF reads it
+    pub occlusion: Vec<u8>,
+    pub normal: Vec<u8>,
+}
+
+// Point of the mesh a texel maps to, with the interpolated surface frame the
+// SDF normal is expressed in
+struct TexelFrame {
+    texel: usize,
+    position: Vec3,
+    normal: Vec3,
+    tangent: Vec3,
+    bitangent: Vec3,
+}
+
+/// The mesh unwrapped, with texture coordinates, tangents and the shading of
+/// `sdf` baked over it (async). None when it has too many triangles to fit the
+/// largest atlas.
+pub async fn bake_mesh_textures_async(
+    mesh: &SdfMesh,
+    sdf: &SceneSdf,
+    progress: Option<&SharedProgress>,
+) -> Option<SdfMesh> {
+    if mesh.triangles.is_empty() {
+        return Some(mesh.clone());
+    }
+    let cell_count = mesh.triangles.len().div_ceil(2);
+    let mut grid = (cell_count as f64).sqrt() as u32;
+    while (grid as usize * grid as usize) < cell_count {
+        grid += 1;
+    }
+    let atlas_size = std::iter::successors(Some(MIN_ATLAS_SIZE), |size| Some(size * 2))
+        .take_while(|size| *size <= MAX_ATLAS_SIZE)
+        .find(|size| size / grid >= MIN_CELL_SIZE)?;
+    let cell_size = atlas_size / grid;
+
+    let mut baked = unwrap(mesh, grid, cell_size, atlas_size);
+    let texel_count = (atlas_size * atlas_size) as usize;
+    // Texels between the cells are never sampled; leave them unoccluded and flat
+    let mut occlusion = vec![255; texel_count * 3];
+    let mut normal: Vec<u8> = [128, 128, 255].repeat(texel_count);
+
+    for first_cell in (0..cell_count).step_by(CELLS_PER_CHUNK) {
+        let cells = first_cell..(first_cell + CELLS_PER_CHUNK).min(cell_count);
+        let frames: Vec<TexelFrame> = cells
+            .flat_map(|cell| cell_frames(&baked, cell, grid, cell_size, atlas_size))
+            .collect();
+        let positions: Vec<Vec3> = frames.iter().map(|frame| frame.position).collect();
+        let shading = bake_shading(sdf, &positions);
+
+        for (i, frame) in frames.iter().enumerate() {
+            let ao = (shading.ambient_occlusion[i] * 255.).round() as u8;
+            occlusion[frame.texel * 3..frame.texel * 3 + 3].fill(ao);
+
+            let sdf_normal = shading.normals[i];
+            let local = Vec3::new(
+                sdf_normal.dot(frame.tangent),
+                sdf_normal.dot(frame.bitangent),
+                sdf_normal.dot(frame.normal),
+            );
+            let encoded = ((local.normalize_or(Vec3::Z) * 0.5 + 0.5) * 255.).round();
+            normal[frame.texel * 3..frame.texel * 3 + 3]
+                .copy_from_slice(&encoded.to_array().map(|c| c as u8));
+        }
+
+        if let Some(progress) = progress {
+            let done = (first_cell + CELLS_PER_CHUNK).min(cell_count);
+            progress.set(done as f32 / cell_count as f32);
+        }
+        future::yield_now().await;
+    }
+
+    baked.textures = Some(BakedTextures {
+        occlusion: encode_png(atlas_size, occlusion)?,
+        normal: encode_png(atlas_size, normal)?,
+    });
+    Some(baked)
+}
+
+// Corners of a cell's two triangles in texels from the cell origin, inset
+// half a texel from the cell edges and from each other along the diagonal
+fn cell_corners(cell_size: u32, second: bool) -> [Vec2; 3] {
+    let size = cell_size as f32;
+    if second {
+        [
+            Vec2::new(size - 0.5, size - 0.5),
+            Vec2::new(1.5, size - 0.5),
+            Vec2::new(size - 0.5, 1.5),
+        ]
+    } else {
+        [Vec2::new(0.5, 0.5), Vec2::new(size - 1.5, 0.5), Vec2::new(0.5, size - 1.5)]
+    }
+}
+
+fn cell_origin(cell: usize, grid: u32, cell_size: u32) -> UVec2 {
+    UVec2::new(cell as u32 % grid, cell as u32 / grid) * cell_size
+}
+
+// One vertex per triangle corner, with the texture coordinates of its cell and
+// tangents from the triangle's texture mapping
+fn unwrap(mesh: &SdfMesh, grid: u32, cell_size: u32, atlas_size: u32) -> SdfMesh {
+    let normals = mesh.vertex_normals();
+    let has_colors = mesh.colors.len() == mesh.positions.len();
+    let mut unwrapped = SdfMesh::default();
+
+    for (t, triangle) in mesh.triangles.iter().enumerate() {
+        let origin = cell_origin(t / 2, grid, cell_size).as_vec2();
+        let texels = cell_corners(cell_size, t % 2 == 1).map(|corner| origin + corner);
+        let positions = triangle.map(|i| mesh.positions[i as usize]);
+        let (tangent, bitangent) = triangle_tangent(positions, texels);
+
+        let first = unwrapped.positions.len() as u32;
+        for (corner, &i) in triangle.iter().enumerate() {
+            let normal = normals[i as usize];
+            let mut vertex_tangent = (tangent - normal * normal.dot(tangent)).normalize_or_zero();
+            if vertex_tangent == Vec3::ZERO {
+                vertex_tangent = normal.any_orthonormal_vector();
+            }
+            let handedness = if normal.cross(vertex_tangent).dot(bitangent) < 0. {
+                -1.
+            } else {
+                1.
+            };
+
+            unwrapped.positions.push(positions[corner]);
+            unwrapped.normals.push(normal);
+            if has_colors {
+                unwrapped.colors.push(mesh.colors[i as usize]);
+            }
+            unwrapped.uvs.push(texels[corner] / atlas_size as f32);
+            unwrapped.tangents.push(vertex_tangent.extend(handedness));
+        }
+        unwrapped.triangles.push([first, first + 1, first + 2]);
+    }
+    unwrapped
+}
+
+// Directions of increasing u and v across the triangle
+fn triangle_tangent(positions: [Vec3; 3], texels: [Vec2; 3]) -> (Vec3, Vec3) {
+    let (e1, e2) = (positions[1] - positions[0], positions[2] - positions[0]);
+    let (d1, d2) = (texels[1] - texels[0], texels[2] - texels[0]);
+    let r = 1. / d1.perp_dot(d2);
+    ((e1 * d2.y - e2 * d1.y) * r, (e2 * d1.x - e1 * d2.x) * r)
+}
+
+// The frames of every texel of a cell, each from the triangle on its side of
+// the diagonal. A cell with a single triangle gets it everywhere
+fn cell_frames(
+    mesh: &SdfMesh,
+    cell: usize,
+    grid: u32,
+    cell_size: u32,
+    atlas_size: u32,
+) -> Vec<TexelFrame> {
+    let origin = cell_origin(cell, grid, cell_size);
+    let has_second = cell * 2 + 1 < mesh.triangles.len();
+    let mut frames = Vec::with_capacity((cell_size * cell_size) as usize);
+    for y in 0..cell_size {
+        for x in 0..cell_size {
+            let second = has_second && x + y + 1 >= cell_size;
+            let triangle = mesh.triangles[cell * 2 + second as usize];
+            let weights = barycentric(
+                Vec2::new(x as f32 + 0.5, y as f32 + 0.5),
+                cell_corners(cell_size, second),
+            );
+            let (mut position, mut normal, mut tangent) = (Vec3::ZERO, Vec3::ZERO, Vec3::ZERO);
+            for (&i, weight) in triangle.iter().zip(weights.to_array()) {
+                position += mesh.positions[i as usize] * weight;
+                normal += mesh.normals[i as usize] * weight;
+                tangent += mesh.tangents[i as usize].truncate() * weight;
+            }
+
+            let normal = normal.normalize_or(Vec3::Y);
+            let mut tangent = (tangent - normal * normal.dot(tangent)).normalize_or_zero();
+            if tangent == Vec3::ZERO {
+                tangent = normal.any_orthonormal_vector();
+            }
+            let handedness = mesh.tangents[triangle[0] as usize].w;
+            let texel = origin + UVec2::new(x, y);
+            frames.push(TexelFrame {
+                texel: (texel.y * atlas_size + texel.x) as usize,
+                position,
+                normal,
+                tangent,
+                bitangent: normal.cross(tangent) * handedness,
+            });
+        }
+    }
+    frames
+}
+
+// Weights of the corners at a point. Points outside the triangle, along the
+// cell edges and the diagonal, get those of a point on its border
+fn barycentric(point: Vec2, [a, b, c]: [Vec2; 3]) -> Vec3 {
+    let (ab, ac, ap) = (b - a, c - a, point - a);
+    let area = ab.perp_dot(ac);
+    let v = ap.perp_dot(ac) / area;
+    let w = ab.perp_dot(ap) / area;
+    let weights = Vec3::new(1. - v - w, v, w).max(Vec3::ZERO);
+    weights / weights.element_sum()
+}
+
+fn encode_png(size: u32, rgb: Vec<u8>) -> Option<Vec<u8>> {
+    let image = image::RgbImage::from_raw(size, size, rgb)?;
+    let mut png = Cursor::new(Vec::new());
+    match image.write_to(&mut png, image::ImageFormat::Png) {
+        Ok(()) => Some(png.into_inner()),
+        Err(err) => {
+            warn!("Failed to encode baked texture: {}", err);
+            None
+        }
+    }
+}
@@ -1,9 +1,25 @@
 use crate::{
+    edit_history::{Edit, EditHistory},
     overlay::{OverlayCamera, OVERLAY_LAYER},
-    selection::{EntityDeselectedEvent, EntitySelectedEvent, Selected},
+    pipeline_warmup::PipelineWarmupState,
+    sdf_compute::{evaluate_sdf_async, SdfEvaluationSender},
+    pivot::{pivot_world, PivotOffset},
+    sdf_render::SDFRenderEntity,
+    selection::{EntityDeselectedEvent, EntitySelectedEvent, Selected, SelectionState},
+    settings::KeyBindings,
+    snapping::SnapSettings,
     AppMode, AppModeState,
 };
-use bevy::{prelude::*, render::view::RenderLayers};
+use bevy::{
+    input::{
+        keyboard::{Key, KeyboardInput},
+        ButtonState,
+    },
+    prelude::*,
+    render::view::RenderLayers,
+    tasks::{block_on, futures_lite::future, Task},
+    window::PrimaryWindow,
+};
 use bevy_panorbit_camera::PanOrbitCamera;
 
 // Plugin for the translation system
@@ -14,7 +30,24 @@ impl Plugin for TranslationPlugin {
         app.init_resource::<DragData>()
             .init_resource::<DragData>()
             .init_resource::<DragHandlesResource>()
-            .add_systems(Update, on_change_app_mode)
+            .init_resource::<NumericInput>()
+            .init_resource::<GizmoOrientation>()
+            .init_resource::<GizmoOcclusion>()
+            .init_resource::<HandleOcclusionQuery>()
+            .add_systems(Startup, spawn_numeric_input_text)
+            .add_systems(
+                Update,
+                (
+                    on_change_app_mode,
+                    toggle_gizmo_orientation,
+                    follow_selection_centroid,
+                    (update_handle_occlusion, update_handle_materials).chain(),
+                    record_drag_edits,
+                    nudge_selection,
+                    (capture_numeric_input, handle_keyboard_grab).chain(),
+                    update_numeric_input_text,
+                ),
+            )
             .add_observer(on_add_translatable);
     }
 }
@@ -26,10 +59,29 @@ pub struct Translatable;
 // Resource to track drag state
 #[derive(Resource)]
 pub enum DragData {
+    // Positions are those of the selection centroid
     Dragging {
         start_position: Vec3,
         entity_start_position: Vec3,
-        active_axis: TranslationAxis,
+        constraint: DragConstraint,
+        // Orientation of the gizmo axes
+        rotation: Quat,
+    },
+    // Keyboard grab (G), optionally constrained to an axis with X/Y/Z
+    Grabbing {
+        // Cursor position on the camera-facing plane through the entity at grab start
+        start_position: Vec3,
+        entity_start_position: Vec3,
+        axis: Option<TranslationAxis>,
+    },
+    // Start scales are kept per entity in TransformDragStart
+    Scaling {
+        start_position: Vec3,
+        // Gizmo position the axis handles scale relative to
+        center: Vec3,
+        // None for the uniform handle
+        axis: Option<TranslationAxis>,
+        rotation: Quat,
     },
     Idle,
 }
@@ -40,18 +92,134 @@ impl Default for DragData {
     }
 }
 
+// Offset typed during an axis-constrained drag or grab, applied along the active axis
+#[derive(Resource, Default)]
+pub struct NumericInput {
+    pub buffer: String,
+    pub axis: Option<TranslationAxis>,
+}
+
+impl NumericInput {
+    pub fn value(&self) -> Option<f32> {
+        self.buffer.parse().ok()
+    }
+
+    fn clear(&mut self) {
+        self.buffer.clear();
+        self.axis = None;
+    }
+}
+
+#[derive(Component)]
+struct NumericInputText;
+
+// Whether the gizmo axes follow the world or the last selected entity
+#[derive(Resource, Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GizmoOrientation {
+    #[default]
+    World,
+    Local,
+}
+
+impl GizmoOrientation {
+    fn rotation(self, transforms: &Query<&GlobalTransform>, entity: Entity) -> Quat {
+        match self {
+            GizmoOrientation::World => Quat::IDENTITY,
+            GizmoOrientation::Local => transforms
+                .get(entity)
+                .map(|t| t.rotation())
+                .unwrap_or(Quat::IDENTITY),
+        }
+    }
+}
+
 #[derive(Resource)]
 pub struct DragHandlesResource {
     entity: Entity,
+    // Material of every handle, recolored to show hover and the active handle
+    materials: Vec<HandleMaterial>,
+    hovered: Option<Entity>,
+    // Handle being dragged
+    active: Option<Entity>,
+    // Handles behind SDF geometry, when occlusion is enabled
+    occluded: Vec<Entity>,
+}
+
+// User setting: dim handles hidden behind geometry instead of always drawing
+// them on top at full strength
+#[derive(Resource, Default)]
+pub struct GizmoOcclusion {
+    pub enabled: bool,
+}
+
+// Pending GPU query of which handles are occluded
+#[derive(Resource, Default)]
+struct HandleOcclusionQuery {
+    task: Option<Task<Vec<Entity>>>,
+}
+
+struct HandleMaterial {
+    entity: Entity,
+    material: Handle<StandardMaterial>,
+    color: Color,
 }
 
 #[derive(Component)]
-pub struct DragHandle(TranslationAxis);
+pub struct DragHandle(DragConstraint);
+
+// Scale handle along an axis, or the uniform handle in the center when None
+#[derive(Component)]
+pub struct ScaleHandle(Option<TranslationAxis>);
+
+// State of a selected entity when the current drag, grab or scale started.
+// Compared against the end state to record the drag in the edit history.
+#[derive(Component)]
+struct TransformDragStart {
+    transform: Transform,
+    // Present if the entity is rendered as an SDF
+    sdf: Option<SDFRenderEntity>,
+}
+
+type SelectedTranslatables<'w, 's> =
+    Query<'w, 's, (Entity, &'static mut Transform), (With<Translatable>, With<Selected>)>;
+
+fn selection_centroid(selected: &SelectedTranslatables) -> Option<Vec3> {
+    let count = selected.iter().count();
+    (count > 0).then(|| selected.iter().map(|(_, t)| t.translation).sum::<Vec3>() / count as f32)
+}
+
+// Move every selected entity by the same offset so their centroid ends up at `target`
+fn move_selection_to(selected: &mut SelectedTranslatables, target: Vec3) {
+    let Some(centroid) = selection_centroid(selected) else {
+        return;
+    };
+    let offset = target - centroid;
+    for (_, mut transform) in selected.iter_mut() {
+        transform.translation += offset;
+    }
+}
+
+fn mark_drag_start(
+    commands: &mut Commands,
+    selected: &SelectedTranslatables,
+    sdf_entities: &Query<&SDFRenderEntity>,
+) {
+    for (entity, transform) in selected.iter() {
+        commands.entity(entity).insert(TransformDragStart {
+            transform: *transform,
+            sdf: sdf_entities.get(entity).ok().cloned(),
+        });
+    }
+}
 
 impl Default for DragHandlesResource {
     fn default() -> Self {
         Self {
             entity: Entity::PLACEHOLDER,
+            materials: Vec::new(),
+            hovered: None,
+            active: None,
+            occluded: Vec::new(),
         }
     }
 }
@@ -64,6 +232,41 @@ pub enum TranslationAxis {
     Z,
 }
 
+// Plane a quad handle constrains dragging to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TranslationPlane {
+    XY,
+    XZ,
+    YZ,
+}
+
+impl TranslationPlane {
+    pub fn normal(self) -> Dir3 {
+        match self {
+            TranslationPlane::XY => Dir3::Z,
+            TranslationPlane::XZ => Dir3::Y,
+            TranslationPlane::YZ => Dir3::X,
+        }
+    }
+}
+
+// What a translation handle constrains dragging to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DragConstraint {
+    Axis(TranslationAxis),
+    Plane(TranslationPlane),
+}
+
+impl TranslationAxis {
+    pub fn direction(self) -> Vec3 {
+        match self {
+            TranslationAxis::X => Vec3::X,
+            TranslationAxis::Y => Vec3::Y,
+            TranslationAxis::Z => Vec3::Z,
+        }
+    }
+}
+
 fn on_add_translatable(trigger: Trigger<OnAdd, Translatable>, mut commands: Commands) {
     let target = trigger.target();
 
@@ -78,22 +281,93 @@ fn on_add_translatable(trigger: Trigger<OnAdd, Translatable>, mut commands: Comm
 }
 
 const HANDLE_DIST: f32 = 1.5;
+const SCALE_HANDLE_DIST: f32 = 1.0;
+const PLANE_HANDLE_DIST: f32 = 0.6;
+
+// Alpha of handles hidden behind geometry
+const OCCLUDED_HANDLE_ALPHA: f32 = 0.3;
+// Surfaces closer than this in front of a handle don't hide it
+const OCCLUSION_BIAS: f32 = 0.05;
+
+// Smallest factor a single drag can scale an entity by
+const MIN_SCALE_FACTOR: f32 = 0.05;
+// Uniform scale factor per pixel of horizontal drag, applied exponentially
+const UNIFORM_SCALE_SPEED: f32 = 0.01;
+// Nudge steps holding Shift are this many snap increments
+const NUDGE_LARGE_FACTOR: f32 = 4.;
 
 pub fn on_change_app_mode(
     app_mode: Res<AppModeState>,
-    drag_handles_resource: ResMut<DragHandlesResource>,
+    mut drag_handles_resource: ResMut<DragHandlesResource>,
     mut commands: Commands,
 ) {
     if app_mode.is_mode(AppMode::Translate) || !app_mode.is_changed() {
         return;
     }
+    despawn_drag_handles(&mut commands, &mut drag_handles_resource);
+}
+
+fn despawn_drag_handles(commands: &mut Commands, drag_handles_resource: &mut DragHandlesResource) {
     let handle_entity = drag_handles_resource.entity;
+    if handle_entity == Entity::PLACEHOLDER {
+        return;
+    }
 
-    info!("deselect translatable");
     info!("handle_entity: {:?}", handle_entity);
 
     // Properly despawn the handle entity
     commands.entity(handle_entity).despawn();
+    *drag_handles_resource = DragHandlesResource::default();
+}
+
+// O toggles between world and local gizmo axes
+fn toggle_gizmo_orientation(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    drag_data: Res<DragData>,
+    mut orientation: ResMut<GizmoOrientation>,
+) {
+    if !keyboard_input.just_pressed(KeyCode::KeyO) || !matches!(*drag_data, DragData::Idle) {
+        return;
+    }
+    *orientation = match *orientation {
+        GizmoOrientation::World => GizmoOrientation::Local,
+        GizmoOrientation::Local => GizmoOrientation::World,
+    };
+    info!("Gizmo orientation: {:?}", *orientation);
+}
+
+// Keeps the gizmo at the centroid of the pivots of the selected entities,
+// oriented along the axes of the last selected one in local mode
+fn follow_selection_centroid(
+    drag_handles_resource: Res<DragHandlesResource>,
+    selection_state: Res<SelectionState>,
+    orientation: Res<GizmoOrientation>,
+    drag_data: Res<DragData>,
+    selected: Query<(&GlobalTransform, Option<&PivotOffset>), (With<Translatable>, With<Selected>)>,
+    global_transforms: Query<&GlobalTransform>,
+    mut transforms: Query<&mut Transform, Without<Translatable>>,
+) {
+    let count = selected.iter().count();
+    if count == 0 {
+        return;
+    }
+    let centroid = selected
+        .iter()
+        .map(|(transform, pivot)| pivot_world(transform, pivot))
+        .sum::<Vec3>()
+        / count as f32;
+    let Ok(mut handle_transform) = transforms.get_mut(drag_handles_resource.entity) else {
+        return;
+    };
+    handle_transform.translation = centroid;
+
+    // Axes stay fixed during a drag, which captured them at the start
+    if !matches!(*drag_data, DragData::Idle) {
+        return;
+    }
+    if let Some(primary) = selection_state.selected_entities.last() {
+        handle_transform.rotation = orientation.rotation(&global_transforms, *primary);
+    }
 }
 
 pub fn on_select_translatable(
@@ -103,99 +377,242 @@ pub fn on_select_translatable(
     mut materials: ResMut<Assets<StandardMaterial>>, // Resource to store material data)
     mut drag_handles_resource: ResMut<DragHandlesResource>,
     app_mode: Res<AppModeState>,
+    orientation: Res<GizmoOrientation>,
+    transforms: Query<&GlobalTransform>,
+    pivots: Query<&PivotOffset>,
 ) {
     if !app_mode.is_mode(AppMode::Translate) {
         return;
     }
+
+    // One gizmo is shared by the whole selection
+    if drag_handles_resource.entity != Entity::PLACEHOLDER {
+        return;
+    }
     let target = trigger.target();
 
     info!("selected something translatable");
 
-    // Create a parent entity to hold our drag handles
+    // Create a parent entity to hold our drag handles; it follows the selection centroid
     let handle_entity = commands
-        .spawn((Transform::default(), Visibility::default()))
+        .spawn((
+            Transform::from_translation(
+                transforms
+                    .get(target)
+                    .map(|t| pivot_world(t, pivots.get(target).ok()))
+                    .unwrap_or_default(),
+            )
+                .with_rotation(orientation.rotation(&transforms, target)),
+            Visibility::default(),
+        ))
         .id();
 
-    // Attach the parent to the target
-    commands.entity(target).add_child(handle_entity);
+    let mut handle_materials = Vec::new();
 
     // Spawn X axis handle
-    commands
+    let color = Color::srgb(0.9, 0.2, 0.2); // Red for X axis
+    let material = materials.add(StandardMaterial {
+        base_color: color,
+        ..default()
+    });
+    let handle = commands
         .spawn((
             Transform::from_xyz(HANDLE_DIST, 0.0, 0.0),
             Mesh3d(meshes.add(Sphere {
                 radius: 0.1,
                 ..default()
             })),
-            MeshMaterial3d(materials.add(StandardMaterial {
-                base_color: Color::srgb(0.9, 0.2, 0.2), // Red for X axis
-                ..default()
-            })),
+            MeshMaterial3d(material.clone()),
             ChildOf(handle_entity),
-            DragHandle(TranslationAxis::X),
+            DragHandle(DragConstraint::Axis(TranslationAxis::X)),
             RenderLayers::layer(OVERLAY_LAYER),
         ))
         .observe(on_drag_start_handle)
         .observe(on_drag_handle)
-        .observe(on_drag_end_handle);
+        .observe(on_drag_end_handle)
+        .observe(on_over_handle)
+        .observe(on_out_handle)
+        .id();
+    handle_materials.push(HandleMaterial {
+        entity: handle,
+        material,
+        color,
+    });
 
     // Spawn Y axis handle
-    commands
+    let color = Color::srgb(0.2, 0.9, 0.2); // Green for Y axis
+    let material = materials.add(StandardMaterial {
+        base_color: color,
+        ..default()
+    });
+    let handle = commands
         .spawn((
             Transform::from_xyz(0., HANDLE_DIST, 0.0),
             Mesh3d(meshes.add(Sphere {
                 radius: 0.1,
                 ..default()
             })),
-            MeshMaterial3d(materials.add(StandardMaterial {
-                base_color: Color::srgb(0.2, 0.9, 0.2), // Green for Y axis
-                ..default()
-            })),
+            MeshMaterial3d(material.clone()),
             ChildOf(handle_entity),
-            DragHandle(TranslationAxis::Y),
+            DragHandle(DragConstraint::Axis(TranslationAxis::Y)),
             RenderLayers::layer(OVERLAY_LAYER),
         ))
         .observe(on_drag_start_handle)
         .observe(on_drag_handle)
-        .observe(on_drag_end_handle);
+        .observe(on_drag_end_handle)
+        .observe(on_over_handle)
+        .observe(on_out_handle)
+        .id();
+    handle_materials.push(HandleMaterial {
+        entity: handle,
+        material,
+        color,
+    });
 
     // Spawn Z axis handle
-    commands
+    let color = Color::srgb(0.2, 0.2, 0.9); // Blue for Z axis
+    let material = materials.add(StandardMaterial {
+        base_color: color,
+        ..default()
+    });
+    let handle = commands
         .spawn((
             Transform::from_xyz(0., 0.0, HANDLE_DIST),
             Mesh3d(meshes.add(Sphere {
                 radius: 0.1,
                 ..default()
             })),
-            MeshMaterial3d(materials.add(StandardMaterial {
-                base_color: Color::srgb(0.2, 0.2, 0.9), // Blue for Z axis
-                ..default()
-            })),
+            MeshMaterial3d(material.clone()),
             ChildOf(handle_entity),
-            DragHandle(TranslationAxis::Z),
+            DragHandle(DragConstraint::Axis(TranslationAxis::Z)),
             RenderLayers::layer(OVERLAY_LAYER),
         ))
         .observe(on_drag_start_handle)
         .observe(on_drag_handle)
-        .observe(on_drag_end_handle);
+        .observe(on_drag_end_handle)
+        .observe(on_over_handle)
+        .observe(on_out_handle)
+        .id();
+    handle_materials.push(HandleMaterial {
+        entity: handle,
+        material,
+        color,
+    });
+
+    // Spawn plane handles: small quads between the axes
+    for (plane, position, color) in [
+        (
+            TranslationPlane::XY,
+            Vec3::new(PLANE_HANDLE_DIST, PLANE_HANDLE_DIST, 0.),
+            Color::srgb(0.9, 0.9, 0.2),
+        ),
+        (
+            TranslationPlane::XZ,
+            Vec3::new(PLANE_HANDLE_DIST, 0., PLANE_HANDLE_DIST),
+            Color::srgb(0.9, 0.2, 0.9),
+        ),
+        (
+            TranslationPlane::YZ,
+            Vec3::new(0., PLANE_HANDLE_DIST, PLANE_HANDLE_DIST),
+            Color::srgb(0.2, 0.9, 0.9),
+        ),
+    ] {
+        let material = materials.add(StandardMaterial {
+            base_color: color,
+            ..default()
+        });
+        let handle = commands
+            .spawn((
+                Transform::from_translation(position),
+                // Thin along the plane normal
+                Mesh3d(meshes.add(Cuboid::from_size(Vec3::splat(0.3) - *plane.normal() * 0.28))),
+                MeshMaterial3d(material.clone()),
+                ChildOf(handle_entity),
+                DragHandle(DragConstraint::Plane(plane)),
+                RenderLayers::layer(OVERLAY_LAYER),
+            ))
+            .observe(on_drag_start_handle)
+            .observe(on_drag_handle)
+            .observe(on_drag_end_handle)
+            .observe(on_over_handle)
+            .observe(on_out_handle)
+            .id();
+        handle_materials.push(HandleMaterial {
+            entity: handle,
+            material,
+            color,
+        });
+    }
+
+    // Spawn scale handles: a cube cap per axis plus a uniform handle in the center
+    for (axis, color) in [
+        (Some(TranslationAxis::X), Color::srgb(0.9, 0.2, 0.2)),
+        (Some(TranslationAxis::Y), Color::srgb(0.2, 0.9, 0.2)),
+        (Some(TranslationAxis::Z), Color::srgb(0.2, 0.2, 0.9)),
+        (None, Color::srgb(0.9, 0.9, 0.9)),
+    ] {
+        let position = axis.map_or(Vec3::ZERO, |axis| axis.direction() * SCALE_HANDLE_DIST);
+        let size = if axis.is_some() { 0.15 } else { 0.2 };
+        let material = materials.add(StandardMaterial {
+            base_color: color,
+            ..default()
+        });
+        let handle = commands
+            .spawn((
+                Transform::from_translation(position),
+                Mesh3d(meshes.add(Cuboid::from_length(size))),
+                MeshMaterial3d(material.clone()),
+                ChildOf(handle_entity),
+                ScaleHandle(axis),
+                RenderLayers::layer(OVERLAY_LAYER),
+            ))
+            .observe(on_drag_start_scale_handle)
+            .observe(on_drag_scale_handle)
+            .observe(on_drag_end_handle)
+            .observe(on_over_handle)
+            .observe(on_out_handle)
+            .id();
+        handle_materials.push(HandleMaterial {
+            entity: handle,
+            material,
+            color,
+        });
+    }
 
     drag_handles_resource.entity = handle_entity;
+    drag_handles_resource.materials = handle_materials;
+}
+
+fn world_position(transforms: &Query<&GlobalTransform>, target: Entity) -> Vec3 {
+    transforms
+        .get(target)
+        .map(|t| t.translation())
+        .unwrap_or_default()
+}
+
+fn gizmo_rotation(transforms: &Query<&GlobalTransform>, handle_entity: Entity) -> Quat {
+    transforms
+        .get(handle_entity)
+        .map(|t| t.rotation())
+        .unwrap_or(Quat::IDENTITY)
 }
 
 fn on_deselect_translatable(
     trigger: Trigger<EntityDeselectedEvent>,
-    handle: Res<DragHandlesResource>,
+    mut handle: ResMut<DragHandlesResource>,
+    selection_state: Res<SelectionState>,
     mut commands: Commands,
 ) {
     let target = trigger.target();
-    let handle_entity = handle.entity;
 
     info!("deselect translatable");
     info!("target: {:?}", target);
-    info!("handle_entity: {:?}", handle_entity);
 
-    // Properly despawn the handle entity
-    commands.entity(handle_entity).despawn();
+    // Keep the gizmo while other entities are still selected
+    if !selection_state.is_empty() {
+        return;
+    }
+    despawn_drag_handles(&mut commands, &mut handle);
 }
 
 fn on_drag_start_handle(
@@ -203,7 +620,11 @@ fn on_drag_start_handle(
     drag_handles: Query<&DragHandle>,
     mut drag_data: ResMut<DragData>,
     mut pan_orbit_query: Query<&mut PanOrbitCamera>,
-    transform_query: Query<(&Transform, &Selected)>,
+    selected_translatable: SelectedTranslatables,
+    sdf_entities: Query<&SDFRenderEntity>,
+    mut drag_handles_resource: ResMut<DragHandlesResource>,
+    transforms: Query<&GlobalTransform>,
+    mut commands: Commands,
 ) {
     let Some(hit_position) = trigger.event().hit.position else {
         return;
@@ -219,98 +640,353 @@ fn on_drag_start_handle(
 
     info!("dragstart");
 
-    let Ok((entity_start_transform, _)) = transform_query.single() else {
+    let Some(entity_start_position) = selection_centroid(&selected_translatable) else {
         return;
     };
-
-    let active_axis = handle.0;
+    mark_drag_start(&mut commands, &selected_translatable, &sdf_entities);
 
     *drag_data = DragData::Dragging {
         start_position: hit_position,
-        active_axis,
-        entity_start_position: entity_start_transform.translation,
+        constraint: handle.0,
+        entity_start_position,
+        rotation: gizmo_rotation(&transforms, drag_handles_resource.entity),
     };
+    drag_handles_resource.active = Some(trigger.target());
 }
 
 fn on_drag_handle(
     trigger: Trigger<Pointer<Drag>>,
     drag_data: ResMut<DragData>,
-    mut selected_translatable: Query<(&mut Transform, &Translatable, &Selected)>,
+    mut selected_translatable: SelectedTranslatables,
     cameras: Query<(&Camera, &GlobalTransform, &OverlayCamera)>,
+    snap_settings: Res<SnapSettings>,
+    numeric_input: Res<NumericInput>,
 ) {
-    let (start_pos, entity_start_position, active_axis) = match *drag_data {
+    // A typed offset overrides the mouse
+    if numeric_input.value().is_some() {
+        return;
+    }
+
+    let (start_pos, entity_start_position, constraint, rotation) = match *drag_data {
         DragData::Dragging {
             start_position,
             entity_start_position,
-            active_axis,
-        } => (start_position, entity_start_position, active_axis),
-        DragData::Idle => return,
+            constraint,
+            rotation,
+        } => (start_position, entity_start_position, constraint, rotation),
+        _ => return,
     };
 
     let Ok((camera, camera_transform, _)) = cameras.single() else {
         return;
     };
 
-    let Ok((mut entity_transform, _, _)) = selected_translatable.single_mut() else {
+    info!("dragging");
+
+    let Ok(ray) = camera
+        .viewport_to_world(camera_transform, trigger.event().pointer_location.position)
+    else {
         return;
     };
 
-    info!("dragging");
+    let active_axis = match constraint {
+        DragConstraint::Axis(axis) => axis,
+        DragConstraint::Plane(plane) => {
+            let normal = rotation * plane.normal();
 
-    match active_axis {
-        TranslationAxis::X => {
-            let Ok(ray) = camera
-                .viewport_to_world(camera_transform, trigger.event().pointer_location.position)
-            else {
+            // The start position lies on the plane, so the movement stays within it
+            let Some(t) = ray.intersect_plane(start_pos, InfinitePlane3d { normal }) else {
                 return;
             };
-            let diff = start_pos.y - ray.origin.y;
-            let t = diff / ray.direction.y;
-            if t < 0. {
-                return;
-            }
-            let intersection = ray.get_point(t);
 
-            let x_movement = (intersection - start_pos).dot(Vec3::X);
-
-            entity_transform.translation = entity_start_position + Vec3::X * x_movement;
+            let target = snap_target(
+                &snap_settings,
+                rotation,
+                entity_start_position,
+                entity_start_position + (ray.get_point(t) - start_pos),
+                Vec3::ONE - *plane.normal(),
+            );
+            move_selection_to(&mut selected_translatable, target);
+            return;
         }
-        TranslationAxis::Y => {
+    };
+
+    let direction = rotation * active_axis.direction();
+    let Some(movement) = ray_axis_offset(ray, start_pos, direction) else {
+        return;
+    };
+
+    let target = snap_target(
+        &snap_settings,
+        rotation,
+        entity_start_position,
+        entity_start_position + direction * movement,
+        active_axis.direction(),
+    );
+    move_selection_to(&mut selected_translatable, target);
+}
+
+// Offset along the axis through `origin` of the point closest to the ray, from
+// the closest-points-between-lines formula. None if the ray is (nearly) parallel
+// to the axis or the point lies behind the camera.
+pub fn ray_axis_offset(ray: Ray3d, origin: Vec3, direction: Vec3) -> Option<f32> {
+    let ray_direction = *ray.direction;
+    let w = origin - ray.origin;
+    let b = direction.dot(ray_direction);
+    let denom = 1. - b * b;
+    if denom < 1e-4 {
+        return None;
+    }
+    let d = direction.dot(w);
+    let e = ray_direction.dot(w);
+    let t = (e - b * d) / denom;
+    if t < 0. {
+        return None;
+    }
+    Some((b * e - d) / denom)
+}
+
+// Snap a drag target; `axes` are given in the gizmo's frame. World-aligned
+// gizmos snap to the world grid, local ones snap the offset from the start.
+fn snap_target(
+    snap_settings: &SnapSettings,
+    rotation: Quat,
+    start: Vec3,
+    target: Vec3,
+    axes: Vec3,
+) -> Vec3 {
+    if rotation == Quat::IDENTITY {
+        return snap_settings.snap_position(target, axes);
+    }
+    let local_offset = rotation.inverse() * (target - start);
+    start + rotation * snap_settings.snap_position(local_offset, axes)
+}
+
+fn on_drag_start_scale_handle(
+    trigger: Trigger<Pointer<DragStart>>,
+    scale_handles: Query<&ScaleHandle>,
+    mut drag_data: ResMut<DragData>,
+    mut pan_orbit_query: Query<&mut PanOrbitCamera>,
+    selected_translatable: SelectedTranslatables,
+    sdf_entities: Query<&SDFRenderEntity>,
+    mut drag_handles_resource: ResMut<DragHandlesResource>,
+    transforms: Query<&GlobalTransform>,
+    mut commands: Commands,
+) {
+    let Some(hit_position) = trigger.event().hit.position else {
+        return;
+    };
+
+    let Ok(handle) = scale_handles.get(trigger.target()) else {
+        return;
+    };
+
+    if let Ok(mut pan_orbit) = pan_orbit_query.single_mut() {
+        pan_orbit.enabled = false;
+    };
+
+    mark_drag_start(&mut commands, &selected_translatable, &sdf_entities);
+
+    *drag_data = DragData::Scaling {
+        start_position: hit_position,
+        center: world_position(&transforms, drag_handles_resource.entity),
+        axis: handle.0,
+        rotation: gizmo_rotation(&transforms, drag_handles_resource.entity),
+    };
+    drag_handles_resource.active = Some(trigger.target());
+}
+
+// Scales the mesh through Transform.scale and the SDF through its scale and
+// shape, both relative to the drag start so they stay in sync. Each selected
+// entity is scaled around its own pivot.
+fn on_drag_scale_handle(
+    trigger: Trigger<Pointer<Drag>>,
+    drag_data: Res<DragData>,
+    mut selected_translatable: Query<
+        (
+            &mut Transform,
+            Option<&mut SDFRenderEntity>,
+            &TransformDragStart,
+            Option<&PivotOffset>,
+        ),
+        (With<Translatable>, With<Selected>),
+    >,
+    cameras: Query<(&Camera, &GlobalTransform, &OverlayCamera)>,
+) {
+    let DragData::Scaling {
+        start_position,
+        center,
+        axis,
+        rotation,
+    } = *drag_data
+    else {
+        return;
+    };
+
+    let factor = match axis {
+        None => Vec3::splat((trigger.event().distance.x * UNIFORM_SCALE_SPEED).exp()),
+        Some(axis) => {
+            let Ok((camera, camera_transform, _)) = cameras.single() else {
+                return;
+            };
             let Ok(ray) = camera
                 .viewport_to_world(camera_transform, trigger.event().pointer_location.position)
             else {
                 return;
             };
 
-            let Some(t) = ray.intersect_plane(
-                start_pos,
-                InfinitePlane3d::new((ray.origin - start_pos).with_y(0.)),
-            ) else {
+            // Intersect with the plane that contains the axis and faces the camera
+            let direction = rotation * axis.direction();
+            let Ok(normal) = Dir3::new(direction.cross(*ray.direction).cross(direction)) else {
                 return;
             };
-
-            let intersection = ray.get_point(t);
-
-            let y_movement = (intersection - start_pos).dot(Vec3::Y);
-
-            entity_transform.translation = entity_start_position + Vec3::Y * y_movement;
-        }
-        TranslationAxis::Z => {
-            let Ok(ray) = camera
-                .viewport_to_world(camera_transform, trigger.event().pointer_location.position)
-            else {
+            let Some(t) = ray.intersect_plane(center, InfinitePlane3d { normal }) else {
                 return;
             };
-            let diff = start_pos.y - ray.origin.y;
-            let t = diff / ray.direction.y;
-            if t < 0. {
+
+            let start_along = (start_position - center).dot(direction);
+            if start_along.abs() < f32::EPSILON {
                 return;
             }
-            let intersection = ray.get_point(t);
+            let along = (ray.get_point(t) - center).dot(direction);
+            let axis_factor = (along / start_along).max(MIN_SCALE_FACTOR);
+
+            // Transform.scale is along the local axes
+            Vec3::ONE + axis.direction() * (axis_factor - 1.)
+        }
+    };
+
+    for (mut entity_transform, sdf_entity, start, pivot) in selected_translatable.iter_mut() {
+        let offset = pivot.map_or(Vec3::ZERO, |p| p.0);
+        let pivot_position = start.transform.transform_point(offset);
+        entity_transform.scale = start.transform.scale * factor;
+        // Keep the pivot in place while the entity grows around it
+        entity_transform.translation =
+            pivot_position - entity_transform.rotation * (entity_transform.scale * offset);
+
+        if let (Some(mut sdf_entity), Some(start_sdf)) = (sdf_entity, &start.sdf) {
+            let (scale, primitive) = start_sdf.primitive.scaled(start_sdf.scale, factor);
+            sdf_entity.scale = scale;
+            sdf_entity.primitive = primitive;
+        }
+    }
+}
+
+fn on_over_handle(
+    trigger: Trigger<Pointer<Over>>,
+    mut drag_handles_resource: ResMut<DragHandlesResource>,
+) {
+    drag_handles_resource.hovered = Some(trigger.target());
+}
+
+fn on_out_handle(
+    trigger: Trigger<Pointer<Out>>,
+    mut drag_handles_resource: ResMut<DragHandlesResource>,
+) {
+    if drag_handles_resource.hovered == Some(trigger.target()) {
+        drag_handles_resource.hovered = None;
+    }
+}
+
+// Ray-marches the SDF scene towards every handle on the GPU and marks the
+// handles whose ray hits a surface first. Like the brush preview, the result
+// trails the handles by a round trip.
+fn update_handle_occlusion(
+    occlusion: Res<GizmoOcclusion>,
+    warmup: Res<PipelineWarmupState>,
+    window: Single<&Window, With<PrimaryWindow>>,
+    sdf_sender: Res<SdfEvaluationSender>,
+    cameras: Query<(&Camera, &GlobalTransform, &OverlayCamera)>,
+    transforms: Query<&GlobalTransform>,
+    mut query: ResMut<HandleOcclusionQuery>,
+    mut drag_handles_resource: ResMut<DragHandlesResource>,
+) {
+    if !occlusion.enabled || !warmup.is_ready() || drag_handles_resource.materials.is_empty() {
+        query.task = None;
+        if !drag_handles_resource.occluded.is_empty() {
+            drag_handles_resource.occluded.clear();
+        }
+        return;
+    }
+
+    if let Some(task) = &mut query.task {
+        let Some(occluded) = block_on(future::poll_once(task)) else {
+            return;
+        };
+        query.task = None;
+        if drag_handles_resource.occluded != occluded {
+            drag_handles_resource.occluded = occluded;
+        }
+    }
+
+    let Ok((camera, camera_transform, _)) = cameras.single() else {
+        return;
+    };
+    let size = Vec2::new(window.resolution.width(), window.resolution.height());
+
+    // Viewport position and distance from the ray origin of each visible handle
+    let mut handles = Vec::new();
+    for handle in &drag_handles_resource.materials {
+        let Ok(transform) = transforms.get(handle.entity) else {
+            continue;
+        };
+        let Ok(viewport_position) =
+            camera.world_to_viewport(camera_transform, transform.translation())
+        else {
+            continue;
+        };
+        let Ok(ray) = camera.viewport_to_world(camera_transform, viewport_position) else {
+            continue;
+        };
+        handles.push((
+            handle.entity,
+            viewport_position / size,
+            ray.origin.distance(transform.translation()),
+        ));
+    }
 
-            let z_movement = (intersection - start_pos).dot(Vec3::Z);
+    let sender_clone = sdf_sender.clone();
+    query.task = Some(bevy::tasks::AsyncComputeTaskPool::get().spawn(async move {
+        let points = handles.iter().map(|(_, uv, _)| *uv).collect();
+        let Ok(results) = evaluate_sdf_async(points, &sender_clone).await else {
+            return Vec::new();
+        };
+        handles
+            .iter()
+            .zip(results)
+            .filter(|((_, _, distance), result)| result.distance + OCCLUSION_BIAS < *distance)
+            .map(|((entity, _, _), _)| *entity)
+            .collect()
+    }));
+}
 
-            entity_transform.translation = entity_start_position + Vec3::Z * z_movement;
+// Brightens the hovered or dragged handle and dims the others during a drag.
+// Occluded handles are drawn translucent.
+fn update_handle_materials(
+    drag_handles_resource: Res<DragHandlesResource>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    if !drag_handles_resource.is_changed() {
+        return;
+    }
+    let active = drag_handles_resource.active;
+    let hovered = drag_handles_resource.hovered;
+    for handle in &drag_handles_resource.materials {
+        let Some(material) = materials.get_mut(&handle.material) else {
+            continue;
+        };
+        let color = match active {
+            Some(active) if active != handle.entity => handle.color.darker(0.3),
+            Some(_) => handle.color.lighter(0.2),
+            None if hovered == Some(handle.entity) => handle.color.lighter(0.2),
+            None => handle.color,
+        };
+        if drag_handles_resource.occluded.contains(&handle.entity) {
+            material.base_color = color.with_alpha(OCCLUDED_HANDLE_ALPHA);
+            material.alpha_mode = AlphaMode::Blend;
+        } else {
+            material.base_color = color;
+            material.alpha_mode = AlphaMode::Opaque;
         }
     }
 }
@@ -318,11 +994,364 @@ fn on_drag_handle(
 fn on_drag_end_handle(
     _: Trigger<Pointer<DragEnd>>,
     mut drag_data: ResMut<DragData>,
+    mut drag_handles_resource: ResMut<DragHandlesResource>,
     mut pan_orbit_query: Query<&mut PanOrbitCamera>,
 ) {
     *drag_data = DragData::Idle;
+    drag_handles_resource.active = None;
 
     if let Ok(mut pan_orbit) = pan_orbit_query.single_mut() {
         pan_orbit.enabled = true;
     };
 }
+
+// Blender-style grab: G (rebindable) grabs the selection, X/Y/Z toggle an axis
+// constraint, the mouse moves it, click/Enter confirms and Esc cancels
+fn handle_keyboard_grab(
+    app_mode: Res<AppModeState>,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    keys: Res<KeyBindings>,
+    buttons: Res<ButtonInput<MouseButton>>,
+    window: Single<&Window, With<PrimaryWindow>>,
+    cameras: Query<(&Camera, &GlobalTransform, &OverlayCamera)>,
+    mut drag_data: ResMut<DragData>,
+    mut selected_translatable: SelectedTranslatables,
+    sdf_entities: Query<&SDFRenderEntity>,
+    mut pan_orbit_query: Query<&mut PanOrbitCamera>,
+    snap_settings: Res<SnapSettings>,
+    numeric_input: Res<NumericInput>,
+    mut commands: Commands,
+    mut gizmos: Gizmos,
+) {
+    if !app_mode.is_mode(AppMode::Translate) {
+        return;
+    }
+
+    let Some(centroid) = selection_centroid(&selected_translatable) else {
+        return;
+    };
+    let Ok((camera, camera_transform, _)) = cameras.single() else {
+        return;
+    };
+
+    // Project the cursor onto the camera-facing plane through `origin`
+    let cursor_on_plane = |origin: Vec3| {
+        let position = window.cursor_position()?;
+        let ray = camera.viewport_to_world(camera_transform, position).ok()?;
+        let t = ray.intersect_plane(origin, InfinitePlane3d::new(camera_transform.forward()))?;
+        Some(ray.get_point(t))
+    };
+
+    match *drag_data {
+        DragData::Idle => {
+            if !keyboard_input.just_pressed(keys.grab) {
+                return;
+            }
+            let entity_start_position = centroid;
+            let Some(start_position) = cursor_on_plane(entity_start_position) else {
+                return;
+            };
+            mark_drag_start(&mut commands, &selected_translatable, &sdf_entities);
+            *drag_data = DragData::Grabbing {
+                start_position,
+                entity_start_position,
+                axis: None,
+            };
+            if let Ok(mut pan_orbit) = pan_orbit_query.single_mut() {
+                pan_orbit.enabled = false;
+            };
+        }
+        DragData::Grabbing {
+            start_position,
+            entity_start_position,
+            axis,
+        } => {
+            let cancelled = keyboard_input.just_pressed(KeyCode::Escape)
+                || buttons.just_pressed(MouseButton::Right);
+            let confirmed = keyboard_input.just_pressed(KeyCode::Enter)
+                || buttons.just_pressed(MouseButton::Left);
+            if cancelled || confirmed {
+                if cancelled {
+                    move_selection_to(&mut selected_translatable, entity_start_position);
+                }
+                *drag_data = DragData::Idle;
+                if let Ok(mut pan_orbit) = pan_orbit_query.single_mut() {
+                    pan_orbit.enabled = true;
+                };
+                return;
+            }
+
+            // Pressing the active axis again removes the constraint
+            let mut axis = axis;
+            for (key, pressed_axis) in [
+                (KeyCode::KeyX, TranslationAxis::X),
+                (KeyCode::KeyY, TranslationAxis::Y),
+                (KeyCode::KeyZ, TranslationAxis::Z),
+            ] {
+                if keyboard_input.just_pressed(key) {
+                    axis = (axis != Some(pressed_axis)).then_some(pressed_axis);
+                }
+            }
+            *drag_data = DragData::Grabbing {
+                start_position,
+                entity_start_position,
+                axis,
+            };
+
+            // A typed offset overrides the mouse
+            if numeric_input.value().is_some() {
+                return;
+            }
+
+            let Some(cursor) = cursor_on_plane(entity_start_position) else {
+                return;
+            };
+            let movement = cursor - start_position;
+            let target = match axis {
+                Some(axis) => {
+                    let direction = axis.direction();
+                    gizmos.line(
+                        entity_start_position - direction * 100.,
+                        entity_start_position + direction * 100.,
+                        Color::srgba(1., 1., 1., 0.5),
+                    );
+                    snap_settings.snap_position(
+                        entity_start_position + direction * movement.dot(direction),
+                        direction,
+                    )
+                }
+                None => snap_settings.snap_position(entity_start_position + movement, Vec3::ONE),
+            };
+            move_selection_to(&mut selected_translatable, target);
+        }
+        _ => {}
+    }
+}
+
+// Axis a typed offset applies to for the current drag, if any
+fn numeric_input_axis(drag_data: &DragData) -> Option<TranslationAxis> {
+    match *drag_data {
+        DragData::Dragging {
+            constraint: DragConstraint::Axis(axis),
+            ..
+        } => Some(axis),
+        DragData::Grabbing { axis, .. } => axis,
+        _ => None,
+    }
+}
+
+// Accumulates typed digits while dragging along an axis, moves the entity by
+// the typed offset and confirms the move on Enter
+fn capture_numeric_input(
+    mut keyboard_events: EventReader<KeyboardInput>,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut drag_data: ResMut<DragData>,
+    mut numeric_input: ResMut<NumericInput>,
+    mut selected_translatable: SelectedTranslatables,
+    mut pan_orbit_query: Query<&mut PanOrbitCamera>,
+) {
+    let Some(axis) = numeric_input_axis(&drag_data) else {
+        keyboard_events.clear();
+        if !numeric_input.buffer.is_empty() {
+            numeric_input.clear();
+        }
+        return;
+    };
+
+    // Switching axes starts the entry over
+    if numeric_input.axis != Some(axis) {
+        numeric_input.clear();
+        numeric_input.axis = Some(axis);
+    }
+
+    for event in keyboard_events.read() {
+        if event.state != ButtonState::Pressed {
+            continue;
+        }
+        match &event.logical_key {
+            Key::Character(c) if c.chars().all(|ch| ch.is_ascii_digit() || ch == '.') => {
+                numeric_input.buffer.push_str(c);
+            }
+            // Minus toggles the sign like in Blender
+            Key::Character(c) if c.as_str() == "-" => {
+                if numeric_input.buffer.starts_with('-') {
+                    numeric_input.buffer.remove(0);
+                } else {
+                    numeric_input.buffer.insert(0, '-');
+                }
+            }
+            Key::Backspace => {
+                numeric_input.buffer.pop();
+            }
+            _ => {}
+        }
+    }
+
+    let Some(value) = numeric_input.value() else {
+        return;
+    };
+
+    // Handle drags move along the gizmo axes, which may be local
+    let (entity_start_position, rotation) = match *drag_data {
+        DragData::Dragging {
+            entity_start_position,
+            rotation,
+            ..
+        } => (entity_start_position, rotation),
+        DragData::Grabbing {
+            entity_start_position,
+            ..
+        } => (entity_start_position, Quat::IDENTITY),
+        _ => return,
+    };
+
+    move_selection_to(
+        &mut selected_translatable,
+        entity_start_position + rotation * axis.direction() * value,
+    );
+
+    if keyboard_input.just_pressed(KeyCode::Enter) {
+        *drag_data = DragData::Idle;
+        numeric_input.clear();
+        if let Ok(mut pan_orbit) = pan_orbit_query.single_mut() {
+            pan_orbit.enabled = true;
+        };
+    }
+}
+
+fn spawn_numeric_input_text(mut commands: Commands) {
+    commands.spawn((
+        Text::default(),
+        Node {
+            position_type: PositionType::Absolute,
+            bottom: Val::Px(12.),
+            left: Val::Px(12.),
+            ..default()
+        },
+        Visibility::Hidden,
+        NumericInputText,
+    ));
+}
+
+// Echoes the typed offset in the overlay
+fn update_numeric_input_text(
+    numeric_input: Res<NumericInput>,
+    mut text_query: Query<(&mut Text, &mut Visibility), With<NumericInputText>>,
+) {
+    if !numeric_input.is_changed() {
+        return;
+    }
+
+    for (mut text, mut visibility) in text_query.iter_mut() {
+        match numeric_input.axis {
+            Some(axis) if !numeric_input.buffer.is_empty() => {
+                text.0 = format!("{:?}: {}", axis, numeric_input.buffer);
+                *visibility = Visibility::Inherited;
+            }
+            _ => *visibility = Visibility::Hidden,
+        }
+    }
+}
+
+// Once a drag, grab or scale is over, record what changed as one undo step
+fn record_drag_edits(
+    drag_data: Res<DragData>,
+    started: Query<(
+        Entity,
+        &Transform,
+        Option<&SDFRenderEntity>,
+        &TransformDragStart,
+    )>,
+    mut history: ResMut<EditHistory>,
+    mut commands: Commands,
+) {
+    if !matches!(*drag_data, DragData::Idle) || started.is_empty() {
+        return;
+    }
+
+    let mut edits = Vec::new();
+    for (entity, transform, sdf_entity, start) in started.iter() {
+        if *transform != start.transform {
+            edits.push(Edit::Transform {
+                entity,
+                before: start.transform,
+                after: *transform,
+            });
+        }
+        if let (Some(sdf_entity), Some(before)) = (sdf_entity, &start.sdf) {
+            if sdf_entity.scale != before.scale || sdf_entity.primitive != before.primitive {
+                edits.push(Edit::Sdf {
+                    entity,
+                    before: before.clone(),
+                    after: sdf_entity.clone(),
+                });
+            }
+        }
+        commands.entity(entity).remove::<TransformDragStart>();
+    }
+    history.record_all(edits);
+}
+
+// Arrow keys nudge the selection by the snap increment in the ground plane,
+// along the world axes closest to the camera's forward and right; PageUp and
+// PageDown nudge along Y. Shift takes larger steps.
+fn nudge_selection(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    app_mode: Res<AppModeState>,
+    drag_data: Res<DragData>,
+    snap_settings: Res<SnapSettings>,
+    cameras: Query<&GlobalTransform, With<OverlayCamera>>,
+    mut selected_translatable: SelectedTranslatables,
+    mut history: ResMut<EditHistory>,
+) {
+    if !app_mode.is_mode(AppMode::Translate) || !matches!(*drag_data, DragData::Idle) {
+        return;
+    }
+    let Ok(camera_transform) = cameras.single() else {
+        return;
+    };
+
+    let ground_axis = |direction: Vec3| {
+        if direction.x.abs() > direction.z.abs() {
+            Vec3::X * direction.x.signum()
+        } else {
+            Vec3::Z * direction.z.signum()
+        }
+    };
+    let forward = ground_axis(*camera_transform.forward());
+    let right = ground_axis(*camera_transform.right());
+
+    let mut direction = Vec3::ZERO;
+    for (key, key_direction) in [
+        (KeyCode::ArrowUp, forward),
+        (KeyCode::ArrowDown, -forward),
+        (KeyCode::ArrowRight, right),
+        (KeyCode::ArrowLeft, -right),
+        (KeyCode::PageUp, Vec3::Y),
+        (KeyCode::PageDown, -Vec3::Y),
+    ] {
+        if keyboard_input.just_pressed(key) {
+            direction += key_direction;
+        }
+    }
+    if direction == Vec3::ZERO {
+        return;
+    }
+
+    let mut step = snap_settings.grid_size;
+    if keyboard_input.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]) {
+        step *= NUDGE_LARGE_FACTOR;
+    }
+
+    let mut edits = Vec::new();
+    for (entity, mut transform) in selected_translatable.iter_mut() {
+        let before = *transform;
+        transform.translation += direction * step;
+        edits.push(Edit::Transform {
+            entity,
+            before,
+            after: *transform,
+        });
+    }
+    history.record_all(edits);
+}
@@ -0,0 +1,147 @@
+//! Turntable capture
+//!
+//! Orbits the camera a full turn around its focus over a fixed number of
+//! frames and saves every rendered frame as a numbered PNG, ready to be
+//! stitched into a turntable video of the sculpt. Started with F12 or from the
+//! UI through the command bridge.
+
+use std::f32::consts::TAU;
+
+use bevy::{
+    prelude::*,
+    render::{
+        camera::RenderTarget,
+        view::screenshot::{save_to_disk, Screenshot},
+    },
+};
+use bevy_panorbit_camera::PanOrbitCamera;
+
+use crate::sdf_render::SdfRenderCamera;
+use crate::progress::{AppEvent, Operation};
+use crate::settings::KeyBindings;
+
+pub struct TurntableCapturePlugin;
+
+#[derive(Event)]
+pub struct StartTurntableCapture {
+    pub frames: u32,
+}
+
+#[derive(Resource)]
+pub struct TurntableCapture {
+    // Frames a capture started from the keyboard spreads the turn over
+    pub frame_count: u32,
+    active: Option<ActiveCapture>,
+}
+
+struct ActiveCapture {
+    frame: u32,
+    frame_count: u32,
+    // Yaw the turn starts from and the camera returns to afterwards
+    start_yaw: f32,
+}
+
+impl TurntableCapture {
+    pub fn is_active(&self) -> bool {
+        self.active.is_some()
+    }
+}
+
+impl Default for TurntableCapture {
+    fn default() -> Self {
+        Self {
+            frame_count: 120,
+            active: None,
+        }
+    }
+}
+
+impl Plugin for TurntableCapturePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<TurntableCapture>()
+            .add_event::<StartTurntableCapture>()
+            .add_systems(
+                Update,
+                (start_turntable_capture, advance_turntable_capture).chain(),
+            );
+    }
+}
+
+fn start_turntable_capture(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    keys: Res<KeyBindings>,
+    mut start_events: EventReader<StartTurntableCapture>,
+    mut capture: ResMut<TurntableCapture>,
+    pan_orbit_query: Query<&PanOrbitCamera>,
+) {
+    let mut frames = start_events.read().map(|event| event.frames).last();
+    if keyboard_input.just_pressed(keys.turntable_capture) {
+        frames = Some(capture.frame_count);
+    }
+    let Some(frames) = frames else {
+        return;
+    };
+
+    if capture.active.is_some() {
+        warn!("A turntable capture is already running");
+        return;
+    }
+    let Ok(pan_orbit) = pan_orbit_query.single() else {
+        return;
+    };
+
+    info!("Capturing {} turntable frames", frames.max(1));
+    capture.active = Some(ActiveCapture {
+        frame: 0,
+        frame_count: frames.max(1),
+        start_yaw: pan_orbit.target_yaw,
+    });
+}
+
+fn advance_turntable_capture(
+    mut commands: Commands,
+    mut capture: ResMut<TurntableCapture>,
+    mut app_events: EventWriter<AppEvent>,
+    mut pan_orbit_query: Query<(&mut PanOrbitCamera, &Camera), With<SdfRenderCamera>>,
+) {
+    let Some(active) = capture.active.as_mut() else {
+        return;
+    };
+    let Ok((mut pan_orbit, camera)) = pan_orbit_query.single_mut() else {
+        return;
+    };
+
+    if active.frame == active.frame_count {
+        set_yaw(&mut pan_orbit, active.start_yaw);
+        info!("Turntable capture finished");
+        capture.active = None;
+        app_events.write(AppEvent::complete(Operation::TurntableCapture, true));
+        return;
+    }
+    let fraction = active.frame as f32 / active.frame_count as f32;
+    app_events.write(AppEvent::progress(Operation::TurntableCapture, fraction));
+
+    let yaw = active.start_yaw + TAU * fraction;
+    set_yaw(&mut pan_orbit, yaw);
+
+    // The screenshot reads back the frame rendered with the yaw set above
+    commands
+        .spawn(camera_screenshot(camera))
+        .observe(save_to_disk(format!("turntable_{:04}.png", active.frame)));
+    active.frame += 1;
+}
+
+// Screenshots what the camera renders to: the window, or an image in headless runs
+pub fn camera_screenshot(camera: &Camera) -> Screenshot {
+    match &camera.target {
+        RenderTarget::Image(image) => Screenshot::image(image.handle.clone()),
+        _ => Screenshot::primary_window(),
+    }
+}
+
+// Jumps straight to the yaw, skipping the smoothing so every frame lands on its angle
+fn set_yaw(pan_orbit: &mut PanOrbitCamera, yaw: f32) {
+    pan_orbit.yaw = Some(yaw);
+    pan_orbit.target_yaw = yaw;
+    pan_orbit.force_update = true;
+}
@@ -0,0 +1,168 @@
+//! Per-view frustum culling of SDF entities
+//!
+//! The coarse prepass evaluates every entity for every one of its pixels, so
+//! entities outside a camera's frustum cost as much as visible ones. Each SDF
+//! camera gets a list of the entities whose bounds reach into its frustum,
+//! uploaded per view and iterated by the coarse prepass instead of the whole
+//! entity buffer. The entity buffer itself stays complete, since the compute
+//! evaluation path and the BVH still index into it.
+
+use bevy::{
+    math::Affine3A,
+    prelude::*,
+    render::{
+        extract_component::{ExtractComponent, ExtractComponentPlugin},
+        primitives::{Aabb, Frustum},
+        render_resource::{Buffer, BufferDescriptor, BufferUsages},
+        renderer::{RenderDevice, RenderQueue},
+        view::VisibilitySystems,
+        Render, RenderApp, RenderSet,
+    },
+};
+use bvh::aabb::Bounded;
+
+use crate::sdf_render::{EntityData, SdfRenderCamera};
+
+pub struct ViewCullingPlugin;
+
+#[derive(Resource, Clone)]
+pub struct SdfViewCulling {
+    pub enabled: bool,
+    // Distance entity bounds are grown by, so entities just outside the view
+    // still shadow and blend into those inside it
+    pub margin: f32,
+}
+
+impl Default for SdfViewCulling {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            margin: 1.0,
+        }
+    }
+}
+
+// Indices into the entity data of the entities within a camera's frustum
+#[derive(Component, Clone, Default, ExtractComponent)]
+#[extract_component_filter(With<SdfRenderCamera>)]
+pub struct SdfViewEntities(Vec<u32>);
+
+impl Plugin for ViewCullingPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SdfViewCulling>()
+            .add_plugins(ExtractComponentPlugin::<SdfViewEntities>::default())
+            .add_systems(
+                PostUpdate,
+                cull_view_entities.after(VisibilitySystems::UpdateFrusta),
+            );
+
+        let Some(render_app) = app.get_sub_app_mut(RenderApp) else {
+            return;
+        };
+
+        render_app.add_systems(
+            Render,
+            prepare_view_entities_buffers.in_set(RenderSet::PrepareResources),
+        );
+    }
+}
+
+fn cull_view_entities(
+    mut commands: Commands,
+    culling: Res<SdfViewCulling>,
+    entity_data: Option<Res<EntityData>>,
+    mut camera_query: Query<
+        (Entity, Ref<Frustum>, Option<&mut SdfViewEntities>),
+        With<SdfRenderCamera>,
+    >,
+) {
+    let Some(entity_data) = entity_data else {
+        return;
+    };
+
+    let entities = entity_data.entities();
+    for (camera, frustum, view_entities) in camera_query.iter_mut() {
+        let up_to_date = view_entities.is_some()
+            && !(entity_data.is_changed() || culling.is_changed() || frustum.is_changed());
+        if up_to_date {
+            continue;
+        }
+
+        let margin = Vec3::splat(culling.margin.max(0.0));
+        let visible = entities
+            .iter()
+            .enumerate()
+            .filter(|(_, entity)| {
+                if !culling.enabled {
+                    return true;
+                }
+                let aabb = entity.aabb();
+                let bounds = Aabb::from_min_max(
+                    Vec3::new(aabb.min.x, aabb.min.y, aabb.min.z) - margin,
+                    Vec3::new(aabb.max.x, aabb.max.y, aabb.max.z) + margin,
+                );
+                frustum.intersects_obb(&bounds, &Affine3A::IDENTITY, true, true)
+            })
+            .map(|(index, _)| index as u32)
+            .collect();
+
+        match view_entities {
+            Some(mut view_entities) => view_entities.0 = visible,
+            None => {
+                commands.entity(camera).insert(SdfViewEntities(visible));
+            }
+        }
+    }
+}
+
+// The view's entity list on the GPU, laid out as its length followed by the
+// indices (must match ViewEntities in sdf_common.wgsl)
+#[derive(Component)]
+pub struct SdfViewEntitiesBuffer {
+    pub buffer: Buffer,
+    capacity: usize,
+    data: Vec<u32>,
+}
+
+fn prepare_view_entities_buffers(
+    mut commands: Commands,
+    mut views: Query<(Entity, &SdfViewEntities, Option<&mut SdfViewEntitiesBuffer>)>,
+    render_device: Res<RenderDevice>,
+    render_queue: Res<RenderQueue>,
+) {
+    for (view, view_entities, existing) in views.iter_mut() {
+        let mut data = Vec::with_capacity(view_entities.0.len() + 1);
+        data.push(view_entities.0.len() as u32);
+        data.extend_from_slice(&view_entities.0);
+        // The runtime sized array has to hold at least one element
+        let data_size = (data.len() + 1) * std::mem::size_of::<u32>();
+
+        let mut view_buffer = match existing {
+            Some(existing) if existing.capacity >= data_size => {
+                if existing.data == data {
+                    continue;
+                }
+                existing
+            }
+            _ => {
+                let capacity = (data_size * 2).max(256);
+                let buffer = render_device.create_buffer(&BufferDescriptor {
+                    label: Some("sdf_view_entities_buffer"),
+                    size: capacity as u64,
+                    usage: BufferUsages::STORAGE | BufferUsages::COPY_DST,
+                    mapped_at_creation: false,
+                });
+                render_queue.write_buffer(&buffer, 0, bytemuck::cast_slice(&data));
+                commands.entity(view).insert(SdfViewEntitiesBuffer {
+                    buffer,
+                    capacity,
+                    data,
+                });
+                continue;
+            }
+        };
+
+        render_queue.write_buffer(&view_buffer.buffer, 0, bytemuck::cast_slice(&data));
+        view_buffer.data = data;
+    }
+}
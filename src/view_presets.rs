@@ -0,0 +1,190 @@
+//! Numpad view presets
+//!
+//! Numpad 1, 3 and 7 orbit the camera to the front, right and top views (with
+//! Ctrl held to the back, left and bottom ones) and switch to an orthographic
+//! projection; numpad 5 toggles between orthographic and perspective.
+//! PanOrbitCamera animates towards the new angles by itself.
+//!
+//! `CameraRequest` events let the host page drive the camera too: move it to
+//! a pose (for view bookmarks), frame the selection or switch the projection.
+
+use std::f32::consts::{FRAC_PI_2, PI, TAU};
+
+use bevy::{prelude::*, render::camera::ScalingMode};
+use bevy_panorbit_camera::PanOrbitCamera;
+
+use crate::pivot::bounds;
+use crate::sdf_render::{SDFRenderEntity, SdfRenderCamera};
+use crate::selection::SelectionState;
+
+// Room left around framed entities, relative to their size
+const FRAME_MARGIN: f32 = 1.2;
+
+pub struct ViewPresetsPlugin;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ViewPreset {
+    Front,
+    Back,
+    Right,
+    Left,
+    Top,
+    Bottom,
+}
+
+impl ViewPreset {
+    // Yaw and pitch of the PanOrbitCamera looking from this side
+    fn angles(&self) -> (f32, f32) {
+        match self {
+            ViewPreset::Front => (0.0, 0.0),
+            ViewPreset::Back => (PI, 0.0),
+            ViewPreset::Right => (FRAC_PI_2, 0.0),
+            ViewPreset::Left => (-FRAC_PI_2, 0.0),
+            ViewPreset::Top => (0.0, FRAC_PI_2),
+            ViewPreset::Bottom => (0.0, -FRAC_PI_2),
+        }
+    }
+}
+
+impl Plugin for ViewPresetsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<CameraRequest>()
+            .add_systems(Update, (handle_view_preset_keys, handle_camera_requests));
+    }
+}
+
+#[derive(Event, Debug, Clone, Copy)]
+pub enum CameraRequest {
+    // Orbit around `target`, looking at it from `position`
+    Pose { position: Vec3, target: Vec3 },
+    // Fit the selection, or the whole scene when nothing is selected, in view
+    FrameSelected,
+    Projection { orthographic: bool },
+}
+
+fn handle_view_preset_keys(
+    keyboard: Res<ButtonInput<KeyCode>>,
+    mut cameras: Query<(&mut PanOrbitCamera, &mut Projection)>,
+) {
+    let Ok((mut pan_orbit, mut projection)) = cameras.single_mut() else {
+        return;
+    };
+
+    if keyboard.just_pressed(KeyCode::Numpad5) {
+        let orthographic = matches!(*projection, Projection::Perspective(_));
+        set_orthographic(&mut projection, orthographic);
+        pan_orbit.force_update = true;
+        info!("Orthographic view: {}", orthographic);
+        return;
+    }
+
+    let opposite = keyboard.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]);
+    let preset = if keyboard.just_pressed(KeyCode::Numpad1) {
+        if opposite { ViewPreset::Back } else { ViewPreset::Front }
+    } else if keyboard.just_pressed(KeyCode::Numpad3) {
+        if opposite { ViewPreset::Left } else { ViewPreset::Right }
+    } else if keyboard.just_pressed(KeyCode::Numpad7) {
+        if opposite { ViewPreset::Bottom } else { ViewPreset::Top }
+    } else {
+        return;
+    };
+
+    apply_view_preset(&mut pan_orbit, &mut projection, preset);
+}
+
+pub fn apply_view_preset(
+    pan_orbit: &mut PanOrbitCamera,
+    projection: &mut Projection,
+    preset: ViewPreset,
+) {
+    let (yaw, pitch) = preset.angles();
+
+    // Take the short way around instead of unwinding previous orbits
+    let yaw_delta = (yaw - pan_orbit.target_yaw + PI).rem_euclid(TAU) - PI;
+    pan_orbit.target_yaw += yaw_delta;
+    pan_orbit.target_pitch = pitch;
+
+    set_orthographic(projection, true);
+    pan_orbit.force_update = true;
+}
+
+pub fn set_orthographic(projection: &mut Projection, orthographic: bool) {
+    match (&*projection, orthographic) {
+        (Projection::Perspective(_), true) => {
+            *projection = Projection::Orthographic(OrthographicProjection {
+                // PanOrbitCamera zooms orthographic views through the scale, which
+                // it keeps equal to the orbit radius
+                scaling_mode: ScalingMode::FixedVertical {
+                    viewport_height: 1.0,
+                },
+                ..OrthographicProjection::default_3d()
+            });
+        }
+        (Projection::Orthographic(_), false) => {
+            *projection = Projection::Perspective(PerspectiveProjection::default());
+        }
+        _ => {}
+    }
+}
+
+fn handle_camera_requests(
+    mut requests: EventReader<CameraRequest>,
+    mut cameras: Query<(&mut PanOrbitCamera, &mut Projection), With<SdfRenderCamera>>,
+    selection: Res<SelectionState>,
+    sdf_entities: Query<&SDFRenderEntity>,
+    children: Query<&Children>,
+) {
+    let Ok((mut pan_orbit, mut projection)) = cameras.single_mut() else {
+        requests.clear();
+        return;
+    };
+
+    for request in requests.read() {
+        match *request {
+            CameraRequest::Pose { position, target } => {
+                let offset = position - target;
+                let radius = offset.length();
+                if radius < f32::EPSILON {
+                    warn!("Camera position and target must differ");
+                    continue;
+                }
+                // PanOrbitCamera sits at focus + (yaw about Y, then pitch) * (0, 0, radius)
+                pan_orbit.target_focus = target;
+                pan_orbit.target_radius = radius;
+                pan_orbit.target_yaw = offset.x.atan2(offset.z);
+                pan_orbit.target_pitch = (offset.y / radius).clamp(-1.0, 1.0).asin();
+            }
+            CameraRequest::FrameSelected => {
+                // Groups are framed by their children
+                let framed = if selection.is_empty() {
+                    bounds(sdf_entities.iter())
+                } else {
+                    bounds(
+                        selection
+                            .iter()
+                            .flat_map(|entity| {
+                                std::iter::once(entity).chain(children.iter_descendants(entity))
+                            })
+                            .filter_map(|entity| sdf_entities.get(entity).ok()),
+                    )
+                };
+                let Some((min, max)) = framed else {
+                    warn!("Nothing to frame");
+                    continue;
+                };
+                let extent = (max - min).length() * 0.5 * FRAME_MARGIN;
+                pan_orbit.target_focus = (min + max) * 0.5;
+                pan_orbit.target_radius = match &*projection {
+                    // The view is as tall as the radius, see set_orthographic
+                    Projection::Orthographic(_) => extent * 2.0,
+                    Projection::Perspective(perspective) => extent / (perspective.fov * 0.5).sin(),
+                    _ => extent * 2.0,
+                };
+            }
+            CameraRequest::Projection { orthographic } => {
+                set_orthographic(&mut projection, orthographic);
+            }
+        }
+        pan_orbit.force_update = true;
+    }
+}
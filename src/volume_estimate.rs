@@ -0,0 +1,159 @@
+//! Monte Carlo estimate of the enclosed volume and its center of mass
+//!
+//! Points spread uniformly over the bounds of the scene are evaluated against
+//! the SDF a batch of `POINTS_PER_REQUEST` at a time, and the fraction that
+//! lands inside the surface scales the volume of the bounds. The centroid is
+//! the mean of the inside points, assuming uniform density. Meant as a quick
+//! sanity check before 3D printing: the error shrinks with the square root of
+//! the sample count. F7 logs an estimate, JavaScript gets one through
+//! `estimate_volume`.
+
+use bevy::{
+    prelude::*,
+    tasks::{block_on, futures_lite::future, AsyncComputeTaskPool, Task},
+};
+use futures::channel::oneshot;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use serde::Serialize;
+
+use crate::meshing::POINTS_PER_REQUEST;
+use crate::pipeline_warmup::PipelineWarmupState;
+use crate::progress::{AppEvent, Operation};
+use crate::sdf_compute::{sample_sdf_async, SdfEvaluationSender};
+use crate::sdf_render::EntityData;
+use crate::settings::KeyBindings;
+
+// Samples taken when none are asked for
+pub const DEFAULT_VOLUME_SAMPLES: u32 = 65536;
+// More samples only shave off a little error, so larger counts are clamped
+pub const MAX_VOLUME_SAMPLES: u32 = 1 << 24;
+
+pub struct VolumeEstimatePlugin;
+
+impl Plugin for VolumeEstimatePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<VolumeEstimation>()
+            .add_event::<EstimateVolume>()
+            .add_event::<VolumeEstimated>()
+            .add_systems(Update, run_volume_estimation);
+    }
+}
+
+#[derive(Event)]
+pub struct EstimateVolume {
+    pub samples: u32,
+}
+
+// Sent once an estimate is back from the GPU
+#[derive(Event, Clone, Copy, Debug)]
+pub struct VolumeEstimated(pub VolumeEstimate);
+
+#[derive(Serialize, Clone, Copy, Debug)]
+pub struct VolumeEstimate {
+    pub volume: f32,
+    pub centroid: [f32; 3],
+    pub samples: u32,
+    // Points that landed inside the surface
+    pub inside: u32,
+}
+
+#[derive(Resource, Default)]
+pub struct VolumeEstimation {
+    task: Option<Task<Option<VolumeEstimate>>>,
+    pub last: Option<VolumeEstimate>,
+}
+
+/// Estimates the volume inside the SDF surface within `min`..`max` and its
+/// centroid (async) from `samples` uniformly spread points, clamped to
+/// `MAX_VOLUME_SAMPLES`
+pub async fn estimate_volume_async(
+    min: Vec3,
+    max: Vec3,
+    samples: u32,
+    sender: &SdfEvaluationSender,
+) -> Result<VolumeEstimate, oneshot::Canceled> {
+    let samples = samples.clamp(1, MAX_VOLUME_SAMPLES);
+    let mut rng = StdRng::from_os_rng();
+    let (mut inside, mut sum) = (0u32, Vec3::ZERO);
+    for start in (0..samples as usize).step_by(POINTS_PER_REQUEST) {
+        let count = POINTS_PER_REQUEST.min(samples as usize - start);
+        let points = (0..count)
+            .map(|_| {
+                let t = Vec3::new(rng.random(), rng.random(), rng.random());
+                min + (max - min) * t
+            })
+            .collect();
+        let results = sample_sdf_async(points, sender).await?;
+        for result in results.iter().filter(|result| result.distance < 0.0) {
+            inside += 1;
+            sum += result.position;
+        }
+    }
+    let fraction = inside as f32 / samples as f32;
+    let centroid = if inside > 0 {
+        sum / inside as f32
+    } else {
+        (min + max) * 0.5
+    };
+
+    Ok(VolumeEstimate {
+        volume: (max - min).element_product() * fraction,
+        centroid: centroid.to_array(),
+        samples,
+        inside,
+    })
+}
+
+fn run_volume_estimation(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    keys: Res<KeyBindings>,
+    mut start_events: EventReader<EstimateVolume>,
+    mut estimated_events: EventWriter<VolumeEstimated>,
+    mut app_events: EventWriter<AppEvent>,
+    warmup: Res<PipelineWarmupState>,
+    entity_data: Option<Res<EntityData>>,
+    sdf_sender: Res<SdfEvaluationSender>,
+    mut estimation: ResMut<VolumeEstimation>,
+) {
+    let mut requested = start_events.read().map(|event| event.samples).last();
+    if keyboard_input.just_pressed(keys.estimate_volume) {
+        requested = Some(DEFAULT_VOLUME_SAMPLES);
+    }
+
+    if let Some(samples) = requested {
+        let bounds = entity_data.as_ref().and_then(|data| data.bounds());
+        if estimation.task.is_some() || !warmup.is_ready() {
+            info!("Volume estimation is busy, try again shortly");
+            app_events.write(AppEvent::complete(Operation::VolumeEstimate, false));
+        } else if let Some((min, max)) = bounds {
+            let sender = sdf_sender.clone();
+            let samples = samples.max(1);
+            estimation.task = Some(AsyncComputeTaskPool::get().spawn(async move {
+                estimate_volume_async(min, max, samples, &sender).await.ok()
+            }));
+        } else {
+            info!("Nothing to estimate the volume of");
+            app_events.write(AppEvent::complete(Operation::VolumeEstimate, false));
+        }
+    }
+
+    let Some(task) = &mut estimation.task else {
+        return;
+    };
+    let Some(estimate) = block_on(future::poll_once(task)) else {
+        return;
+    };
+    estimation.task = None;
+
+    let Some(estimate) = estimate else {
+        app_events.write(AppEvent::complete(Operation::VolumeEstimate, false));
+        return;
+    };
+    info!(
+        "Estimated volume {:.4} with centroid {:?} ({} of {} samples inside)",
+        estimate.volume, estimate.centroid, estimate.inside, estimate.samples
+    );
+    estimation.last = Some(estimate);
+    estimated_events.write(VolumeEstimated(estimate));
+    app_events.write(AppEvent::complete(Operation::VolumeEstimate, true));
+}
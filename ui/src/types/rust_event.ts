@@ -1,4 +1,4 @@
 import { Mode } from "./modes";
 
 // expand as union later
-export type RustEvent = Mode;
+export type RustEvent = Mode | string;
@@ -31,7 +31,526 @@ export interface WasmBindings {
    */
   spawn_sphere_at_origin(): string;
 
+  /**
+   * Spawns a cluster of `count` overlapping spheres of the given radius,
+   * randomly offset by up to `jitter` along each axis around the position.
+   * The cluster is selected and moved as a single group. `count` is clamped
+   * to 1..=256.
+   */
+  spawn_blob: (x: number, y: number, z: number, count: number, radius: number, jitter: number) => void;
+
   set_mode: (name: Mode) => void;
+
+  /**
+   * Switches what the brush does: add material, erase existing entities,
+   * drag out a single capsule per stroke, pull nearby entities along, lay
+   * a tube of connected capsules along the stroke, paint entities, or stamp
+   * spheres along a curve through clicked points (Enter places it).
+   * Holding left Ctrl while sculpting, dragging a capsule or placing a curve
+   * carves instead of adding, or the other way round; the key is `carve` in
+   * the keybindings of the settings file. Cmd is left to pan the camera.
+   */
+  set_brush_tool: (
+    tool: "Sculpt" | "Erase" | "Capsule" | "Grab" | "Tube" | "Paint" | "Curve",
+  ) => void;
+
+  /**
+   * Sets the color the paint brush blends towards (components 0.0 to 1.0).
+   */
+  set_brush_color: (r: number, g: number, b: number) => void;
+
+  /**
+   * Shapes how pen pressure scales the brush size: `minScale` at no
+   * pressure, `maxScale` at full pressure, with `pressure ^ exponent` in between.
+   */
+  set_pressure_curve: (minScale: number, maxScale: number, exponent: number) => void;
+
+  /**
+   * Randomizes the spheres placed by the sculpt brush: each is offset by up to
+   * `position` along every axis and its radius varies by up to `sizeVariance`
+   * (0.0 to 1.0). With a `seed` every stroke repeats the same pattern.
+   */
+  set_brush_jitter: (position: number, sizeVariance: number, seed?: number) => void;
+
+  /**
+   * Shapes the curve brush: spheres are `spacing` radii apart and their radius
+   * goes from `startRadius` to `endRadius` along the curve.
+   */
+  set_curve_profile: (spacing: number, startRadius: number, endRadius: number) => void;
+
+  /**
+   * Repeats everything the brush places `fold` times around a world axis
+   * through the origin, and mirrors it across the plane normal to
+   * `mirrorAxis` when given. A fold of 1 without a mirror axis turns it off.
+   */
+  set_brush_symmetry: (
+    axis: "X" | "Y" | "Z",
+    fold: number,
+    mirrorAxis?: "X" | "Y" | "Z",
+  ) => void;
+
+  /**
+   * Sets the language of text rendered by the editor, e.g. "en" or "de-DE".
+   */
+  set_language: (code: string) => void;
+
+  /**
+   * Enables or disables the infinite ground plane at the given height.
+   */
+  set_ground_plane: (enabled: boolean, height: number) => void;
+
+  /**
+   * Sets the gradient behind the scene, from the horizon color up to the
+   * zenith color (components 0-1).
+   */
+  set_background: (
+    zenithR: number,
+    zenithG: number,
+    zenithB: number,
+    horizonR: number,
+    horizonG: number,
+    horizonB: number,
+  ) => void;
+
+  /**
+   * Shows a reference grid at the ground plane height, with lines `spacing`
+   * apart in the given color (components 0-1).
+   */
+  set_ground_grid: (
+    enabled: boolean,
+    spacing: number,
+    r: number,
+    g: number,
+    b: number,
+    opacity: number,
+  ) => void;
+
+  /**
+   * Enables or disables shadows cast by the scene light onto the SDF surface.
+   * Larger `softness` values give wider penumbras.
+   */
+  set_shadows: (enabled: boolean, softness: number) => void;
+
+  /**
+   * Enables or disables ambient occlusion on the SDF surface (also toggled
+   * with L). `intensity` scales the darkening of creases, `radius` is how far
+   * from the surface occluders are looked for.
+   */
+  set_ambient_occlusion: (enabled: boolean, intensity: number, radius: number) => void;
+
+  /**
+   * Isolate view (also toggled with I): while enabled, every entity except the
+   * selected ones is drawn as a desaturated ghost with the given opacity (0-1).
+   */
+  set_isolate_mode: (enabled: boolean, opacity: number) => void;
+
+  /**
+   * Fades the scene into a fog color (components 0-1) with distance. Fog
+   * starts `start` units from the camera and thickens by `density` per unit.
+   */
+  set_fog: (
+    enabled: boolean,
+    r: number,
+    g: number,
+    b: number,
+    density: number,
+    start: number,
+  ) => void;
+
+  /**
+   * Lights the SDF surface with an equirectangular HDR image, given as a path
+   * relative to the assets folder, e.g. "environments/studio.hdr". The image
+   * adds ambient color and reflections scaled by `intensity`; pass no path to
+   * remove it.
+   */
+  set_environment_map: (path: string | undefined, intensity: number) => void;
+
+  /**
+   * When enabled, the SDF is rendered at a lower resolution and upscaled
+   * whenever its GPU time exceeds `targetFrameTimeMs`, down to `minScale`
+   * (a fraction of the viewport size, e.g. 0.5), and recovers as load drops.
+   */
+  set_adaptive_resolution: (enabled: boolean, targetFrameTimeMs: number, minScale: number) => void;
+
+  /**
+   * When enabled, the resolution and step count of the coarse prepass are
+   * adjusted at runtime from the measured fine march iterations whenever the
+   * SDF pass takes longer than `targetFrameTimeMs`.
+   */
+  set_coarse_tuning: (enabled: boolean, targetFrameTimeMs: number) => void;
+
+  /**
+   * When enabled, scenes with many entities are baked into a distance volume
+   * of `resolution` voxels per side (16 to 256, default 64) once they stop
+   * changing, which lets the SDF pass skip empty space cheaply. Entities
+   * edited since the last bake are always evaluated exactly.
+   */
+  set_volume_cache: (enabled: boolean, resolution: number) => void;
+
+  /**
+   * Picks how the SDF pass finds the entities along each ray: "bvh" (the
+   * default) or "brick_map", a uniform grid that is cheaper to rebuild in
+   * dense scenes.
+   */
+  set_acceleration_structure: (structure: "bvh" | "brick_map") => void;
+
+  /**
+   * Renders a few seconds with each acceleration structure and logs the
+   * average GPU time of the SDF pass for each to the console.
+   */
+  benchmark_acceleration_structures: () => void;
+
+  /**
+   * Estimates the volume enclosed by the SDF surface and its centroid by
+   * sampling `samples` random points (default 65536, at most 16777216)
+   * within the scene bounds.
+   * Results are delivered as JSON (`volume`, `centroid`, `samples`, `inside`)
+   * through the `volumeEstimated` event.
+   */
+  estimate_volume: (samples?: number) => void;
+
+  /**
+   * When enabled (the default), the coarse prepass only evaluates entities
+   * whose bounds, grown by `margin` world units (default 1), reach into the
+   * camera's frustum.
+   */
+  set_view_culling: (enabled: boolean, margin: number) => void;
+
+  /**
+   * Replaces the ray-marched SDF with a low resolution mesh of the scene,
+   * remeshed in the background wherever entities change. `cell_size` is the
+   * grid spacing in world units (default 0.1). Much cheaper to draw on weak
+   * GPUs.
+   */
+  set_preview_mesh: (enabled: boolean, cell_size: number) => void;
+
+  /**
+   * Smooths out aliasing by blending each frame of the SDF with the previous
+   * one, reprojected to the current camera. `blend` is the weight of the
+   * previous frame (e.g. 0.9). Has no effect while adaptive resolution renders
+   * below full size.
+   */
+  set_temporal_accumulation: (enabled: boolean, blend: number) => void;
+
+  /**
+   * Slices the scene with a plane to look inside it. Everything on the side
+   * the normal points to is cut away; the plane can be moved along its normal
+   * by dragging its handle in the viewport.
+   */
+  set_clip_plane: (enabled: boolean, normalX: number, normalY: number, normalZ: number) => void;
+
+  /**
+   * Fills the cut face of the clip plane with a flat color (components 0-1),
+   * or shades it like the rest of the surface when disabled.
+   */
+  set_clip_cap: (enabled: boolean, r: number, g: number, b: number) => void;
+
+  /**
+   * Orbits the camera a full turn over the given number of frames and saves
+   * each frame as a numbered PNG (downloaded in the browser).
+   */
+  start_turntable_capture: (frames: number) => void;
+
+  /**
+   * Saves the current frame as an image, its format following the extension
+   * of the path. In the browser the image is downloaded under that name.
+   */
+  capture_screenshot: (path: string) => void;
+
+  /**
+   * When enabled, gizmo handles hidden behind geometry are drawn translucent
+   * instead of always appearing on top at full strength.
+   */
+  set_gizmo_occlusion: (enabled: boolean) => void;
+
+  /**
+   * Sets the noise displacement of the selected entities.
+   * An amplitude of 0 disables the displacement.
+   */
+  set_selected_displacement: (amplitude: number, frequency: number) => void;
+
+  /**
+   * Turns the selected entities into hollow shells of the given thickness.
+   * A thickness of 0 makes them solid again.
+   */
+  set_selected_shell: (thickness: number) => void;
+
+  /**
+   * Repeats each selected entity `count` times along an axis with the given spacing.
+   * Each selected entity stays the base instance that the gizmo moves. Counts above 64
+   * are clamped.
+   */
+  set_selected_repetition: (count: number, axis: "X" | "Y" | "Z", spacing: number) => void;
+
+  /**
+   * Renames the entity with the given id.
+   */
+  rename_entity: (id: number, name: string) => void;
+
+  /**
+   * Adds a tag to the entity with the given id.
+   */
+  tag_entity: (id: number, tag: string) => void;
+
+  /**
+   * Looks up entities carrying a tag.
+   * Results are delivered as JSON through the `entitiesFound` event.
+   */
+  find_entities_by_tag: (tag: string) => void;
+
+  /**
+   * Deletes the entity with the given id, as an undoable step.
+   * The ids of the deleted entities are delivered as a JSON array through the
+   * `entitiesDeleted` event, as they are for the other deletions.
+   */
+  delete_entity: (id: number) => void;
+
+  /**
+   * Deletes the selected entities, and the children of selected groups.
+   */
+  delete_selected: () => void;
+
+  /**
+   * Deletes every entity in the scene as a single undo step.
+   */
+  clear_scene: () => void;
+
+  /**
+   * Describes every entity and group, for outliners. Delivered as JSON through
+   * the `sceneSummary` event: `entities` ordered by id, each with its name,
+   * tags, world position, shape, sRGBA color, selection state and group
+   * name; `groups` with the ids of their entities; and `selected`, the
+   * selected ids in selection order.
+   */
+  get_scene_summary: () => void;
+
+  /**
+   * Describes one entity like `get_scene_summary` does, through the
+   * `entityDetails` event. The detail is `null` when no entity has the id.
+   */
+  get_entity: (id: number) => void;
+
+  /**
+   * Selects the entity with the given id in place of the current selection,
+   * like clicking it in the viewport. Selection changes from either side are
+   * reported through the `entitySelected` and `entityDeselected` events, with
+   * the entity described as in `get_entity`.
+   */
+  select_entity: (id: number) => void;
+
+  /**
+   * Moves the selected entities by an exact offset along an axis.
+   */
+  translate_selected: (axis: "X" | "Y" | "Z", offset: number) => void;
+
+  /**
+   * Sets the transform of the entity with the given id as one undo step:
+   * its position relative to its group, XYZ Euler rotation in degrees, and
+   * scale relative to the size it was spawned with. Ellipsoids only turn
+   * exactly by quarter turns, otherwise they keep axis aligned extents.
+   */
+  set_entity_transform: (
+    id: number,
+    position: [number, number, number],
+    rotation: [number, number, number],
+    scale: [number, number, number],
+  ) => void;
+
+  /**
+   * Moves the camera to look at `target` from `position`, orbiting around
+   * `target` afterwards. The camera animates to the new pose.
+   */
+  set_camera_pose: (position: [number, number, number], target: [number, number, number]) => void;
+
+  /**
+   * Zooms the camera to fit the selection, or the whole scene when nothing is
+   * selected.
+   */
+  frame_selected: () => void;
+
+  set_projection: (projection: "ortho" | "persp") => void;
+
+  /**
+   * Lines the selected entities up on their lowest, middle or highest
+   * position along an axis, like Alt+X/Y/Z for "Center".
+   */
+  align_selected: (axis: "X" | "Y" | "Z", mode: "Min" | "Center" | "Max") => void;
+
+  /**
+   * Spaces three or more selected entities evenly along an axis, keeping the
+   * outermost ones in place, like Alt+Shift+X/Y/Z.
+   */
+  distribute_selected: (axis: "X" | "Y" | "Z") => void;
+
+  /**
+   * Moves the pivot of the selected entities to a world position. The gizmo
+   * sits at the pivot and scaling happens around it.
+   */
+  set_selected_pivot: (x: number, y: number, z: number) => void;
+
+  /**
+   * Moves the pivot of the selected entities to the center of their bounds,
+   * including the children of groups.
+   */
+  center_selected_pivot: () => void;
+
+  /**
+   * Undoes the last edit, like Ctrl+Z.
+   */
+  undo: () => void;
+
+  /**
+   * Redoes the last undone edit, like Ctrl+Shift+Z.
+   */
+  redo: () => void;
+
+  /**
+   * Exports a sphere-set collider description of the scene.
+   * The JSON is delivered through the `collidersExported` event.
+   */
+  export_colliders: () => void;
+
+  /**
+   * Exports the scene as a glTF document with every blob as a node.
+   * The JSON is delivered through the `sceneGltfExported` event.
+   */
+  export_scene_gltf: () => void;
+
+  /**
+   * Meshes the SDF surface on a grid with `resolution` cells (default 96, at
+   * most 256) along the longest side of the scene and exports it as OBJ,
+   * ASCII STL or glTF. glTF meshes carry vertex normals from the SDF gradient
+   * and vertex colors blended from the entity colors. The file contents are
+   * delivered through the `meshObjExported`, `meshStlExported` or
+   * `meshGltfExported` event.
+   */
+  export_mesh: (format: "obj" | "stl" | "gltf", resolution?: number) => void;
+
+  /**
+   * Serializes every entity, group, the camera and the brush settings to a
+   * versioned document, RON when `path` ends in `.ron` and JSON otherwise.
+   * The document is delivered through the `sceneSaved` event.
+   */
+  save_scene: (path: string) => void;
+
+  /**
+   * Reads a scene document from `path` and replaces the current scene with
+   * it. Only available in native builds; use `load_scene_from_string` on the
+   * web.
+   */
+  load_scene: (path: string) => void;
+
+  /**
+   * Replaces the current scene with a RON or JSON document as produced by
+   * `save_scene`. Clears the undo history.
+   */
+  load_scene_from_string: (contents: string) => void;
+
+  /**
+   * Reads a scene document from `path` and merges it into the current scene
+   * as one undoable step, like `import_scene_from_string`. Only available in
+   * native builds.
+   */
+  import_scene: (
+    path: string,
+    x: number,
+    y: number,
+    z: number,
+    scale?: number,
+  ) => void;
+
+  /**
+   * Merges a saved scene into the current one, keeping the existing entities,
+   * camera and brush. The imported entities are scaled by `scale` (default 1)
+   * about the document origin, which is placed at (x, y, z), and end up
+   * selected.
+   */
+  import_scene_from_string: (
+    contents: string,
+    x: number,
+    y: number,
+    z: number,
+    scale?: number,
+  ) => void;
+
+  /**
+   * Saves a project: a zip archive holding the scene document, a thumbnail
+   * of the next rendered frame and metadata (author, creation and
+   * modification times, app version). The archive is delivered base64
+   * encoded through the `projectSaved` event.
+   */
+  save_project: (path: string, author?: string) => void;
+
+  /**
+   * Reads a project archive from `path` and replaces the current scene with
+   * it. Only available in native builds; use `open_project_from_bytes` on
+   * the web.
+   */
+  open_project: (path: string) => void;
+
+  /**
+   * Replaces the current scene with a project archive. `path` names the
+   * project in the recent files list.
+   */
+  open_project_from_bytes: (path: string, bytes: Uint8Array) => void;
+
+  /**
+   * Lists the recently saved or opened projects, most recent first, as JSON
+   * through the `recentProjects` event. Each entry has the path, the metadata
+   * and the thumbnail as a data URL. The paths persist between sessions; on
+   * the web entries from an earlier session only have their path until the
+   * project is opened again.
+   */
+  list_recent_projects: () => void;
+
+  /**
+   * Takes a named snapshot of the entities (not the camera or brush),
+   * replacing an earlier snapshot with the same name. Snapshots last for the
+   * session.
+   */
+  create_snapshot: (name: string) => void;
+
+  /**
+   * Replaces the current entities with those of a snapshot. The restore is a
+   * single undo step.
+   */
+  restore_snapshot: (name: string) => void;
+
+  delete_snapshot: (name: string) => void;
+
+  /**
+   * Lists the snapshots, oldest first, as JSON through the `snapshotsListed`
+   * event. Each entry has the name, the creation time in milliseconds since
+   * the Unix epoch and the entity count.
+   */
+  list_snapshots: () => void;
+
+  /**
+   * Starts a journal of every edit, undo and redo, timestamped, beginning
+   * with the current scene. Native builds rewrite the journal to `path`
+   * every second for crash recovery. Loading a scene stops the journal.
+   */
+  start_journal: (path?: string) => void;
+
+  stop_journal: () => void;
+
+  /**
+   * Delivers the journal recorded so far, as RON, through the
+   * `journalExported` event.
+   */
+  export_journal: () => void;
+
+  /**
+   * Replaces the scene with the journal's starting scene and replays its
+   * entries, at most one per frame. `speed` scales the recorded timing, for
+   * time-lapses; without it the replay runs as fast as possible.
+   */
+  replay_journal: (contents: string, speed?: number) => void;
+
+  /**
+   * Replays the journal at `path`. Only available in native builds.
+   */
+  replay_journal_file: (path: string, speed?: number) => void;
 }
 
 declare global {
@@ -47,6 +566,36 @@ declare global {
 
   interface WindowEventMap {
     modeChanged: CustomEvent<Mode>;
+    collidersExported: CustomEvent<string>;
+    sceneGltfExported: CustomEvent<string>;
+    meshObjExported: CustomEvent<string>;
+    meshStlExported: CustomEvent<string>;
+    meshGltfExported: CustomEvent<string>;
+    entitiesFound: CustomEvent<string>;
+    entitiesDeleted: CustomEvent<string>;
+    sceneSummary: CustomEvent<string>;
+    entityDetails: CustomEvent<string>;
+    entitySelected: CustomEvent<string>;
+    entityDeselected: CustomEvent<string>;
+    /**
+     * JSON `{ operation, progress }` while a mesh export or turntable capture
+     * runs, `progress` going from 0 to 1. `operation` is "meshExport",
+     * "bvhRebuild", "turntableCapture" or "volumeEstimate".
+     */
+    exportProgress: CustomEvent<string>;
+    /**
+     * JSON `{ operation, success }` once a long operation finished, also when
+     * a request was turned down because there was nothing to do or another
+     * one was running. Only BVH rebuilds that took noticeably long are
+     * reported.
+     */
+    exportComplete: CustomEvent<string>;
+    volumeEstimated: CustomEvent<string>;
+    sceneSaved: CustomEvent<string>;
+    projectSaved: CustomEvent<string>;
+    recentProjects: CustomEvent<string>;
+    snapshotsListed: CustomEvent<string>;
+    journalExported: CustomEvent<string>;
   }
 }
 